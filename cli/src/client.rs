@@ -0,0 +1,221 @@
+use crate::config::Config;
+use crate::local::Local;
+use crate::Error;
+use reqwest::blocking::{multipart, RequestBuilder, Response};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use zkprotocol::content::ZkNoteId;
+use zkprotocol::messages::{PrivateStreamingMessage, PrivateStreamingRequests};
+use zkprotocol::private::{PrivateReply, PrivateRequest};
+use zkprotocol::search::ZkNoteSearch;
+use zkprotocol::sync_data::SyncMessage;
+use zkprotocol::upload::UploadReply;
+
+// a zknotes server, or with --db a local database.
+pub enum Client {
+  Remote(Remote),
+  Local(Local),
+}
+
+impl Client {
+  pub fn new(config: &Config) -> Result<Client, Error> {
+    match &config.db {
+      Some(db) => Ok(Client::Local(Local::open(
+        Path::new(db),
+        config.files.as_ref().map(Path::new),
+        config
+          .user
+          .as_ref()
+          .ok_or(Error::String("--db-user is required with --db".to_string()))?
+          .as_str(),
+        config.migrate,
+      )?)),
+      None => Ok(Client::Remote(Remote::new(config)?)),
+    }
+  }
+
+  pub fn private(&self, rq: &PrivateRequest) -> Result<PrivateReply, Error> {
+    match self {
+      Client::Remote(r) => r.private(rq),
+      Client::Local(l) => l.private(rq),
+    }
+  }
+
+  pub fn search_stream(
+    &self,
+    search: &ZkNoteSearch,
+    f: &mut dyn FnMut(SyncMessage) -> Result<(), Error>,
+  ) -> Result<(), Error> {
+    match self {
+      Client::Remote(r) => r.search_stream(search, f),
+      Client::Local(l) => l.search_stream(search, f),
+    }
+  }
+
+  pub fn upload(&self, files: &Vec<String>) -> Result<UploadReply, Error> {
+    match self {
+      Client::Remote(r) => r.upload(files),
+      Client::Local(l) => l.upload(files),
+    }
+  }
+
+  pub fn download(&self, id: &ZkNoteId, out: &mut dyn std::io::Write) -> Result<u64, Error> {
+    match self {
+      Client::Remote(r) => r.download(id, out),
+      Client::Local(l) => l.download(id, out),
+    }
+  }
+}
+
+pub struct Remote {
+  url: String,
+  cookie: Option<String>,
+  token: Option<String>,
+  http: reqwest::blocking::Client,
+}
+
+impl Remote {
+  pub fn new(config: &Config) -> Result<Remote, Error> {
+    Ok(Remote {
+      url: config
+        .url
+        .clone()
+        .ok_or(Error::String(
+          "no server url; use --url or 'login'".to_string(),
+        ))?
+        .trim_end_matches('/')
+        .to_string(),
+      cookie: config.cookie.clone(),
+      token: config.token.clone(),
+      http: reqwest::blocking::Client::builder().build()?,
+    })
+  }
+
+  // tokens are preferred over cookies when there are both.
+  fn auth(&self, rb: RequestBuilder) -> RequestBuilder {
+    match (&self.token, &self.cookie) {
+      (Some(token), _) => rb.bearer_auth(token),
+      (None, Some(cookie)) => rb.header(reqwest::header::COOKIE, cookie),
+      (None, None) => rb,
+    }
+  }
+
+  fn check(res: Response) -> Result<Response, Error> {
+    if res.status().is_success() {
+      Ok(res)
+    } else {
+      let status = res.status();
+      Err(Error::Server(format!("{}: {}", status, res.text()?)))
+    }
+  }
+
+  // PvyServerError replies become Error::Server.
+  pub fn private(&self, rq: &PrivateRequest) -> Result<PrivateReply, Error> {
+    let res = self
+      .auth(self.http.post(format!("{}/private", self.url)))
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .body(serde_json::to_string(rq)?)
+      .send()?;
+    let mut buf = String::new();
+    Remote::check(res)?.read_to_string(&mut buf)?;
+    match serde_json::from_str::<PrivateReply>(buf.as_str())? {
+      PrivateReply::PvyServerError(e) => Err(Error::Server(format!("{:?}", e))),
+      r => Ok(r),
+    }
+  }
+
+  // search results from /stream, passed to f as they arrive.
+  pub fn search_stream(
+    &self,
+    search: &ZkNoteSearch,
+    f: &mut dyn FnMut(SyncMessage) -> Result<(), Error>,
+  ) -> Result<(), Error> {
+    let res = self
+      .auth(self.http.post(format!("{}/stream", self.url)))
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .body(serde_json::to_string(&PrivateStreamingMessage {
+        what: PrivateStreamingRequests::SearchZkNotes,
+        data: Some(serde_json::to_value(search)?),
+      })?)
+      .send()?;
+    for line in BufReader::new(Remote::check(res)?).lines() {
+      let line = line?;
+      if line.trim().is_empty() {
+        continue;
+      }
+      match serde_json::from_str::<SyncMessage>(line.as_str()) {
+        Ok(SyncMessage::SyncError(e)) => return Err(Error::Server(e)),
+        Ok(sm) => f(sm)?,
+        // errors before the stream starts come back as a PrivateReply.
+        Err(e) => match serde_json::from_str::<PrivateReply>(line.as_str()) {
+          Ok(PrivateReply::PvyServerError(e)) => return Err(Error::Server(format!("{:?}", e))),
+          _ => return Err(e.into()),
+        },
+      }
+    }
+    Ok(())
+  }
+
+  pub fn upload(&self, files: &Vec<String>) -> Result<UploadReply, Error> {
+    let mut form = multipart::Form::new();
+    for f in files.iter() {
+      form = form.file("file", Path::new(f))?;
+    }
+    let res = self
+      .auth(self.http.post(format!("{}/upload", self.url)))
+      .multipart(form)
+      .send()?;
+    Ok(serde_json::from_str(Remote::check(res)?.text()?.as_str())?)
+  }
+
+  // file downloads go by the session cookie.
+  pub fn download(&self, id: &ZkNoteId, out: &mut dyn std::io::Write) -> Result<u64, Error> {
+    let res = self
+      .auth(self.http.get(format!("{}/file/{}", self.url, id)))
+      .send()?;
+    Ok(Remote::check(res)?.copy_to(out)?)
+  }
+}
+
+// log in and return the session cookie.  code is for accounts with two factor auth.
+pub fn login(url: &str, user: &str, pwd: &str, code: Option<&str>) -> Result<String, Error> {
+  let url = url.trim_end_matches('/');
+  let http = reqwest::blocking::Client::builder().build()?;
+  let rb = match code {
+    Some(code) => http
+      .post(format!("{}/totp", url))
+      .body(serde_json::to_string(&zkprotocol::content::TotpLogin {
+        uid: user.to_string(),
+        pwd: pwd.to_string(),
+        code: code.to_string(),
+      })?),
+    None => http
+      .post(format!("{}/user", url))
+      .body(serde_json::to_string(
+        &orgauth::data::UserRequest::UrqLogin(orgauth::data::Login {
+          uid: user.to_string(),
+          pwd: pwd.to_string(),
+        }),
+      )?),
+  };
+  let res = Remote::check(
+    rb.header(reqwest::header::CONTENT_TYPE, "application/json")
+      .send()?,
+  )?;
+
+  // just the 'id=...' part of the cookie.
+  let cookie = res
+    .headers()
+    .get(reqwest::header::SET_COOKIE)
+    .and_then(|c| c.to_str().ok())
+    .and_then(|c| c.split(';').next())
+    .map(|c| c.trim().to_string());
+
+  match (serde_json::from_str(res.text()?.as_str())?, cookie) {
+    (orgauth::data::UserResponse::UrpLoggedIn(_), Some(cookie)) => Ok(cookie),
+    (orgauth::data::UserResponse::UrpLoggedIn(_), None) => {
+      Err(Error::String("cookie not found!".to_string()))
+    }
+    (r, _) => Err(Error::Server(format!("{:?}", r))),
+  }
+}
@@ -0,0 +1,63 @@
+use crate::Error;
+use serde_derive::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// stored credentials, so the cookie or token doesn't go on every command line.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Config {
+  pub url: Option<String>,
+  // the session cookie from 'login', as 'id=...'.
+  pub cookie: Option<String>,
+  // an api token, sent as a bearer token.
+  pub token: Option<String>,
+  // offline mode, from the command line only.
+  #[serde(skip)]
+  pub db: Option<String>,
+  #[serde(skip)]
+  pub files: Option<String>,
+  #[serde(skip)]
+  pub user: Option<String>,
+  #[serde(skip)]
+  pub migrate: bool,
+}
+
+// $ZKNOTES_CLI_CONFIG, or ~/.config/zknotes/cli.json.
+pub fn config_path() -> Result<PathBuf, Error> {
+  if let Ok(p) = std::env::var("ZKNOTES_CLI_CONFIG") {
+    return Ok(PathBuf::from(p));
+  }
+  let base = match std::env::var("XDG_CONFIG_HOME") {
+    Ok(x) if !x.is_empty() => PathBuf::from(x),
+    _ => match std::env::var("HOME") {
+      Ok(h) => PathBuf::from(h).join(".config"),
+      Err(_) => return Err(Error::String("can't find a config directory".to_string())),
+    },
+  };
+  Ok(base.join("zknotes").join("cli.json"))
+}
+
+pub fn load() -> Result<Config, Error> {
+  let path = config_path()?;
+  if path.exists() {
+    Ok(serde_json::from_str(
+      std::fs::read_to_string(path)?.as_str(),
+    )?)
+  } else {
+    Ok(Config::default())
+  }
+}
+
+pub fn save(config: &Config) -> Result<(), Error> {
+  let path = config_path()?;
+  if let Some(dir) = path.parent() {
+    std::fs::create_dir_all(dir)?;
+  }
+  std::fs::write(&path, serde_json::to_string_pretty(config)?)?;
+  // credentials are only for this user.
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+  }
+  Ok(())
+}
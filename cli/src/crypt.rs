@@ -0,0 +1,163 @@
+use crate::Error;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::cell::{OnceCell, RefCell};
+use std::collections::HashMap;
+
+// client side encryption for note content.  the key is derived from a passphrase
+// with scrypt, and the content is encrypted with AES-256-GCM, so the server only
+// ever has the ciphertext.
+//
+// encrypted content is PREFIX followed by base64 of salt, nonce and ciphertext.
+// each run uses one random salt for what it encrypts, so the key is derived once
+// per run, and once per salt when decrypting.
+
+const PREFIX: &str = "zkenc1:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+pub const PASSPHRASE_VAR: &str = "ZKNOTES_PASSPHRASE";
+
+pub struct Crypt {
+  passphrase: OnceCell<String>,
+  salt: [u8; SALT_LEN],
+  keys: RefCell<HashMap<[u8; SALT_LEN], Key<Aes256Gcm>>>,
+  params: scrypt::Params,
+}
+
+impl Crypt {
+  // without a passphrase, it's read when it's first needed, from
+  // $ZKNOTES_PASSPHRASE or the terminal.
+  pub fn new(passphrase: Option<String>) -> Crypt {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    Crypt {
+      passphrase: passphrase.map(OnceCell::from).unwrap_or_default(),
+      salt,
+      keys: RefCell::new(HashMap::new()),
+      params: scrypt::Params::recommended(),
+    }
+  }
+
+  // when encrypting, a typed passphrase is asked for twice, since a typo would
+  // leave the note unreadable.
+  fn passphrase(&self, confirm: bool) -> Result<&str, Error> {
+    if let Some(p) = self.passphrase.get() {
+      return Ok(p.as_str());
+    }
+    let p = match std::env::var(PASSPHRASE_VAR) {
+      Ok(p) => p,
+      Err(_) => {
+        let p = rpassword::prompt_password("passphrase: ")?;
+        if confirm && rpassword::prompt_password("passphrase again: ")? != p {
+          return Err(Error::String("passphrases don't match".to_string()));
+        }
+        p
+      }
+    };
+    if p.is_empty() {
+      return Err(Error::String("empty passphrase".to_string()));
+    }
+    Ok(self.passphrase.get_or_init(|| p).as_str())
+  }
+
+  fn key(&self, salt: &[u8; SALT_LEN], confirm: bool) -> Result<Key<Aes256Gcm>, Error> {
+    if let Some(k) = self.keys.borrow().get(salt) {
+      return Ok(*k);
+    }
+    let mut key = Key::<Aes256Gcm>::default();
+    scrypt::scrypt(
+      self.passphrase(confirm)?.as_bytes(),
+      salt,
+      &self.params,
+      key.as_mut_slice(),
+    )
+    .map_err(|e| Error::String(e.to_string()))?;
+    self.keys.borrow_mut().insert(*salt, key);
+    Ok(key)
+  }
+
+  pub fn encrypt(&self, plaintext: &str) -> Result<String, Error> {
+    let cipher = Aes256Gcm::new(&self.key(&self.salt, true)?);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+      .encrypt(&nonce, plaintext.as_bytes())
+      .map_err(|_| Error::String("encryption failed".to_string()))?;
+    let mut bytes = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+    bytes.extend_from_slice(&self.salt);
+    bytes.extend_from_slice(&nonce);
+    bytes.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", PREFIX, STANDARD.encode(bytes)))
+  }
+
+  pub fn decrypt(&self, content: &str) -> Result<String, Error> {
+    let bytes = content
+      .trim()
+      .strip_prefix(PREFIX)
+      .and_then(|b| STANDARD.decode(b).ok())
+      .filter(|b| b.len() > SALT_LEN + NONCE_LEN)
+      .ok_or(Error::String(
+        "encrypted content isn't in a known format".to_string(),
+      ))?;
+    let (salt, rest) = bytes.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let salt: [u8; SALT_LEN] = salt
+      .try_into()
+      .map_err(|_| Error::String("bad salt".to_string()))?;
+    let cipher = Aes256Gcm::new(&self.key(&salt, false)?);
+    let plaintext = cipher
+      .decrypt(Nonce::from_slice(nonce), ciphertext)
+      .map_err(|_| Error::String("can't decrypt; wrong passphrase?".to_string()))?;
+    String::from_utf8(plaintext).map_err(|e| Error::String(e.to_string()))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  // the recommended scrypt parameters take seconds in a debug build.
+  fn crypt(passphrase: &str) -> Crypt {
+    let mut c = Crypt::new(Some(passphrase.to_string()));
+    c.params = scrypt::Params::new(10, 8, 1, 32).unwrap();
+    c
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let c = crypt("correct horse");
+    let text = "some *markdown*\nwith a password: hunter2";
+    let enc = c.encrypt(text).unwrap();
+    assert!(enc.starts_with(PREFIX));
+    assert!(!enc.contains("hunter2"));
+    assert_eq!(c.decrypt(enc.as_str()).unwrap(), text);
+
+    // same salt, different nonce.
+    assert_ne!(c.encrypt(text).unwrap(), enc);
+
+    // another run has another salt, and derives the key from the passphrase.
+    let c2 = crypt("correct horse");
+    assert_eq!(c2.decrypt(enc.as_str()).unwrap(), text);
+  }
+
+  #[test]
+  fn test_wrong_passphrase() {
+    let enc = crypt("correct horse").encrypt("secret").unwrap();
+    assert!(crypt("wrong horse").decrypt(enc.as_str()).is_err());
+  }
+
+  #[test]
+  fn test_tampered() {
+    let c = crypt("correct horse");
+    let enc = c.encrypt("secret").unwrap();
+    let mut bytes = STANDARD.decode(&enc[PREFIX.len()..]).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    let tampered = format!("{}{}", PREFIX, STANDARD.encode(bytes));
+    assert!(c.decrypt(tampered.as_str()).is_err());
+    assert!(c.decrypt("plain text").is_err());
+  }
+}
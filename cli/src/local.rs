@@ -0,0 +1,228 @@
+use crate::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use zknotes_server_lib::jobs::JobMonitor;
+use zknotes_server_lib::search::{self, SearchResult};
+use zknotes_server_lib::state::{offline_state, State};
+use zknotes_server_lib::{bundle, sqldata, sync};
+use zknotes_server_lib::{interfaces, rusqlite::Connection};
+use zkprotocol::content::ZkNoteId;
+use zkprotocol::private::{PrivateReply, PrivateRequest};
+use zkprotocol::search::{ZkNoteSearch, ZkSearchResultHeader};
+use zkprotocol::sync_data::{SyncMessage, SyncStart};
+use zkprotocol::upload::UploadReply;
+
+// progress messages go to stderr.
+struct Progress {}
+
+impl JobMonitor for Progress {
+  fn write_fmt(&self, args: fmt::Arguments<'_>) {
+    eprintln!("{}", args);
+  }
+}
+
+// a zknotes db opened directly, acting as one of its users.
+pub struct Local {
+  state: State,
+  conn: Connection,
+  uid: orgauth::data::UserId,
+  rt: actix_rt::SystemRunner,
+  migrate: bool,
+}
+
+// dbinit migrates whatever it opens, so an older db is only updated with --migrate.
+fn check_migration(db: &Path, migrate: bool) -> Result<(), Error> {
+  let level = sqldata::migration_level(&sqldata::connection_open(db)?)?;
+  if level < sqldata::MIGRATION_LEVEL {
+    if !migrate {
+      return Err(Error::String(format!(
+        "{} is at migration level {}, this version needs {}; back it up and use --migrate to update it",
+        db.display(),
+        level,
+        sqldata::MIGRATION_LEVEL
+      )));
+    }
+    eprintln!(
+      "migrating {} from level {} to {}",
+      db.display(),
+      level,
+      sqldata::MIGRATION_LEVEL
+    );
+  }
+  Ok(())
+}
+
+impl Local {
+  // files defaults to the 'files' directory next to the db.
+  pub fn open(db: &Path, files: Option<&Path>, user: &str, migrate: bool) -> Result<Local, Error> {
+    if !db.exists() {
+      return Err(Error::String(format!("db not found: {}", db.display())));
+    }
+    let dir = db.parent().unwrap_or(Path::new("."));
+    let mut config = zknotes_server_lib::defcon();
+    config.orgauth_config.db = db.to_path_buf();
+    config.file_path = match files {
+      Some(f) => f.to_path_buf(),
+      None => dir.join("files"),
+    };
+    config.file_tmp_path = dir.join("temp");
+    std::fs::create_dir_all(&config.file_tmp_path)?;
+
+    // brings the db up to date with this version, same as the server would.
+    check_migration(db, migrate)?;
+    let server = sqldata::dbinit(
+      config.orgauth_config.db.as_path(),
+      config.orgauth_config.login_token_expiration_ms,
+    )?;
+    let conn = sqldata::connection_open(config.orgauth_config.db.as_path())?;
+    let uid = orgauth::dbfun::user_id(&conn, user)?;
+
+    Ok(Local {
+      state: offline_state(config, server),
+      conn,
+      uid,
+      rt: actix_rt::System::new(),
+      migrate,
+    })
+  }
+
+  pub fn private(&self, rq: &PrivateRequest) -> Result<PrivateReply, Error> {
+    Ok(self.rt.block_on(interfaces::zk_interface_loggedin(
+      &self.state,
+      &self.conn,
+      None,
+      self.uid,
+      rq,
+    ))?)
+  }
+
+  // the same messages as /stream, from a regular search.
+  pub fn search_stream(
+    &self,
+    zns: &ZkNoteSearch,
+    f: &mut dyn FnMut(SyncMessage) -> Result<(), Error>,
+  ) -> Result<(), Error> {
+    let sr = search::search_zknotes(&self.conn, &self.state.config.file_path, self.uid, zns)?;
+    f(SyncMessage::from(ZkSearchResultHeader {
+      what: zns.what.clone(),
+      resulttype: zns.resulttype,
+      offset: zns.offset,
+    }))?;
+    match sr {
+      SearchResult::SrId(r) => {
+        for id in r.notes {
+          f(SyncMessage::ZkNoteId(id.to_string()))?;
+        }
+      }
+      SearchResult::SrListNote(r) => {
+        for n in r.notes {
+          f(SyncMessage::ZkListNote(n))?;
+        }
+      }
+      SearchResult::SrNote(r) => {
+        for n in r.notes {
+          f(SyncMessage::ZkNote(n, None))?;
+        }
+      }
+      SearchResult::SrNoteAndLink(_) => {
+        return Err(Error::String("notes and links aren't streamed".to_string()))
+      }
+    }
+    Ok(())
+  }
+
+  pub fn upload(&self, files: &Vec<String>) -> Result<UploadReply, Error> {
+    let mut notes = Vec::new();
+    for f in files.iter() {
+      let fpath = Path::new(f);
+      let name = fpath
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or(f.clone());
+      let (nid, _, _) = self.rt.block_on(sqldata::make_file_note(
+        &self.conn,
+        &self.state.server,
+        &None,
+        &self.state.config.file_path,
+        self.uid,
+        &name,
+        fpath,
+        true,
+        &self.state.config.quotas,
+      ))?;
+      notes.push(sqldata::read_zklistnote(
+        &self.conn,
+        &self.state.config.file_path,
+        Some(self.uid),
+        nid,
+      )?);
+    }
+    Ok(UploadReply::UrFilesUploaded(notes))
+  }
+
+  // two way sync with the same user in another db.
+  pub fn sync_db(&self, other: &Path, other_files: Option<&Path>) -> Result<PrivateReply, Error> {
+    if !other.exists() {
+      return Err(Error::String(format!("db not found: {}", other.display())));
+    }
+    let other_files = match other_files {
+      Some(f) => f.to_path_buf(),
+      None => other.parent().unwrap_or(Path::new(".")).join("files"),
+    };
+    check_migration(other, self.migrate)?;
+    sqldata::dbinit(other, None)?;
+    Ok(self.rt.block_on(sync::sync_local(
+      self.state.config.orgauth_config.db.as_path(),
+      self.state.config.file_path.as_path(),
+      other,
+      other_files.as_path(),
+      &None,
+      self.uid,
+      &mut sqldata::zknotes_callbacks(),
+      &Progress {},
+    ))?)
+  }
+
+  // a sync bundle of the user's changes after 'after', and the number of files in it.
+  pub fn write_bundle(&self, out: &Path, after: Option<i64>) -> Result<(SyncStart, usize), Error> {
+    Ok(self.rt.block_on(bundle::write_bundle(
+      Arc::new(sqldata::connection_open(
+        self.state.config.orgauth_config.db.as_path(),
+      )?),
+      self.state.config.file_path.as_path(),
+      self.state.config.file_tmp_path.as_path(),
+      self.uid,
+      after,
+      out,
+      &mut sqldata::zknotes_callbacks(),
+      &Progress {},
+    ))?)
+  }
+
+  // apply a sync bundle; returns the number of new files.
+  pub fn read_bundle(&self, path: &Path) -> Result<usize, Error> {
+    Ok(self.rt.block_on(bundle::read_bundle(
+      &self.conn,
+      &None,
+      &self.state.server,
+      &orgauth::dbfun::read_user_by_id(&self.conn, self.uid)?,
+      self.state.config.file_path.as_path(),
+      self.state.config.file_tmp_path.as_path(),
+      &self.state.config.quotas,
+      path,
+      &mut sqldata::zknotes_callbacks(),
+      &Progress {},
+    ))?)
+  }
+
+  pub fn download(&self, id: &ZkNoteId, out: &mut dyn std::io::Write) -> Result<u64, Error> {
+    let (nid, _) =
+      sqldata::read_zknote(&self.conn, &self.state.config.file_path, Some(self.uid), id)?;
+    let hash = sqldata::read_zknote_filehash(&self.conn, Some(self.uid), nid)?.ok_or(
+      Error::String(format!("note {} has no file, or is private", id)),
+    )?;
+    let fpath: PathBuf = self.state.config.file_path.join(hash.as_str());
+    Ok(std::io::copy(&mut std::fs::File::open(fpath)?, out)?)
+  }
+}
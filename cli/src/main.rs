@@ -1,13 +1,32 @@
-use clap::{self, Arg};
+mod client;
+mod config;
+mod crypt;
+mod local;
+mod mirror;
+
+use clap::{self, Arg, ArgAction, ArgMatches, Command};
+use client::Client;
+use crypt::Crypt;
 use serde_json;
-use std::io::Read;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
 use std::{fmt, io};
-use zkprotocol::content::SaveZkNote;
+use uuid::Uuid;
+use zkprotocol::content::{
+  GetZkNoteArchives, GetZkNoteComments, JobState, JobStatus, MakeSyncBundle, SaveZkLink2,
+  SaveZkLinks, SaveZkNote, ZkListNote, ZkNote, ZkNoteId,
+};
+use zkprotocol::private::{PrivateReply, PrivateRequest};
 use zkprotocol::search::{self as zs, ZkNoteSearch};
 use zkprotocol::search_util::tag_search_parser;
+use zkprotocol::sync_data::SyncMessage;
+use zkprotocol::upload::UploadReply;
 
 pub enum Error {
   String(String),
+  // an error reply from the server.
+  Server(String),
 }
 
 impl std::error::Error for Error {
@@ -20,6 +39,7 @@ impl fmt::Display for Error {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match &self {
       Error::String(e) => write!(f, "{}", e),
+      Error::Server(e) => write!(f, "server error: {}", e),
     }
   }
 }
@@ -28,203 +48,1042 @@ impl fmt::Debug for Error {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match &self {
       Error::String(e) => write!(f, "{}", e),
+      Error::Server(e) => write!(f, "server error: {}", e),
     }
   }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-  let matches = clap::Command::new("zknotes cli")
+impl From<reqwest::Error> for Error {
+  fn from(e: reqwest::Error) -> Self {
+    Error::String(e.to_string())
+  }
+}
+impl From<serde_json::Error> for Error {
+  fn from(e: serde_json::Error) -> Self {
+    Error::String(e.to_string())
+  }
+}
+impl From<io::Error> for Error {
+  fn from(e: io::Error) -> Self {
+    Error::String(e.to_string())
+  }
+}
+impl From<uuid::Error> for Error {
+  fn from(e: uuid::Error) -> Self {
+    Error::String(e.to_string())
+  }
+}
+impl From<orgauth::error::Error> for Error {
+  fn from(e: orgauth::error::Error) -> Self {
+    Error::String(e.to_string())
+  }
+}
+// with --db, the errors the server would have replied with.
+impl From<zknotes_server_lib::error::Error> for Error {
+  fn from(e: zknotes_server_lib::error::Error) -> Self {
+    Error::Server(e.to_string())
+  }
+}
+impl From<Box<dyn std::error::Error>> for Error {
+  fn from(e: Box<dyn std::error::Error>) -> Self {
+    Error::Server(e.to_string())
+  }
+}
+
+// exit codes.
+const EXIT_ERROR: u8 = 1;
+const EXIT_SERVER_ERROR: u8 = 2;
+
+fn note_arg(name: &'static str) -> Arg {
+  Arg::new(name).required(true).help("note uuid")
+}
+
+fn command() -> Command {
+  Command::new("zknotes cli")
     .version("1.0")
     .author("Ben Burdette")
-    .about("zknotes cli client")
-    .arg(
-      Arg::new("command")
-        .short('c')
-        .long("command")
-        .value_name("command to execute")
-        .help("search, savenote, login"),
-    )
+    .about("zknotes cli client.  exits with 1 on errors, and 2 on errors from the server.")
+    .subcommand_required(true)
     .arg(
       Arg::new("server_url")
         .short('r')
         .long("url")
+        .global(true)
         .value_name("zknotes server url")
-        .help("server to connect to"),
+        .help("server to connect to; saved by 'login'"),
     )
     .arg(
-      Arg::new("user")
-        .short('u')
-        .long("user")
-        .value_name("user name")
-        .help("name of user to search with"),
+      Arg::new("cookie")
+        .short('k')
+        .long("cookie")
+        .global(true)
+        .value_name("cookie")
+        .help("session cookie, instead of the saved credentials"),
     )
     .arg(
-      Arg::new("password")
-        .short('p')
-        .long("password")
-        .value_name("password")
-        .help("password"),
+      Arg::new("token")
+        .short('t')
+        .long("token")
+        .global(true)
+        .value_name("api token")
+        .help("api token, instead of the saved credentials"),
     )
     .arg(
-      Arg::new("cookie")
-        .short('k')
-        .long("cookie")
-        .value_name("cookie"),
+      Arg::new("db")
+        .long("db")
+        .global(true)
+        .value_name("zknotes.db")
+        .help("use a local database instead of a server; no login needed"),
     )
     .arg(
-      Arg::new("search")
-        .short('s')
-        .long("search")
-        .value_name("search expression"),
+      Arg::new("db_user")
+        .long("db-user")
+        .global(true)
+        .value_name("user name")
+        .help("the user to act as, with --db"),
     )
     .arg(
-      Arg::new("search_result_format")
-        .short('f')
-        .long("search_format")
-        .value_name("search result format")
-        .help("RtId, RtListNote, RtNote, RtNoteAndLinks"),
-    )
-    .get_matches();
-
-  let url = matches
-    .get_one::<String>("server_url")
-    .ok_or(Error::String("'server_url' is required!".to_string()))?;
-
-  match matches
-    .get_one::<String>("command")
-    .ok_or(Error::String("'command' is required!".to_string()))?
-    .as_str()
-  {
-    "login" => {
-      let client = reqwest::blocking::Client::builder().build()?;
-
-      let username = matches
-        .get_one::<String>("user")
-        .ok_or(Error::String("'user' is required!".to_string()))?;
-      let password = matches
-        .get_one::<String>("password")
-        .ok_or(Error::String("'password' is required!".to_string()))?;
-
-      let rq = client
-        .post(url)
-        .header(reqwest::header::CONTENT_TYPE, "application/json")
-        .body(serde_json::to_string(
-          &orgauth::data::UserRequest::UrqLogin(orgauth::data::Login {
-            uid: username.clone(),
-            pwd: password.clone(),
-          }),
-        )?);
-
-      let res = rq.send();
-
-      match res {
-        Ok(r) => {
-          if r.status().is_success() {
-            if let Some(cookie) = r.headers().get("set-cookie") {
-              // let x: &reqwest::header::HeaderValue = cookie;
-              println!("cookie: {}", cookie.to_str()?);
-            } else {
-              println!("cookie not found!");
-            }
-          } else {
-            println!("bad response: {:?}", r);
-          }
-        }
-        Err(e) => {
-          println!("result: {:?}", e);
-        }
-      };
+      Arg::new("files")
+        .long("files")
+        .global(true)
+        .value_name("files directory")
+        .help("with --db; defaults to 'files' next to the db"),
+    )
+    .arg(
+      Arg::new("migrate")
+        .long("migrate")
+        .global(true)
+        .action(ArgAction::SetTrue)
+        .help("with --db, update an older db to this version's schema"),
+    )
+    .arg(
+      Arg::new("json")
+        .short('j')
+        .long("json")
+        .global(true)
+        .action(ArgAction::SetTrue)
+        .help("json output"),
+    )
+    .subcommand(
+      Command::new("login")
+        .about("log in and save the session cookie, or save an api token")
+        .arg(
+          Arg::new("user")
+            .short('u')
+            .long("user")
+            .value_name("user name"),
+        )
+        .arg(
+          Arg::new("password")
+            .short('p')
+            .long("password")
+            .value_name("password")
+            .help("read from stdin if not given"),
+        )
+        .arg(
+          Arg::new("code")
+            .long("code")
+            .value_name("code")
+            .help("two factor code, or a recovery code"),
+        ),
+    )
+    .subcommand(Command::new("logout").about("forget the saved cookie and token"))
+    .subcommand(
+      Command::new("search")
+        .about("search notes")
+        .arg(Arg::new("search").required(true).help("search expression"))
+        .arg(
+          Arg::new("search_result_format")
+            .short('f')
+            .long("search_format")
+            .value_name("search result format")
+            .help("RtId, RtListNote, RtNote, RtNoteAndLinks"),
+        )
+        .arg(
+          Arg::new("limit")
+            .short('l')
+            .long("limit")
+            .value_parser(clap::value_parser!(i64)),
+        )
+        .arg(
+          Arg::new("offset")
+            .long("offset")
+            .value_parser(clap::value_parser!(i64)),
+        )
+        .arg(
+          Arg::new("deleted")
+            .long("deleted")
+            .action(ArgAction::SetTrue)
+            .help("include deleted notes"),
+        )
+        .arg(
+          Arg::new("order")
+            .short('o')
+            .long("order")
+            .value_name("field[-asc|-desc]")
+            .help("title, created or changed; descending unless -asc"),
+        )
+        .arg(
+          Arg::new("archives")
+            .short('a')
+            .long("archives")
+            .value_parser(["current", "archives", "both"])
+            .default_value("current")
+            .help("search current notes, archived versions, or both"),
+        ),
+    )
+    .subcommand(
+      Command::new("get")
+        .about("show a note; encrypted notes are decrypted")
+        .arg(note_arg("id")),
+    )
+    .subcommand(
+      Command::new("edit")
+        .about("edit a note in $EDITOR")
+        .arg(note_arg("id")),
+    )
+    .subcommand(
+      Command::new("new")
+        .about("make a note")
+        .arg(Arg::new("title").required(true))
+        .arg(
+          Arg::new("file")
+            .short('f')
+            .long("file")
+            .value_name("file")
+            .help("content from a file, or - for stdin"),
+        )
+        .arg(
+          Arg::new("edit")
+            .short('e')
+            .long("edit")
+            .action(ArgAction::SetTrue)
+            .help("write the content in $EDITOR"),
+        )
+        .arg(
+          Arg::new("encrypt")
+            .long("encrypt")
+            .action(ArgAction::SetTrue)
+            .help("encrypt the content with a passphrase, from $ZKNOTES_PASSPHRASE or a prompt"),
+        ),
+    )
+    .subcommand(
+      Command::new("savenote")
+        .about("save a SaveZkNote from stdin, as json")
+        .hide(true),
+    )
+    .subcommand(
+      Command::new("delete")
+        .about("delete a note")
+        .arg(note_arg("id")),
+    )
+    .subcommand(
+      Command::new("link")
+        .about("link two notes")
+        .arg(note_arg("from"))
+        .arg(note_arg("to")),
+    )
+    .subcommand(
+      Command::new("unlink")
+        .about("remove a link between two notes")
+        .arg(note_arg("from"))
+        .arg(note_arg("to")),
+    )
+    .subcommand(
+      Command::new("archives")
+        .about("list the previous versions of a note")
+        .arg(note_arg("id"))
+        .arg(
+          Arg::new("limit")
+            .short('l')
+            .long("limit")
+            .value_parser(clap::value_parser!(i64)),
+        ),
+    )
+    .subcommand(
+      Command::new("comments")
+        .about("show the comments on a note")
+        .arg(note_arg("id"))
+        .arg(
+          Arg::new("limit")
+            .short('l')
+            .long("limit")
+            .value_parser(clap::value_parser!(i64)),
+        ),
+    )
+    .subcommand(
+      Command::new("upload")
+        .about("upload files as file notes")
+        .arg(Arg::new("files").required(true).num_args(1..)),
+    )
+    .subcommand(
+      Command::new("download")
+        .about("download the file for a file note")
+        .arg(note_arg("id"))
+        .arg(
+          Arg::new("output")
+            .short('o')
+            .long("output")
+            .value_name("file")
+            .help("defaults to the note title; - for stdout"),
+        ),
+    )
+    .subcommand(
+      Command::new("checkout")
+        .about("write the notes from a search to a directory, as markdown files")
+        .arg(Arg::new("dir").required(true))
+        .arg(Arg::new("search").required(true).help("search expression"))
+        .arg(
+          Arg::new("limit")
+            .short('l')
+            .long("limit")
+            .value_parser(clap::value_parser!(i64)),
+        ),
+    )
+    .subcommand(
+      Command::new("push")
+        .about("save changed files in a checkout to the server")
+        .arg(Arg::new("dir").required(true))
+        .arg(
+          Arg::new("force")
+            .long("force")
+            .action(ArgAction::SetTrue)
+            .help("overwrite notes that changed on the server"),
+        )
+        .arg(
+          Arg::new("watch")
+            .short('w')
+            .long("watch")
+            .action(ArgAction::SetTrue)
+            .help("keep running, and push files when they're saved"),
+        ),
+    )
+    .subcommand(
+      Command::new("pull")
+        .about("update a checkout with changes from the server")
+        .arg(Arg::new("dir").required(true)),
+    )
+    .subcommand(
+      Command::new("sync")
+        .about("sync with the remote server")
+        .arg(
+          Arg::new("wait")
+            .short('w')
+            .long("wait")
+            .action(ArgAction::SetTrue)
+            .help("wait for the sync job to finish"),
+        ),
+    )
+    .subcommand(
+      Command::new("jobs")
+        .about("show the status of a job")
+        .arg(
+          Arg::new("jobno")
+            .required(true)
+            .value_parser(clap::value_parser!(i64)),
+        )
+        .arg(
+          Arg::new("wait")
+            .short('w')
+            .long("wait")
+            .action(ArgAction::SetTrue)
+            .help("wait for the job to finish"),
+        ),
+    )
+    .subcommand(
+      Command::new("syncdb")
+        .about("sync the --db user with the same user in another db, without a server")
+        .arg(
+          Arg::new("other")
+            .required(true)
+            .help("the other zknotes.db"),
+        )
+        .arg(
+          Arg::new("other_files")
+            .long("other-files")
+            .value_name("files directory")
+            .help("defaults to 'files' next to the other db"),
+        ),
+    )
+    .subcommand(
+      Command::new("bundle")
+        .about("write a sync bundle, with the user's changes and files, for 'unbundle' elsewhere")
+        .arg(
+          Arg::new("file")
+            .help("required with --db; a server saves the bundle as a note, for 'download'"),
+        )
+        .arg(
+          Arg::new("after")
+            .long("after")
+            .value_name("ms since epoch")
+            .value_parser(clap::value_parser!(i64))
+            .help("only changes after this time"),
+        ),
+    )
+    .subcommand(
+      Command::new("unbundle")
+        .about("apply a sync bundle from 'bundle'; on a server, admins only")
+        .arg(Arg::new("file").required(true)),
+    )
+}
 
-      return Ok(());
-    }
-    "search" => {
-      match (
-        matches.get_one::<String>("cookie"),
-        matches.get_one::<String>("search"),
-      ) {
-        (Some(cookie), Some(search)) => {
-          let result_type = match matches.get_one::<String>("search_result_format") {
-            Some(s) => {
-              let quoted = format!("\"{}\"", s);
-              serde_json::from_str::<zs::ResultType>(quoted.as_str())?
-            }
-            None => zs::ResultType::RtListNote,
-          };
+fn local_client(cfg: &config::Config) -> Result<local::Local, Error> {
+  match Client::new(cfg)? {
+    Client::Local(l) => Ok(l),
+    Client::Remote(_) => Err(Error::String("this command needs --db".to_string())),
+  }
+}
 
-          let (_extra, tag_search) = match tag_search_parser(search) {
-            Ok(ts) => ts,
-            Err(e) => return Err(Box::new(Error::String(e.to_string()))),
-          };
+fn file_arg<'a>(matches: &'a ArgMatches, name: &str) -> Result<&'a Path, Error> {
+  matches
+    .get_one::<String>(name)
+    .map(Path::new)
+    .ok_or(Error::String(format!("'{}' is required!", name)))
+}
 
-          let zns = ZkNoteSearch {
-            tagsearch: vec![tag_search],
-            offset: 0,
-            limit: None,
-            what: "".to_string(),
-            resulttype: result_type,
-            archives: zs::ArchivesOrCurrent::Current,
-            deleted: false, // include deleted notes
-            ordering: None,
-          };
+fn note_id(matches: &ArgMatches, name: &str) -> Result<ZkNoteId, Error> {
+  let s = matches
+    .get_one::<String>(name)
+    .ok_or(Error::String(format!("'{}' is required!", name)))?;
+  Ok(ZkNoteId::Zni(Uuid::parse_str(s.as_str())?))
+}
 
-          let client = reqwest::blocking::Client::builder().build()?;
-          let rq = client
-            .post(url)
-            .header(reqwest::header::CONTENT_TYPE, "application/json")
-            .header(reqwest::header::COOKIE, cookie)
-            .body(serde_json::to_string(
-              &zkprotocol::private::PrivateRequest::PvqSearchZkNotes(zns),
-            )?);
+fn mirror_dir(matches: &ArgMatches) -> Result<&Path, Error> {
+  matches
+    .get_one::<String>("dir")
+    .map(Path::new)
+    .ok_or(Error::String("'dir' is required!".to_string()))
+}
 
-          let mut res = rq.send()?;
+fn date(ms: i64) -> String {
+  match chrono::DateTime::from_timestamp_millis(ms) {
+    Some(d) => d
+      .with_timezone(&chrono::Local)
+      .format("%Y-%m-%d %H:%M")
+      .to_string(),
+    None => ms.to_string(),
+  }
+}
 
-          let mut buf = String::new();
-          res.read_to_string(&mut buf)?;
+fn print_listnotes(notes: &Vec<ZkListNote>) {
+  for n in notes.iter() {
+    println!("{}  {}  {}", n.id, date(n.changeddate), n.title);
+  }
+}
 
-          println!("{}", buf);
+fn print_note(note: &ZkNote) {
+  println!("title: {}", note.title);
+  println!("id: {}", note.id);
+  println!("user: {}", note.username);
+  println!("changed: {}", date(note.changeddate));
+  if note.encrypted {
+    println!("encrypted: true");
+  }
+  println!();
+  println!("{}", note.content);
+}
+
+fn print_job(js: &JobStatus) {
+  println!("job {}: {:?}", js.jobno, js.state);
+  if !js.message.is_empty() {
+    println!("{}", js.message);
+  }
+}
 
-          ()
+// human readable output for a reply; the reply as json with --json.
+fn print_reply(json: bool, reply: &PrivateReply) -> Result<(), Error> {
+  if json {
+    println!("{}", serde_json::to_string_pretty(reply)?);
+    return Ok(());
+  }
+  match reply {
+    PrivateReply::PvyZkNote(note) => print_note(note),
+    PrivateReply::PvyZkListNoteSearchResult(r) => print_listnotes(&r.notes),
+    PrivateReply::PvyZkNoteIdSearchResult(r) => {
+      for id in r.notes.iter() {
+        println!("{}", id);
+      }
+    }
+    PrivateReply::PvyZkNoteSearchResult(r) => {
+      for (i, note) in r.notes.iter().enumerate() {
+        if i > 0 {
+          println!("\n--------\n");
         }
-        _ => {
-          println!("cookie, user and search parameters are required");
+        print_note(note);
+      }
+    }
+    PrivateReply::PvyZkNoteAndLinksSearchResult(r) => {
+      for (i, znl) in r.notes.iter().enumerate() {
+        if i > 0 {
+          println!("\n--------\n");
         }
-      };
+        print_note(&znl.zknote);
+        for l in znl.links.iter() {
+          println!(
+            "{:?}: {}  {}",
+            l.direction,
+            l.otherid,
+            l.othername.clone().unwrap_or_default()
+          );
+        }
+      }
     }
-    "savenote" => {
-      match matches.get_one::<String>("cookie") {
-        Some(cookie) => {
-          let stdin = io::stdin();
+    PrivateReply::PvyZkNoteArchives(za) => print_listnotes(&za.results.notes),
+    PrivateReply::PvyZkNoteComments(notes) => {
+      for n in notes.iter() {
+        println!("{}  {}  {}", n.id, date(n.createdate), n.username);
+        println!("{}\n", n.content);
+      }
+    }
+    PrivateReply::PvySavedZkNote(szn) => println!("{}", szn.id),
+    PrivateReply::PvyDeletedZkNote(id) => println!("deleted {}", id),
+    PrivateReply::PvySavedZkLinks => println!("ok"),
+    PrivateReply::PvyJobStatus(js) => print_job(js),
+    PrivateReply::PvyJobNotFound(jobno) => println!("job {} not found", jobno),
+    r => println!("{:?}", r),
+  }
+  Ok(())
+}
+
+fn read_note(client: &Client, id: &ZkNoteId) -> Result<ZkNote, Error> {
+  match client.private(&PrivateRequest::PvqGetZkNote(id.clone()))? {
+    PrivateReply::PvyZkNote(note) => Ok(note),
+    r => Err(Error::String(format!("unexpected reply: {:?}", r))),
+  }
+}
+
+// edit text in $VISUAL or $EDITOR.
+fn edit_text(text: &str) -> Result<String, Error> {
+  let editor = std::env::var("VISUAL")
+    .or_else(|_| std::env::var("EDITOR"))
+    .unwrap_or("vi".to_string());
+  let path = std::env::temp_dir().join(format!("zknote-{}.md", Uuid::new_v4()));
+  std::fs::write(&path, text)?;
+  // the editor setting can have arguments, like 'code --wait'.
+  let status = std::process::Command::new("sh")
+    .arg("-c")
+    .arg(format!("{} \"$1\"", editor))
+    .arg("sh")
+    .arg(&path)
+    .status();
+  let result = match status {
+    Ok(s) if s.success() => Ok(std::fs::read_to_string(&path)?),
+    Ok(s) => Err(Error::String(format!("editor exited with {}", s))),
+    Err(e) => Err(Error::String(format!(
+      "can't run editor '{}': {}",
+      editor, e
+    ))),
+  };
+  std::fs::remove_file(&path)?;
+  result
+}
 
-          // turns out this can read a serialized zknote!
-          let sn: SaveZkNote = serde_json::from_reader(stdin)?;
+fn print_mirror(json: bool, results: &Vec<mirror::MirrorResult>) -> Result<(), Error> {
+  if json {
+    println!("{}", serde_json::to_string_pretty(results)?);
+  } else {
+    for r in results.iter() {
+      println!("{:?}: {}", r.action, r.file);
+    }
+  }
+  Ok(())
+}
 
-          let client = reqwest::blocking::Client::builder().build()?;
-          let rq = client
-            .post(url)
-            .header(reqwest::header::CONTENT_TYPE, "application/json")
-            .header(reqwest::header::COOKIE, cookie)
-            .body(serde_json::to_string(
-              &zkprotocol::private::PrivateRequest::PvqSaveZkNote(sn),
-            )?);
+// conflicts are errors, after the report.
+fn mirror_report(json: bool, results: &Vec<mirror::MirrorResult>) -> Result<(), Error> {
+  print_mirror(json, results)?;
+  let conflicts = results
+    .iter()
+    .filter(|r| matches!(r.action, mirror::MirrorAction::Conflict))
+    .count();
+  if conflicts > 0 {
+    Err(Error::String(format!(
+      "{} conflicts; pull, or push with --force",
+      conflicts
+    )))
+  } else {
+    Ok(())
+  }
+}
 
-          let mut res = rq.send()?;
+fn parse_search(search: &String) -> Result<zs::TagSearch, Error> {
+  match tag_search_parser(search) {
+    Ok((_extra, ts)) => Ok(ts),
+    Err(e) => Err(Error::String(e.to_string())),
+  }
+}
 
-          let mut buf = String::new();
-          res.read_to_string(&mut buf)?;
+// 'changed', 'title-asc', 'created-desc' and so on.
+fn parse_order(order: &str) -> Result<zs::Ordering, Error> {
+  let (field, direction) = match order.rsplit_once('-') {
+    Some((f, "asc")) => (f, zs::OrderDirection::Ascending),
+    Some((f, "desc")) => (f, zs::OrderDirection::Descending),
+    _ => (order, zs::OrderDirection::Descending),
+  };
+  let field = match field {
+    "title" => zs::OrderField::Title,
+    "created" => zs::OrderField::Created,
+    "changed" => zs::OrderField::Changed,
+    _ => Err(Error::String(format!("invalid order: {}", order)))?,
+  };
+  Ok(zs::Ordering { field, direction })
+}
+
+// one line per streamed result; json lines with --json.
+fn print_search_item(json: bool, sm: SyncMessage) -> Result<(), Error> {
+  if json {
+    match sm {
+      SyncMessage::ZkSearchResultHeader(_) => (),
+      SyncMessage::ZkNoteId(id) => println!("{}", serde_json::to_string(&id)?),
+      SyncMessage::ZkListNote(n) => println!("{}", serde_json::to_string(&n)?),
+      SyncMessage::ZkNote(n, _) => println!("{}", serde_json::to_string(&n)?),
+      sm => println!("{}", serde_json::to_string(&sm)?),
+    }
+  } else {
+    match sm {
+      SyncMessage::ZkSearchResultHeader(_) => (),
+      SyncMessage::ZkNoteId(id) => println!("{}", id),
+      SyncMessage::ZkListNote(n) => println!("{}  {}  {}", n.id, date(n.changeddate), n.title),
+      SyncMessage::ZkNote(n, _) => {
+        print_note(&n);
+        println!();
+      }
+      sm => println!("{:?}", sm),
+    }
+  }
+  // show results as they come, even when piped.
+  io::stdout().flush()?;
+  Ok(())
+}
+
+fn wait_job(client: &Client, json: bool, js: JobStatus) -> Result<(), Error> {
+  let mut js = js;
+  loop {
+    match js.state {
+      JobState::Completed => break,
+      JobState::Failed => {
+        print_reply(json, &PrivateReply::PvyJobStatus(js.clone()))?;
+        return Err(Error::Server(format!("job {} failed", js.jobno)));
+      }
+      JobState::Started | JobState::Running => (),
+    }
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    js = match client.private(&PrivateRequest::PvqGetJobStatus(js.jobno))? {
+      PrivateReply::PvyJobStatus(js) => js,
+      r => return Err(Error::String(format!("unexpected reply: {:?}", r))),
+    };
+  }
+  print_reply(json, &PrivateReply::PvyJobStatus(js))
+}
+
+fn run(matches: &ArgMatches) -> Result<(), Error> {
+  // global args are in the subcommand matches.
+  let (cmd, matches) = matches
+    .subcommand()
+    .ok_or(Error::String("a command is required!".to_string()))?;
+  let json = matches.get_flag("json");
+
+  // command line settings override the saved ones.
+  let mut cfg = config::load()?;
+  if let Some(url) = matches.get_one::<String>("server_url") {
+    cfg.url = Some(url.clone());
+  }
+  if let Some(cookie) = matches.get_one::<String>("cookie") {
+    cfg.cookie = Some(cookie.clone());
+    cfg.token = None;
+  }
+  if let Some(token) = matches.get_one::<String>("token") {
+    cfg.token = Some(token.clone());
+  }
+  cfg.db = matches.get_one::<String>("db").cloned();
+  cfg.files = matches.get_one::<String>("files").cloned();
+  cfg.user = matches.get_one::<String>("db_user").cloned();
+  cfg.migrate = matches.get_flag("migrate");
+
+  let sm = matches;
+  match cmd {
+    "login" => {
+      let url = cfg
+        .url
+        .clone()
+        .ok_or(Error::String("'server_url' is required!".to_string()))?;
+      match sm.get_one::<String>("user") {
+        Some(user) => {
+          let password = match sm.get_one::<String>("password") {
+            Some(p) => p.clone(),
+            None => {
+              eprint!("password: ");
+              io::stderr().flush()?;
+              let mut p = String::new();
+              io::stdin().read_line(&mut p)?;
+              p.trim_end_matches(&['\r', '\n'][..]).to_string()
+            }
+          };
+          let cookie = client::login(
+            url.as_str(),
+            user.as_str(),
+            password.as_str(),
+            sm.get_one::<String>("code").map(|s| s.as_str()),
+          )?;
+          cfg.cookie = Some(cookie);
+          cfg.token = None;
+        }
+        // with --token and no user, just save the token.
+        None if matches.get_one::<String>("token").is_some() => (),
+        None => return Err(Error::String("'user' or 'token' is required!".to_string())),
+      }
+      config::save(&cfg)?;
+      println!("saved credentials to {}", config::config_path()?.display());
+      Ok(())
+    }
+    "logout" => {
+      cfg.cookie = None;
+      cfg.token = None;
+      config::save(&cfg)?;
+      Ok(())
+    }
+    "search" => {
+      let client = Client::new(&cfg)?;
+      let result_type = match sm.get_one::<String>("search_result_format") {
+        Some(s) => {
+          let quoted = format!("\"{}\"", s);
+          serde_json::from_str::<zs::ResultType>(quoted.as_str())?
+        }
+        None => zs::ResultType::RtListNote,
+      };
 
-          println!("{}", buf);
+      let search = sm
+        .get_one::<String>("search")
+        .ok_or(Error::String("'search' is required!".to_string()))?;
+      let zns = ZkNoteSearch {
+        tagsearch: vec![parse_search(search)?],
+        offset: sm.get_one::<i64>("offset").copied().unwrap_or(0),
+        limit: sm.get_one::<i64>("limit").copied(),
+        what: "".to_string(),
+        resulttype: result_type,
+        archives: match sm.get_one::<String>("archives").map(|s| s.as_str()) {
+          Some("archives") => zs::ArchivesOrCurrent::Archives,
+          Some("both") => zs::ArchivesOrCurrent::CurrentAndArchives,
+          _ => zs::ArchivesOrCurrent::Current,
+        },
+        deleted: sm.get_flag("deleted"),
+        ordering: match sm.get_one::<String>("order") {
+          Some(o) => Some(parse_order(o)?),
+          None => None,
+        },
+      };
 
-          ()
+      match zns.resulttype {
+        // notes and links aren't streamed.
+        zs::ResultType::RtNoteAndLinks => print_reply(
+          json,
+          &client.private(&PrivateRequest::PvqSearchZkNotes(zns))?,
+        ),
+        _ => client.search_stream(&zns, &mut |sm| print_search_item(json, sm)),
+      }
+    }
+    "get" => {
+      let client = Client::new(&cfg)?;
+      let mut note = read_note(&client, &note_id(sm, "id")?)?;
+      if note.encrypted {
+        note.content = Crypt::new(None).decrypt(note.content.as_str())?;
+      }
+      print_reply(json, &PrivateReply::PvyZkNote(note))
+    }
+    "edit" => {
+      let client = Client::new(&cfg)?;
+      let note = read_note(&client, &note_id(sm, "id")?)?;
+      let crypt = Crypt::new(None);
+      let text = if note.encrypted {
+        crypt.decrypt(note.content.as_str())?
+      } else {
+        note.content.clone()
+      };
+      let content = edit_text(text.as_str())?;
+      if content == text {
+        eprintln!("no changes");
+        return Ok(());
+      }
+      print_reply(
+        json,
+        &client.private(&PrivateRequest::PvqSaveZkNote(SaveZkNote {
+          id: Some(note.id),
+          title: note.title,
+          pubid: note.pubid,
+          content: if note.encrypted {
+            crypt.encrypt(content.as_str())?
+          } else {
+            content
+          },
+          editable: note.editableValue,
+          showtitle: note.showtitle,
+          deleted: note.deleted,
+          encrypted: note.encrypted,
+          what: None,
+        }))?,
+      )
+    }
+    "new" => {
+      let client = Client::new(&cfg)?;
+      let content = match (sm.get_one::<String>("file"), sm.get_flag("edit")) {
+        (Some(f), _) if f == "-" => {
+          let mut s = String::new();
+          io::stdin().read_to_string(&mut s)?;
+          s
+        }
+        (Some(f), _) => std::fs::read_to_string(f)?,
+        (None, true) => edit_text("")?,
+        (None, false) => "".to_string(),
+      };
+      let encrypted = sm.get_flag("encrypt");
+      let content = if encrypted {
+        Crypt::new(None).encrypt(content.as_str())?
+      } else {
+        content
+      };
+      print_reply(
+        json,
+        &client.private(&PrivateRequest::PvqSaveZkNote(SaveZkNote {
+          id: None,
+          title: sm
+            .get_one::<String>("title")
+            .ok_or(Error::String("'title' is required!".to_string()))?
+            .clone(),
+          pubid: None,
+          content,
+          editable: false,
+          showtitle: true,
+          deleted: false,
+          encrypted,
+          what: None,
+        }))?,
+      )
+    }
+    "savenote" => {
+      let client = Client::new(&cfg)?;
+      // turns out this can read a serialized zknote!
+      let sn: SaveZkNote = serde_json::from_reader(io::stdin())?;
+      print_reply(json, &client.private(&PrivateRequest::PvqSaveZkNote(sn))?)
+    }
+    "delete" => {
+      let client = Client::new(&cfg)?;
+      print_reply(
+        json,
+        &client.private(&PrivateRequest::PvqDeleteZkNote(note_id(sm, "id")?))?,
+      )
+    }
+    "link" | "unlink" => {
+      let client = Client::new(&cfg)?;
+      print_reply(
+        json,
+        &client.private(&PrivateRequest::PvqSaveZkLinks(SaveZkLinks {
+          links: vec![SaveZkLink2 {
+            from: note_id(sm, "from")?,
+            to: note_id(sm, "to")?,
+            linkzknote: None,
+            delete: if cmd == "unlink" { Some(true) } else { None },
+          }],
+        }))?,
+      )
+    }
+    "archives" => {
+      let client = Client::new(&cfg)?;
+      print_reply(
+        json,
+        &client.private(&PrivateRequest::PvqGetZkNoteArchives(GetZkNoteArchives {
+          zknote: note_id(sm, "id")?,
+          offset: 0,
+          limit: sm.get_one::<i64>("limit").copied(),
+        }))?,
+      )
+    }
+    "comments" => {
+      let client = Client::new(&cfg)?;
+      print_reply(
+        json,
+        &client.private(&PrivateRequest::PvqGetZkNoteComments(GetZkNoteComments {
+          zknote: note_id(sm, "id")?,
+          offset: 0,
+          limit: sm.get_one::<i64>("limit").copied(),
+        }))?,
+      )
+    }
+    "upload" => {
+      let client = Client::new(&cfg)?;
+      let files: Vec<String> = sm
+        .get_many::<String>("files")
+        .map(|f| f.cloned().collect())
+        .unwrap_or_default();
+      let ur = client.upload(&files)?;
+      if json {
+        println!("{}", serde_json::to_string_pretty(&ur)?);
+      } else {
+        match ur {
+          UploadReply::UrFilesUploaded(notes) => print_listnotes(&notes),
+          UploadReply::UrFilesAttached(fa) => println!("{:?}", fa),
         }
-        _ => {
-          println!("cookie, user and search parameters are required");
+      }
+      Ok(())
+    }
+    "download" => {
+      let client = Client::new(&cfg)?;
+      let id = note_id(sm, "id")?;
+      let output = match sm.get_one::<String>("output") {
+        Some(o) => o.clone(),
+        None => read_note(&client, &id)?.title.replace('/', "_"),
+      };
+      if output == "-" {
+        client.download(&id, &mut io::stdout())?;
+      } else {
+        let mut f = std::fs::File::create(&output)?;
+        let n = client.download(&id, &mut f)?;
+        if json {
+          println!("{}", serde_json::json!({ "file": output, "bytes": n }));
+        } else {
+          println!("wrote {} bytes to {}", n, output);
         }
+      }
+      Ok(())
+    }
+    "checkout" => {
+      let client = Client::new(&cfg)?;
+      let search = sm
+        .get_one::<String>("search")
+        .ok_or(Error::String("'search' is required!".to_string()))?;
+      let zns = ZkNoteSearch {
+        tagsearch: vec![parse_search(search)?],
+        offset: 0,
+        limit: sm.get_one::<i64>("limit").copied(),
+        what: "".to_string(),
+        resulttype: zs::ResultType::RtNote,
+        archives: zs::ArchivesOrCurrent::Current,
+        deleted: false,
+        ordering: None,
       };
+      mirror_report(
+        json,
+        &mirror::checkout(&client, mirror_dir(sm)?, &zns, &Crypt::new(None))?,
+      )
+    }
+    "push" => {
+      let client = Client::new(&cfg)?;
+      let dir = mirror_dir(sm)?;
+      let force = sm.get_flag("force");
+      let crypt = Crypt::new(None);
+      if sm.get_flag("watch") {
+        mirror::watch(&client, dir, force, &crypt, &|results| {
+          print_mirror(json, results)
+        })
+      } else {
+        mirror_report(json, &mirror::push(&client, dir, force, &crypt)?)
+      }
     }
-    &_ => {
-      println!("unsupported command");
+    "pull" => {
+      let client = Client::new(&cfg)?;
+      mirror_report(
+        json,
+        &mirror::pull(&client, mirror_dir(sm)?, &Crypt::new(None))?,
+      )
     }
+    "sync" => {
+      let client = Client::new(&cfg)?;
+      match client.private(&PrivateRequest::PvqSyncRemote)? {
+        PrivateReply::PvyJobStatus(js) if sm.get_flag("wait") || cfg.db.is_some() => {
+          wait_job(&client, json, js)
+        }
+        r => print_reply(json, &r),
+      }
+    }
+    "jobs" => {
+      let client = Client::new(&cfg)?;
+      let jobno = *sm
+        .get_one::<i64>("jobno")
+        .ok_or(Error::String("'jobno' is required!".to_string()))?;
+      match client.private(&PrivateRequest::PvqGetJobStatus(jobno))? {
+        PrivateReply::PvyJobStatus(js) if sm.get_flag("wait") => wait_job(&client, json, js),
+        r => print_reply(json, &r),
+      }
+    }
+    "syncdb" => {
+      let local = local_client(&cfg)?;
+      print_reply(
+        json,
+        &local.sync_db(
+          file_arg(sm, "other")?,
+          sm.get_one::<String>("other_files").map(Path::new),
+        )?,
+      )
+    }
+    "bundle" => {
+      let after = sm.get_one::<i64>("after").copied();
+      match (Client::new(&cfg)?, sm.get_one::<String>("file")) {
+        (Client::Local(local), Some(file)) => {
+          let (ss, files) = local.write_bundle(Path::new(file), after)?;
+          if json {
+            println!("{}", serde_json::json!({ "syncstart": ss, "files": files }));
+          } else {
+            println!(
+              "wrote changes from {} to {}, and {} files",
+              ss.after.map(date).unwrap_or("the beginning".to_string()),
+              date(ss.before),
+              files
+            );
+          }
+          Ok(())
+        }
+        (Client::Local(_), None) => Err(Error::String("'file' is required with --db".to_string())),
+        (Client::Remote(_), Some(_)) => Err(Error::String(
+          "the server saves the bundle as a note; leave out 'file', then 'download' it".to_string(),
+        )),
+        (client, None) => {
+          match client.private(&PrivateRequest::PvqMakeSyncBundle(MakeSyncBundle { after }))? {
+            PrivateReply::PvyJobStatus(js) => wait_job(&client, json, js),
+            r => print_reply(json, &r),
+          }
+        }
+      }
+    }
+    "unbundle" => {
+      let file = file_arg(sm, "file")?;
+      match Client::new(&cfg)? {
+        Client::Local(local) => {
+          let files = local.read_bundle(file)?;
+          if json {
+            println!("{}", serde_json::json!({ "files": files }));
+          } else {
+            println!("applied {}, with {} new files", file.display(), files);
+          }
+          Ok(())
+        }
+        client => {
+          // upload the bundle, then apply it on the server.
+          let id = match client.upload(&vec![file.to_string_lossy().to_string()])? {
+            UploadReply::UrFilesUploaded(notes) => notes
+              .first()
+              .map(|n| n.id)
+              .ok_or(Error::String("bundle upload failed".to_string()))?,
+            r => return Err(Error::String(format!("unexpected upload reply: {:?}", r))),
+          };
+          match client.private(&PrivateRequest::PvqApplySyncBundle(id))? {
+            PrivateReply::PvyJobStatus(js) => wait_job(&client, json, js),
+            r => print_reply(json, &r),
+          }
+        }
+      }
+    }
+    _ => Err(Error::String("unsupported command".to_string())),
   }
+}
 
-  Ok(())
+fn main() -> ExitCode {
+  match run(&command().get_matches()) {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(e) => {
+      eprintln!("{}", e);
+      match e {
+        Error::Server(_) => ExitCode::from(EXIT_SERVER_ERROR),
+        Error::String(_) => ExitCode::from(EXIT_ERROR),
+      }
+    }
+  }
 }
@@ -0,0 +1,494 @@
+use crate::client::Client;
+use crate::crypt::Crypt;
+use crate::Error;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use uuid::Uuid;
+use zkprotocol::content::{FileStatus, GetZknIfChanged, SaveZkNote, ZkNote, ZkNoteId};
+use zkprotocol::private::{PrivateReply, PrivateRequest};
+use zkprotocol::search::{ResultType, ZkNoteSearch};
+
+// a directory of notes as markdown files, for editing with other tools.
+// each file has a header with the note id and title.  the state file has the
+// notes as of the last checkout, push or pull, to tell local and remote
+// changes apart.
+//
+// encrypted notes are decrypted into their files, and have 'encrypted: true'
+// in the header.  files with that header are encrypted when they're pushed.
+
+const STATE_FILE: &str = ".zknotes-mirror.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct MirrorNote {
+  file: String,
+  changeddate: i64,
+  title: String,
+  content: String,
+  pubid: Option<String>,
+  editable: bool,
+  showtitle: bool,
+  #[serde(default)]
+  encrypted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct MirrorState {
+  // by note uuid.
+  notes: BTreeMap<String, MirrorNote>,
+}
+
+#[derive(Serialize, Debug)]
+pub enum MirrorAction {
+  CheckedOut,
+  Created,
+  Pushed,
+  Pulled,
+  Conflict,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MirrorResult {
+  pub file: String,
+  pub title: String,
+  pub action: MirrorAction,
+}
+
+fn load_state(dir: &Path) -> Result<MirrorState, Error> {
+  let p = dir.join(STATE_FILE);
+  if p.exists() {
+    Ok(serde_json::from_str(std::fs::read_to_string(p)?.as_str())?)
+  } else {
+    Err(Error::String(format!(
+      "{} isn't a checkout; no {}",
+      dir.display(),
+      STATE_FILE
+    )))
+  }
+}
+
+fn save_state(dir: &Path, state: &MirrorState) -> Result<(), Error> {
+  std::fs::write(dir.join(STATE_FILE), serde_json::to_string_pretty(state)?)?;
+  Ok(())
+}
+
+// a file name for a title, without characters that are trouble in file names.
+fn file_name(title: &str) -> String {
+  let s: String = title
+    .trim()
+    .chars()
+    .map(|c| match c {
+      '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+      c if c.is_control() => '-',
+      c => c,
+    })
+    .collect();
+  let s = s.trim_matches('.').trim().to_string();
+  if s.is_empty() {
+    "untitled".to_string()
+  } else {
+    s
+  }
+}
+
+fn note_text(
+  id: &str,
+  title: &str,
+  changeddate: i64,
+  encrypted: bool,
+  content: &str,
+) -> Result<String, Error> {
+  Ok(format!(
+    "---\nid: {}\ntitle: {}\nchangeddate: {}\n{}---\n{}",
+    id,
+    serde_json::to_string(title)?,
+    changeddate,
+    if encrypted { "encrypted: true\n" } else { "" },
+    content
+  ))
+}
+
+// (id, title, encrypted, content) from a note file.  files without a header are
+// new notes.  line endings are normalized, in case an editor saved with CRLF.
+fn parse_note(text: &str) -> (Option<String>, Option<String>, bool, String) {
+  let text = text.replace("\r\n", "\n");
+  if let Some(rest) = text.strip_prefix("---\n") {
+    let end = match rest.find("\n---\n") {
+      Some(end) => Some(end),
+      None if rest.ends_with("\n---") => Some(rest.len() - 4),
+      None => None,
+    };
+    if let Some(end) = end {
+      let mut id = None;
+      let mut title = None;
+      let mut encrypted = false;
+      for l in rest[..end].lines() {
+        match l.split_once(':') {
+          Some(("id", v)) => id = Some(v.trim().to_string()),
+          Some(("title", v)) => {
+            let v = v.trim();
+            title = Some(serde_json::from_str(v).unwrap_or(v.to_string()));
+          }
+          Some(("encrypted", v)) => encrypted = v.trim() == "true",
+          _ => (),
+        }
+      }
+      return (
+        id,
+        title,
+        encrypted,
+        rest.get(end + 5..).unwrap_or("").to_string(),
+      );
+    }
+  }
+  (None, None, false, text)
+}
+
+// encrypted content is decrypted.
+fn mirror_note(file: String, note: &ZkNote, crypt: &Crypt) -> Result<MirrorNote, Error> {
+  Ok(MirrorNote {
+    file,
+    changeddate: note.changeddate,
+    title: note.title.clone(),
+    content: if note.encrypted {
+      crypt.decrypt(note.content.as_str())?
+    } else {
+      note.content.clone()
+    },
+    pubid: note.pubid.clone(),
+    editable: note.editableValue,
+    showtitle: note.showtitle,
+    encrypted: note.encrypted,
+  })
+}
+
+fn write_note(dir: &Path, id: &str, mn: &MirrorNote) -> Result<(), Error> {
+  std::fs::write(
+    dir.join(mn.file.as_str()),
+    note_text(
+      id,
+      mn.title.as_str(),
+      mn.changeddate,
+      mn.encrypted,
+      mn.content.as_str(),
+    )?,
+  )?;
+  Ok(())
+}
+
+fn save_content(content: &str, encrypted: bool, crypt: &Crypt) -> Result<String, Error> {
+  if encrypted {
+    crypt.encrypt(content)
+  } else {
+    Ok(content.to_string())
+  }
+}
+
+// write the search results to dir, one markdown file per note.
+// file notes are left out.
+pub fn checkout(
+  client: &Client,
+  dir: &Path,
+  zns: &ZkNoteSearch,
+  crypt: &Crypt,
+) -> Result<Vec<MirrorResult>, Error> {
+  if dir.join(STATE_FILE).exists() {
+    return Err(Error::String(format!(
+      "{} is already a checkout; use pull",
+      dir.display()
+    )));
+  }
+  let mut zns = zns.clone();
+  zns.resulttype = ResultType::RtNote;
+  let notes = match client.private(&PrivateRequest::PvqSearchZkNotes(zns))? {
+    PrivateReply::PvyZkNoteSearchResult(r) => r.notes,
+    r => return Err(Error::String(format!("unexpected reply: {:?}", r))),
+  };
+
+  std::fs::create_dir_all(dir)?;
+  let mut state = MirrorState::default();
+  let mut results = Vec::new();
+  let mut used = std::collections::BTreeSet::new();
+  for note in notes.iter() {
+    if note.filestatus != FileStatus::NotAFile {
+      continue;
+    }
+    let id = note.id.to_string();
+    let name = file_name(note.title.as_str());
+    let file = if used.contains(&name.to_lowercase()) {
+      format!("{} ({}).md", name, &id[..8])
+    } else {
+      format!("{}.md", name)
+    };
+    used.insert(name.to_lowercase());
+    let mn = mirror_note(file.clone(), note, crypt)?;
+    write_note(dir, id.as_str(), &mn)?;
+    state.notes.insert(id, mn);
+    results.push(MirrorResult {
+      file,
+      title: note.title.clone(),
+      action: MirrorAction::CheckedOut,
+    });
+  }
+  save_state(dir, &state)?;
+  Ok(results)
+}
+
+// the note if it changed on the server since changeddate.
+fn remote_change(
+  client: &Client,
+  id: &ZkNoteId,
+  changeddate: i64,
+) -> Result<Option<ZkNote>, Error> {
+  match client.private(&PrivateRequest::PvqGetZknIfChanged(GetZknIfChanged {
+    zknote: id.clone(),
+    changeddate,
+    what: "".to_string(),
+  }))? {
+    PrivateReply::PvyNoop => Ok(None),
+    PrivateReply::PvyZkNoteAndLinksWhat(znlw) => Ok(Some(znlw.znl.zknote)),
+    r => Err(Error::String(format!("unexpected reply: {:?}", r))),
+  }
+}
+
+fn md_files(dir: &Path) -> Result<Vec<String>, Error> {
+  let mut files = Vec::new();
+  for entry in std::fs::read_dir(dir)? {
+    let entry = entry?;
+    let name = entry.file_name().to_string_lossy().to_string();
+    if entry.file_type()?.is_file() && name.ends_with(".md") && !name.starts_with('.') {
+      files.push(name);
+    }
+  }
+  files.sort();
+  Ok(files)
+}
+
+// send local changes to the server.  notes that changed on the server since the
+// last checkout, push or pull are conflicts, and aren't pushed unless force.
+// files without an id header become new notes.
+pub fn push(
+  client: &Client,
+  dir: &Path,
+  force: bool,
+  crypt: &Crypt,
+) -> Result<Vec<MirrorResult>, Error> {
+  let mut state = load_state(dir)?;
+  let mut results = Vec::new();
+  let byfile: BTreeMap<String, String> = state
+    .notes
+    .iter()
+    .map(|(id, mn)| (mn.file.clone(), id.clone()))
+    .collect();
+
+  for file in md_files(dir)? {
+    let (hid, htitle, encrypted, content) =
+      parse_note(std::fs::read_to_string(dir.join(&file))?.as_str());
+    // without its header, a checked out file would be pushed with the header text
+    // as content.
+    if hid.is_none() && byfile.contains_key(&file) {
+      return Err(Error::String(format!(
+        "{}: the note header is missing or unreadable, not pushing",
+        file
+      )));
+    }
+    let id = match hid {
+      Some(id) if state.notes.contains_key(&id) => id,
+      Some(id) => {
+        return Err(Error::String(format!(
+          "{}: note {} isn't in this checkout",
+          file, id
+        )))
+      }
+      None => {
+        // a new note.
+        let title = htitle.unwrap_or(file.trim_end_matches(".md").to_string());
+        let szn = match client.private(&PrivateRequest::PvqSaveZkNote(SaveZkNote {
+          id: None,
+          title: title.clone(),
+          pubid: None,
+          content: save_content(content.as_str(), encrypted, crypt)?,
+          editable: false,
+          showtitle: true,
+          deleted: false,
+          encrypted,
+          what: None,
+        }))? {
+          PrivateReply::PvySavedZkNote(szn) => szn,
+          r => return Err(Error::String(format!("unexpected reply: {:?}", r))),
+        };
+        let mn = MirrorNote {
+          file: file.clone(),
+          changeddate: szn.changeddate,
+          title: title.clone(),
+          content,
+          pubid: None,
+          editable: false,
+          showtitle: true,
+          encrypted,
+        };
+        write_note(dir, szn.id.to_string().as_str(), &mn)?;
+        state.notes.insert(szn.id.to_string(), mn);
+        results.push(MirrorResult {
+          file,
+          title,
+          action: MirrorAction::Created,
+        });
+        continue;
+      }
+    };
+
+    let base = state.notes[&id].clone();
+    let title = htitle.unwrap_or(base.title.clone());
+    if title == base.title && content == base.content && encrypted == base.encrypted {
+      continue;
+    }
+
+    let zknid = ZkNoteId::Zni(Uuid::parse_str(id.as_str())?);
+    if !force && remote_change(client, &zknid, base.changeddate)?.is_some() {
+      results.push(MirrorResult {
+        file,
+        title,
+        action: MirrorAction::Conflict,
+      });
+      continue;
+    }
+
+    let szn = match client.private(&PrivateRequest::PvqSaveZkNote(SaveZkNote {
+      id: Some(zknid),
+      title: title.clone(),
+      pubid: base.pubid.clone(),
+      content: save_content(content.as_str(), encrypted, crypt)?,
+      editable: base.editable,
+      showtitle: base.showtitle,
+      deleted: false,
+      encrypted,
+      what: None,
+    }))? {
+      PrivateReply::PvySavedZkNote(szn) => szn,
+      r => return Err(Error::String(format!("unexpected reply: {:?}", r))),
+    };
+    let mn = MirrorNote {
+      changeddate: szn.changeddate,
+      title: title.clone(),
+      content,
+      encrypted,
+      ..base
+    };
+    write_note(dir, id.as_str(), &mn)?;
+    state.notes.insert(id, mn);
+    results.push(MirrorResult {
+      file,
+      title,
+      action: MirrorAction::Pushed,
+    });
+  }
+
+  save_state(dir, &state)?;
+  Ok(results)
+}
+
+// update files with changes from the server.  files with local changes are
+// conflicts, and are left alone.
+pub fn pull(client: &Client, dir: &Path, crypt: &Crypt) -> Result<Vec<MirrorResult>, Error> {
+  let mut state = load_state(dir)?;
+  let mut results = Vec::new();
+  let ids: Vec<String> = state.notes.keys().cloned().collect();
+  for id in ids {
+    let base = state.notes[&id].clone();
+    let zknid = ZkNoteId::Zni(Uuid::parse_str(id.as_str())?);
+    let note = match remote_change(client, &zknid, base.changeddate)? {
+      Some(note) => note,
+      None => continue,
+    };
+    let local = match std::fs::read_to_string(dir.join(&base.file)) {
+      Ok(text) => Some(parse_note(text.as_str())),
+      Err(_) => None,
+    };
+    let modified = match &local {
+      Some((_, title, encrypted, content)) => {
+        title.as_ref().map_or(false, |t| *t != base.title)
+          || *encrypted != base.encrypted
+          || *content != base.content
+      }
+      None => false,
+    };
+    if modified {
+      results.push(MirrorResult {
+        file: base.file.clone(),
+        title: base.title.clone(),
+        action: MirrorAction::Conflict,
+      });
+      continue;
+    }
+    let mn = mirror_note(base.file.clone(), &note, crypt)?;
+    write_note(dir, id.as_str(), &mn)?;
+    state.notes.insert(id, mn);
+    results.push(MirrorResult {
+      file: base.file,
+      title: note.title,
+      action: MirrorAction::Pulled,
+    });
+  }
+  save_state(dir, &state)?;
+  Ok(results)
+}
+
+fn modified_times(dir: &Path) -> Result<BTreeMap<PathBuf, SystemTime>, Error> {
+  let mut times = BTreeMap::new();
+  for f in md_files(dir)? {
+    let p = dir.join(f);
+    times.insert(p.clone(), std::fs::metadata(&p)?.modified()?);
+  }
+  Ok(times)
+}
+
+// push whenever a file is saved.  polls, so it works the same everywhere.
+pub fn watch(
+  client: &Client,
+  dir: &Path,
+  force: bool,
+  crypt: &Crypt,
+  report: &dyn Fn(&Vec<MirrorResult>) -> Result<(), Error>,
+) -> Result<(), Error> {
+  let mut times = modified_times(dir)?;
+  report(&push(client, dir, force, crypt)?)?;
+  loop {
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let now = modified_times(dir)?;
+    if now != times {
+      let results = push(client, dir, force, crypt)?;
+      report(&results)?;
+      // pushing rewrites the headers, so get the times after.
+      times = modified_times(dir)?;
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_note_header() {
+    let text = note_text("abc", "a \"title\"", 5, true, "content\n---\nmore").unwrap();
+    let parsed = (
+      Some("abc".to_string()),
+      Some("a \"title\"".to_string()),
+      true,
+      "content\n---\nmore".to_string(),
+    );
+    assert_eq!(parse_note(text.as_str()), parsed);
+    assert_eq!(parse_note(text.replace('\n', "\r\n").as_str()), parsed);
+
+    let plain = note_text("abc", "title", 5, false, "content").unwrap();
+    assert!(!plain.contains("encrypted"));
+    assert!(!parse_note(plain.as_str()).2);
+
+    assert_eq!(
+      parse_note("no header"),
+      (None, None, false, "no header".to_string())
+    );
+  }
+}
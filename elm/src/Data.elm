@@ -74,6 +74,7 @@ type alias ZkNote =
     , createdate : Int
     , changeddate : Int
     , deleted : Bool
+    , encrypted : Bool
     , filestatus : FileStatus
     , server : String
     , sysids : List (ZkNoteId)
@@ -96,6 +97,7 @@ zkNoteEncoder struct =
         , ( "createdate", (Json.Encode.int) struct.createdate )
         , ( "changeddate", (Json.Encode.int) struct.changeddate )
         , ( "deleted", (Json.Encode.bool) struct.deleted )
+        , ( "encrypted", (Json.Encode.bool) struct.encrypted )
         , ( "filestatus", (fileStatusEncoder) struct.filestatus )
         , ( "server", (Json.Encode.string) struct.server )
         , ( "sysids", (Json.Encode.list (zkNoteIdEncoder)) struct.sysids )
@@ -168,6 +170,7 @@ type alias SaveZkNote =
     , editable : Bool
     , showtitle : Bool
     , deleted : Bool
+    , encrypted : Bool
     , what : Maybe (String)
     }
 
@@ -182,6 +185,7 @@ saveZkNoteEncoder struct =
         , ( "editable", (Json.Encode.bool) struct.editable )
         , ( "showtitle", (Json.Encode.bool) struct.showtitle )
         , ( "deleted", (Json.Encode.bool) struct.deleted )
+        , ( "encrypted", (Json.Encode.bool) struct.encrypted )
         , ( "what", (Maybe.withDefault Json.Encode.null << Maybe.map (Json.Encode.string)) struct.what )
         ]
 
@@ -379,6 +383,191 @@ importZkNoteEncoder struct =
         ]
 
 
+type ImportPolicy
+    = Replace
+    | Append
+    | Skip
+    | Duplicate
+
+
+importPolicyEncoder : ImportPolicy -> Json.Encode.Value
+importPolicyEncoder enum =
+    case enum of
+        Replace ->
+            Json.Encode.string "Replace"
+        Append ->
+            Json.Encode.string "Append"
+        Skip ->
+            Json.Encode.string "Skip"
+        Duplicate ->
+            Json.Encode.string "Duplicate"
+
+type alias ImportZkNotes =
+    { notes : List (ImportZkNote)
+    , policy : ImportPolicy
+    }
+
+
+importZkNotesEncoder : ImportZkNotes -> Json.Encode.Value
+importZkNotesEncoder struct =
+    Json.Encode.object
+        [ ( "notes", (Json.Encode.list (importZkNoteEncoder)) struct.notes )
+        , ( "policy", (importPolicyEncoder) struct.policy )
+        ]
+
+
+type ImportAction
+    = Added
+    | Replaced
+    | Appended
+    | Unchanged
+    | Skipped
+    | Duplicated
+
+
+importActionEncoder : ImportAction -> Json.Encode.Value
+importActionEncoder enum =
+    case enum of
+        Added ->
+            Json.Encode.string "Added"
+        Replaced ->
+            Json.Encode.string "Replaced"
+        Appended ->
+            Json.Encode.string "Appended"
+        Unchanged ->
+            Json.Encode.string "Unchanged"
+        Skipped ->
+            Json.Encode.string "Skipped"
+        Duplicated ->
+            Json.Encode.string "Duplicated"
+
+type alias ImportZkNoteResult =
+    { title : String
+    , id : ZkNoteId
+    , action : ImportAction
+    }
+
+
+importZkNoteResultEncoder : ImportZkNoteResult -> Json.Encode.Value
+importZkNoteResultEncoder struct =
+    Json.Encode.object
+        [ ( "title", (Json.Encode.string) struct.title )
+        , ( "id", (zkNoteIdEncoder) struct.id )
+        , ( "action", (importActionEncoder) struct.action )
+        ]
+
+
+type VaultSource
+    = Zip (ZkNoteId)
+    | Directory (String)
+
+
+vaultSourceEncoder : VaultSource -> Json.Encode.Value
+vaultSourceEncoder enum =
+    case enum of
+        Zip inner ->
+            Json.Encode.object [ ( "Zip", zkNoteIdEncoder inner ) ]
+        Directory inner ->
+            Json.Encode.object [ ( "Directory", Json.Encode.string inner ) ]
+
+type alias ImportVault =
+    { source : VaultSource
+    , policy : ImportPolicy
+    }
+
+
+importVaultEncoder : ImportVault -> Json.Encode.Value
+importVaultEncoder struct =
+    Json.Encode.object
+        [ ( "source", (vaultSourceEncoder) struct.source )
+        , ( "policy", (importPolicyEncoder) struct.policy )
+        ]
+
+
+type alias ExportSubtree =
+    { root : ZkNoteId
+    , depth : Int
+    }
+
+
+exportSubtreeEncoder : ExportSubtree -> Json.Encode.Value
+exportSubtreeEncoder struct =
+    Json.Encode.object
+        [ ( "root", (zkNoteIdEncoder) struct.root )
+        , ( "depth", (Json.Encode.int) struct.depth )
+        ]
+
+
+type ExportSelection
+    = Search (ZkNoteSearch)
+    | Subtree (ExportSubtree)
+
+
+exportSelectionEncoder : ExportSelection -> Json.Encode.Value
+exportSelectionEncoder enum =
+    case enum of
+        Search inner ->
+            Json.Encode.object [ ( "Search", zkNoteSearchEncoder inner ) ]
+        Subtree inner ->
+            Json.Encode.object [ ( "Subtree", exportSubtreeEncoder inner ) ]
+
+type ExportFormat
+    = Markdown
+    | Html
+    | Obsidian
+
+
+exportFormatEncoder : ExportFormat -> Json.Encode.Value
+exportFormatEncoder enum =
+    case enum of
+        Markdown ->
+            Json.Encode.string "Markdown"
+        Html ->
+            Json.Encode.string "Html"
+        Obsidian ->
+            Json.Encode.string "Obsidian"
+
+type ExportDestination
+    = ZipNote
+    | ServerDirectory (String)
+
+
+exportDestinationEncoder : ExportDestination -> Json.Encode.Value
+exportDestinationEncoder enum =
+    case enum of
+        ZipNote ->
+            Json.Encode.string "ZipNote"
+        ServerDirectory inner ->
+            Json.Encode.object [ ( "ServerDirectory", Json.Encode.string inner ) ]
+
+type alias ExportSite =
+    { selection : ExportSelection
+    , format : ExportFormat
+    , destination : ExportDestination
+    }
+
+
+exportSiteEncoder : ExportSite -> Json.Encode.Value
+exportSiteEncoder struct =
+    Json.Encode.object
+        [ ( "selection", (exportSelectionEncoder) struct.selection )
+        , ( "format", (exportFormatEncoder) struct.format )
+        , ( "destination", (exportDestinationEncoder) struct.destination )
+        ]
+
+
+type alias MakeSyncBundle =
+    { after : Maybe (Int)
+    }
+
+
+makeSyncBundleEncoder : MakeSyncBundle -> Json.Encode.Value
+makeSyncBundleEncoder struct =
+    Json.Encode.object
+        [ ( "after", (Maybe.withDefault Json.Encode.null << Maybe.map (Json.Encode.int)) struct.after )
+        ]
+
+
 type alias GetZkLinks =
     { zknote : ZkNoteId
     }
@@ -612,10 +801,333 @@ jobStatusEncoder struct =
         ]
 
 
+type alias GetStorageStats =
+    { largest : Int
+    }
+
+
+getStorageStatsEncoder : GetStorageStats -> Json.Encode.Value
+getStorageStatsEncoder struct =
+    Json.Encode.object
+        [ ( "largest", (Json.Encode.int) struct.largest )
+        ]
+
+
+type alias FileStats =
+    { id : ZkNoteId
+    , title : String
+    , hash : String
+    , size : Int
+    }
+
+
+fileStatsEncoder : FileStats -> Json.Encode.Value
+fileStatsEncoder struct =
+    Json.Encode.object
+        [ ( "id", (zkNoteIdEncoder) struct.id )
+        , ( "title", (Json.Encode.string) struct.title )
+        , ( "hash", (Json.Encode.string) struct.hash )
+        , ( "size", (Json.Encode.int) struct.size )
+        ]
+
+
+type alias UserStorageStats =
+    { user : UserId
+    , name : String
+    , notes : Int
+    , archives : Int
+    , files : Int
+    , fileBytes : Int
+    , uniqueFileBytes : Int
+    , largestFiles : List (FileStats)
+    }
+
+
+userStorageStatsEncoder : UserStorageStats -> Json.Encode.Value
+userStorageStatsEncoder struct =
+    Json.Encode.object
+        [ ( "user", (userIdEncoder) struct.user )
+        , ( "name", (Json.Encode.string) struct.name )
+        , ( "notes", (Json.Encode.int) struct.notes )
+        , ( "archives", (Json.Encode.int) struct.archives )
+        , ( "files", (Json.Encode.int) struct.files )
+        , ( "file_bytes", (Json.Encode.int) struct.fileBytes )
+        , ( "unique_file_bytes", (Json.Encode.int) struct.uniqueFileBytes )
+        , ( "largest_files", (Json.Encode.list (fileStatsEncoder)) struct.largestFiles )
+        ]
+
+
+type alias StorageStats =
+    { users : List (UserStorageStats)
+    , fileBytes : Int
+    , uniqueFileBytes : Int
+    }
+
+
+storageStatsEncoder : StorageStats -> Json.Encode.Value
+storageStatsEncoder struct =
+    Json.Encode.object
+        [ ( "users", (Json.Encode.list (userStorageStatsEncoder)) struct.users )
+        , ( "file_bytes", (Json.Encode.int) struct.fileBytes )
+        , ( "unique_file_bytes", (Json.Encode.int) struct.uniqueFileBytes )
+        ]
+
+
+type SharePermission
+    = Read
+    | Comment
+    | Write
+
+
+sharePermissionEncoder : SharePermission -> Json.Encode.Value
+sharePermissionEncoder enum =
+    case enum of
+        Read ->
+            Json.Encode.string "Read"
+        Comment ->
+            Json.Encode.string "Comment"
+        Write ->
+            Json.Encode.string "Write"
+
+type alias UserSharePermission =
+    { share : ZkNoteId
+    , user : UserId
+    , permission : SharePermission
+    }
+
+
+userSharePermissionEncoder : UserSharePermission -> Json.Encode.Value
+userSharePermissionEncoder struct =
+    Json.Encode.object
+        [ ( "share", (zkNoteIdEncoder) struct.share )
+        , ( "user", (userIdEncoder) struct.user )
+        , ( "permission", (sharePermissionEncoder) struct.permission )
+        ]
+
+
+type ApiTokenScope
+    = ReadOnly
+    | ReadWrite
+    | UploadOnly
+
+
+apiTokenScopeEncoder : ApiTokenScope -> Json.Encode.Value
+apiTokenScopeEncoder enum =
+    case enum of
+        ReadOnly ->
+            Json.Encode.string "ReadOnly"
+        ReadWrite ->
+            Json.Encode.string "ReadWrite"
+        UploadOnly ->
+            Json.Encode.string "UploadOnly"
+
+type alias CreateApiToken =
+    { name : String
+    , scope : ApiTokenScope
+    }
+
+
+createApiTokenEncoder : CreateApiToken -> Json.Encode.Value
+createApiTokenEncoder struct =
+    Json.Encode.object
+        [ ( "name", (Json.Encode.string) struct.name )
+        , ( "scope", (apiTokenScopeEncoder) struct.scope )
+        ]
+
+
+type alias ApiToken =
+    { id : String
+    , name : String
+    , scope : ApiTokenScope
+    , createdate : Int
+    , lastused : Maybe (Int)
+    }
+
+
+apiTokenEncoder : ApiToken -> Json.Encode.Value
+apiTokenEncoder struct =
+    Json.Encode.object
+        [ ( "id", (Json.Encode.string) struct.id )
+        , ( "name", (Json.Encode.string) struct.name )
+        , ( "scope", (apiTokenScopeEncoder) struct.scope )
+        , ( "createdate", (Json.Encode.int) struct.createdate )
+        , ( "lastused", (Maybe.withDefault Json.Encode.null << Maybe.map (Json.Encode.int)) struct.lastused )
+        ]
+
+
+type alias NewApiToken =
+    { info : ApiToken
+    , token : String
+    }
+
+
+newApiTokenEncoder : NewApiToken -> Json.Encode.Value
+newApiTokenEncoder struct =
+    Json.Encode.object
+        [ ( "info", (apiTokenEncoder) struct.info )
+        , ( "token", (Json.Encode.string) struct.token )
+        ]
+
+
+type alias CreateShareLink =
+    { zknote : ZkNoteId
+    , expires : Maybe (Int)
+    }
+
+
+createShareLinkEncoder : CreateShareLink -> Json.Encode.Value
+createShareLinkEncoder struct =
+    Json.Encode.object
+        [ ( "zknote", (zkNoteIdEncoder) struct.zknote )
+        , ( "expires", (Maybe.withDefault Json.Encode.null << Maybe.map (Json.Encode.int)) struct.expires )
+        ]
+
+
+type alias ShareLink =
+    { id : String
+    , token : String
+    , zknote : ZkNoteId
+    , createdate : Int
+    , expires : Maybe (Int)
+    , revoked : Bool
+    , views : Int
+    , lastview : Maybe (Int)
+    }
+
+
+shareLinkEncoder : ShareLink -> Json.Encode.Value
+shareLinkEncoder struct =
+    Json.Encode.object
+        [ ( "id", (Json.Encode.string) struct.id )
+        , ( "token", (Json.Encode.string) struct.token )
+        , ( "zknote", (zkNoteIdEncoder) struct.zknote )
+        , ( "createdate", (Json.Encode.int) struct.createdate )
+        , ( "expires", (Maybe.withDefault Json.Encode.null << Maybe.map (Json.Encode.int)) struct.expires )
+        , ( "revoked", (Json.Encode.bool) struct.revoked )
+        , ( "views", (Json.Encode.int) struct.views )
+        , ( "lastview", (Maybe.withDefault Json.Encode.null << Maybe.map (Json.Encode.int)) struct.lastview )
+        ]
+
+
+type alias GetShareLinkNote =
+    { token : String
+    , zknote : Maybe (ZkNoteId)
+    }
+
+
+getShareLinkNoteEncoder : GetShareLinkNote -> Json.Encode.Value
+getShareLinkNoteEncoder struct =
+    Json.Encode.object
+        [ ( "token", (Json.Encode.string) struct.token )
+        , ( "zknote", (Maybe.withDefault Json.Encode.null << Maybe.map (zkNoteIdEncoder)) struct.zknote )
+        ]
+
+
+type AuditAction
+    = DeleteNote
+    | PowerDelete
+    | ShareChange
+    | PublicChange
+    | Admin
+    | PromoteAdmin
+    | Sync
+
+
+auditActionEncoder : AuditAction -> Json.Encode.Value
+auditActionEncoder enum =
+    case enum of
+        DeleteNote ->
+            Json.Encode.string "DeleteNote"
+        PowerDelete ->
+            Json.Encode.string "PowerDelete"
+        ShareChange ->
+            Json.Encode.string "ShareChange"
+        PublicChange ->
+            Json.Encode.string "PublicChange"
+        Admin ->
+            Json.Encode.string "Admin"
+        PromoteAdmin ->
+            Json.Encode.string "PromoteAdmin"
+        Sync ->
+            Json.Encode.string "Sync"
+
+type alias AuditEntry =
+    { id : Int
+    , user : Maybe (UserId)
+    , action : AuditAction
+    , detail : String
+    , createdate : Int
+    }
+
+
+auditEntryEncoder : AuditEntry -> Json.Encode.Value
+auditEntryEncoder struct =
+    Json.Encode.object
+        [ ( "id", (Json.Encode.int) struct.id )
+        , ( "user", (Maybe.withDefault Json.Encode.null << Maybe.map (userIdEncoder)) struct.user )
+        , ( "action", (auditActionEncoder) struct.action )
+        , ( "detail", (Json.Encode.string) struct.detail )
+        , ( "createdate", (Json.Encode.int) struct.createdate )
+        ]
+
+
+type alias AuditQuery =
+    { user : Maybe (UserId)
+    , action : Maybe (AuditAction)
+    , after : Maybe (Int)
+    , before : Maybe (Int)
+    , offset : Int
+    , limit : Maybe (Int)
+    }
+
+
+auditQueryEncoder : AuditQuery -> Json.Encode.Value
+auditQueryEncoder struct =
+    Json.Encode.object
+        [ ( "user", (Maybe.withDefault Json.Encode.null << Maybe.map (userIdEncoder)) struct.user )
+        , ( "action", (Maybe.withDefault Json.Encode.null << Maybe.map (auditActionEncoder)) struct.action )
+        , ( "after", (Maybe.withDefault Json.Encode.null << Maybe.map (Json.Encode.int)) struct.after )
+        , ( "before", (Maybe.withDefault Json.Encode.null << Maybe.map (Json.Encode.int)) struct.before )
+        , ( "offset", (Json.Encode.int) struct.offset )
+        , ( "limit", (Maybe.withDefault Json.Encode.null << Maybe.map (Json.Encode.int)) struct.limit )
+        ]
+
+
+type alias TotpEnrollment =
+    { uri : String
+    , secret : String
+    }
+
+
+totpEnrollmentEncoder : TotpEnrollment -> Json.Encode.Value
+totpEnrollmentEncoder struct =
+    Json.Encode.object
+        [ ( "uri", (Json.Encode.string) struct.uri )
+        , ( "secret", (Json.Encode.string) struct.secret )
+        ]
+
+
+type alias TotpLogin =
+    { uid : String
+    , pwd : String
+    , code : String
+    }
+
+
+totpLoginEncoder : TotpLogin -> Json.Encode.Value
+totpLoginEncoder struct =
+    Json.Encode.object
+        [ ( "uid", (Json.Encode.string) struct.uid )
+        , ( "pwd", (Json.Encode.string) struct.pwd )
+        , ( "code", (Json.Encode.string) struct.code )
+        ]
+
+
 type PublicRequest
     = PbrGetZkNoteAndLinks (GetZkNoteAndLinks)
     | PbrGetZknIfChanged (GetZknIfChanged)
     | PbrGetZkNotePubId (String)
+    | PbrGetShareLinkNote (GetShareLinkNote)
 
 
 publicRequestEncoder : PublicRequest -> Json.Encode.Value
@@ -627,6 +1139,8 @@ publicRequestEncoder enum =
             Json.Encode.object [ ( "PbrGetZknIfChanged", getZknIfChangedEncoder inner ) ]
         PbrGetZkNotePubId inner ->
             Json.Encode.object [ ( "PbrGetZkNotePubId", Json.Encode.string inner ) ]
+        PbrGetShareLinkNote inner ->
+            Json.Encode.object [ ( "PbrGetShareLinkNote", getShareLinkNoteEncoder inner ) ]
 
 type PublicReply
     = PbyServerError (PublicError)
@@ -672,16 +1186,39 @@ type PrivateRequest
     | PvqGetArchiveZklinks (GetArchiveZkLinks)
     | PvqGetZkLinksSince (GetZkLinksSince)
     | PvqSearchZkNotes (ZkNoteSearch)
-    | PvqPowerDelete (List (TagSearch))
+    | PvqGetChangedNotes (GetChangedNotes)
+    | PvqPowerDeletePreview (List (TagSearch))
+    | PvqPowerDelete (PowerDeleteConfirm)
+    | PvqUndoPowerDelete (String)
     | PvqDeleteZkNote (ZkNoteId)
     | PvqSaveZkNote (SaveZkNote)
     | PvqSaveZkLinks (SaveZkLinks)
     | PvqSaveZkNoteAndLinks (SaveZkNoteAndLinks)
     | PvqSaveImportZkNotes (List (ImportZkNote))
+    | PvqImportZkNotes (ImportZkNotes)
+    | PvqImportVault (ImportVault)
+    | PvqExportSite (ExportSite)
     | PvqSetHomeNote (ZkNoteId)
     | PvqSyncRemote
     | PvqSyncFiles (ZkNoteSearch)
+    | PvqMakeSyncBundle (MakeSyncBundle)
+    | PvqApplySyncBundle (ZkNoteId)
     | PvqGetJobStatus (Int)
+    | PvqGetStorageStats (GetStorageStats)
+    | PvqCreateApiToken (CreateApiToken)
+    | PvqGetApiTokens
+    | PvqRevokeApiToken (String)
+    | PvqSetSharePermission (UserSharePermission)
+    | PvqGetSharePermissions (ZkNoteId)
+    | PvqCreateShareLink (CreateShareLink)
+    | PvqGetShareLinks (ZkNoteId)
+    | PvqRevokeShareLink (String)
+    | PvqGetAuditLog (AuditQuery)
+    | PvqTotpStatus
+    | PvqTotpBegin
+    | PvqTotpConfirm (String)
+    | PvqTotpDisable (String)
+    | PvqAdminResetTotp (UserId)
 
 
 privateRequestEncoder : PrivateRequest -> Json.Encode.Value
@@ -703,8 +1240,14 @@ privateRequestEncoder enum =
             Json.Encode.object [ ( "PvqGetZkLinksSince", getZkLinksSinceEncoder inner ) ]
         PvqSearchZkNotes inner ->
             Json.Encode.object [ ( "PvqSearchZkNotes", zkNoteSearchEncoder inner ) ]
+        PvqGetChangedNotes inner ->
+            Json.Encode.object [ ( "PvqGetChangedNotes", getChangedNotesEncoder inner ) ]
+        PvqPowerDeletePreview inner ->
+            Json.Encode.object [ ( "PvqPowerDeletePreview", Json.Encode.list (tagSearchEncoder) inner ) ]
         PvqPowerDelete inner ->
-            Json.Encode.object [ ( "PvqPowerDelete", Json.Encode.list (tagSearchEncoder) inner ) ]
+            Json.Encode.object [ ( "PvqPowerDelete", powerDeleteConfirmEncoder inner ) ]
+        PvqUndoPowerDelete inner ->
+            Json.Encode.object [ ( "PvqUndoPowerDelete", Json.Encode.string inner ) ]
         PvqDeleteZkNote inner ->
             Json.Encode.object [ ( "PvqDeleteZkNote", zkNoteIdEncoder inner ) ]
         PvqSaveZkNote inner ->
@@ -715,14 +1258,54 @@ privateRequestEncoder enum =
             Json.Encode.object [ ( "PvqSaveZkNoteAndLinks", saveZkNoteAndLinksEncoder inner ) ]
         PvqSaveImportZkNotes inner ->
             Json.Encode.object [ ( "PvqSaveImportZkNotes", Json.Encode.list (importZkNoteEncoder) inner ) ]
+        PvqImportZkNotes inner ->
+            Json.Encode.object [ ( "PvqImportZkNotes", importZkNotesEncoder inner ) ]
+        PvqImportVault inner ->
+            Json.Encode.object [ ( "PvqImportVault", importVaultEncoder inner ) ]
+        PvqExportSite inner ->
+            Json.Encode.object [ ( "PvqExportSite", exportSiteEncoder inner ) ]
         PvqSetHomeNote inner ->
             Json.Encode.object [ ( "PvqSetHomeNote", zkNoteIdEncoder inner ) ]
         PvqSyncRemote ->
             Json.Encode.string "PvqSyncRemote"
         PvqSyncFiles inner ->
             Json.Encode.object [ ( "PvqSyncFiles", zkNoteSearchEncoder inner ) ]
+        PvqMakeSyncBundle inner ->
+            Json.Encode.object [ ( "PvqMakeSyncBundle", makeSyncBundleEncoder inner ) ]
+        PvqApplySyncBundle inner ->
+            Json.Encode.object [ ( "PvqApplySyncBundle", zkNoteIdEncoder inner ) ]
         PvqGetJobStatus inner ->
             Json.Encode.object [ ( "PvqGetJobStatus", Json.Encode.int inner ) ]
+        PvqGetStorageStats inner ->
+            Json.Encode.object [ ( "PvqGetStorageStats", getStorageStatsEncoder inner ) ]
+        PvqCreateApiToken inner ->
+            Json.Encode.object [ ( "PvqCreateApiToken", createApiTokenEncoder inner ) ]
+        PvqGetApiTokens ->
+            Json.Encode.string "PvqGetApiTokens"
+        PvqRevokeApiToken inner ->
+            Json.Encode.object [ ( "PvqRevokeApiToken", Json.Encode.string inner ) ]
+        PvqSetSharePermission inner ->
+            Json.Encode.object [ ( "PvqSetSharePermission", userSharePermissionEncoder inner ) ]
+        PvqGetSharePermissions inner ->
+            Json.Encode.object [ ( "PvqGetSharePermissions", zkNoteIdEncoder inner ) ]
+        PvqCreateShareLink inner ->
+            Json.Encode.object [ ( "PvqCreateShareLink", createShareLinkEncoder inner ) ]
+        PvqGetShareLinks inner ->
+            Json.Encode.object [ ( "PvqGetShareLinks", zkNoteIdEncoder inner ) ]
+        PvqRevokeShareLink inner ->
+            Json.Encode.object [ ( "PvqRevokeShareLink", Json.Encode.string inner ) ]
+        PvqGetAuditLog inner ->
+            Json.Encode.object [ ( "PvqGetAuditLog", auditQueryEncoder inner ) ]
+        PvqTotpStatus ->
+            Json.Encode.string "PvqTotpStatus"
+        PvqTotpBegin ->
+            Json.Encode.string "PvqTotpBegin"
+        PvqTotpConfirm inner ->
+            Json.Encode.object [ ( "PvqTotpConfirm", Json.Encode.string inner ) ]
+        PvqTotpDisable inner ->
+            Json.Encode.object [ ( "PvqTotpDisable", Json.Encode.string inner ) ]
+        PvqAdminResetTotp inner ->
+            Json.Encode.object [ ( "PvqAdminResetTotp", userIdEncoder inner ) ]
 
 type PrivateReply
     = PvyServerError (PrivateError)
@@ -738,17 +1321,35 @@ type PrivateReply
     | PvyZkNoteSearchResult (ZkNoteSearchResult)
     | PvyZkNoteIdSearchResult (ZkIdSearchResult)
     | PvyZkNoteAndLinksSearchResult (ZkNoteAndLinksSearchResult)
-    | PvyPowerDeleteComplete (Int)
+    | PvyChangedNotes (List (ChangedNote))
+    | PvyPowerDeletePreview (PowerDeletePreview)
+    | PvyPowerDeleteComplete (PowerDeleteBatch)
+    | PvyPowerDeleteUndone (Int)
     | PvyDeletedZkNote (ZkNoteId)
     | PvySavedZkNote (SavedZkNote)
     | PvySavedZkLinks
     | PvySavedZkNoteAndLinks (SavedZkNote)
     | PvySavedImportZkNotes
+    | PvyImportedZkNotes (List (ImportZkNoteResult))
     | PvyHomeNoteSet (ZkNoteId)
     | PvyJobStatus (JobStatus)
     | PvyJobNotFound (Int)
     | PvyFileSyncComplete
     | PvySyncComplete
+    | PvyStorageStats (StorageStats)
+    | PvyApiToken (NewApiToken)
+    | PvyApiTokens (List (ApiToken))
+    | PvyApiTokenRevoked (String)
+    | PvySharePermissionSet
+    | PvySharePermissions (List (UserSharePermission))
+    | PvyShareLink (ShareLink)
+    | PvyShareLinks (List (ShareLink))
+    | PvyShareLinkRevoked (String)
+    | PvyAuditLog (List (AuditEntry))
+    | PvyTotpStatus (Bool)
+    | PvyTotpEnrollment (TotpEnrollment)
+    | PvyTotpRecoveryCodes (List (String))
+    | PvyTotpDisabled
 
 
 privateReplyEncoder : PrivateReply -> Json.Encode.Value
@@ -780,8 +1381,14 @@ privateReplyEncoder enum =
             Json.Encode.object [ ( "PvyZkNoteIdSearchResult", zkIdSearchResultEncoder inner ) ]
         PvyZkNoteAndLinksSearchResult inner ->
             Json.Encode.object [ ( "PvyZkNoteAndLinksSearchResult", zkNoteAndLinksSearchResultEncoder inner ) ]
+        PvyChangedNotes inner ->
+            Json.Encode.object [ ( "PvyChangedNotes", Json.Encode.list (changedNoteEncoder) inner ) ]
+        PvyPowerDeletePreview inner ->
+            Json.Encode.object [ ( "PvyPowerDeletePreview", powerDeletePreviewEncoder inner ) ]
         PvyPowerDeleteComplete inner ->
-            Json.Encode.object [ ( "PvyPowerDeleteComplete", Json.Encode.int inner ) ]
+            Json.Encode.object [ ( "PvyPowerDeleteComplete", powerDeleteBatchEncoder inner ) ]
+        PvyPowerDeleteUndone inner ->
+            Json.Encode.object [ ( "PvyPowerDeleteUndone", Json.Encode.int inner ) ]
         PvyDeletedZkNote inner ->
             Json.Encode.object [ ( "PvyDeletedZkNote", zkNoteIdEncoder inner ) ]
         PvySavedZkNote inner ->
@@ -792,6 +1399,8 @@ privateReplyEncoder enum =
             Json.Encode.object [ ( "PvySavedZkNoteAndLinks", savedZkNoteEncoder inner ) ]
         PvySavedImportZkNotes ->
             Json.Encode.string "PvySavedImportZkNotes"
+        PvyImportedZkNotes inner ->
+            Json.Encode.object [ ( "PvyImportedZkNotes", Json.Encode.list (importZkNoteResultEncoder) inner ) ]
         PvyHomeNoteSet inner ->
             Json.Encode.object [ ( "PvyHomeNoteSet", zkNoteIdEncoder inner ) ]
         PvyJobStatus inner ->
@@ -802,6 +1411,34 @@ privateReplyEncoder enum =
             Json.Encode.string "PvyFileSyncComplete"
         PvySyncComplete ->
             Json.Encode.string "PvySyncComplete"
+        PvyStorageStats inner ->
+            Json.Encode.object [ ( "PvyStorageStats", storageStatsEncoder inner ) ]
+        PvyApiToken inner ->
+            Json.Encode.object [ ( "PvyApiToken", newApiTokenEncoder inner ) ]
+        PvyApiTokens inner ->
+            Json.Encode.object [ ( "PvyApiTokens", Json.Encode.list (apiTokenEncoder) inner ) ]
+        PvyApiTokenRevoked inner ->
+            Json.Encode.object [ ( "PvyApiTokenRevoked", Json.Encode.string inner ) ]
+        PvySharePermissionSet ->
+            Json.Encode.string "PvySharePermissionSet"
+        PvySharePermissions inner ->
+            Json.Encode.object [ ( "PvySharePermissions", Json.Encode.list (userSharePermissionEncoder) inner ) ]
+        PvyShareLink inner ->
+            Json.Encode.object [ ( "PvyShareLink", shareLinkEncoder inner ) ]
+        PvyShareLinks inner ->
+            Json.Encode.object [ ( "PvyShareLinks", Json.Encode.list (shareLinkEncoder) inner ) ]
+        PvyShareLinkRevoked inner ->
+            Json.Encode.object [ ( "PvyShareLinkRevoked", Json.Encode.string inner ) ]
+        PvyAuditLog inner ->
+            Json.Encode.object [ ( "PvyAuditLog", Json.Encode.list (auditEntryEncoder) inner ) ]
+        PvyTotpStatus inner ->
+            Json.Encode.object [ ( "PvyTotpStatus", Json.Encode.bool inner ) ]
+        PvyTotpEnrollment inner ->
+            Json.Encode.object [ ( "PvyTotpEnrollment", totpEnrollmentEncoder inner ) ]
+        PvyTotpRecoveryCodes inner ->
+            Json.Encode.object [ ( "PvyTotpRecoveryCodes", Json.Encode.list (Json.Encode.string) inner ) ]
+        PvyTotpDisabled ->
+            Json.Encode.string "PvyTotpDisabled"
 
 type PrivateError
     = PveString (String)
@@ -869,6 +1506,7 @@ zkNoteRqEncoder struct =
 
 type UploadReply
     = UrFilesUploaded (List (ZkListNote))
+    | UrFilesAttached (FilesAttached)
 
 
 uploadReplyEncoder : UploadReply -> Json.Encode.Value
@@ -876,6 +1514,54 @@ uploadReplyEncoder enum =
     case enum of
         UrFilesUploaded inner ->
             Json.Encode.object [ ( "UrFilesUploaded", Json.Encode.list (zkListNoteEncoder) inner ) ]
+        UrFilesAttached inner ->
+            Json.Encode.object [ ( "UrFilesAttached", filesAttachedEncoder inner ) ]
+
+type alias FilesAttached =
+    { notes : List (ZkListNote)
+    , links : List (ZkLink)
+    }
+
+
+filesAttachedEncoder : FilesAttached -> Json.Encode.Value
+filesAttachedEncoder struct =
+    Json.Encode.object
+        [ ( "notes", (Json.Encode.list (zkListNoteEncoder)) struct.notes )
+        , ( "links", (Json.Encode.list (zkLinkEncoder)) struct.links )
+        ]
+
+
+type alias CreateUpload =
+    { name : String
+    , size : Int
+    }
+
+
+createUploadEncoder : CreateUpload -> Json.Encode.Value
+createUploadEncoder struct =
+    Json.Encode.object
+        [ ( "name", (Json.Encode.string) struct.name )
+        , ( "size", (Json.Encode.int) struct.size )
+        ]
+
+
+type alias UploadStatus =
+    { id : String
+    , name : String
+    , size : Int
+    , received : Int
+    }
+
+
+uploadStatusEncoder : UploadStatus -> Json.Encode.Value
+uploadStatusEncoder struct =
+    Json.Encode.object
+        [ ( "id", (Json.Encode.string) struct.id )
+        , ( "name", (Json.Encode.string) struct.name )
+        , ( "size", (Json.Encode.int) struct.size )
+        , ( "received", (Json.Encode.int) struct.received )
+        ]
+
 
 type alias ZkNoteSearch =
     { tagsearch : List (TagSearch)
@@ -996,6 +1682,7 @@ type SearchMod
     | Create
     | Mod
     | Server
+    | Unviewed
 
 
 searchModEncoder : SearchMod -> Json.Encode.Value
@@ -1029,6 +1716,8 @@ searchModEncoder enum =
             Json.Encode.string "Mod"
         Server ->
             Json.Encode.string "Server"
+        Unviewed ->
+            Json.Encode.string "Unviewed"
 
 type AndOr
     = And
@@ -1075,49 +1764,129 @@ zkListNoteSearchResultEncoder struct =
         ]
 
 
-type alias ZkNoteSearchResult =
-    { notes : List (ZkNote)
-    , offset : Int
-    , what : String
+type alias PowerDeletePreview =
+    { notes : List (ZkListNote)
+    , skipped : Int
+    , token : String
     }
 
 
-zkNoteSearchResultEncoder : ZkNoteSearchResult -> Json.Encode.Value
-zkNoteSearchResultEncoder struct =
+powerDeletePreviewEncoder : PowerDeletePreview -> Json.Encode.Value
+powerDeletePreviewEncoder struct =
     Json.Encode.object
-        [ ( "notes", (Json.Encode.list (zkNoteEncoder)) struct.notes )
-        , ( "offset", (Json.Encode.int) struct.offset )
-        , ( "what", (Json.Encode.string) struct.what )
+        [ ( "notes", (Json.Encode.list (zkListNoteEncoder)) struct.notes )
+        , ( "skipped", (Json.Encode.int) struct.skipped )
+        , ( "token", (Json.Encode.string) struct.token )
         ]
 
 
-type alias ZkSearchResultHeader =
-    { what : String
-    , resulttype : ResultType
-    , offset : Int
+type alias PowerDeleteConfirm =
+    { search : List (TagSearch)
+    , token : String
     }
 
 
-zkSearchResultHeaderEncoder : ZkSearchResultHeader -> Json.Encode.Value
-zkSearchResultHeaderEncoder struct =
+powerDeleteConfirmEncoder : PowerDeleteConfirm -> Json.Encode.Value
+powerDeleteConfirmEncoder struct =
     Json.Encode.object
-        [ ( "what", (Json.Encode.string) struct.what )
-        , ( "resulttype", (resultTypeEncoder) struct.resulttype )
-        , ( "offset", (Json.Encode.int) struct.offset )
+        [ ( "search", (Json.Encode.list (tagSearchEncoder)) struct.search )
+        , ( "token", (Json.Encode.string) struct.token )
         ]
 
 
-type alias ZkNoteAndLinksSearchResult =
-    { notes : List (ZkNoteAndLinks)
-    , offset : Int
-    , what : String
+type alias PowerDeleteBatch =
+    { id : String
+    , count : Int
+    , undountil : Int
     }
 
 
-zkNoteAndLinksSearchResultEncoder : ZkNoteAndLinksSearchResult -> Json.Encode.Value
-zkNoteAndLinksSearchResultEncoder struct =
+powerDeleteBatchEncoder : PowerDeleteBatch -> Json.Encode.Value
+powerDeleteBatchEncoder struct =
     Json.Encode.object
-        [ ( "notes", (Json.Encode.list (zkNoteAndLinksEncoder)) struct.notes )
+        [ ( "id", (Json.Encode.string) struct.id )
+        , ( "count", (Json.Encode.int) struct.count )
+        , ( "undountil", (Json.Encode.int) struct.undountil )
+        ]
+
+
+type alias GetChangedNotes =
+    { since : Maybe (Int)
+    , offset : Int
+    , limit : Maybe (Int)
+    }
+
+
+getChangedNotesEncoder : GetChangedNotes -> Json.Encode.Value
+getChangedNotesEncoder struct =
+    Json.Encode.object
+        [ ( "since", (Maybe.withDefault Json.Encode.null << Maybe.map (Json.Encode.int)) struct.since )
+        , ( "offset", (Json.Encode.int) struct.offset )
+        , ( "limit", (Maybe.withDefault Json.Encode.null << Maybe.map (Json.Encode.int)) struct.limit )
+        ]
+
+
+type alias ChangedNote =
+    { note : ZkListNote
+    , editor : UserId
+    , editorname : String
+    , lastviewed : Maybe (Int)
+    }
+
+
+changedNoteEncoder : ChangedNote -> Json.Encode.Value
+changedNoteEncoder struct =
+    Json.Encode.object
+        [ ( "note", (zkListNoteEncoder) struct.note )
+        , ( "editor", (userIdEncoder) struct.editor )
+        , ( "editorname", (Json.Encode.string) struct.editorname )
+        , ( "lastviewed", (Maybe.withDefault Json.Encode.null << Maybe.map (Json.Encode.int)) struct.lastviewed )
+        ]
+
+
+type alias ZkNoteSearchResult =
+    { notes : List (ZkNote)
+    , offset : Int
+    , what : String
+    }
+
+
+zkNoteSearchResultEncoder : ZkNoteSearchResult -> Json.Encode.Value
+zkNoteSearchResultEncoder struct =
+    Json.Encode.object
+        [ ( "notes", (Json.Encode.list (zkNoteEncoder)) struct.notes )
+        , ( "offset", (Json.Encode.int) struct.offset )
+        , ( "what", (Json.Encode.string) struct.what )
+        ]
+
+
+type alias ZkSearchResultHeader =
+    { what : String
+    , resulttype : ResultType
+    , offset : Int
+    }
+
+
+zkSearchResultHeaderEncoder : ZkSearchResultHeader -> Json.Encode.Value
+zkSearchResultHeaderEncoder struct =
+    Json.Encode.object
+        [ ( "what", (Json.Encode.string) struct.what )
+        , ( "resulttype", (resultTypeEncoder) struct.resulttype )
+        , ( "offset", (Json.Encode.int) struct.offset )
+        ]
+
+
+type alias ZkNoteAndLinksSearchResult =
+    { notes : List (ZkNoteAndLinks)
+    , offset : Int
+    , what : String
+    }
+
+
+zkNoteAndLinksSearchResultEncoder : ZkNoteAndLinksSearchResult -> Json.Encode.Value
+zkNoteAndLinksSearchResultEncoder struct =
+    Json.Encode.object
+        [ ( "notes", (Json.Encode.list (zkNoteAndLinksEncoder)) struct.notes )
         , ( "offset", (Json.Encode.int) struct.offset )
         , ( "what", (Json.Encode.string) struct.what )
         ]
@@ -1206,6 +1975,7 @@ zkNoteDecoder =
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "createdate" (Json.Decode.int)))
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "changeddate" (Json.Decode.int)))
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "deleted" (Json.Decode.bool)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "encrypted" (Json.Decode.bool)))
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "filestatus" (fileStatusDecoder)))
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "server" (Json.Decode.string)))
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "sysids" (Json.Decode.list (zkNoteIdDecoder))))
@@ -1274,6 +2044,7 @@ saveZkNoteDecoder =
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "editable" (Json.Decode.bool)))
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "showtitle" (Json.Decode.bool)))
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "deleted" (Json.Decode.bool)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "encrypted" (Json.Decode.bool)))
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "what" (Json.Decode.nullable (Json.Decode.string))))
 
 
@@ -1390,6 +2161,210 @@ importZkNoteDecoder =
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "toLinks" (Json.Decode.list (Json.Decode.string))))
 
 
+importPolicyDecoder : Json.Decode.Decoder ImportPolicy
+importPolicyDecoder = 
+    Json.Decode.oneOf
+        [ Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Replace" ->
+                            Json.Decode.succeed Replace
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Append" ->
+                            Json.Decode.succeed Append
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Skip" ->
+                            Json.Decode.succeed Skip
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Duplicate" ->
+                            Json.Decode.succeed Duplicate
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        ]
+
+importZkNotesDecoder : Json.Decode.Decoder ImportZkNotes
+importZkNotesDecoder =
+    Json.Decode.succeed ImportZkNotes
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "notes" (Json.Decode.list (importZkNoteDecoder))))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "policy" (importPolicyDecoder)))
+
+
+importActionDecoder : Json.Decode.Decoder ImportAction
+importActionDecoder = 
+    Json.Decode.oneOf
+        [ Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Added" ->
+                            Json.Decode.succeed Added
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Replaced" ->
+                            Json.Decode.succeed Replaced
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Appended" ->
+                            Json.Decode.succeed Appended
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Unchanged" ->
+                            Json.Decode.succeed Unchanged
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Skipped" ->
+                            Json.Decode.succeed Skipped
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Duplicated" ->
+                            Json.Decode.succeed Duplicated
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        ]
+
+importZkNoteResultDecoder : Json.Decode.Decoder ImportZkNoteResult
+importZkNoteResultDecoder =
+    Json.Decode.succeed ImportZkNoteResult
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "title" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "id" (zkNoteIdDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "action" (importActionDecoder)))
+
+
+vaultSourceDecoder : Json.Decode.Decoder VaultSource
+vaultSourceDecoder = 
+    Json.Decode.oneOf
+        [ Json.Decode.map Zip (Json.Decode.field "Zip" (zkNoteIdDecoder))
+        , Json.Decode.map Directory (Json.Decode.field "Directory" (Json.Decode.string))
+        ]
+
+importVaultDecoder : Json.Decode.Decoder ImportVault
+importVaultDecoder =
+    Json.Decode.succeed ImportVault
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "source" (vaultSourceDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "policy" (importPolicyDecoder)))
+
+
+exportSubtreeDecoder : Json.Decode.Decoder ExportSubtree
+exportSubtreeDecoder =
+    Json.Decode.succeed ExportSubtree
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "root" (zkNoteIdDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "depth" (Json.Decode.int)))
+
+
+exportSelectionDecoder : Json.Decode.Decoder ExportSelection
+exportSelectionDecoder = 
+    Json.Decode.oneOf
+        [ Json.Decode.map Search (Json.Decode.field "Search" (zkNoteSearchDecoder))
+        , Json.Decode.map Subtree (Json.Decode.field "Subtree" (exportSubtreeDecoder))
+        ]
+
+exportFormatDecoder : Json.Decode.Decoder ExportFormat
+exportFormatDecoder = 
+    Json.Decode.oneOf
+        [ Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Markdown" ->
+                            Json.Decode.succeed Markdown
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Html" ->
+                            Json.Decode.succeed Html
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Obsidian" ->
+                            Json.Decode.succeed Obsidian
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        ]
+
+exportDestinationDecoder : Json.Decode.Decoder ExportDestination
+exportDestinationDecoder = 
+    Json.Decode.oneOf
+        [ Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "ZipNote" ->
+                            Json.Decode.succeed ZipNote
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.map ServerDirectory (Json.Decode.field "ServerDirectory" (Json.Decode.string))
+        ]
+
+exportSiteDecoder : Json.Decode.Decoder ExportSite
+exportSiteDecoder =
+    Json.Decode.succeed ExportSite
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "selection" (exportSelectionDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "format" (exportFormatDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "destination" (exportDestinationDecoder)))
+
+
+makeSyncBundleDecoder : Json.Decode.Decoder MakeSyncBundle
+makeSyncBundleDecoder =
+    Json.Decode.succeed MakeSyncBundle
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "after" (Json.Decode.nullable (Json.Decode.int))))
+
+
 getZkLinksDecoder : Json.Decode.Decoder GetZkLinks
 getZkLinksDecoder =
     Json.Decode.succeed GetZkLinks
@@ -1538,12 +2513,276 @@ jobStatusDecoder =
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "message" (Json.Decode.string)))
 
 
+getStorageStatsDecoder : Json.Decode.Decoder GetStorageStats
+getStorageStatsDecoder =
+    Json.Decode.succeed GetStorageStats
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "largest" (Json.Decode.int)))
+
+
+fileStatsDecoder : Json.Decode.Decoder FileStats
+fileStatsDecoder =
+    Json.Decode.succeed FileStats
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "id" (zkNoteIdDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "title" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "hash" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "size" (Json.Decode.int)))
+
+
+userStorageStatsDecoder : Json.Decode.Decoder UserStorageStats
+userStorageStatsDecoder =
+    Json.Decode.succeed UserStorageStats
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "user" (userIdDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "name" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "notes" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "archives" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "files" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "file_bytes" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "unique_file_bytes" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "largest_files" (Json.Decode.list (fileStatsDecoder))))
+
+
+storageStatsDecoder : Json.Decode.Decoder StorageStats
+storageStatsDecoder =
+    Json.Decode.succeed StorageStats
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "users" (Json.Decode.list (userStorageStatsDecoder))))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "file_bytes" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "unique_file_bytes" (Json.Decode.int)))
+
+
+sharePermissionDecoder : Json.Decode.Decoder SharePermission
+sharePermissionDecoder = 
+    Json.Decode.oneOf
+        [ Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Read" ->
+                            Json.Decode.succeed Read
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Comment" ->
+                            Json.Decode.succeed Comment
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Write" ->
+                            Json.Decode.succeed Write
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        ]
+
+userSharePermissionDecoder : Json.Decode.Decoder UserSharePermission
+userSharePermissionDecoder =
+    Json.Decode.succeed UserSharePermission
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "share" (zkNoteIdDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "user" (userIdDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "permission" (sharePermissionDecoder)))
+
+
+apiTokenScopeDecoder : Json.Decode.Decoder ApiTokenScope
+apiTokenScopeDecoder = 
+    Json.Decode.oneOf
+        [ Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "ReadOnly" ->
+                            Json.Decode.succeed ReadOnly
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "ReadWrite" ->
+                            Json.Decode.succeed ReadWrite
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "UploadOnly" ->
+                            Json.Decode.succeed UploadOnly
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        ]
+
+createApiTokenDecoder : Json.Decode.Decoder CreateApiToken
+createApiTokenDecoder =
+    Json.Decode.succeed CreateApiToken
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "name" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "scope" (apiTokenScopeDecoder)))
+
+
+apiTokenDecoder : Json.Decode.Decoder ApiToken
+apiTokenDecoder =
+    Json.Decode.succeed ApiToken
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "id" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "name" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "scope" (apiTokenScopeDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "createdate" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "lastused" (Json.Decode.nullable (Json.Decode.int))))
+
+
+newApiTokenDecoder : Json.Decode.Decoder NewApiToken
+newApiTokenDecoder =
+    Json.Decode.succeed NewApiToken
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "info" (apiTokenDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "token" (Json.Decode.string)))
+
+
+createShareLinkDecoder : Json.Decode.Decoder CreateShareLink
+createShareLinkDecoder =
+    Json.Decode.succeed CreateShareLink
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "zknote" (zkNoteIdDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "expires" (Json.Decode.nullable (Json.Decode.int))))
+
+
+shareLinkDecoder : Json.Decode.Decoder ShareLink
+shareLinkDecoder =
+    Json.Decode.succeed ShareLink
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "id" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "token" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "zknote" (zkNoteIdDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "createdate" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "expires" (Json.Decode.nullable (Json.Decode.int))))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "revoked" (Json.Decode.bool)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "views" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "lastview" (Json.Decode.nullable (Json.Decode.int))))
+
+
+getShareLinkNoteDecoder : Json.Decode.Decoder GetShareLinkNote
+getShareLinkNoteDecoder =
+    Json.Decode.succeed GetShareLinkNote
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "token" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "zknote" (Json.Decode.nullable (zkNoteIdDecoder))))
+
+
+auditActionDecoder : Json.Decode.Decoder AuditAction
+auditActionDecoder = 
+    Json.Decode.oneOf
+        [ Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "DeleteNote" ->
+                            Json.Decode.succeed DeleteNote
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "PowerDelete" ->
+                            Json.Decode.succeed PowerDelete
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "ShareChange" ->
+                            Json.Decode.succeed ShareChange
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "PublicChange" ->
+                            Json.Decode.succeed PublicChange
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Admin" ->
+                            Json.Decode.succeed Admin
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "PromoteAdmin" ->
+                            Json.Decode.succeed PromoteAdmin
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Sync" ->
+                            Json.Decode.succeed Sync
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        ]
+
+auditEntryDecoder : Json.Decode.Decoder AuditEntry
+auditEntryDecoder =
+    Json.Decode.succeed AuditEntry
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "id" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "user" (Json.Decode.nullable (userIdDecoder))))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "action" (auditActionDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "detail" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "createdate" (Json.Decode.int)))
+
+
+auditQueryDecoder : Json.Decode.Decoder AuditQuery
+auditQueryDecoder =
+    Json.Decode.succeed AuditQuery
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "user" (Json.Decode.nullable (userIdDecoder))))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "action" (Json.Decode.nullable (auditActionDecoder))))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "after" (Json.Decode.nullable (Json.Decode.int))))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "before" (Json.Decode.nullable (Json.Decode.int))))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "offset" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "limit" (Json.Decode.nullable (Json.Decode.int))))
+
+
+totpEnrollmentDecoder : Json.Decode.Decoder TotpEnrollment
+totpEnrollmentDecoder =
+    Json.Decode.succeed TotpEnrollment
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "uri" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "secret" (Json.Decode.string)))
+
+
+totpLoginDecoder : Json.Decode.Decoder TotpLogin
+totpLoginDecoder =
+    Json.Decode.succeed TotpLogin
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "uid" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "pwd" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "code" (Json.Decode.string)))
+
+
 publicRequestDecoder : Json.Decode.Decoder PublicRequest
 publicRequestDecoder = 
     Json.Decode.oneOf
         [ Json.Decode.map PbrGetZkNoteAndLinks (Json.Decode.field "PbrGetZkNoteAndLinks" (getZkNoteAndLinksDecoder))
         , Json.Decode.map PbrGetZknIfChanged (Json.Decode.field "PbrGetZknIfChanged" (getZknIfChangedDecoder))
         , Json.Decode.map PbrGetZkNotePubId (Json.Decode.field "PbrGetZkNotePubId" (Json.Decode.string))
+        , Json.Decode.map PbrGetShareLinkNote (Json.Decode.field "PbrGetShareLinkNote" (getShareLinkNoteDecoder))
         ]
 
 publicReplyDecoder : Json.Decode.Decoder PublicReply
@@ -1582,12 +2821,18 @@ privateRequestDecoder =
         , Json.Decode.map PvqGetArchiveZklinks (Json.Decode.field "PvqGetArchiveZklinks" (getArchiveZkLinksDecoder))
         , Json.Decode.map PvqGetZkLinksSince (Json.Decode.field "PvqGetZkLinksSince" (getZkLinksSinceDecoder))
         , Json.Decode.map PvqSearchZkNotes (Json.Decode.field "PvqSearchZkNotes" (zkNoteSearchDecoder))
-        , Json.Decode.map PvqPowerDelete (Json.Decode.field "PvqPowerDelete" (Json.Decode.list (tagSearchDecoder)))
+        , Json.Decode.map PvqGetChangedNotes (Json.Decode.field "PvqGetChangedNotes" (getChangedNotesDecoder))
+        , Json.Decode.map PvqPowerDeletePreview (Json.Decode.field "PvqPowerDeletePreview" (Json.Decode.list (tagSearchDecoder)))
+        , Json.Decode.map PvqPowerDelete (Json.Decode.field "PvqPowerDelete" (powerDeleteConfirmDecoder))
+        , Json.Decode.map PvqUndoPowerDelete (Json.Decode.field "PvqUndoPowerDelete" (Json.Decode.string))
         , Json.Decode.map PvqDeleteZkNote (Json.Decode.field "PvqDeleteZkNote" (zkNoteIdDecoder))
         , Json.Decode.map PvqSaveZkNote (Json.Decode.field "PvqSaveZkNote" (saveZkNoteDecoder))
         , Json.Decode.map PvqSaveZkLinks (Json.Decode.field "PvqSaveZkLinks" (saveZkLinksDecoder))
         , Json.Decode.map PvqSaveZkNoteAndLinks (Json.Decode.field "PvqSaveZkNoteAndLinks" (saveZkNoteAndLinksDecoder))
         , Json.Decode.map PvqSaveImportZkNotes (Json.Decode.field "PvqSaveImportZkNotes" (Json.Decode.list (importZkNoteDecoder)))
+        , Json.Decode.map PvqImportZkNotes (Json.Decode.field "PvqImportZkNotes" (importZkNotesDecoder))
+        , Json.Decode.map PvqImportVault (Json.Decode.field "PvqImportVault" (importVaultDecoder))
+        , Json.Decode.map PvqExportSite (Json.Decode.field "PvqExportSite" (exportSiteDecoder))
         , Json.Decode.map PvqSetHomeNote (Json.Decode.field "PvqSetHomeNote" (zkNoteIdDecoder))
         , Json.Decode.string
             |> Json.Decode.andThen
@@ -1599,7 +2844,48 @@ privateRequestDecoder =
                             Json.Decode.fail <| "Unexpected variant " ++ unexpected
                 )
         , Json.Decode.map PvqSyncFiles (Json.Decode.field "PvqSyncFiles" (zkNoteSearchDecoder))
+        , Json.Decode.map PvqMakeSyncBundle (Json.Decode.field "PvqMakeSyncBundle" (makeSyncBundleDecoder))
+        , Json.Decode.map PvqApplySyncBundle (Json.Decode.field "PvqApplySyncBundle" (zkNoteIdDecoder))
         , Json.Decode.map PvqGetJobStatus (Json.Decode.field "PvqGetJobStatus" (Json.Decode.int))
+        , Json.Decode.map PvqGetStorageStats (Json.Decode.field "PvqGetStorageStats" (getStorageStatsDecoder))
+        , Json.Decode.map PvqCreateApiToken (Json.Decode.field "PvqCreateApiToken" (createApiTokenDecoder))
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "PvqGetApiTokens" ->
+                            Json.Decode.succeed PvqGetApiTokens
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.map PvqRevokeApiToken (Json.Decode.field "PvqRevokeApiToken" (Json.Decode.string))
+        , Json.Decode.map PvqSetSharePermission (Json.Decode.field "PvqSetSharePermission" (userSharePermissionDecoder))
+        , Json.Decode.map PvqGetSharePermissions (Json.Decode.field "PvqGetSharePermissions" (zkNoteIdDecoder))
+        , Json.Decode.map PvqCreateShareLink (Json.Decode.field "PvqCreateShareLink" (createShareLinkDecoder))
+        , Json.Decode.map PvqGetShareLinks (Json.Decode.field "PvqGetShareLinks" (zkNoteIdDecoder))
+        , Json.Decode.map PvqRevokeShareLink (Json.Decode.field "PvqRevokeShareLink" (Json.Decode.string))
+        , Json.Decode.map PvqGetAuditLog (Json.Decode.field "PvqGetAuditLog" (auditQueryDecoder))
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "PvqTotpStatus" ->
+                            Json.Decode.succeed PvqTotpStatus
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "PvqTotpBegin" ->
+                            Json.Decode.succeed PvqTotpBegin
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.map PvqTotpConfirm (Json.Decode.field "PvqTotpConfirm" (Json.Decode.string))
+        , Json.Decode.map PvqTotpDisable (Json.Decode.field "PvqTotpDisable" (Json.Decode.string))
+        , Json.Decode.map PvqAdminResetTotp (Json.Decode.field "PvqAdminResetTotp" (userIdDecoder))
         ]
 
 privateReplyDecoder : Json.Decode.Decoder PrivateReply
@@ -1626,7 +2912,10 @@ privateReplyDecoder =
         , Json.Decode.map PvyZkNoteSearchResult (Json.Decode.field "PvyZkNoteSearchResult" (zkNoteSearchResultDecoder))
         , Json.Decode.map PvyZkNoteIdSearchResult (Json.Decode.field "PvyZkNoteIdSearchResult" (zkIdSearchResultDecoder))
         , Json.Decode.map PvyZkNoteAndLinksSearchResult (Json.Decode.field "PvyZkNoteAndLinksSearchResult" (zkNoteAndLinksSearchResultDecoder))
-        , Json.Decode.map PvyPowerDeleteComplete (Json.Decode.field "PvyPowerDeleteComplete" (Json.Decode.int))
+        , Json.Decode.map PvyChangedNotes (Json.Decode.field "PvyChangedNotes" (Json.Decode.list (changedNoteDecoder)))
+        , Json.Decode.map PvyPowerDeletePreview (Json.Decode.field "PvyPowerDeletePreview" (powerDeletePreviewDecoder))
+        , Json.Decode.map PvyPowerDeleteComplete (Json.Decode.field "PvyPowerDeleteComplete" (powerDeleteBatchDecoder))
+        , Json.Decode.map PvyPowerDeleteUndone (Json.Decode.field "PvyPowerDeleteUndone" (Json.Decode.int))
         , Json.Decode.map PvyDeletedZkNote (Json.Decode.field "PvyDeletedZkNote" (zkNoteIdDecoder))
         , Json.Decode.map PvySavedZkNote (Json.Decode.field "PvySavedZkNote" (savedZkNoteDecoder))
         , Json.Decode.string
@@ -1648,6 +2937,7 @@ privateReplyDecoder =
                         unexpected ->
                             Json.Decode.fail <| "Unexpected variant " ++ unexpected
                 )
+        , Json.Decode.map PvyImportedZkNotes (Json.Decode.field "PvyImportedZkNotes" (Json.Decode.list (importZkNoteResultDecoder)))
         , Json.Decode.map PvyHomeNoteSet (Json.Decode.field "PvyHomeNoteSet" (zkNoteIdDecoder))
         , Json.Decode.map PvyJobStatus (Json.Decode.field "PvyJobStatus" (jobStatusDecoder))
         , Json.Decode.map PvyJobNotFound (Json.Decode.field "PvyJobNotFound" (Json.Decode.int))
@@ -1669,6 +2959,36 @@ privateReplyDecoder =
                         unexpected ->
                             Json.Decode.fail <| "Unexpected variant " ++ unexpected
                 )
+        , Json.Decode.map PvyStorageStats (Json.Decode.field "PvyStorageStats" (storageStatsDecoder))
+        , Json.Decode.map PvyApiToken (Json.Decode.field "PvyApiToken" (newApiTokenDecoder))
+        , Json.Decode.map PvyApiTokens (Json.Decode.field "PvyApiTokens" (Json.Decode.list (apiTokenDecoder)))
+        , Json.Decode.map PvyApiTokenRevoked (Json.Decode.field "PvyApiTokenRevoked" (Json.Decode.string))
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "PvySharePermissionSet" ->
+                            Json.Decode.succeed PvySharePermissionSet
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
+        , Json.Decode.map PvySharePermissions (Json.Decode.field "PvySharePermissions" (Json.Decode.list (userSharePermissionDecoder)))
+        , Json.Decode.map PvyShareLink (Json.Decode.field "PvyShareLink" (shareLinkDecoder))
+        , Json.Decode.map PvyShareLinks (Json.Decode.field "PvyShareLinks" (Json.Decode.list (shareLinkDecoder)))
+        , Json.Decode.map PvyShareLinkRevoked (Json.Decode.field "PvyShareLinkRevoked" (Json.Decode.string))
+        , Json.Decode.map PvyAuditLog (Json.Decode.field "PvyAuditLog" (Json.Decode.list (auditEntryDecoder)))
+        , Json.Decode.map PvyTotpStatus (Json.Decode.field "PvyTotpStatus" (Json.Decode.bool))
+        , Json.Decode.map PvyTotpEnrollment (Json.Decode.field "PvyTotpEnrollment" (totpEnrollmentDecoder))
+        , Json.Decode.map PvyTotpRecoveryCodes (Json.Decode.field "PvyTotpRecoveryCodes" (Json.Decode.list (Json.Decode.string)))
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "PvyTotpDisabled" ->
+                            Json.Decode.succeed PvyTotpDisabled
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
         ]
 
 privateErrorDecoder : Json.Decode.Decoder PrivateError
@@ -1714,8 +3034,32 @@ uploadReplyDecoder : Json.Decode.Decoder UploadReply
 uploadReplyDecoder = 
     Json.Decode.oneOf
         [ Json.Decode.map UrFilesUploaded (Json.Decode.field "UrFilesUploaded" (Json.Decode.list (zkListNoteDecoder)))
+        , Json.Decode.map UrFilesAttached (Json.Decode.field "UrFilesAttached" (filesAttachedDecoder))
         ]
 
+filesAttachedDecoder : Json.Decode.Decoder FilesAttached
+filesAttachedDecoder =
+    Json.Decode.succeed FilesAttached
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "notes" (Json.Decode.list (zkListNoteDecoder))))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "links" (Json.Decode.list (zkLinkDecoder))))
+
+
+createUploadDecoder : Json.Decode.Decoder CreateUpload
+createUploadDecoder =
+    Json.Decode.succeed CreateUpload
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "name" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "size" (Json.Decode.int)))
+
+
+uploadStatusDecoder : Json.Decode.Decoder UploadStatus
+uploadStatusDecoder =
+    Json.Decode.succeed UploadStatus
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "id" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "name" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "size" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "received" (Json.Decode.int)))
+
+
 zkNoteSearchDecoder : Json.Decode.Decoder ZkNoteSearch
 zkNoteSearchDecoder =
     Json.Decode.succeed ZkNoteSearch
@@ -1977,6 +3321,15 @@ searchModDecoder =
                         unexpected ->
                             Json.Decode.fail <| "Unexpected variant " ++ unexpected
                 )
+        , Json.Decode.string
+            |> Json.Decode.andThen
+                (\x ->
+                    case x of
+                        "Unviewed" ->
+                            Json.Decode.succeed Unviewed
+                        unexpected ->
+                            Json.Decode.fail <| "Unexpected variant " ++ unexpected
+                )
         ]
 
 andOrDecoder : Json.Decode.Decoder AndOr
@@ -2018,6 +3371,46 @@ zkListNoteSearchResultDecoder =
         |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "what" (Json.Decode.string)))
 
 
+powerDeletePreviewDecoder : Json.Decode.Decoder PowerDeletePreview
+powerDeletePreviewDecoder =
+    Json.Decode.succeed PowerDeletePreview
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "notes" (Json.Decode.list (zkListNoteDecoder))))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "skipped" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "token" (Json.Decode.string)))
+
+
+powerDeleteConfirmDecoder : Json.Decode.Decoder PowerDeleteConfirm
+powerDeleteConfirmDecoder =
+    Json.Decode.succeed PowerDeleteConfirm
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "search" (Json.Decode.list (tagSearchDecoder))))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "token" (Json.Decode.string)))
+
+
+powerDeleteBatchDecoder : Json.Decode.Decoder PowerDeleteBatch
+powerDeleteBatchDecoder =
+    Json.Decode.succeed PowerDeleteBatch
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "id" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "count" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "undountil" (Json.Decode.int)))
+
+
+getChangedNotesDecoder : Json.Decode.Decoder GetChangedNotes
+getChangedNotesDecoder =
+    Json.Decode.succeed GetChangedNotes
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "since" (Json.Decode.nullable (Json.Decode.int))))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "offset" (Json.Decode.int)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "limit" (Json.Decode.nullable (Json.Decode.int))))
+
+
+changedNoteDecoder : Json.Decode.Decoder ChangedNote
+changedNoteDecoder =
+    Json.Decode.succeed ChangedNote
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "note" (zkListNoteDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "editor" (userIdDecoder)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "editorname" (Json.Decode.string)))
+        |> Json.Decode.andThen (\x -> Json.Decode.map x (Json.Decode.field "lastviewed" (Json.Decode.nullable (Json.Decode.int))))
+
+
 zkNoteSearchResultDecoder : Json.Decode.Decoder ZkNoteSearchResult
 zkNoteSearchResultDecoder =
     Json.Decode.succeed ZkNoteSearchResult
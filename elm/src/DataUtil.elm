@@ -84,6 +84,9 @@ getPrqNoteInfo pr =
         PbrGetZkNotePubId _ ->
             Nothing
 
+        PbrGetShareLinkNote _ ->
+            Nothing
+
 
 zkNoteIdToString : ZkNoteId -> String
 zkNoteIdToString id =
@@ -374,6 +377,7 @@ saveZkNote fzn =
     , editable = fzn.editableValue
     , showtitle = fzn.showtitle
     , deleted = fzn.deleted
+    , encrypted = fzn.encrypted
     , what = Nothing
     }
 
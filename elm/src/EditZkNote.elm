@@ -217,6 +217,7 @@ type alias Model =
     , editableValue : Bool -- is this note editable by other users?
     , showtitle : Bool
     , deleted : Bool
+    , encrypted : Bool -- content was encrypted by the cli; saved back as-is.
     , filestatus : Data.FileStatus
     , pubidtxt : String
     , title : String
@@ -764,6 +765,7 @@ toZkNote model =
                 , createdate = createdate
                 , changeddate = changeddate
                 , deleted = model.deleted
+                , encrypted = model.encrypted
                 , filestatus = model.filestatus
                 , sysids = getSysids model
                 , server = model.server
@@ -784,6 +786,7 @@ sznFromModel model =
     , editable = model.editableValue
     , showtitle = model.showtitle
     , deleted = model.deleted
+    , encrypted = model.encrypted
     , what = Nothing
     }
 
@@ -907,6 +910,7 @@ revert model =
                                 EM.initMd r.content
                     , editableValue = r.editable
                     , showtitle = r.showtitle
+                    , encrypted = r.encrypted
                     , zklDict = model.initialZklDict
                 }
             )
@@ -1703,6 +1707,10 @@ zknview stylePalette zone size spmodel zknSearchResult recentZkns trqs tjobs not
                 && not search
                 && not model.deleted
 
+        -- encrypted content is opaque here, so title and content stay as-is.
+        contentEditable =
+            editable && not model.encrypted
+
         -- super lame math because images suck in html/elm-ui
         mdw =
             min 1000
@@ -1846,7 +1854,7 @@ zknview stylePalette zone size spmodel zknSearchResult recentZkns trqs tjobs not
             EI.text
                 (EE.onLoseFocus (TitleFocus False)
                     :: E.htmlAttribute (HA.id "title-edit")
-                    :: (if editable then
+                    :: (if contentEditable then
                             if isdirty then
                                 [ E.focused [ EBd.glow TC.darkYellow 3 ] ]
 
@@ -1858,7 +1866,7 @@ zknview stylePalette zone size spmodel zknSearchResult recentZkns trqs tjobs not
                        )
                 )
                 { onChange =
-                    if editable then
+                    if contentEditable then
                         OnTitleChanged
 
                     else
@@ -1953,6 +1961,11 @@ zknview stylePalette zone size spmodel zknSearchResult recentZkns trqs tjobs not
                      else
                         [ E.paragraph [ EF.bold, EE.onClick (TitleFocus True) ]
                             [ E.text model.title ]
+                        , if model.encrypted then
+                            E.el [ EF.italic, EF.color TC.darkGrey ] <| E.text "encrypted"
+
+                          else
+                            E.none
                         , EI.button
                             [ E.focused []
                             ]
@@ -2136,7 +2149,7 @@ zknview stylePalette zone size spmodel zknSearchResult recentZkns trqs tjobs not
                 , E.paddingXY 5 0
                 ]
                 [ EI.multiline
-                    ([ if editable then
+                    ([ if contentEditable then
                         EF.color TC.black
 
                        else
@@ -2152,7 +2165,7 @@ zknview stylePalette zone size spmodel zknSearchResult recentZkns trqs tjobs not
                            )
                     )
                     { onChange =
-                        if editable then
+                        if contentEditable then
                             OnMarkdownInput
 
                         else
@@ -2759,6 +2772,7 @@ initFull fui ld zknas mobile =
       , editable = zknote.editable
       , editableValue = zknote.editableValue
       , deleted = zknote.deleted
+      , encrypted = zknote.encrypted
       , filestatus = zknote.filestatus
       , showtitle = zknote.showtitle
       , createdate = Just zknote.createdate
@@ -2819,6 +2833,7 @@ initNew fui ld links mobile =
     , editable = True
     , editableValue = False
     , deleted = False
+    , encrypted = False
     , filestatus = Data.NotAFile
     , showtitle = True
     , createdate = Nothing
@@ -2877,6 +2892,7 @@ sznToZkn uid uname unote sysids sdzn szn =
     , createdate = sdzn.changeddate
     , changeddate = sdzn.changeddate
     , deleted = szn.deleted
+    , encrypted = szn.encrypted
     , filestatus = Data.NotAFile
     , sysids = sysids
     , server = sdzn.server
@@ -3573,7 +3589,11 @@ update noteCache msg model =
                     ( model, None )
 
         OnTitleChanged t ->
-            ( { model | title = t }, None )
+            if model.encrypted then
+                ( model, None )
+
+            else
+                ( { model | title = t }, None )
 
         OnPubidChanged t ->
             ( { model | pubidtxt = t }, None )
@@ -3628,6 +3648,7 @@ update noteCache msg model =
                             , editable = False
                             , showtitle = True
                             , deleted = False
+                            , encrypted = False
                             , what = Nothing
                             }
                     in
@@ -3672,24 +3693,28 @@ update noteCache msg model =
                     ( model, None )
 
         OnMarkdownInput newMarkdown ->
-            let
-                cells =
-                    newMarkdown
-                        |> MC.mdCells
-                        |> Result.withDefault (CellDict Dict.empty)
+            if model.encrypted then
+                ( model, None )
 
-                ( cc, _ ) =
-                    evalCellsFully
-                        (mkCc cells)
-            in
-            ( { model
-                | edMarkdown =
-                    EM.initMd <|
+            else
+                let
+                    cells =
                         newMarkdown
-                , cells = getCd cc
-              }
-            , None
-            )
+                            |> MC.mdCells
+                            |> Result.withDefault (CellDict Dict.empty)
+
+                    ( cc, _ ) =
+                        evalCellsFully
+                            (mkCc cells)
+                in
+                ( { model
+                    | edMarkdown =
+                        EM.initMd <|
+                            newMarkdown
+                    , cells = getCd cc
+                  }
+                , None
+                )
 
         OnSchelmeCodeChanged name string ->
             let
@@ -4192,7 +4217,7 @@ update noteCache msg model =
                                         |> Result.toMaybe
                                 )
             in
-            if edok then
+            if edok && not model.encrypted then
                 ( { model | blockEdit = eblk bidx }, None )
 
             else
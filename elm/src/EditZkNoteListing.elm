@@ -27,14 +27,14 @@ type Msg
     | TitlePress
     | CreatedPress
     | ChangedPress
-    | PowerDeletePress Int
+    | PowerDeletePress
     | SPMsg SP.Msg
     | DialogMsg D.Msg
     | SearchHistoryPress
 
 
 type DWhich
-    = DeleteAll
+    = DeleteAll Data.PowerDeleteConfirm
     | DeleteComplete
 
 
@@ -49,11 +49,45 @@ type Command
     | Done
     | Import
     | None
-    | PowerDelete (List Data.TagSearch)
+    | PowerDeletePreview (List Data.TagSearch)
+    | PowerDelete Data.PowerDeleteConfirm
     | SPMod (SP.Model -> ( SP.Model, SP.Command ))
     | SearchHistory
 
 
+onPowerDeletePreview : Int -> Data.PowerDeletePreview -> LoginData -> Model -> SP.Model -> Data.ZkListNoteSearchResult -> Model
+onPowerDeletePreview fontsize preview ld model spmodel notes =
+    case SP.getSearch spmodel of
+        Nothing ->
+            model
+
+        Just s ->
+            -- the server checks the token against the search, so if the search changed
+            -- since the preview the delete fails instead of deleting other notes.
+            { model
+                | dialog =
+                    Just <|
+                        ( D.init
+                            ("delete these "
+                                ++ String.fromInt (List.length preview.notes)
+                                ++ " notes matching this search?\n"
+                                ++ String.concat (List.map showTagSearch s.tagsearch)
+                                ++ (if preview.skipped > 0 then
+                                        "\n"
+                                            ++ String.fromInt preview.skipped
+                                            ++ " matching notes belong to other users, and won't be deleted."
+
+                                    else
+                                        ""
+                                   )
+                            )
+                            True
+                            (\size -> E.map (\_ -> ()) (listview fontsize ld size model spmodel { notes | notes = preview.notes }))
+                        , DeleteAll { search = s.tagsearch, token = preview.token }
+                        )
+            }
+
+
 onPowerDeleteComplete : Int -> Int -> LoginData -> Model -> SP.Model -> Data.ZkListNoteSearchResult -> Model
 onPowerDeleteComplete fontsize count ld model spmodel notes =
     { model
@@ -125,7 +159,7 @@ listview fontsize ld _ model spmodel notes =
             , E.row [ E.spacing 8 ]
                 [ EI.button Common.buttonStyle { onPress = Just NewPress, label = E.text "new" }
                 , EI.button Common.buttonStyle { onPress = Just ImportPress, label = E.text "import" }
-                , EI.button Common.buttonStyle { onPress = Just (PowerDeletePress fontsize), label = E.text "delete..." }
+                , EI.button Common.buttonStyle { onPress = Just PowerDeletePress, label = E.text "delete..." }
                 ]
             , E.column
                 [ E.padding 8
@@ -249,26 +283,13 @@ update msg model spmodel notes ld =
         SearchHistoryPress ->
             ( model, SearchHistory )
 
-        PowerDeletePress fontsize ->
+        PowerDeletePress ->
             case SP.getSearch spmodel of
                 Nothing ->
                     ( model, None )
 
                 Just s ->
-                    ( { model
-                        | dialog =
-                            Just <|
-                                ( D.init
-                                    ("delete all notes matching this search?\n"
-                                        ++ String.concat (List.map showTagSearch s.tagsearch)
-                                    )
-                                    True
-                                    (\size -> E.map (\_ -> ()) (listview fontsize ld size model spmodel notes))
-                                , DeleteAll
-                                )
-                      }
-                    , None
-                    )
+                    ( model, PowerDeletePreview s.tagsearch )
 
         DialogMsg dm ->
             case model.dialog of
@@ -277,13 +298,8 @@ update msg model spmodel notes ld =
                         ( D.Cancel, _ ) ->
                             ( { model | dialog = Nothing }, None )
 
-                        ( D.Ok, DeleteAll ) ->
-                            case SP.getSearch spmodel of
-                                Just s ->
-                                    ( { model | dialog = Nothing }, PowerDelete s.tagsearch )
-
-                                Nothing ->
-                                    ( { model | dialog = Nothing }, None )
+                        ( D.Ok, DeleteAll pd ) ->
+                            ( { model | dialog = Nothing }, PowerDelete pd )
 
                         ( D.Ok, DeleteComplete ) ->
                             ( { model | dialog = Nothing }, None )
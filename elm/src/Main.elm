@@ -57,6 +57,7 @@ import TSet
 import TagAThing
 import TagFiles
 import TagNotes
+import TotpDialog
 import Task
 import Time
 import Toop
@@ -116,6 +117,7 @@ type Msg
     | RequestProgress String Http.Progress
     | RequestsDialogMsg (GD.Msg RequestsDialog.Msg)
     | JobsDialogMsg (GD.Msg JobsDialog.Msg)
+    | TotpDialogMsg (GD.Msg TotpDialog.Msg)
     | MdInlineXformMsg (GD.Msg MdInlineXform.Msg)
     | MdInlineXformCmd MdInlineXform.Command
     | TagFilesMsg (TagAThing.Msg TagFiles.Msg)
@@ -152,6 +154,7 @@ type State
     | MessageNLink MessageNLink.GDModel State
     | RequestsDialog RequestsDialog.GDModel State
     | JobsDialog JobsDialog.GDModel State
+    | TotpDialog TotpDialog.GDModel State
     | TagFiles (TagAThing.Model TagFiles.Model TagFiles.Msg TagFiles.Command) LoginData State
     | TagNotes TagNotes.Model LoginData State
     | InviteUser (TagAThing.Model InviteUser.Model InviteUser.Msg InviteUser.Command) LoginData State
@@ -708,6 +711,9 @@ stateRoute state =
         JobsDialog _ _ ->
             top
 
+        TotpDialog _ _ ->
+            top
+
         TagFiles _ _ _ ->
             top
 
@@ -908,6 +914,9 @@ showMessage msg =
         JobsDialogMsg _ ->
             "JobsDialogMsg"
 
+        TotpDialogMsg _ ->
+            "TotpDialogMsg"
+
         MdInlineXformMsg _ ->
             "MdInlineXformMsg"
 
@@ -1014,6 +1023,9 @@ showState state =
         JobsDialog _ _ ->
             "JobsDialog"
 
+        TotpDialog _ _ ->
+            "TotpDialog"
+
         MdInlineXform _ _ ->
             "MdInlineXform"
 
@@ -1135,6 +1147,9 @@ viewState size state model =
             -- render is at the layout level, not here.
             E.none
 
+        TotpDialog _ _ ->
+            E.none
+
         MdInlineXform _ _ ->
             E.none
 
@@ -1233,6 +1248,9 @@ stateLogin state =
         JobsDialog _ instate ->
             stateLogin instate
 
+        TotpDialog _ instate ->
+            stateLogin instate
+
         MdInlineXform _ instate ->
             stateLogin instate
 
@@ -1330,6 +1348,9 @@ prevState st =
         JobsDialog _ state ->
             Just state
 
+        TotpDialog _ state ->
+            Just state
+
         TagFiles _ _ state ->
             Just state
 
@@ -1362,6 +1383,15 @@ sendUIMsg fui msg =
             }
 
 
+sendTotpMsg : FileUrlInfo -> Data.TotpLogin -> Cmd Msg
+sendTotpMsg fui tl =
+    Http.post
+        { url = fui.location ++ "/totp"
+        , body = Http.jsonBody (Data.totpLoginEncoder tl)
+        , expect = Http.expectJson UserReplyData OD.userResponseDecoder
+        }
+
+
 sendAIMsg : String -> OD.AdminRequest -> Cmd Msg
 sendAIMsg location msg =
     sendAIMsgExp location msg AdminReplyData
@@ -1438,6 +1468,7 @@ sendSearch model search =
                         , editable = False
                         , showtitle = True
                         , deleted = False
+                        , encrypted = False
                         , what = Nothing
                         }
                     , links =
@@ -1646,6 +1677,12 @@ view model =
                             (Just { width = min 600 model.size.width, height = min 500 model.size.height })
                             { dm | model = model.jobs }
 
+            TotpDialog tdm _ ->
+                Html.map TotpDialogMsg <|
+                    GD.layout
+                        (Just { width = min 600 model.size.width, height = min 200 model.size.height })
+                        tdm
+
             MdInlineXform gdm _ ->
                 if model.mobile then
                     E.layout [] <|
@@ -2840,7 +2877,27 @@ actualupdate msg model =
                 Ok uiresponse ->
                     case uiresponse of
                         OD.UrpServerError e ->
-                            ( displayMessageDialog model <| e, Cmd.none )
+                            case state of
+                                Login lgst _ ->
+                                    if e == "two factor code required" then
+                                        ( { model
+                                            | state =
+                                                TotpDialog
+                                                    (TotpDialog.init lgst.userId
+                                                        lgst.password
+                                                        Common.buttonStyle
+                                                        (E.map (\_ -> ()) (viewState model.size model.state model))
+                                                    )
+                                                    model.state
+                                          }
+                                        , Cmd.none
+                                        )
+
+                                    else
+                                        ( displayMessageDialog model <| e, Cmd.none )
+
+                                _ ->
+                                    ( displayMessageDialog model <| e, Cmd.none )
 
                         OD.UrpRegistrationSent ->
                             case model.state of
@@ -3167,10 +3224,18 @@ actualupdate msg model =
                         Data.PvyServerError e ->
                             ( displayMessageDialog model <| DataUtil.showPrivateError e, Cmd.none )
 
-                        Data.PvyPowerDeleteComplete count ->
+                        Data.PvyPowerDeletePreview pdp ->
+                            case model.state of
+                                EditZkNoteListing mod li ->
+                                    ( { model | state = EditZkNoteListing (EditZkNoteListing.onPowerDeletePreview model.stylePalette.fontSize pdp li mod model.spmodel model.zknSearchResult) li }, Cmd.none )
+
+                                _ ->
+                                    ( model, Cmd.none )
+
+                        Data.PvyPowerDeleteComplete batch ->
                             case model.state of
                                 EditZkNoteListing mod li ->
-                                    ( { model | state = EditZkNoteListing (EditZkNoteListing.onPowerDeleteComplete model.stylePalette.fontSize count li mod model.spmodel model.zknSearchResult) li }, Cmd.none )
+                                    ( { model | state = EditZkNoteListing (EditZkNoteListing.onPowerDeleteComplete model.stylePalette.fontSize batch.count li mod model.spmodel model.zknSearchResult) li }, Cmd.none )
 
                                 _ ->
                                     ( model, Cmd.none )
@@ -3445,6 +3510,58 @@ actualupdate msg model =
                         Data.PvySyncComplete ->
                             ( model, Cmd.none )
 
+                        -- not requested by the web client yet.
+                        Data.PvyStorageStats _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyApiToken _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyApiTokens _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyApiTokenRevoked _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvySharePermissionSet ->
+                            ( model, Cmd.none )
+
+                        Data.PvySharePermissions _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyShareLink _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyShareLinks _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyShareLinkRevoked _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyAuditLog _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyPowerDeleteUndone _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyTotpStatus _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyTotpEnrollment _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyTotpRecoveryCodes _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyTotpDisabled ->
+                            ( model, Cmd.none )
+
+                        Data.PvyChangedNotes _ ->
+                            ( model, Cmd.none )
+
+                        Data.PvyImportedZkNotes _ ->
+                            ( model, Cmd.none )
+
         ( ViewMsg em, View es ) ->
             let
                 ( emod, ecmd ) =
@@ -3742,33 +3859,42 @@ actualupdate msg model =
                     ( displayMessageDialog model <| Util.httpErrorString e, Cmd.none )
 
                 Ok ( _, ziresponse ) ->
-                    case ziresponse of
-                        Data.UrFilesUploaded files ->
-                            ( { model
-                                | trackedRequests =
-                                    case Dict.get what model.trackedRequests.requests of
-                                        Just (FileUpload fu) ->
-                                            let
-                                                trqs =
-                                                    model.trackedRequests
-                                            in
-                                            { trqs
-                                                | requests =
-                                                    Dict.insert what
-                                                        (FileUpload
-                                                            { fu
-                                                                | files =
-                                                                    Just files
-                                                            }
-                                                        )
-                                                        trqs.requests
-                                            }
-
-                                        _ ->
+                    let
+                        files =
+                            case ziresponse of
+                                Data.UrFilesUploaded fs ->
+                                    fs
+
+                                -- the web client doesn't upload to a target note, but
+                                -- the reply still has the new file notes.
+                                Data.UrFilesAttached fa ->
+                                    fa.notes
+                    in
+                    ( { model
+                        | trackedRequests =
+                            case Dict.get what model.trackedRequests.requests of
+                                Just (FileUpload fu) ->
+                                    let
+                                        trqs =
                                             model.trackedRequests
-                              }
-                            , Cmd.none
-                            )
+                                    in
+                                    { trqs
+                                        | requests =
+                                            Dict.insert what
+                                                (FileUpload
+                                                    { fu
+                                                        | files =
+                                                            Just files
+                                                    }
+                                                )
+                                                trqs.requests
+                                    }
+
+                                _ ->
+                                    model.trackedRequests
+                      }
+                    , Cmd.none
+                    )
 
         ( RequestProgress a b, _ ) ->
             let
@@ -3876,6 +4002,20 @@ actualupdate msg model =
         ( JobsDialogMsg _, _ ) ->
             ( model, Cmd.none )
 
+        ( WkMsg (Ok key), TotpDialog bs prevstate ) ->
+            case Toop.T4 key.key key.ctrl key.alt key.shift of
+                Toop.T4 "Enter" False False False ->
+                    handleTotpDialog model prevstate (GD.update (GD.EltMsg TotpDialog.OkClick) bs)
+
+                _ ->
+                    ( model, Cmd.none )
+
+        ( TotpDialogMsg bm, TotpDialog bs prevstate ) ->
+            handleTotpDialog model prevstate (GD.update bm bs)
+
+        ( TotpDialogMsg _, _ ) ->
+            ( model, Cmd.none )
+
         ( JobsPollTick _, _ ) ->
             ( model
             , model.jobs.jobs
@@ -4813,10 +4953,16 @@ handleEditZkNoteListing model login ( emod, ecmd ) =
             , Cmd.none
             )
 
-        EditZkNoteListing.PowerDelete s ->
+        EditZkNoteListing.PowerDeletePreview s ->
             ( { model | state = EditZkNoteListing emod login }
             , sendZIMsg model.fui
-                (Data.PvqPowerDelete s)
+                (Data.PvqPowerDeletePreview s)
+            )
+
+        EditZkNoteListing.PowerDelete pd ->
+            ( { model | state = EditZkNoteListing emod login }
+            , sendZIMsg model.fui
+                (Data.PvqPowerDelete pd)
             )
 
         EditZkNoteListing.SearchHistory ->
@@ -4886,6 +5032,19 @@ handleLogin model route ( lmod, lcmd ) =
             )
 
 
+handleTotpDialog : Model -> State -> GD.Transition TotpDialog.GDModel TotpDialog.Command -> ( Model, Cmd Msg )
+handleTotpDialog model prevstate gdmsg =
+    case gdmsg of
+        GD.Dialog nmod ->
+            ( { model | state = TotpDialog nmod prevstate }, Cmd.none )
+
+        GD.Ok (TotpDialog.Login tl) ->
+            ( { model | state = prevstate }, sendTotpMsg model.fui tl )
+
+        GD.Cancel ->
+            ( { model | state = prevstate }, Cmd.none )
+
+
 handleInvited : Model -> ( Invited.Model, Invited.Cmd ) -> ( Model, Cmd Msg )
 handleInvited model ( lmod, lcmd ) =
     case lcmd of
@@ -110,6 +110,8 @@ view nblevel hmod =
                         , thingAndDef 2 "bm'1784853290802'" "modification date before 1784853290802."
                         , thingAndDef 2 "ac'1784853290802'" "creation date after 1784853290802."
                         , thingAndDef 2 "s'local'" "notes created on current server, not remote."
+                        , thingAndDef 2 "v''" "notes someone else changed since you last viewed them."
+                        , thingAndDef 2 "v'1784853290802'" "notes someone else changed after 1784853290802."
                         ]
 
                 FullMonty ->
@@ -107,6 +107,9 @@ showSearchMod mod =
         Server ->
             "Server"
 
+        Unviewed ->
+            "Unviewed"
+
 
 showAndOr : AndOr -> String
 showAndOr ao =
@@ -327,6 +330,9 @@ printSearchMod mod =
         Server ->
             "s"
 
+        Unviewed ->
+            "v"
+
 
 printAndOr : AndOr -> String
 printAndOr ao =
@@ -382,6 +388,8 @@ searchMod =
             |. symbol "m"
         , succeed Server
             |. symbol "s"
+        , succeed Unviewed
+            |. symbol "v"
         ]
 
 
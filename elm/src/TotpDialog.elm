@@ -0,0 +1,78 @@
+module TotpDialog exposing (..)
+
+import Data
+import Element as E exposing (Element)
+import Element.Font as EF
+import Element.Input as EI
+import GenDialog as GD
+import Util
+
+
+type alias Model =
+    { uid : String
+    , pwd : String
+    , code : String
+    }
+
+
+type Msg
+    = CodeChanged String
+    | OkClick
+    | CancelClick
+    | Noop
+
+
+type Command
+    = Login Data.TotpLogin
+
+
+type alias GDModel =
+    GD.Model Model Msg Command
+
+
+init : String -> String -> List (E.Attribute Msg) -> Element () -> GDModel
+init uid pwd buttonStyle underLay =
+    { view = view buttonStyle
+    , update = update
+    , model = { uid = uid, pwd = pwd, code = "" }
+    , underLay = underLay
+    }
+
+
+view : List (E.Attribute Msg) -> Maybe Util.Size -> Model -> Element Msg
+view buttonStyle mbsize model =
+    E.column
+        [ E.width (mbsize |> Maybe.map .width |> Maybe.map E.px |> Maybe.withDefault E.fill)
+        , E.height E.shrink
+        , E.spacing 15
+        ]
+        [ E.el [ E.centerX, EF.bold ] <| E.text "two factor code"
+        , EI.text []
+            { onChange = CodeChanged
+            , text = model.code
+            , placeholder = Nothing
+            , label = EI.labelAbove [] <| E.text "code from your authenticator app, or a recovery code"
+            }
+        , E.row [ E.width E.fill, E.spacing 10 ]
+            [ EI.button (E.centerX :: buttonStyle)
+                { onPress = Just OkClick, label = E.text "log in" }
+            , EI.button (E.centerX :: buttonStyle)
+                { onPress = Just CancelClick, label = E.text "cancel" }
+            ]
+        ]
+
+
+update : Msg -> Model -> GD.Transition Model Command
+update msg model =
+    case msg of
+        CodeChanged s ->
+            GD.Dialog { model | code = s }
+
+        OkClick ->
+            GD.Ok (Login { uid = model.uid, pwd = model.pwd, code = model.code })
+
+        CancelClick ->
+            GD.Cancel
+
+        Noop ->
+            GD.Dialog model
@@ -28,7 +28,7 @@ use zkprotocol::{
     SaveZkNoteAndLinks,
   },
   private::{PrivateReply, PrivateRequest},
-  upload::UploadReply,
+  upload::{FilesAttached, UploadReply},
 };
 
 // #[tokio::main(flavor = "multi_thread")]
@@ -412,6 +412,7 @@ pub async fn yeet_service(
                             editable: false,
                             showtitle: false,
                             deleted: false,
+                            encrypted: false,
                             what: None,
                           };
                           let res = client
@@ -501,7 +502,8 @@ pub async fn yeet_service(
                                 )
                                 .await?;
                                 match uploadreply {
-                                  UploadReply::UrFilesUploaded(notes) => {
+                                  UploadReply::UrFilesUploaded(notes)
+                                  | UploadReply::UrFilesAttached(FilesAttached { notes, .. }) => {
                                     fs::remove_file(f.clone())?;
                                     fs::remove_dir(wkdir)?;
                                     let id = notes
@@ -538,7 +540,8 @@ pub async fn yeet_service(
                                 )
                                 .await?;
                                 match uploadreply {
-                                  UploadReply::UrFilesUploaded(notes) => {
+                                  UploadReply::UrFilesUploaded(notes)
+                                  | UploadReply::UrFilesAttached(FilesAttached { notes, .. }) => {
                                     for f in paths {
                                       fs::remove_file(f)?;
                                     }
@@ -565,6 +568,7 @@ pub async fn yeet_service(
                                         editable: false,
                                         showtitle: false,
                                         deleted: false,
+                                        encrypted: false,
                                         what: None,
                                       },
                                       links: notes
@@ -662,6 +666,7 @@ pub async fn yeet_service(
                             editable: zkn.editable,
                             showtitle: zkn.showtitle,
                             deleted: zkn.deleted,
+                            encrypted: zkn.encrypted,
                             what: None,
                           },
                           links: linkids
@@ -835,7 +840,10 @@ async fn resize_video(
   let thumbfile = video_resize(idstring).await?;
 
   // upload resized thumb
-  let zkprotocol::upload::UploadReply::UrFilesUploaded(uploadreply) = upload_file(
+  let (UploadReply::UrFilesUploaded(uploadreply)
+  | UploadReply::UrFilesAttached(FilesAttached {
+    notes: uploadreply, ..
+  })) = upload_file(
     &client,
     Path::new(thumbfile.as_str()),
     &omfn.token,
@@ -916,7 +924,10 @@ async fn resize_image(
   let thumbfile = image_resize(idstring).await?;
 
   // upload resized thumb
-  let zkprotocol::upload::UploadReply::UrFilesUploaded(uploadreply) = upload_file(
+  let (UploadReply::UrFilesUploaded(uploadreply)
+  | UploadReply::UrFilesAttached(FilesAttached {
+    notes: uploadreply, ..
+  })) = upload_file(
     &client,
     Path::new(thumbfile.as_str()),
     &omfn.token,
@@ -0,0 +1,83 @@
+use crate::error as zkerr;
+use orgauth::data::UserId;
+use orgauth::util::now;
+use rusqlite::{params, Connection};
+use zkprotocol::content::{AuditAction, AuditEntry, AuditQuery};
+
+fn action_str(action: &AuditAction) -> &'static str {
+  match action {
+    AuditAction::DeleteNote => "deletenote",
+    AuditAction::PowerDelete => "powerdelete",
+    AuditAction::ShareChange => "sharechange",
+    AuditAction::PublicChange => "publicchange",
+    AuditAction::Admin => "admin",
+    AuditAction::PromoteAdmin => "promoteadmin",
+    AuditAction::Sync => "sync",
+  }
+}
+
+fn action(s: &str) -> Result<AuditAction, zkerr::Error> {
+  match s {
+    "deletenote" => Ok(AuditAction::DeleteNote),
+    "powerdelete" => Ok(AuditAction::PowerDelete),
+    "sharechange" => Ok(AuditAction::ShareChange),
+    "publicchange" => Ok(AuditAction::PublicChange),
+    "admin" => Ok(AuditAction::Admin),
+    "promoteadmin" => Ok(AuditAction::PromoteAdmin),
+    "sync" => Ok(AuditAction::Sync),
+    _ => Err(zkerr::Error::String(format!("unknown audit action: {}", s))),
+  }
+}
+
+// user is None for actions from the command line.
+pub fn audit(
+  conn: &Connection,
+  user: Option<UserId>,
+  action: AuditAction,
+  detail: &str,
+) -> Result<(), zkerr::Error> {
+  conn.execute(
+    "insert into audit (user, action, detail, createdate) values (?1, ?2, ?3, ?4)",
+    params![
+      user.map(|u| *u.to_i64()),
+      action_str(&action),
+      detail,
+      now()?
+    ],
+  )?;
+  Ok(())
+}
+
+pub fn read_audit(conn: &Connection, aq: &AuditQuery) -> Result<Vec<AuditEntry>, zkerr::Error> {
+  let mut pstmt = conn.prepare(
+    "select id, user, action, detail, createdate from audit
+      where (?1 is null or user = ?1)
+        and (?2 is null or action = ?2)
+        and (?3 is null or createdate > ?3)
+        and (?4 is null or createdate < ?4)
+      order by createdate desc, id desc
+      limit ?5 offset ?6",
+  )?;
+  let r = pstmt
+    .query_and_then(
+      params![
+        aq.user.map(|u| *u.to_i64()),
+        aq.action.as_ref().map(action_str),
+        aq.after,
+        aq.before,
+        aq.limit.unwrap_or(-1),
+        aq.offset
+      ],
+      |row| {
+        Ok::<AuditEntry, zkerr::Error>(AuditEntry {
+          id: row.get(0)?,
+          user: row.get::<usize, Option<i64>>(1)?.map(UserId::Uid),
+          action: action(row.get::<usize, String>(2)?.as_str())?,
+          detail: row.get(3)?,
+          createdate: row.get(4)?,
+        })
+      },
+    )?
+    .collect::<Result<Vec<AuditEntry>, zkerr::Error>>()?;
+  Ok(r)
+}
@@ -0,0 +1,210 @@
+use crate::config::Quotas;
+use crate::error as zkerr;
+use crate::jobs::JobMonitor;
+use crate::sqldata::{self, LapinInfo};
+use crate::sync::{apply_sync_stream, write_sync_file};
+use orgauth::data::{User, UserId};
+use orgauth::endpoints::Callbacks;
+use rusqlite::{params, Connection};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use tokio_util::io::{ReaderStream, StreamReader};
+use uuid::Uuid;
+use zkprotocol::content::{Server, ZkNoteId};
+use zkprotocol::sync_data::{SyncMessage, SyncStart};
+
+// sync bundles, for syncing without a network.  a zip with the sync stream in
+// sync.ndjson, and the files it refers to in files/<hash>.
+
+const STREAM: &str = "sync.ndjson";
+const FILES: &str = "files/";
+
+// write a bundle of the user's changes after 'after'.  returns the bundle's SyncStart,
+// and the number of files in it.
+pub async fn write_bundle(
+  conn: Arc<Connection>,
+  files_dir: &Path,
+  file_tmp_path: &Path,
+  uid: UserId,
+  after: Option<i64>,
+  out: &Path,
+  callbacks: &mut Callbacks,
+  monitor: &dyn JobMonitor,
+) -> Result<(SyncStart, usize), Box<dyn Error>> {
+  let tmp = file_tmp_path.join(Uuid::new_v4().to_string());
+  let ss = write_sync_file(conn, files_dir, uid, after, &tmp, callbacks, monitor).await?;
+
+  let r = (|| -> Result<usize, zkerr::Error> {
+    // files for the notes in the stream.
+    let mut hashes = BTreeSet::new();
+    for line in BufReader::new(File::open(&tmp)?).lines() {
+      match serde_json::from_str::<SyncMessage>(line?.as_str())? {
+        SyncMessage::ZkNote(_, Some(fi)) => {
+          if files_dir.join(fi.hash.as_str()).exists() {
+            hashes.insert(fi.hash);
+          }
+        }
+        SyncMessage::SyncError(e) => return Err(zkerr::Error::String(e)),
+        _ => (),
+      }
+    }
+
+    write!(monitor, "writing bundle with {} files", hashes.len());
+    let mut zw = zip::ZipWriter::new(File::create(out)?);
+    zw.start_file(STREAM, zip::write::SimpleFileOptions::default())?;
+    std::io::copy(&mut File::open(&tmp)?, &mut zw)?;
+    for hash in hashes.iter() {
+      zw.start_file(
+        format!("{}{}", FILES, hash),
+        zip::write::SimpleFileOptions::default(),
+      )?;
+      std::io::copy(&mut File::open(files_dir.join(hash.as_str()))?, &mut zw)?;
+    }
+    zw.finish()?;
+    Ok(hashes.len())
+  })();
+
+  std::fs::remove_file(&tmp)?;
+
+  Ok((ss, r?))
+}
+
+// apply a bundle through the same path as a streamed sync, then add its files.
+// returns the number of files added.
+pub async fn read_bundle(
+  conn: &Connection,
+  lapin_info: &Option<LapinInfo>,
+  server: &Server,
+  user: &User,
+  files_dir: &Path,
+  file_tmp_path: &Path,
+  quotas: &Quotas,
+  path: &Path,
+  callbacks: &mut Callbacks,
+  monitor: &dyn JobMonitor,
+) -> Result<usize, Box<dyn Error>> {
+  let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+
+  let tmp = file_tmp_path.join(Uuid::new_v4().to_string());
+  std::io::copy(
+    &mut archive
+      .by_name(STREAM)
+      .map_err(|_| zkerr::Error::String(format!("{} not found in bundle", STREAM)))?,
+    &mut File::create(&tmp)?,
+  )?;
+
+  let tr = conn.unchecked_transaction()?;
+
+  let r = async {
+    let mut br = StreamReader::new(ReaderStream::new(tokio::fs::File::open(&tmp).await?));
+    apply_sync_stream(
+      conn, lapin_info, server, user, files_dir, None, None, None, None, callbacks, true, &mut br,
+    )
+    .await
+  }
+  .await;
+  std::fs::remove_file(&tmp)?;
+  r?;
+
+  let mut count = 0;
+  for i in 0..archive.len() {
+    let mut zf = archive.by_index(i)?;
+    let hash = match zf.name().strip_prefix(FILES) {
+      Some(h) if !h.is_empty() && h.chars().all(|c| c.is_ascii_hexdigit()) => h.to_string(),
+      _ => continue,
+    };
+    let finalpath = files_dir.join(hash.as_str());
+    if finalpath.exists() {
+      continue;
+    }
+    // only files the sync stream made records for.
+    let size: i64 = match conn.query_row(
+      "select size from file where hash = ?1",
+      params![hash],
+      |row| row.get(0),
+    ) {
+      Ok(s) => s,
+      Err(rusqlite::Error::QueryReturnedNoRows) => continue,
+      Err(e) => Err(e)?,
+    };
+    match sqldata::check_file_quota(conn, quotas, user.id, hash.as_str(), size) {
+      Ok(()) => (),
+      Err(zkerr::Error::QuotaExceeded(s)) => {
+        write!(monitor, "not adding file {}, quota exceeded: {}", hash, s);
+        continue;
+      }
+      Err(e) => Err(e)?,
+    }
+
+    let temphashpath = file_tmp_path.join(hash.as_str());
+    std::io::copy(&mut zf, &mut File::create(&temphashpath)?)?;
+    if sha256::try_digest(temphashpath.as_path())? != hash {
+      std::fs::remove_file(temphashpath)?;
+      return Err(zkerr::Error::String(format!("bundle file hash doesn't match: {}", hash)).into());
+    }
+    std::fs::rename(temphashpath, finalpath)?;
+
+    // add to files_dir table.
+    match conn.execute(
+      "insert into files_dir (filename) values (?1)",
+      params![hash],
+    ) {
+      Ok(_) => (),
+      Err(e) => {
+        // if insert error, log it but proceed.
+        log::error!("insert into files_dir error {:?}", e);
+      }
+    }
+    count = count + 1;
+  }
+
+  tr.commit()?;
+
+  Ok(count)
+}
+
+// write a bundle and save it as a file note for the user.
+pub async fn make_bundle_note(
+  conn: Arc<Connection>,
+  server: &Server,
+  lapin_info: &Option<LapinInfo>,
+  files_dir: &Path,
+  file_tmp_path: &Path,
+  quotas: &Quotas,
+  uid: UserId,
+  after: Option<i64>,
+  callbacks: &mut Callbacks,
+  monitor: &dyn JobMonitor,
+) -> Result<(ZkNoteId, usize), Box<dyn Error>> {
+  let tmp = file_tmp_path.join(Uuid::new_v4().to_string());
+  let (ss, files) = write_bundle(
+    conn.clone(),
+    files_dir,
+    file_tmp_path,
+    uid,
+    after,
+    &tmp,
+    callbacks,
+    monitor,
+  )
+  .await?;
+
+  let (_id, zni, _fid) = sqldata::make_file_note(
+    &conn,
+    server,
+    lapin_info,
+    files_dir,
+    uid,
+    &format!("syncbundle-{}.zip", ss.before),
+    &tmp,
+    false,
+    quotas,
+  )
+  .await?;
+
+  Ok((zni, files))
+}
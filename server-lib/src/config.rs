@@ -14,5 +14,79 @@ pub struct Config {
   pub error_index_note: Option<i64>,
   pub tauri_mode: bool,
   pub aqmp_uri: Option<String>,
+  #[serde(default)]
+  pub quotas: Quotas,
+  #[serde(default)]
+  pub session: SessionConfig,
+  #[serde(default)]
+  pub ratelimits: RateLimits,
   pub orgauth_config: orgauth_data::Config,
 }
+
+// limits on uploaded/synced file storage, in bytes.  None means no limit.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Quotas {
+  pub user_bytes: Option<i64>,
+  pub global_bytes: Option<i64>,
+}
+
+// session cookie settings.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SessionConfig {
+  // file holding the cookie signing key.  if None, the key is kept in the db.
+  // either way it's generated on first run.
+  pub key_file: Option<PathBuf>,
+  pub cookie_secure: bool,
+  pub same_site: SameSite,
+  pub ttl_days: i64,
+}
+
+impl Default for SessionConfig {
+  fn default() -> Self {
+    SessionConfig {
+      key_file: None,
+      cookie_secure: false,
+      same_site: SameSite::Lax,
+      ttl_days: 52 * 7,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum SameSite {
+  Strict,
+  Lax,
+  None,
+}
+
+// request limits for login and public endpoints.  None means no limit.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RateLimits {
+  // requests per minute from one ip, to /user, /totp and /public.
+  pub ip_per_minute: Option<u32>,
+  // login attempts per minute for one account.
+  pub account_logins_per_minute: Option<u32>,
+  // failed logins before an account is locked out.  the lockout starts at
+  // lockout_secs and doubles with each further failure, up to lockout_max_secs.
+  pub lockout_failures: u32,
+  pub lockout_secs: u64,
+  pub lockout_max_secs: u64,
+  // addresses of reverse proxies whose X-Forwarded-For header is believed.  requests
+  // from anywhere else are limited by their own address.
+  pub trusted_proxies: Vec<String>,
+}
+
+impl Default for RateLimits {
+  fn default() -> Self {
+    RateLimits {
+      ip_per_minute: Some(120),
+      account_logins_per_minute: Some(10),
+      lockout_failures: 5,
+      lockout_secs: 30,
+      lockout_max_secs: 60 * 60,
+      trusted_proxies: Vec::new(),
+    }
+  }
+}
@@ -30,6 +30,13 @@ pub enum Error {
   NoteIsPrivate,
   NoteIsReadOnly,
   NotLoggedIn,
+  NotAdmin,
+  QuotaExceeded(String),
+  InvalidApiToken,
+  ApiTokenScope,
+  TotpRequired,
+  InvalidTotp,
+  UploadBusy,
   EmptyStream,
 }
 
@@ -84,6 +91,17 @@ pub fn to_orgauth_error(e: Error) -> orgauth::error::Error {
     Error::NoteIsPrivate => orgauth::error::Error::String("note is private".to_string()),
     Error::NoteIsReadOnly => orgauth::error::Error::String("note is readonly".to_string()),
     Error::NotLoggedIn => orgauth::error::Error::String("not logged in".to_string()),
+    Error::NotAdmin => orgauth::error::Error::String("admin privileges required".to_string()),
+    Error::QuotaExceeded(s) => orgauth::error::Error::String(format!("quota exceeded: {}", s)),
+    Error::InvalidApiToken => orgauth::error::Error::String("invalid api token".to_string()),
+    Error::TotpRequired => orgauth::error::Error::String("two factor code required".to_string()),
+    Error::InvalidTotp => orgauth::error::Error::String("invalid two factor code".to_string()),
+    Error::UploadBusy => {
+      orgauth::error::Error::String("another request is using this upload".to_string())
+    }
+    Error::ApiTokenScope => {
+      orgauth::error::Error::String("api token scope doesn't allow this request".to_string())
+    }
     Error::EmptyStream => orgauth::error::Error::String("empty stream".to_string()),
   }
 }
@@ -118,6 +136,13 @@ impl fmt::Display for Error {
       Error::NoteIsPrivate => write!(f, "{}", "note is private"),
       Error::NoteIsReadOnly => write!(f, "{}", "note is readonly"),
       Error::NotLoggedIn => write!(f, "{}", "not logged in"),
+      Error::NotAdmin => write!(f, "{}", "admin privileges required"),
+      Error::QuotaExceeded(s) => write!(f, "quota exceeded: {}", s),
+      Error::InvalidApiToken => write!(f, "{}", "invalid api token"),
+      Error::ApiTokenScope => write!(f, "{}", "api token scope doesn't allow this request"),
+      Error::TotpRequired => write!(f, "{}", "two factor code required"),
+      Error::InvalidTotp => write!(f, "{}", "invalid two factor code"),
+      Error::UploadBusy => write!(f, "{}", "another request is using this upload"),
       Error::EmptyStream => write!(f, "{}", "empty stream"),
     }
   }
@@ -147,6 +172,13 @@ impl fmt::Debug for Error {
       Error::NoteIsPrivate => write!(f, "{}", "note is private"),
       Error::NoteIsReadOnly => write!(f, "{}", "note is readonly"),
       Error::NotLoggedIn => write!(f, "{}", "not logged in"),
+      Error::NotAdmin => write!(f, "{}", "admin privileges required"),
+      Error::QuotaExceeded(s) => write!(f, "quota exceeded: {}", s),
+      Error::InvalidApiToken => write!(f, "{}", "invalid api token"),
+      Error::ApiTokenScope => write!(f, "{}", "api token scope doesn't allow this request"),
+      Error::TotpRequired => write!(f, "{}", "two factor code required"),
+      Error::InvalidTotp => write!(f, "{}", "invalid two factor code"),
+      Error::UploadBusy => write!(f, "{}", "another request is using this upload"),
       Error::EmptyStream => write!(f, "{}", "empty stream"),
     }
   }
@@ -245,3 +277,8 @@ impl From<lapin::Error> for Error {
     Error::Lapin(e)
   }
 }
+impl From<zip::result::ZipError> for Error {
+  fn from(e: zip::result::ZipError) -> Self {
+    Error::String(e.to_string())
+  }
+}
@@ -0,0 +1,961 @@
+use crate::config::Quotas;
+use crate::error as zkerr;
+use crate::jobs::JobMonitor;
+use crate::search::search_zknotes;
+use crate::sqldata::{self, LapinInfo};
+use orgauth::data::UserId;
+use orgauth::dbfun::user_id;
+use regex::{Captures, Regex};
+use rusqlite::Connection;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+use zkprotocol::content::{
+  Direction, ExportFormat, ExportSelection, FileStatus, Server, ZkNote, ZkNoteId,
+};
+use zkprotocol::search::{ResultType, SearchResult};
+
+// static site export.  notes are pages in the top folder, files are in
+// files/<hash>/<name>, and index lists all the pages.
+
+pub enum ExportWriter {
+  Dir(PathBuf),
+  Zip(zip::ZipWriter<File>),
+}
+
+impl ExportWriter {
+  fn write(&mut self, path: &str, data: &[u8]) -> Result<(), zkerr::Error> {
+    match self {
+      ExportWriter::Dir(dir) => {
+        let p = dir.join(path);
+        if let Some(parent) = p.parent() {
+          std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(p, data)?;
+      }
+      ExportWriter::Zip(zw) => {
+        zw.start_file(path, zip::write::SimpleFileOptions::default())?;
+        zw.write_all(data)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn copy(&mut self, path: &str, from: &Path) -> Result<(), zkerr::Error> {
+    match self {
+      ExportWriter::Dir(dir) => {
+        let p = dir.join(path);
+        if let Some(parent) = p.parent() {
+          std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(from, p)?;
+      }
+      ExportWriter::Zip(zw) => {
+        zw.start_file(path, zip::write::SimpleFileOptions::default())?;
+        std::io::copy(&mut File::open(from)?, zw)?;
+      }
+    }
+    Ok(())
+  }
+
+  pub fn finish(self) -> Result<(), zkerr::Error> {
+    match self {
+      ExportWriter::Dir(_) => (),
+      ExportWriter::Zip(zw) => {
+        zw.finish()?;
+      }
+    }
+    Ok(())
+  }
+}
+
+struct ExportNote {
+  id: i64,
+  note: ZkNote,
+  // page or file path in the export.
+  path: String,
+}
+
+// title to a file name; only letters, digits, '-', '_' and '.' are kept.
+fn file_name(title: &str) -> String {
+  let s: String = title
+    .trim()
+    .chars()
+    .map(|c| match c {
+      c if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' => c,
+      _ => '-',
+    })
+    .collect();
+  let s = s.trim_matches(|c| c == '-' || c == '.').to_string();
+  if s.is_empty() {
+    "note".to_string()
+  } else {
+    s
+  }
+}
+
+fn html_escape(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn is_image(path: &str) -> bool {
+  let p = path.to_lowercase();
+  [".png", ".jpg", ".jpeg", ".gif", ".svg", ".webp"]
+    .iter()
+    .any(|e| p.ends_with(e))
+}
+
+fn uuid_of(id: &ZkNoteId) -> Option<Uuid> {
+  match id {
+    ZkNoteId::Zni(uuid) => Some(*uuid),
+    ZkNoteId::ArchiveZni(_, _) => None,
+  }
+}
+
+// notes the user can read, from the selection.
+fn select_notes(
+  conn: &Connection,
+  files_dir: &Path,
+  uid: UserId,
+  selection: &ExportSelection,
+) -> Result<Vec<(i64, ZkNote)>, zkerr::Error> {
+  let ids: Vec<ZkNoteId> = match selection {
+    ExportSelection::Search(zns) => {
+      let mut zns = zns.clone();
+      zns.resulttype = ResultType::RtId;
+      match search_zknotes(conn, files_dir, uid, &zns)? {
+        SearchResult::SrId(r) => r.notes,
+        _ => return Err(zkerr::Error::String("unexpected search result".to_string())),
+      }
+    }
+    ExportSelection::Subtree(st) => {
+      // breadth first along links from the root, to depth.
+      let mut ids = Vec::new();
+      let mut seen = BTreeSet::new();
+      let mut queue = VecDeque::new();
+      let rootid = sqldata::note_id_for_zknoteid(conn, &st.root)?;
+      seen.insert(rootid);
+      queue.push_back((st.root, rootid, 0));
+      while let Some((zknid, id, depth)) = queue.pop_front() {
+        ids.push(zknid);
+        // don't follow links through notes the user can't read.
+        if depth >= st.depth
+          || sqldata::zknote_access_id(conn, Some(uid), id)? == sqldata::Access::Private
+        {
+          continue;
+        }
+        for l in sqldata::read_zklinks(conn, uid, id)? {
+          if let Direction::To = l.direction {
+            let oid = sqldata::note_id_for_zknoteid(conn, &l.otherid)?;
+            if seen.insert(oid) {
+              queue.push_back((l.otherid, oid, depth + 1));
+            }
+          }
+        }
+      }
+      ids
+    }
+  };
+
+  let sysid = user_id(conn, "system")?;
+  let mut notes = Vec::new();
+  for zknid in ids.iter() {
+    // skip notes that aren't readable, rather than failing the export.
+    match sqldata::read_zknote(conn, files_dir, Some(uid), zknid) {
+      Ok((id, note)) => {
+        // system notes like 'public' aren't content.
+        if note.user.to_i64() != sysid.to_i64() && !note.deleted {
+          notes.push((id, note));
+        }
+      }
+      Err(zkerr::Error::NoteIsPrivate) => (),
+      Err(e) => return Err(e),
+    }
+  }
+  Ok(notes)
+}
+
+fn note_re() -> Regex {
+  Regex::new(r#"<note\s+id\s*=\s*"([0-9a-fA-F-]{36})"[^>]*/>"#).unwrap()
+}
+
+// in app links, in markdown links or html attributes.
+fn applink_re() -> Regex {
+  Regex::new(r#"(["(])(?:https?://[^/\s")]+)?/(note|file)/([0-9a-fA-F-]{36})"#).unwrap()
+}
+
+// relative path for a link to uuid; file links only go to file notes.
+fn target<'a>(
+  bypage: &BTreeMap<Uuid, &'a ExportNote>,
+  ext: &str,
+  uuid: &str,
+  file: bool,
+) -> Option<(String, &'a ExportNote)> {
+  let en = *bypage.get(&Uuid::parse_str(uuid).ok()?)?;
+  match (en.note.filestatus == FileStatus::NotAFile, file) {
+    (true, false) => Some((format!("{}.{}", en.path, ext), en)),
+    (false, _) => Some((en.path.clone(), en)),
+    (true, true) => None,
+  }
+}
+
+fn rewrite_links(content: &str, bypage: &BTreeMap<Uuid, &ExportNote>, ext: &str) -> String {
+  let content = note_re().replace_all(content, |cap: &Captures| {
+    match target(bypage, ext, &cap[1], false) {
+      Some((path, en)) if en.note.filestatus != FileStatus::NotAFile && is_image(&path) => {
+        format!("![{}]({})", en.note.title, path)
+      }
+      Some((path, en)) => format!("[{}]({})", en.note.title, path),
+      // references to notes outside the export are left as is.
+      None => cap[0].to_string(),
+    }
+  });
+  applink_re()
+    .replace_all(&content, |cap: &Captures| {
+      match target(bypage, ext, &cap[3], &cap[2] == "file") {
+        Some((path, _)) => format!("{}{}", &cap[1], path),
+        None => cap[0].to_string(),
+      }
+    })
+    .to_string()
+}
+
+// encrypted notes are marked in front matter, or a meta tag for html.
+fn render(format: &ExportFormat, title: &str, encrypted: bool, markdown: &str) -> Vec<u8> {
+  match format {
+    ExportFormat::Markdown | ExportFormat::Obsidian => {
+      if encrypted {
+        format!("---\nencrypted: true\n---\n{}", markdown).into_bytes()
+      } else {
+        markdown.as_bytes().to_vec()
+      }
+    }
+    ExportFormat::Html => {
+      let mut body = String::new();
+      pulldown_cmark::html::push_html(
+        &mut body,
+        pulldown_cmark::Parser::new_ext(
+          markdown,
+          pulldown_cmark::Options::ENABLE_TABLES
+            | pulldown_cmark::Options::ENABLE_STRIKETHROUGH
+            | pulldown_cmark::Options::ENABLE_TASKLISTS
+            | pulldown_cmark::Options::ENABLE_FOOTNOTES,
+        ),
+      );
+      format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n{}<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        if encrypted {
+          "<meta name=\"encrypted\" content=\"true\">\n"
+        } else {
+          ""
+        },
+        html_escape(title),
+        body
+      )
+      .into_bytes()
+    }
+  }
+}
+
+fn export_pages(
+  conn: &Connection,
+  files_dir: &Path,
+  uid: UserId,
+  notes: Vec<(i64, ZkNote)>,
+  format: &ExportFormat,
+  ext: &str,
+  writer: &mut ExportWriter,
+  monitor: &dyn JobMonitor,
+) -> Result<(usize, usize), zkerr::Error> {
+  // page names, in selection order; duplicate titles get part of the uuid.
+  let mut used = BTreeSet::new();
+  used.insert("index".to_string());
+  let mut enotes = Vec::new();
+  for (id, note) in notes.into_iter() {
+    let path = if note.filestatus == FileStatus::NotAFile {
+      let name = file_name(note.title.as_str());
+      let name = if used.contains(&name.to_lowercase()) {
+        let u = uuid_of(&note.id).map_or(id.to_string(), |u| u.simple().to_string());
+        format!("{}-{}", name, &u[..8.min(u.len())])
+      } else {
+        name
+      };
+      used.insert(name.to_lowercase());
+      name
+    } else {
+      match sqldata::read_zknote_filehash(conn, Some(uid), id)? {
+        Some(hash) => format!("files/{}/{}", hash, file_name(note.title.as_str())),
+        None => continue,
+      }
+    };
+    enotes.push(ExportNote { id, note, path });
+  }
+
+  let bypage: BTreeMap<Uuid, &ExportNote> = enotes
+    .iter()
+    .filter_map(|en| uuid_of(&en.note.id).map(|u| (u, en)))
+    .collect();
+
+  let mut pages = 0;
+  let mut files = 0;
+  let mut index = String::new();
+  let count = enotes.len();
+  for (i, en) in enotes.iter().enumerate() {
+    write!(
+      monitor,
+      "exporting note {} of {}: {}",
+      i + 1,
+      count,
+      en.note.title
+    );
+    if en.note.filestatus != FileStatus::NotAFile {
+      // file notes are copied in by hash.
+      if let Some(hash) = en.path.split('/').nth(1) {
+        let from = files_dir.join(hash);
+        if from.exists() {
+          writer.copy(en.path.as_str(), &from)?;
+          files += 1;
+        }
+      }
+      continue;
+    }
+
+    let mut md = String::new();
+    if en.note.showtitle {
+      md.push_str(format!("# {}\n\n", en.note.title).as_str());
+    }
+    if en.note.encrypted {
+      // the server can't read encrypted content, so the ciphertext goes out as is.
+      md.push_str(en.note.content.as_str());
+    } else {
+      md.push_str(rewrite_links(en.note.content.as_str(), &bypage, ext).as_str());
+    }
+
+    // linked notes that are in the export.
+    let links: Vec<String> = sqldata::read_zklinks(conn, uid, en.id)?
+      .iter()
+      .filter_map(|l| match l.direction {
+        Direction::To => uuid_of(&l.otherid).and_then(|u| bypage.get(&u)),
+        Direction::From => None,
+      })
+      .map(|len| {
+        if len.note.filestatus == FileStatus::NotAFile {
+          format!("- [{}]({}.{})", len.note.title, len.path, ext)
+        } else {
+          format!("- [{}]({})", len.note.title, len.path)
+        }
+      })
+      .collect();
+    if !links.is_empty() {
+      md.push_str("\n\n---\n\n");
+      md.push_str(links.join("\n").as_str());
+      md.push_str("\n");
+    }
+
+    writer.write(
+      format!("{}.{}", en.path, ext).as_str(),
+      &render(
+        format,
+        en.note.title.as_str(),
+        en.note.encrypted,
+        md.as_str(),
+      ),
+    )?;
+    index.push_str(format!("- [{}]({}.{})\n", en.note.title, en.path, ext).as_str());
+    pages += 1;
+  }
+
+  writer.write(
+    format!("index.{}", ext).as_str(),
+    &render(format, "index", false, index.as_str()),
+  )?;
+
+  Ok((pages, files))
+}
+
+// obsidian file names can't have these, and wikilinks break on them.
+fn vault_name(title: &str) -> String {
+  let s: String = title
+    .trim()
+    .chars()
+    .map(|c| match c {
+      '*' | '"' | '\\' | '/' | '<' | '>' | ':' | '|' | '?' | '#' | '^' | '[' | ']' => '-',
+      c if c.is_control() => '-',
+      c => c,
+    })
+    .collect();
+  let s = s
+    .trim_matches(|c: char| c == '.' || c.is_whitespace())
+    .to_string();
+  if s.is_empty() {
+    "untitled".to_string()
+  } else {
+    s
+  }
+}
+
+// names for notes, without .md.  when titles collide the oldest note gets the
+// plain name and the others get part of their uuid, so re-running an export
+// doesn't rename anything.  for attachments the uuid goes before the extension.
+fn vault_names(notes: &Vec<(i64, ZkNote)>, attachments: bool) -> BTreeMap<i64, String> {
+  let mut groups: BTreeMap<String, Vec<&(i64, ZkNote)>> = BTreeMap::new();
+  for n in notes.iter() {
+    groups
+      .entry(vault_name(n.1.title.as_str()).to_lowercase())
+      .or_default()
+      .push(n);
+  }
+  let mut names = BTreeMap::new();
+  for (_, mut group) in groups.into_iter() {
+    group.sort_by_key(|(id, note)| (note.createdate, uuid_of(&note.id), *id));
+    for (i, (id, note)) in group.iter().enumerate() {
+      let name = vault_name(note.title.as_str());
+      if i == 0 {
+        names.insert(*id, name);
+      } else {
+        let u = uuid_of(&note.id).map_or(id.to_string(), |u| u.simple().to_string());
+        let u = &u[..8.min(u.len())];
+        match name.rsplit_once('.') {
+          Some((stem, ext)) if attachments && !stem.is_empty() => {
+            names.insert(*id, format!("{} ({}).{}", stem, u, ext))
+          }
+          _ => names.insert(*id, format!("{} ({})", name, u)),
+        };
+      }
+    }
+  }
+  names
+}
+
+fn wikilink(en: &ExportNote) -> String {
+  format!("[[{}]]", en.path)
+}
+
+fn md_link_re() -> Regex {
+  Regex::new(r#"(!?)\[([^\]]*)\]\((?:https?://[^/\s)]+)?/(note|file)/([0-9a-fA-F-]{36})\)"#)
+    .unwrap()
+}
+
+// links in the content become wikilinks.
+fn obsidian_content(content: &str, bypage: &BTreeMap<Uuid, &ExportNote>) -> String {
+  let content = note_re().replace_all(content, |cap: &Captures| {
+    match Uuid::parse_str(&cap[1]).ok().and_then(|u| bypage.get(&u)) {
+      Some(len) if len.note.filestatus != FileStatus::NotAFile => {
+        format!("!{}", wikilink(len))
+      }
+      Some(len) => wikilink(len),
+      None => cap[0].to_string(),
+    }
+  });
+  let content = md_link_re().replace_all(&content, |cap: &Captures| {
+    match Uuid::parse_str(&cap[4]).ok().and_then(|u| bypage.get(&u)) {
+      Some(len) if cap[2].is_empty() || cap[2] == len.note.title => {
+        format!("{}{}", &cap[1], wikilink(len))
+      }
+      Some(len) => format!("{}[[{}|{}]]", &cap[1], len.path, &cap[2]),
+      None => cap[0].to_string(),
+    }
+  });
+  let content = applink_re().replace_all(&content, |cap: &Captures| {
+    match Uuid::parse_str(&cap[3]).ok().and_then(|u| bypage.get(&u)) {
+      Some(len) if len.note.filestatus != FileStatus::NotAFile => {
+        format!("{}{}", &cap[1], len.path)
+      }
+      _ => cap[0].to_string(),
+    }
+  });
+  content.to_string()
+}
+
+fn export_obsidian(
+  conn: &Connection,
+  files_dir: &Path,
+  uid: UserId,
+  notes: Vec<(i64, ZkNote)>,
+  writer: &mut ExportWriter,
+  monitor: &dyn JobMonitor,
+) -> Result<(usize, usize), zkerr::Error> {
+  let (filenotes, notes): (Vec<(i64, ZkNote)>, Vec<(i64, ZkNote)>) = notes
+    .into_iter()
+    .partition(|(_, n)| n.filestatus != FileStatus::NotAFile);
+
+  // file notes are attachments.
+  let mut enotes = Vec::new();
+  let attnames = vault_names(&filenotes, true);
+  for (id, note) in filenotes.into_iter() {
+    if let (Some(hash), Some(name)) = (
+      sqldata::read_zknote_filehash(conn, Some(uid), id)?,
+      attnames.get(&id),
+    ) {
+      let from = files_dir.join(hash);
+      if from.exists() {
+        let path = format!("attachments/{}", name);
+        writer.copy(path.as_str(), &from)?;
+        enotes.push(ExportNote { id, note, path });
+      }
+    }
+  }
+  let files = enotes.len();
+
+  let names = vault_names(&notes, false);
+  for (id, note) in notes.into_iter() {
+    if let Some(name) = names.get(&id) {
+      enotes.push(ExportNote {
+        id,
+        note,
+        path: name.clone(),
+      });
+    }
+  }
+
+  let bypage: BTreeMap<Uuid, &ExportNote> = enotes
+    .iter()
+    .filter_map(|en| uuid_of(&en.note.id).map(|u| (u, en)))
+    .collect();
+
+  let mut pages = 0;
+  let count = enotes.len() - files;
+  for en in enotes[files..].iter() {
+    write!(
+      monitor,
+      "exporting note {} of {}: {}",
+      pages + 1,
+      count,
+      en.note.title
+    );
+
+    let mut md = String::new();
+    md.push_str("---\n");
+    md.push_str(format!("title: {}\n", serde_json::to_string(&en.note.title)?).as_str());
+    if let Some(uuid) = uuid_of(&en.note.id) {
+      md.push_str(format!("uuid: {}\n", uuid).as_str());
+    }
+    md.push_str(format!("createdate: {}\n", en.note.createdate).as_str());
+    md.push_str(format!("changeddate: {}\n", en.note.changeddate).as_str());
+    if let Some(pubid) = &en.note.pubid {
+      md.push_str(format!("pubid: {}\n", serde_json::to_string(pubid)?).as_str());
+    }
+    if !en.note.sysids.is_empty() {
+      md.push_str("sysids:\n");
+      for s in en.note.sysids.iter() {
+        md.push_str(format!("  - {}\n", s).as_str());
+      }
+    }
+    if en.note.encrypted {
+      md.push_str("encrypted: true\n");
+    }
+    md.push_str("---\n");
+
+    if en.note.encrypted {
+      md.push_str(en.note.content.as_str());
+    } else {
+      md.push_str(&obsidian_content(en.note.content.as_str(), &bypage));
+    }
+
+    // zklinks, sorted by name so the files are stable.
+    let mut tos = BTreeSet::new();
+    let mut froms = BTreeSet::new();
+    for l in sqldata::read_zklinks(conn, uid, en.id)?.iter() {
+      if let Some(len) = uuid_of(&l.otherid).and_then(|u| bypage.get(&u)) {
+        match l.direction {
+          Direction::To => tos.insert(wikilink(len)),
+          Direction::From => froms.insert(wikilink(len)),
+        };
+      }
+    }
+    for (heading, links) in [("Links to", tos), ("Links from", froms)] {
+      if !links.is_empty() {
+        md.push_str(format!("\n\n## {}\n\n", heading).as_str());
+        for l in links.iter() {
+          md.push_str(format!("- {}\n", l).as_str());
+        }
+      }
+    }
+
+    writer.write(format!("{}.md", en.path).as_str(), md.as_bytes())?;
+    pages += 1;
+  }
+
+  Ok((pages, files))
+}
+
+pub fn export_site(
+  conn: &Connection,
+  files_dir: &Path,
+  uid: UserId,
+  selection: &ExportSelection,
+  format: &ExportFormat,
+  writer: &mut ExportWriter,
+  monitor: &dyn JobMonitor,
+) -> Result<(usize, usize), zkerr::Error> {
+  write!(monitor, "selecting notes");
+  let notes = select_notes(conn, files_dir, uid, selection)?;
+
+  match format {
+    ExportFormat::Markdown => {
+      export_pages(conn, files_dir, uid, notes, format, "md", writer, monitor)
+    }
+    ExportFormat::Html => {
+      export_pages(conn, files_dir, uid, notes, format, "html", writer, monitor)
+    }
+    ExportFormat::Obsidian => export_obsidian(conn, files_dir, uid, notes, writer, monitor),
+  }
+}
+
+// export to a zip, which becomes a file note for the user.
+pub async fn export_site_zip(
+  conn: &Connection,
+  server: &Server,
+  lapin_info: &Option<LapinInfo>,
+  files_dir: &Path,
+  file_tmp_path: &Path,
+  quotas: &Quotas,
+  uid: UserId,
+  selection: &ExportSelection,
+  format: &ExportFormat,
+  monitor: &dyn JobMonitor,
+) -> Result<(ZkNoteId, usize, usize), zkerr::Error> {
+  let tmp = file_tmp_path.join(Uuid::new_v4().to_string());
+  let mut writer = ExportWriter::Zip(zip::ZipWriter::new(File::create(&tmp)?));
+  let (pages, files) = export_site(
+    conn,
+    files_dir,
+    uid,
+    selection,
+    format,
+    &mut writer,
+    monitor,
+  )?;
+  writer.finish()?;
+
+  let (_id, zni, _fid) = sqldata::make_file_note(
+    conn,
+    server,
+    lapin_info,
+    files_dir,
+    uid,
+    &format!("export-{}.zip", orgauth::util::now()?),
+    &tmp,
+    false,
+    quotas,
+  )
+  .await?;
+
+  Ok((zni, pages, files))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::jobs::LogMonitor;
+  use crate::sqldata::{connection_open, dbinit, save_zklink, save_zknote, zknotes_callbacks};
+  use orgauth::data::RegistrationData;
+  use orgauth::dbfun::new_user;
+  use zkprotocol::content::{ExportSubtree, SaveZkNote};
+
+  fn note(title: &str, uuid: Uuid, filestatus: FileStatus) -> ZkNote {
+    ZkNote {
+      id: ZkNoteId::Zni(uuid),
+      title: title.to_string(),
+      content: String::new(),
+      user: UserId::Uid(1),
+      username: "user".to_string(),
+      usernote: ZkNoteId::Zni(Uuid::nil()),
+      editable: false,
+      editableValue: false,
+      showtitle: true,
+      pubid: None,
+      createdate: 0,
+      changeddate: 0,
+      deleted: false,
+      encrypted: false,
+      filestatus,
+      server: String::new(),
+      sysids: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn test_file_name() {
+    assert_eq!(file_name("My Note: v2?"), "My-Note--v2");
+    assert_eq!(file_name("../../etc/passwd"), "etc-passwd");
+    assert_eq!(file_name("résumé.txt"), "résumé.txt");
+    assert_eq!(file_name(" ?? "), "note");
+  }
+
+  #[test]
+  fn test_rewrite_links() {
+    let (u1, u2, u3, u4) = (
+      Uuid::new_v4(),
+      Uuid::new_v4(),
+      Uuid::new_v4(),
+      Uuid::new_v4(),
+    );
+    let enotes = vec![
+      ExportNote {
+        id: 1,
+        note: note("Alpha", u1, FileStatus::NotAFile),
+        path: "Alpha".to_string(),
+      },
+      ExportNote {
+        id: 2,
+        note: note("pic.png", u2, FileStatus::FilePresent),
+        path: "files/abc/pic.png".to_string(),
+      },
+      ExportNote {
+        id: 3,
+        note: note("Beta", u3, FileStatus::NotAFile),
+        path: "Beta".to_string(),
+      },
+    ];
+    let bypage: BTreeMap<Uuid, &ExportNote> = enotes
+      .iter()
+      .filter_map(|en| uuid_of(&en.note.id).map(|u| (u, en)))
+      .collect();
+
+    let content = format!(
+      "<note id=\"{0}\"/> <note id=\"{1}\"/> <note id=\"{3}\"/>\n\
+       [b](/note/{2}) [a](https://zk.example.com/note/{0}) <img src=\"/file/{1}\"> [f](/file/{0})",
+      u1, u2, u3, u4
+    );
+    assert_eq!(
+      rewrite_links(content.as_str(), &bypage, "md"),
+      format!(
+        "[Alpha](Alpha.md) ![pic.png](files/abc/pic.png) <note id=\"{3}\"/>\n\
+         [b](Beta.md) [a](Alpha.md) <img src=\"files/abc/pic.png\"> [f](/file/{0})",
+        u1, u2, u3, u4
+      )
+    );
+  }
+
+  #[test]
+  fn test_vault_names() {
+    let (u1, u2, u3, u4) = (
+      Uuid::new_v4(),
+      Uuid::new_v4(),
+      Uuid::new_v4(),
+      Uuid::new_v4(),
+    );
+    let n = |id: i64, title: &str, uuid: Uuid, createdate: i64| {
+      (
+        id,
+        ZkNote {
+          createdate,
+          ..note(title, uuid, FileStatus::NotAFile)
+        },
+      )
+    };
+    let short = |u: Uuid| u.simple().to_string()[..8].to_string();
+
+    // the oldest note keeps the plain name, whatever order they come in.
+    let mut notes = vec![
+      n(1, "Same", u1, 200),
+      n(2, "same", u2, 100),
+      n(3, "a/b: c?", u3, 0),
+      n(4, "Same", u4, 200),
+    ];
+    let names = vault_names(&notes, false);
+    notes.reverse();
+    assert_eq!(vault_names(&notes, false), names);
+    assert_eq!(names[&2], "same");
+    assert_eq!(names[&3], "a-b- c-");
+    // same createdate; the uuid decides.
+    let (first, second) = if u1 < u4 { (1, 4) } else { (4, 1) };
+    let (ufirst, usecond) = if u1 < u4 { (u1, u4) } else { (u4, u1) };
+    assert_eq!(names[&first], format!("Same ({})", short(ufirst)));
+    assert_eq!(names[&second], format!("Same ({})", short(usecond)));
+
+    // attachments keep their extension.
+    let files = vec![n(5, "pic.png", u1, 100), n(6, "pic.png", u2, 200)];
+    let names = vault_names(&files, true);
+    assert_eq!(names[&5], "pic.png");
+    assert_eq!(names[&6], format!("pic ({}).png", short(u2)));
+  }
+
+  #[actix_web::test]
+  async fn test_export_site() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = Path::new("export_test");
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir.join("files"))?;
+
+    let dbfile = dir.join("export.db");
+    let server = dbinit(dbfile.as_path(), None)?;
+    let conn = connection_open(dbfile.as_path())?;
+    let mut cb = zknotes_callbacks();
+    let mut uids = Vec::new();
+    for name in ["exporter", "other"] {
+      uids.push(new_user(
+        &conn,
+        &RegistrationData {
+          uid: name.to_string(),
+          pwd: "".to_string(),
+          email: "".to_string(),
+          remote_url: "".to_string(),
+        },
+        None,
+        None,
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        &mut cb.on_new_user,
+      )?);
+    }
+    let (uid, otheruid) = (uids[0], uids[1]);
+
+    let mut ids = Vec::new();
+    for (user, title, content, encrypted) in [
+      (uid, "Same", "root", false),
+      (uid, "Same", "same title", false),
+      (uid, "Secret", "zkenc1:c2VjcmV0", true),
+      (otheruid, "Private", "not shared", false),
+    ] {
+      ids.push(
+        save_zknote(
+          &conn,
+          &None,
+          &server,
+          user,
+          &SaveZkNote {
+            id: None,
+            title: title.to_string(),
+            showtitle: true,
+            pubid: None,
+            content: content.to_string(),
+            editable: false,
+            deleted: false,
+            encrypted,
+            what: None,
+          },
+          None,
+        )
+        .await?,
+      );
+    }
+    for (_, to) in ids[1..].iter() {
+      save_zklink(
+        &conn,
+        ids[0].0,
+        sqldata::note_id_for_zknoteid(&conn, &to.id)?,
+        uid,
+        None,
+      )?;
+    }
+
+    let out = dir.join("out");
+    let mut writer = ExportWriter::Dir(out.clone());
+    let (pages, files) = export_site(
+      &conn,
+      &dir.join("files"),
+      uid,
+      &ExportSelection::Subtree(ExportSubtree {
+        root: ids[0].1.id,
+        depth: 1,
+      }),
+      &ExportFormat::Markdown,
+      &mut writer,
+      &LogMonitor {},
+    )?;
+    writer.finish()?;
+    assert_eq!((pages, files), (3, 0));
+
+    // the second 'Same' gets part of its uuid.
+    let second = format!(
+      "Same-{}",
+      &uuid_of(&ids[1].1.id).unwrap().simple().to_string()[..8]
+    );
+    let root = std::fs::read_to_string(out.join("Same.md"))?;
+    assert!(root.starts_with("# Same\n\nroot"));
+    assert!(root.contains(format!("- [Same]({}.md)", second).as_str()));
+    assert!(root.contains("- [Secret](Secret.md)"));
+    assert!(!root.contains("Private"));
+    assert_eq!(
+      std::fs::read_to_string(out.join(format!("{}.md", second)))?,
+      "# Same\n\nsame title"
+    );
+
+    // the other user's note isn't readable, so it's left out.
+    assert!(!out.join("Private.md").exists());
+
+    // encrypted content is exported as is.
+    assert_eq!(
+      std::fs::read_to_string(out.join("Secret.md"))?,
+      "---\nencrypted: true\n---\n# Secret\n\nzkenc1:c2VjcmV0"
+    );
+
+    // obsidian.  notes have front matter and wikilinks.
+    let out = dir.join("vault");
+    let mut writer = ExportWriter::Dir(out.clone());
+    let (pages, files) = export_site(
+      &conn,
+      &dir.join("files"),
+      uid,
+      &ExportSelection::Subtree(ExportSubtree {
+        root: ids[0].1.id,
+        depth: 1,
+      }),
+      &ExportFormat::Obsidian,
+      &mut writer,
+      &LogMonitor {},
+    )?;
+    writer.finish()?;
+    assert_eq!((pages, files), (3, 0));
+
+    let notes: Vec<ZkNote> = ids[..3]
+      .iter()
+      .map(|(_, sn)| sqldata::read_zknote_unchecked(&conn, &dir.join("files"), &sn.id).map(|r| r.1))
+      .collect::<Result<_, zkerr::Error>>()?;
+    let (a, b, secret) = (&notes[0], &notes[1], &notes[2]);
+    let short = |n: &ZkNote| uuid_of(&n.id).unwrap().simple().to_string()[..8].to_string();
+    let (aname, bname) = if (a.createdate, uuid_of(&a.id)) < (b.createdate, uuid_of(&b.id)) {
+      ("Same".to_string(), format!("Same ({})", short(b)))
+    } else {
+      (format!("Same ({})", short(a)), "Same".to_string())
+    };
+    let front = |n: &ZkNote| {
+      format!(
+        "---\ntitle: \"{}\"\nuuid: {}\ncreatedate: {}\nchangeddate: {}\n",
+        n.title,
+        uuid_of(&n.id).unwrap(),
+        n.createdate,
+        n.changeddate
+      )
+    };
+    let read = |name: &str| std::fs::read_to_string(out.join(format!("{}.md", name)));
+
+    assert_eq!(
+      read(aname.as_str())?,
+      format!(
+        "{}---\nroot\n\n## Links to\n\n- [[{}]]\n- [[Secret]]\n",
+        front(a),
+        bname
+      )
+    );
+    assert_eq!(
+      read(bname.as_str())?,
+      format!(
+        "{}---\nsame title\n\n## Links from\n\n- [[{}]]\n",
+        front(b),
+        aname
+      )
+    );
+    assert_eq!(
+      read("Secret")?,
+      format!(
+        "{}encrypted: true\n---\nzkenc1:c2VjcmV0\n\n## Links from\n\n- [[{}]]\n",
+        front(secret),
+        aname
+      )
+    );
+    assert!(!out.join("Private.md").exists());
+
+    Ok(())
+  }
+}
@@ -0,0 +1,220 @@
+use actix_files::HttpRange;
+use actix_web::body::SizedStream;
+use actix_web::http::header::{
+  self, ContentDisposition, DispositionParam, DispositionType, EntityTag, IfMatch, IfNoneMatch,
+  IfRange,
+};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder};
+use std::io::SeekFrom;
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+// files are stored by sha256 hash, so the content for a given hash never changes.
+// that makes the hash a strong etag, and lets clients cache forever.
+// 'private' because file notes may be access controlled.
+const CACHE_CONTROL: &str = "private, max-age=31536000, immutable";
+
+// serve a hash-addressed file, with conditional GET and single byte-range support.
+pub async fn serve_file(req: &HttpRequest, path: &Path, hash: &str, title: &str) -> HttpResponse {
+  let etag = EntityTag::new_strong(hash.to_string());
+
+  let len = match tokio::fs::metadata(path).await {
+    Ok(md) => md.len(),
+    Err(e) => return HttpResponse::NotFound().body(format!("{:?}", e)),
+  };
+
+  // If-Match: strong comparison.
+  if let Some(IfMatch::Items(items)) = req.get_header::<IfMatch>() {
+    if !items.iter().any(|i| i.strong_eq(&etag)) {
+      return HttpResponse::PreconditionFailed().finish();
+    }
+  }
+
+  // If-None-Match: weak comparison.
+  match req.get_header::<IfNoneMatch>() {
+    Some(IfNoneMatch::Any) => return not_modified(&etag),
+    Some(IfNoneMatch::Items(items)) if items.iter().any(|i| i.weak_eq(&etag)) => {
+      return not_modified(&etag)
+    }
+    _ => (),
+  }
+
+  // only honor Range if If-Range is absent or matches.  we don't send
+  // Last-Modified, so a date in If-Range never matches.
+  let range_ok = match req.get_header::<IfRange>() {
+    None => true,
+    Some(IfRange::EntityTag(t)) => t.strong_eq(&etag),
+    Some(IfRange::Date(_)) => false,
+  };
+
+  let (mut resp, offset, length) = match (range_ok, req.headers().get(header::RANGE)) {
+    (true, Some(rh)) => {
+      match rh
+        .to_str()
+        .ok()
+        .and_then(|s| HttpRange::parse(s, len).ok())
+        .and_then(|rs| rs.first().cloned())
+      {
+        Some(r) => {
+          let mut resp = HttpResponse::PartialContent();
+          resp.insert_header((
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", r.start, r.start + r.length - 1, len),
+          ));
+          (resp, r.start, r.length)
+        }
+        None => {
+          return HttpResponse::RangeNotSatisfiable()
+            .insert_header((header::CONTENT_RANGE, format!("bytes */{}", len)))
+            .finish();
+        }
+      }
+    }
+    _ => (HttpResponse::Ok(), 0, len),
+  };
+
+  resp
+    .insert_header((header::ETAG, etag))
+    .insert_header((header::CACHE_CONTROL, CACHE_CONTROL))
+    .insert_header((header::ACCEPT_RANGES, "bytes"))
+    .insert_header((header::CONTENT_TYPE, sniff_content_type(path).await))
+    .insert_header(ContentDisposition {
+      disposition: DispositionType::Inline,
+      parameters: vec![DispositionParam::Filename(title.to_string())],
+    });
+
+  let mut f = match tokio::fs::File::open(path).await {
+    Ok(f) => f,
+    Err(e) => return HttpResponse::NotFound().body(format!("{:?}", e)),
+  };
+  if offset > 0 {
+    if let Err(e) = f.seek(SeekFrom::Start(offset)).await {
+      return HttpResponse::InternalServerError().body(format!("{:?}", e));
+    }
+  }
+
+  resp.body(SizedStream::new(length, ReaderStream::new(f.take(length))))
+}
+
+fn not_modified(etag: &EntityTag) -> HttpResponse {
+  HttpResponseBuilder::new(actix_web::http::StatusCode::NOT_MODIFIED)
+    .insert_header((header::ETAG, etag.clone()))
+    .insert_header((header::CACHE_CONTROL, CACHE_CONTROL))
+    .finish()
+}
+
+// content type from the file's magic bytes.  if those aren't recognized,
+// valid utf8 is served as text, anything else as a binary blob.
+pub async fn sniff_content_type(path: &Path) -> String {
+  let mut buf = [0; 8192];
+  let n = match tokio::fs::File::open(path).await {
+    Ok(mut f) => match f.read(&mut buf).await {
+      Ok(n) => n,
+      Err(_) => return mime::APPLICATION_OCTET_STREAM.to_string(),
+    },
+    Err(_) => return mime::APPLICATION_OCTET_STREAM.to_string(),
+  };
+
+  match infer::get(&buf[..n]) {
+    Some(t) => t.mime_type().to_string(),
+    None => match std::str::from_utf8(&buf[..n]) {
+      Ok(_) => mime::TEXT_PLAIN_UTF_8.to_string(),
+      // a multibyte char may be cut off at the end of the buffer.
+      Err(e) if n == buf.len() && e.error_len().is_none() => mime::TEXT_PLAIN_UTF_8.to_string(),
+      Err(_) => mime::APPLICATION_OCTET_STREAM.to_string(),
+    },
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use actix_web::body::to_bytes;
+  use actix_web::http::StatusCode;
+  use actix_web::test::TestRequest;
+
+  const HASH: &str = "0123abcd";
+
+  async fn serve(req: TestRequest, path: &Path) -> (StatusCode, header::HeaderMap, Vec<u8>) {
+    let resp = serve_file(&req.to_http_request(), path, HASH, "test.txt").await;
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let body = to_bytes(resp.into_body()).await.unwrap_or_default();
+    (status, headers, body.to_vec())
+  }
+
+  #[actix_web::test]
+  async fn test_serve_file() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = Path::new("fileserve_test");
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(HASH);
+    std::fs::write(&path, "0123456789")?;
+    let etag = format!("\"{}\"", HASH);
+
+    let (status, headers, body) = serve(TestRequest::get(), &path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"0123456789");
+    assert_eq!(headers.get(header::ETAG).unwrap(), etag.as_str());
+    assert_eq!(headers.get(header::ACCEPT_RANGES).unwrap(), "bytes");
+
+    // cached copy is current.
+    let (status, headers, body) = serve(
+      TestRequest::get().insert_header((header::IF_NONE_MATCH, etag.as_str())),
+      &path,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert_eq!(headers.get(header::ETAG).unwrap(), etag.as_str());
+    assert!(body.is_empty());
+
+    let (status, _, body) = serve(
+      TestRequest::get().insert_header((header::IF_NONE_MATCH, "\"other\"")),
+      &path,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"0123456789");
+
+    // byte ranges.
+    let (status, headers, body) = serve(
+      TestRequest::get().insert_header((header::RANGE, "bytes=2-5")),
+      &path,
+    )
+    .await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(headers.get(header::CONTENT_RANGE).unwrap(), "bytes 2-5/10");
+    assert_eq!(body, b"2345");
+
+    let (status, headers, body) = serve(
+      TestRequest::get().insert_header((header::RANGE, "bytes=-3")),
+      &path,
+    )
+    .await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(headers.get(header::CONTENT_RANGE).unwrap(), "bytes 7-9/10");
+    assert_eq!(body, b"789");
+
+    let (status, headers, _) = serve(
+      TestRequest::get().insert_header((header::RANGE, "bytes=20-30")),
+      &path,
+    )
+    .await;
+    assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(headers.get(header::CONTENT_RANGE).unwrap(), "bytes */10");
+
+    // a stale If-Range gets the whole file.
+    let (status, _, body) = serve(
+      TestRequest::get()
+        .insert_header((header::RANGE, "bytes=2-5"))
+        .insert_header((header::IF_RANGE, "\"other\"")),
+      &path,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"0123456789");
+
+    Ok(())
+  }
+}
@@ -1,6 +1,9 @@
-use crate::config::Config;
+use crate::audit::{audit, read_audit};
+use crate::bundle;
+use crate::config::{Config, Quotas};
 use crate::error as zkerr;
-use crate::jobs::GirlbossMonitor;
+use crate::export;
+use crate::jobs::spawn_job;
 use crate::jobs::JobId;
 use crate::jobs::JobMonitor;
 use crate::jobs::LogMonitor;
@@ -9,9 +12,10 @@ use crate::sqldata;
 use crate::sqldata::local_server_id;
 use crate::sqldata::zknotes_callbacks;
 use crate::sqldata::LapinInfo;
-use crate::state::new_jobid;
 use crate::state::State;
 use crate::sync;
+use crate::totp;
+use crate::vault;
 use actix_session::Session;
 use actix_web::HttpResponse;
 use futures_util::StreamExt;
@@ -19,7 +23,7 @@ use lapin::ConnectionState;
 use log::{error, info};
 use orgauth;
 use orgauth::data::UserId;
-use orgauth::endpoints::Tokener;
+use orgauth::endpoints::{Tokener, UuidTokener};
 use orgauth::util::now;
 use rusqlite::Connection;
 use std::error::Error;
@@ -29,11 +33,17 @@ use std::sync::Arc;
 use std::time::Duration;
 use uuid::Uuid;
 use zkprotocol::constants::PrivateStreamingRequests;
+use zkprotocol::content::ApiTokenScope;
+use zkprotocol::content::AuditAction;
+use zkprotocol::content::ExportDestination;
+use zkprotocol::content::ImportPolicy;
 use zkprotocol::content::JobState;
 use zkprotocol::content::JobStatus;
-use zkprotocol::content::Server;
+use zkprotocol::content::TotpLogin;
+use zkprotocol::content::VaultSource;
 use zkprotocol::content::{
-  GetArchiveZkLinks, GetZkLinksSince, SyncSince, ZkNoteAndLinks, ZkNoteAndLinksWhat, ZkNoteArchives,
+  GetArchiveZkLinks, GetZkLinksSince, SyncSince, ZkNoteAndLinks, ZkNoteAndLinksWhat,
+  ZkNoteArchives, ZkNoteId,
 };
 use zkprotocol::messages::PrivateStreamingMessage;
 use zkprotocol::private::PrivateReply;
@@ -154,6 +164,33 @@ pub async fn user_interface(
   config: &Config,
   msg: orgauth::data::UserRequest,
 ) -> Result<orgauth::data::UserResponse, zkerr::Error> {
+  // with two factor auth, a password alone doesn't log in.  the password is still
+  // checked, so this doesn't reveal which accounts use 2fa.  orgauth makes a login
+  // token when the password checks out; that's dropped again.
+  if let orgauth::data::UserRequest::UrqLogin(login) = &msg {
+    if totp::totp_enabled_for_name(conn, login.uid.as_str())? {
+      let mut ut = UuidTokener { uuid: None };
+      return match orgauth::endpoints::user_interface(
+        conn,
+        &mut ut,
+        &config.orgauth_config,
+        &mut sqldata::zknotes_callbacks(),
+        Some("user".to_string()),
+        msg,
+      )
+      .await?
+      {
+        orgauth::data::UserResponse::UrpLoggedIn(_) => {
+          if let Some(token) = ut.uuid {
+            totp::drop_login_token(conn, &token)?;
+          }
+          Err(zkerr::Error::TotpRequired)
+        }
+        r => Ok(r),
+      };
+    }
+  }
+
   Ok(
     orgauth::endpoints::user_interface(
       conn,
@@ -167,6 +204,43 @@ pub async fn user_interface(
   )
 }
 
+// login with a password and a second factor.  the login token is only returned
+// if both check out, for the caller to put in the session.
+pub async fn totp_login(
+  conn: &Connection,
+  config: &Config,
+  tl: &TotpLogin,
+) -> Result<(orgauth::data::UserResponse, Option<Uuid>), zkerr::Error> {
+  let mut ut = UuidTokener { uuid: None };
+  let resp = orgauth::endpoints::user_interface(
+    conn,
+    &mut ut,
+    &config.orgauth_config,
+    &mut sqldata::zknotes_callbacks(),
+    Some("user".to_string()),
+    orgauth::data::UserRequest::UrqLogin(orgauth::data::Login {
+      uid: tl.uid.clone(),
+      pwd: tl.pwd.clone(),
+    }),
+  )
+  .await?;
+
+  match resp {
+    orgauth::data::UserResponse::UrpLoggedIn(_) => {
+      let uid = orgauth::dbfun::read_user_by_name(conn, tl.uid.as_str())?.id;
+      if totp::totp_enabled(conn, uid)? && !totp::check_totp_login(conn, uid, tl.code.as_str())? {
+        if let Some(token) = ut.uuid {
+          totp::drop_login_token(conn, &token)?;
+        }
+        Err(zkerr::Error::InvalidTotp)
+      } else {
+        Ok((resp, ut.uuid))
+      }
+    }
+    r => Ok((r, None)),
+  }
+}
+
 pub async fn zk_interface_loggedin_streaming(
   config: &Config,
   uid: UserId,
@@ -216,6 +290,12 @@ pub async fn zk_interface_loggedin_streaming(
         before: now,
         server: luuid,
       };
+      audit(
+        &conn,
+        Some(uid),
+        AuditAction::Sync,
+        format!("sync to client stream, after {:?}", rq.after).as_str(),
+      )?;
       let ss = sync::sync_stream(
         conn,
         PathBuf::from(&config.file_path),
@@ -310,10 +390,21 @@ pub async fn zk_interface_loggedin(
         }
       }
     }
-    PrivateRequest::PvqPowerDelete(search) => {
-      let res = search::power_delete_zknotes(&conn, state.config.file_path.clone(), uid, &search)?;
+    PrivateRequest::PvqGetChangedNotes(gcn) => Ok(PrivateReply::PvyChangedNotes(
+      search::changed_notes(&conn, uid, gcn)?,
+    )),
+    PrivateRequest::PvqPowerDeletePreview(search) => {
+      let res = search::power_delete_preview(&conn, &state.config.file_path, uid, &search)?;
+      Ok(PrivateReply::PvyPowerDeletePreview(res))
+    }
+    PrivateRequest::PvqPowerDelete(pd) => {
+      let res = search::power_delete_zknotes(&conn, state.config.file_path.clone(), uid, &pd)?;
       Ok(PrivateReply::PvyPowerDeleteComplete(res))
     }
+    PrivateRequest::PvqUndoPowerDelete(id) => {
+      let count = search::undo_power_delete(&conn, uid, id)?;
+      Ok(PrivateReply::PvyPowerDeleteUndone(count))
+    }
     PrivateRequest::PvqDeleteZkNote(id) => {
       sqldata::delete_zknote(&conn, state.config.file_path.clone(), uid, &id)?;
       Ok(PrivateReply::PvyDeletedZkNote(id.clone()))
@@ -337,184 +428,341 @@ pub async fn zk_interface_loggedin(
     }
     PrivateRequest::PvqSaveImportZkNotes(gzl) => {
       let li = connect_and_make_lapin_info(state, token).await;
-      sqldata::save_importzknotes(&conn, &li, &state.server, uid, gzl).await?;
+      sqldata::save_importzknotes(&conn, &li, &state.server, uid, gzl, &ImportPolicy::Replace)
+        .await?;
       Ok(PrivateReply::PvySavedImportZkNotes)
     }
+    PrivateRequest::PvqImportZkNotes(izns) => {
+      let li = connect_and_make_lapin_info(state, token).await;
+      Ok(PrivateReply::PvyImportedZkNotes(
+        sqldata::save_importzknotes(&conn, &li, &state.server, uid, &izns.notes, &izns.policy)
+          .await?,
+      ))
+    }
+    PrivateRequest::PvqImportVault(iv) => {
+      let file_path: PathBuf = state.config.file_path.to_path_buf();
+      // check access now, so errors come back before the job starts.
+      let source: PathBuf = match &iv.source {
+        VaultSource::Zip(zknid) => {
+          let id = sqldata::note_id_for_zknoteid(&conn, zknid)?;
+          match sqldata::read_zknote_filehash(&conn, Some(uid), id)? {
+            Some(hash) => file_path.join(hash),
+            None => {
+              return Err(zkerr::Error::String(
+                "vault zip note has no file".to_string(),
+              ))
+            }
+          }
+        }
+        VaultSource::Directory(dir) => {
+          if !orgauth::dbfun::read_user_by_id(&conn, uid)?.admin {
+            return Err(zkerr::Error::NotAdmin);
+          }
+          PathBuf::from(dir)
+        }
+      };
+      let policy = iv.policy.clone();
+      let iszip = match iv.source {
+        VaultSource::Zip(_) => true,
+        VaultSource::Directory(_) => false,
+      };
+      let dbpath: PathBuf = state.config.orgauth_config.db.to_path_buf();
+      let file_tmp_path: PathBuf = state.config.file_tmp_path.to_path_buf();
+      let quotas: Quotas = state.config.quotas.clone();
+      let lapin_info = connect_and_make_lapin_info(state, token).await;
+
+      Ok(PrivateReply::PvyJobStatus(spawn_job(
+        state,
+        uid,
+        move |gbm| async move {
+          write!(gbm, "starting vault import");
+
+          let r = async {
+            let conn = sqldata::connection_open(&dbpath.as_path())?;
+            let server = local_server_id(&conn)?;
+            let mut v = if iszip {
+              vault::Vault::from_zip(source.as_path())?
+            } else {
+              vault::Vault::from_dir(source.as_path())?
+            };
+            vault::import_vault(
+              &conn,
+              &server,
+              &lapin_info,
+              &file_path.as_path(),
+              &file_tmp_path.as_path(),
+              &quotas,
+              uid,
+              &mut v,
+              &policy,
+              &gbm,
+            )
+            .await
+          };
+          match r.await {
+            Ok((results, files)) => {
+              let report: Vec<String> = results
+                .iter()
+                .map(|r| format!("{:?}: {}", r.action, r.title))
+                .collect();
+              write!(
+                gbm,
+                "vault import completed: {} notes, {} files\n{}",
+                results.len(),
+                files,
+                report.join("\n")
+              )
+            }
+            Err(e) => write!(gbm, "vault import err: {:?}", e),
+          };
+        },
+      )))
+    }
+    PrivateRequest::PvqExportSite(es) => {
+      let outdir: Option<PathBuf> = match &es.destination {
+        ExportDestination::ZipNote => None,
+        ExportDestination::ServerDirectory(dir) => {
+          if !orgauth::dbfun::read_user_by_id(&conn, uid)?.admin {
+            return Err(zkerr::Error::NotAdmin);
+          }
+          Some(PathBuf::from(dir))
+        }
+      };
+      let dbpath: PathBuf = state.config.orgauth_config.db.to_path_buf();
+      let file_path: PathBuf = state.config.file_path.to_path_buf();
+      let file_tmp_path: PathBuf = state.config.file_tmp_path.to_path_buf();
+      let quotas: Quotas = state.config.quotas.clone();
+      let es = es.clone();
+      let lapin_info = connect_and_make_lapin_info(state, token).await;
+
+      Ok(PrivateReply::PvyJobStatus(spawn_job(
+        state,
+        uid,
+        move |gbm| async move {
+          write!(gbm, "starting export");
+
+          let r = async {
+            let conn = sqldata::connection_open(&dbpath.as_path())?;
+            match outdir {
+              Some(dir) => {
+                let mut writer = export::ExportWriter::Dir(dir.clone());
+                let (pages, files) = export::export_site(
+                  &conn,
+                  &file_path.as_path(),
+                  uid,
+                  &es.selection,
+                  &es.format,
+                  &mut writer,
+                  &gbm,
+                )?;
+                writer.finish()?;
+                Ok::<String, zkerr::Error>(format!(
+                  "exported {} notes and {} files to {}",
+                  pages,
+                  files,
+                  dir.display()
+                ))
+              }
+              None => {
+                let server = local_server_id(&conn)?;
+                let (zni, pages, files) = export::export_site_zip(
+                  &conn,
+                  &server,
+                  &lapin_info,
+                  &file_path.as_path(),
+                  &file_tmp_path.as_path(),
+                  &quotas,
+                  uid,
+                  &es.selection,
+                  &es.format,
+                  &gbm,
+                )
+                .await?;
+                Ok(format!(
+                  "exported {} notes and {} files to note {}",
+                  pages, files, zni
+                ))
+              }
+            }
+          };
+          match r.await {
+            Ok(msg) => write!(gbm, "{}", msg),
+            Err(e) => write!(gbm, "export err: {:?}", e),
+          };
+        },
+      )))
+    }
     PrivateRequest::PvqSetHomeNote(hn) => {
       sqldata::set_homenote(&conn, uid, hn)?;
       Ok(PrivateReply::PvyHomeNoteSet(hn.clone()))
     }
     PrivateRequest::PvqSyncRemote => {
+      audit(&conn, Some(uid), AuditAction::Sync, "sync with remote")?;
       let dbpath: PathBuf = state.config.orgauth_config.db.to_path_buf();
       let file_path: PathBuf = state.config.file_path.to_path_buf();
-      let uid: UserId = uid;
-      let jid = new_jobid(state, uid);
-      let lgb = state.girlboss.clone();
       let server = state.server.clone();
-      let li = connect_and_make_lapin_info(state, token.clone()).await;
-      let lapin_channelx = li.map(|li| li.channel).clone();
-
-      std::thread::spawn(move || {
-        let rt = actix_rt::System::new();
-
-        async fn startit(
-          lgb: Arc<std::sync::RwLock<girlboss::Girlboss<JobId, girlboss::Monitor>>>,
-          dbpath: PathBuf,
-          file_path: PathBuf,
-          uid: UserId,
-          jid: JobId,
-          server: Server,
-          lapin_channel: Option<lapin::Channel>,
-          token: Option<String>,
-        ) -> () {
-          lgb
-            .write()
-            .map_err(|e| {
-              info!("rwlock error: {}", e);
-              e
-            })
-            .unwrap()
-            .start(jid, move |mon| async move {
-              let gbm = GirlbossMonitor { monitor: mon };
-              let mut callbacks = &mut zknotes_callbacks();
-              let server = server.clone();
-              // let lapin_channel = lapin_channelx.clone();
-              write!(gbm, "starting sync");
-
-              let li = match (lapin_channel, token) {
-                (Some(channel), Some(token)) => Some(LapinInfo { channel, token }),
-                _ => None,
-              };
-              // None for now!
-              let r =
-                sync::sync(&dbpath, &file_path, &li, uid, &server, &mut callbacks, &gbm).await;
-              match r {
-                Ok(_) => write!(gbm, "sync completed"),
-                Err(e) => write!(gbm, "sync err: {:?}", e),
-              };
-              actix_rt::System::current().stop();
-            })
-            .map_err(|e| {
-              info!("girlboss start error: {}", e);
-              e
-            })
-            .unwrap();
-          ()
-        }
+      let li = connect_and_make_lapin_info(state, token).await;
 
-        rt.block_on(startit(
-          lgb,
-          dbpath,
-          file_path,
-          uid,
-          jid,
-          server,
-          lapin_channelx,
-          token,
-        ));
-        rt.run()
-          .map_err(|e| {
-            info!("rt.run error: {}", e);
-            e
-          })
-          .unwrap()
-      });
-
-      Ok(PrivateReply::PvyJobStatus(JobStatus {
-        jobno: jid.jobno,
-        state: JobState::Started,
-        message: "".to_string(),
-      }))
+      Ok(PrivateReply::PvyJobStatus(spawn_job(
+        state,
+        uid,
+        move |gbm| async move {
+          write!(gbm, "starting sync");
+
+          let r = sync::sync(
+            &dbpath,
+            &file_path,
+            &li,
+            uid,
+            &server,
+            &mut zknotes_callbacks(),
+            &gbm,
+          )
+          .await;
+          match r {
+            Ok(_) => write!(gbm, "sync completed"),
+            Err(e) => write!(gbm, "sync err: {:?}", e),
+          };
+        },
+      )))
     }
     PrivateRequest::PvqSyncFiles(znsrq) => {
+      audit(&conn, Some(uid), AuditAction::Sync, "file sync with remote")?;
       let dbpath: PathBuf = state.config.orgauth_config.db.to_path_buf();
       let file_path: PathBuf = state.config.file_path.to_path_buf();
       let file_tmp_path: PathBuf = state.config.file_tmp_path.to_path_buf();
-      let uid: UserId = uid;
-      let jid = new_jobid(state, uid);
-      let lgb = state.girlboss.clone();
+      let quotas: Quotas = state.config.quotas.clone();
       let zns = znsrq.clone();
-      let li = connect_and_make_lapin_info(state, token).await;
+      let lapin_info = connect_and_make_lapin_info(state, token).await;
 
-      std::thread::spawn(move || {
-        let rt = actix_rt::System::new();
-
-        async fn startit(
-          lgb: Arc<std::sync::RwLock<girlboss::Girlboss<JobId, girlboss::Monitor>>>,
-          lapin_info: Option<LapinInfo>,
-          dbpath: PathBuf,
-          file_path: PathBuf,
-          file_tmp_path: PathBuf,
-          uid: UserId,
-          zns: ZkNoteSearch,
-          jid: JobId,
-        ) -> () {
-          lgb
-            .write()
-            .map_err(|e| {
-              info!("rwlock error: {}", e);
-              e
-            })
-            .unwrap()
-            .start(jid, move |mon| async move {
-              let gbm = GirlbossMonitor { monitor: mon };
-              write!(gbm, "starting file sync");
-
-              let r = async {
-                let conn = sqldata::connection_open(&dbpath.as_path())?;
-                let _dv = sync::sync_files_down(
-                  &conn,
-                  &lapin_info,
-                  &file_tmp_path.as_path(),
-                  &file_path.as_path(),
-                  uid,
-                  &zns,
-                )
-                .await?;
-                let _uv = sync::sync_files_up(&conn, &file_path.as_path(), uid, &zns).await?;
-
-                // TODO: send a result with a list of synced files.
-                Ok::<(), zkerr::Error>(())
-              };
-              match r.await {
-                Ok(_) => write!(gbm, "file sync completed"),
-                Err(e) => write!(gbm, "file sync err: {:?}", e),
-              };
-              actix_rt::System::current().stop();
-            })
-            .map_err(|e| {
-              info!("girlboss start error: {}", e);
-              e
-            })
-            .unwrap();
-          ()
+      Ok(PrivateReply::PvyJobStatus(spawn_job(
+        state,
+        uid,
+        move |gbm| async move {
+          write!(gbm, "starting file sync");
+
+          let r = async {
+            let conn = sqldata::connection_open(&dbpath.as_path())?;
+            let _dv = sync::sync_files_down(
+              &conn,
+              &lapin_info,
+              &file_tmp_path.as_path(),
+              &file_path.as_path(),
+              uid,
+              &zns,
+              &quotas,
+            )
+            .await?;
+            let _uv = sync::sync_files_up(&conn, &file_path.as_path(), uid, &zns).await?;
+
+            // TODO: send a result with a list of synced files.
+            Ok::<(), zkerr::Error>(())
+          };
+          match r.await {
+            Ok(_) => write!(gbm, "file sync completed"),
+            Err(e) => write!(gbm, "file sync err: {:?}", e),
+          };
+        },
+      )))
+    }
+    PrivateRequest::PvqMakeSyncBundle(msb) => {
+      audit(&conn, Some(uid), AuditAction::Sync, "make sync bundle")?;
+      let dbpath: PathBuf = state.config.orgauth_config.db.to_path_buf();
+      let file_path: PathBuf = state.config.file_path.to_path_buf();
+      let file_tmp_path: PathBuf = state.config.file_tmp_path.to_path_buf();
+      let quotas: Quotas = state.config.quotas.clone();
+      let after = msb.after;
+      let lapin_info = connect_and_make_lapin_info(state, token).await;
+
+      Ok(PrivateReply::PvyJobStatus(spawn_job(
+        state,
+        uid,
+        move |gbm| async move {
+          write!(gbm, "starting sync bundle");
+
+          let r = async {
+            let conn = Arc::new(sqldata::connection_open(&dbpath.as_path())?);
+            let server = local_server_id(&conn)?;
+            bundle::make_bundle_note(
+              conn,
+              &server,
+              &lapin_info,
+              &file_path.as_path(),
+              &file_tmp_path.as_path(),
+              &quotas,
+              uid,
+              after,
+              &mut zknotes_callbacks(),
+              &gbm,
+            )
+            .await
+          };
+          match r.await {
+            Ok((zni, files)) => {
+              write!(
+                gbm,
+                "sync bundle with {} files saved to note {}",
+                files, zni
+              )
+            }
+            Err(e) => write!(gbm, "sync bundle err: {:?}", e),
+          };
+        },
+      )))
+    }
+    PrivateRequest::PvqApplySyncBundle(zknid) => {
+      // a bundle can carry anyone's notes and links, and applying it doesn't check
+      // ownership, so admins only.
+      if !orgauth::dbfun::read_user_by_id(&conn, uid)?.admin {
+        return Err(zkerr::Error::NotAdmin);
+      }
+      let file_path: PathBuf = state.config.file_path.to_path_buf();
+      // check access now, so errors come back before the job starts.
+      let id = sqldata::note_id_for_zknoteid(&conn, zknid)?;
+      let source: PathBuf = match sqldata::read_zknote_filehash(&conn, Some(uid), id)? {
+        Some(hash) => file_path.join(hash),
+        None => {
+          return Err(zkerr::Error::String(
+            "sync bundle note has no file".to_string(),
+          ))
         }
+      };
+      audit(&conn, Some(uid), AuditAction::Sync, "apply sync bundle")?;
+      let dbpath: PathBuf = state.config.orgauth_config.db.to_path_buf();
+      let file_tmp_path: PathBuf = state.config.file_tmp_path.to_path_buf();
+      let quotas: Quotas = state.config.quotas.clone();
+      let lapin_info = connect_and_make_lapin_info(state, token).await;
 
-        let znsclone = zns.clone();
-
-        rt.block_on(startit(
-          lgb,
-          li,
-          dbpath,
-          file_path,
-          file_tmp_path,
-          uid,
-          znsclone,
-          jid,
-        ));
-        rt.run()
-          .map_err(|e| {
-            info!("rt.run error: {}", e);
-            e
-          })
-          .unwrap()
-      });
-
-      Ok(PrivateReply::PvyJobStatus(JobStatus {
-        jobno: jid.jobno,
-        state: JobState::Started,
-        message: "".to_string(),
-      }))
+      Ok(PrivateReply::PvyJobStatus(spawn_job(
+        state,
+        uid,
+        move |gbm| async move {
+          write!(gbm, "applying sync bundle");
+
+          let r = async {
+            let conn = sqldata::connection_open(&dbpath.as_path())?;
+            let server = local_server_id(&conn)?;
+            let user = orgauth::dbfun::read_user_by_id(&conn, uid)?;
+            bundle::read_bundle(
+              &conn,
+              &lapin_info,
+              &server,
+              &user,
+              &file_path.as_path(),
+              &file_tmp_path.as_path(),
+              &quotas,
+              &source.as_path(),
+              &mut zknotes_callbacks(),
+              &gbm,
+            )
+            .await
+          };
+          match r.await {
+            Ok(files) => write!(gbm, "sync bundle applied, with {} new files", files),
+            Err(e) => write!(gbm, "sync bundle err: {:?}", e),
+          };
+        },
+      )))
     }
 
     PrivateRequest::PvqGetJobStatus(jobno) => {
@@ -547,6 +795,132 @@ pub async fn zk_interface_loggedin(
         None => Ok(PrivateReply::PvyJobNotFound(jobno.clone())),
       }
     }
+    PrivateRequest::PvqGetStorageStats(gss) => {
+      let user = orgauth::dbfun::read_user_by_id(&conn, uid)?;
+      if !user.admin {
+        return Err(zkerr::Error::NotAdmin);
+      }
+      let ss = sqldata::read_storage_stats(&conn, gss.largest)?;
+      Ok(PrivateReply::PvyStorageStats(ss))
+    }
+    PrivateRequest::PvqGetAuditLog(aq) => {
+      let user = orgauth::dbfun::read_user_by_id(&conn, uid)?;
+      if !user.admin {
+        return Err(zkerr::Error::NotAdmin);
+      }
+      Ok(PrivateReply::PvyAuditLog(read_audit(&conn, aq)?))
+    }
+    PrivateRequest::PvqTotpStatus => {
+      Ok(PrivateReply::PvyTotpStatus(totp::totp_enabled(&conn, uid)?))
+    }
+    PrivateRequest::PvqTotpBegin => Ok(PrivateReply::PvyTotpEnrollment(totp::begin_totp(
+      &conn,
+      uid,
+      state.config.orgauth_config.appname.as_str(),
+    )?)),
+    PrivateRequest::PvqTotpConfirm(code) => Ok(PrivateReply::PvyTotpRecoveryCodes(
+      totp::confirm_totp(&conn, uid, code.as_str())?,
+    )),
+    PrivateRequest::PvqTotpDisable(code) => {
+      totp::disable_totp(&conn, uid, code.as_str())?;
+      Ok(PrivateReply::PvyTotpDisabled)
+    }
+    PrivateRequest::PvqAdminResetTotp(resetuid) => {
+      let user = orgauth::dbfun::read_user_by_id(&conn, uid)?;
+      if !user.admin {
+        return Err(zkerr::Error::NotAdmin);
+      }
+      totp::reset_totp(&conn, *resetuid)?;
+      audit(
+        &conn,
+        Some(uid),
+        AuditAction::Admin,
+        format!("reset two factor auth for user {}", resetuid.to_i64()).as_str(),
+      )?;
+      Ok(PrivateReply::PvyTotpDisabled)
+    }
+    PrivateRequest::PvqCreateApiToken(cat) => {
+      let nat = sqldata::create_api_token(&conn, uid, cat.name.as_str(), &cat.scope)?;
+      Ok(PrivateReply::PvyApiToken(nat))
+    }
+    PrivateRequest::PvqGetApiTokens => Ok(PrivateReply::PvyApiTokens(sqldata::read_api_tokens(
+      &conn, uid,
+    )?)),
+    PrivateRequest::PvqRevokeApiToken(id) => {
+      sqldata::revoke_api_token(&conn, uid, id)?;
+      Ok(PrivateReply::PvyApiTokenRevoked(*id))
+    }
+    PrivateRequest::PvqSetSharePermission(usp) => {
+      sqldata::set_share_permission(&conn, uid, usp)?;
+      Ok(PrivateReply::PvySharePermissionSet)
+    }
+    PrivateRequest::PvqGetSharePermissions(id) => Ok(PrivateReply::PvySharePermissions(
+      sqldata::read_share_permissions(&conn, uid, id)?,
+    )),
+    PrivateRequest::PvqCreateShareLink(csl) => Ok(PrivateReply::PvyShareLink(
+      sqldata::create_share_link(&conn, uid, csl)?,
+    )),
+    PrivateRequest::PvqGetShareLinks(id) => Ok(PrivateReply::PvyShareLinks(
+      sqldata::read_share_links(&conn, uid, id)?,
+    )),
+    PrivateRequest::PvqRevokeShareLink(id) => {
+      sqldata::revoke_share_link(&conn, uid, id)?;
+      Ok(PrivateReply::PvyShareLinkRevoked(*id))
+    }
+  }
+}
+
+// can a request authenticated with an api token of this scope do this?
+// tokens can't manage tokens or two factor auth, or read admin data; that
+// takes a regular login.
+pub fn api_token_allows(scope: &ApiTokenScope, msg: &PrivateRequest) -> bool {
+  let read = match msg {
+    PrivateRequest::PvqGetZkNote(_)
+    | PrivateRequest::PvqGetZkNoteAndLinks(_)
+    | PrivateRequest::PvqGetZknIfChanged(_)
+    | PrivateRequest::PvqGetZkNoteComments(_)
+    | PrivateRequest::PvqGetZkNoteArchives(_)
+    | PrivateRequest::PvqGetArchiveZklinks(_)
+    | PrivateRequest::PvqGetZkLinksSince(_)
+    | PrivateRequest::PvqSearchZkNotes(_)
+    | PrivateRequest::PvqGetChangedNotes(_)
+    | PrivateRequest::PvqGetJobStatus(_)
+    | PrivateRequest::PvqGetSharePermissions(_)
+    | PrivateRequest::PvqGetShareLinks(_)
+    | PrivateRequest::PvqPowerDeletePreview(_) => true,
+    PrivateRequest::PvqPowerDelete(_)
+    | PrivateRequest::PvqUndoPowerDelete(_)
+    | PrivateRequest::PvqSetSharePermission(_)
+    | PrivateRequest::PvqCreateShareLink(_)
+    | PrivateRequest::PvqRevokeShareLink(_)
+    | PrivateRequest::PvqDeleteZkNote(_)
+    | PrivateRequest::PvqSaveZkNote(_)
+    | PrivateRequest::PvqSaveZkLinks(_)
+    | PrivateRequest::PvqSaveZkNoteAndLinks(_)
+    | PrivateRequest::PvqSaveImportZkNotes(_)
+    | PrivateRequest::PvqImportZkNotes(_)
+    | PrivateRequest::PvqImportVault(_)
+    | PrivateRequest::PvqExportSite(_)
+    | PrivateRequest::PvqSetHomeNote(_)
+    | PrivateRequest::PvqSyncRemote
+    | PrivateRequest::PvqSyncFiles(_)
+    | PrivateRequest::PvqMakeSyncBundle(_)
+    | PrivateRequest::PvqApplySyncBundle(_) => false,
+    PrivateRequest::PvqCreateApiToken(_)
+    | PrivateRequest::PvqGetApiTokens
+    | PrivateRequest::PvqTotpStatus
+    | PrivateRequest::PvqTotpBegin
+    | PrivateRequest::PvqTotpConfirm(_)
+    | PrivateRequest::PvqTotpDisable(_)
+    | PrivateRequest::PvqAdminResetTotp(_)
+    | PrivateRequest::PvqGetAuditLog(_)
+    | PrivateRequest::PvqGetStorageStats(_)
+    | PrivateRequest::PvqRevokeApiToken(_) => return false,
+  };
+  match scope {
+    ApiTokenScope::ReadOnly => read,
+    ApiTokenScope::ReadWrite => true,
+    ApiTokenScope::UploadOnly => false,
   }
 }
 
@@ -568,7 +942,7 @@ pub fn public_interface(
         what: gzne.what.clone(),
         znl: ZkNoteAndLinks {
           lzlinks: sqldata::read_public_lzlinks(&conn, &note.id)?,
-          links: sqldata::read_public_zklinks(&conn, &note.id)?,
+          links: sqldata::read_public_zklinks(&conn, &note.id, None)?,
           zknote: note,
         },
       }))
@@ -595,7 +969,26 @@ pub fn public_interface(
       );
       Ok(PublicReply::PbyZkNoteAndLinks(ZkNoteAndLinks {
         lzlinks: sqldata::read_public_lzlinks(&conn, &note.id)?,
-        links: sqldata::read_public_zklinks(&conn, &note.id)?,
+        links: sqldata::read_public_zklinks(&conn, &note.id, None)?,
+        zknote: note,
+      }))
+    }
+    PublicRequest::PbrGetShareLinkNote(gsln) => {
+      let conn = sqldata::connection_open(config.orgauth_config.db.as_path())?;
+      let slnote = sqldata::sharelink_note(&conn, gsln.token.as_str())?;
+      let zknoteid = match &gsln.zknote {
+        Some(id) => *id,
+        None => ZkNoteId::Zni(sqldata::uuid_for_note_id(&conn, slnote)?),
+      };
+      let (_, note) =
+        sqldata::read_zknote_sharelink(&conn, &config.file_path, None, Some(slnote), &zknoteid)?;
+      info!(
+        "public#getsharelinknote: {:?} - {} - {:?}",
+        zknoteid, note.title, ipaddr,
+      );
+      Ok(PublicReply::PbyZkNoteAndLinks(ZkNoteAndLinks {
+        lzlinks: sqldata::read_public_lzlinks(&conn, &note.id)?,
+        links: sqldata::read_public_zklinks(&conn, &note.id, Some(slnote))?,
         zknote: note,
       }))
     }
@@ -1,7 +1,11 @@
 use std::fmt;
+use std::future::Future;
 
+use crate::state::{new_jobid, State};
 use girlboss::Monitor;
-use log::{logger, Record};
+use log::{info, logger, Record};
+use orgauth::data::UserId;
+use zkprotocol::content::{JobState, JobStatus};
 
 #[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd, Debug)]
 pub struct JobId {
@@ -36,3 +40,49 @@ impl JobMonitor for LogMonitor {
     logger().log(&r);
   }
 }
+
+// run a job on its own thread and actix system, where girlboss can track it.
+// 'job' reports its progress and result through the monitor.
+pub fn spawn_job<F, Fut>(state: &State, uid: UserId, job: F) -> JobStatus
+where
+  F: FnOnce(GirlbossMonitor) -> Fut + Send + 'static,
+  Fut: Future<Output = ()> + 'static,
+{
+  let jid = new_jobid(state, uid);
+  let lgb = state.girlboss.clone();
+
+  std::thread::spawn(move || {
+    let rt = actix_rt::System::new();
+
+    rt.block_on(async move {
+      lgb
+        .write()
+        .map_err(|e| {
+          info!("rwlock error: {}", e);
+          e
+        })
+        .unwrap()
+        .start(jid, move |mon| async move {
+          job(GirlbossMonitor { monitor: mon }).await;
+          actix_rt::System::current().stop();
+        })
+        .map_err(|e| {
+          info!("girlboss start error: {}", e);
+          e
+        })
+        .unwrap();
+    });
+    rt.run()
+      .map_err(|e| {
+        info!("rt.run error: {}", e);
+        e
+      })
+      .unwrap()
+  });
+
+  JobStatus {
+    jobno: jid.jobno,
+    state: JobState::Started,
+    message: "".to_string(),
+  }
+}
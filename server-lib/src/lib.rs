@@ -1,14 +1,22 @@
+mod audit;
+pub mod bundle;
 pub mod config;
 pub mod error;
+mod export;
+mod fileserve;
 pub mod interfaces;
 pub mod jobs;
 mod migrations;
+mod ratelimit;
+mod resumable;
 pub mod search;
 pub mod sqldata;
 mod sqltest;
 pub mod state;
-mod sync;
+pub mod sync;
 mod synctest;
+mod totp;
+mod vault;
 use crate::{error as zkerr, state::State};
 use actix_cors::Cors;
 use actix_files::NamedFile;
@@ -39,7 +47,8 @@ pub use rusqlite;
 use rusqlite::Connection;
 use serde_json;
 use simple_error::simple_error;
-use sqldata::{get_single_value, local_server_id, LapinInfo};
+use sqldata::{get_single_value, local_server_id, Access, LapinInfo};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{stdin, Write};
 use std::path::Path;
@@ -56,9 +65,10 @@ pub use zkprotocol::content as zc;
 pub use zkprotocol::messages::PrivateStreamingMessage;
 pub use zkprotocol::search as zs;
 use zkprotocol::{
+  content::{AuditAction, Direction, ZkLink, ZkListNote},
   private::{PrivateError, PrivateReply, PrivateRequest},
   public::{PublicError, PublicReply, PublicRequest},
-  upload::UploadReply,
+  upload::{FilesAttached, UploadReply},
 };
 
 /*
@@ -177,6 +187,20 @@ async fn user(
   //   &item,
   //   req.connection_info()
   // );
+  let msg = item.into_inner();
+
+  // login attempts are limited per account, with lockout after repeated failures.
+  let login = match &msg {
+    orgauth::data::UserRequest::UrqLogin(l) => Some(l.uid.clone()),
+    _ => None,
+  };
+  if let Some(account) = &login {
+    if let Err(retry) = data.ratelimiter.check_login(account.as_str()) {
+      info!("login rate limited: {}", account);
+      return ratelimit::too_many_requests(retry);
+    }
+  }
+
   match async {
     let conn = sqldata::connection_open(data.config.orgauth_config.db.as_path())?;
 
@@ -184,13 +208,22 @@ async fn user(
       &conn,
       &mut ActixTokener { session: &session },
       &data.config,
-      item.into_inner(),
+      msg,
     )
     .await
   }
   .await
   {
-    Ok(sr) => HttpResponse::Ok().json(sr),
+    Ok(sr) => {
+      if let Some(account) = &login {
+        match &sr {
+          UserResponse::UrpInvalidUserOrPwd => data.ratelimiter.login_failed(account.as_str()),
+          UserResponse::UrpLoggedIn(_) => data.ratelimiter.login_succeeded(account.as_str()),
+          _ => (),
+        }
+      }
+      HttpResponse::Ok().json(sr)
+    }
     Err(e) => {
       error!("'user' err: {:?}", e);
       let se = UserResponse::UrpServerError(e.to_string());
@@ -199,6 +232,49 @@ async fn user(
   }
 }
 
+// second step of login for accounts with two factor auth.
+async fn totp_login(
+  session: Session,
+  data: web::Data<State>,
+  item: web::Json<zc::TotpLogin>,
+) -> HttpResponse {
+  // DON'T print login messages to the log!
+  let tl = item.into_inner();
+  if let Err(retry) = data.ratelimiter.check_login(tl.uid.as_str()) {
+    info!("login rate limited: {}", tl.uid);
+    return ratelimit::too_many_requests(retry);
+  }
+
+  match async {
+    let conn = sqldata::connection_open(data.config.orgauth_config.db.as_path())?;
+    interfaces::totp_login(&conn, &data.config, &tl).await
+  }
+  .await
+  {
+    Ok((sr, token)) => {
+      match &sr {
+        UserResponse::UrpInvalidUserOrPwd => data.ratelimiter.login_failed(tl.uid.as_str()),
+        UserResponse::UrpLoggedIn(_) => data.ratelimiter.login_succeeded(tl.uid.as_str()),
+        _ => (),
+      }
+      if let Some(token) = token {
+        if let Err(e) = session.insert("token", token) {
+          error!("'totp' session err: {:?}", e);
+          return HttpResponse::Ok().json(UserResponse::UrpServerError(e.to_string()));
+        }
+      }
+      HttpResponse::Ok().json(sr)
+    }
+    Err(e) => {
+      if let zkerr::Error::InvalidTotp = e {
+        data.ratelimiter.login_failed(tl.uid.as_str());
+      }
+      error!("'totp' err: {:?}", e);
+      HttpResponse::Ok().json(UserResponse::UrpServerError(e.to_string()))
+    }
+  }
+}
+
 async fn admin(
   session: Session,
   data: web::Data<State>,
@@ -210,14 +286,29 @@ async fn admin(
   //   &item,
   //   req.connection_info()
   // );
+  let ar = item.into_inner();
+  // just the request name for the audit log, since requests can carry passwords.
+  let what: String = format!("{:?}", ar)
+    .chars()
+    .take_while(|c| c.is_alphanumeric())
+    .collect();
   let mut cb = sqldata::zknotes_callbacks();
   match orgauth::endpoints::admin_interface_check(
     &mut ActixTokener { session: &session },
     &data.config.orgauth_config,
     &mut cb,
-    item.into_inner(),
+    ar,
   ) {
-    Ok(sr) => HttpResponse::Ok().json(sr),
+    Ok(sr) => {
+      match sqldata::connection_open(data.config.orgauth_config.db.as_path()).and_then(|conn| {
+        let uid = session_user(&conn, session, &data).ok().map(|u| u.id);
+        audit::audit(&conn, uid, AuditAction::Admin, what.as_str())
+      }) {
+        Ok(()) => (),
+        Err(e) => error!("admin audit error: {:?}", e),
+      }
+      HttpResponse::Ok().json(sr)
+    }
     Err(e) => {
       error!("'user' err: {:?}", e);
       let se = AdminResponse::ArpServerError(e.to_string());
@@ -304,14 +395,9 @@ async fn file(session: Session, state: web::Data<State>, req: HttpRequest) -> Ht
         Err(e) => return HttpResponse::InternalServerError().body(format!("{:?}", e)),
       };
 
-      let stpath = state.config.file_path.join(hash);
+      let stpath = state.config.file_path.join(hash.as_str());
 
-      match File::open(stpath.clone())
-        .and_then(|f| NamedFile::from_file(f, Path::new(zkln.title.as_str())))
-      {
-        Ok(f) => f.into_response(&req),
-        Err(e) => HttpResponse::NotFound().body(format!("{:?}", e)),
-      }
+      fileserve::serve_file(&req, stpath.as_path(), hash.as_str(), zkln.title.as_str()).await
     }
     None => HttpResponse::BadRequest().body("file id required: /file/<id>"),
   }
@@ -324,9 +410,34 @@ async fn receive_files(
   req: HttpRequest,
 ) -> HttpResponse {
   let li = connect_and_make_lapin_info(config.get_ref(), get_cookie_id(&req)).await;
-  match make_file_notes(session, &config, &li, &mut payload).await {
+  match make_file_notes(session, &config, &li, &mut payload, &req).await {
     Ok(r) => HttpResponse::Ok().json(r),
-    Err(e) => return HttpResponse::InternalServerError().body(format!("{:?}", e)),
+    Err(e) => match e.downcast_ref::<zkerr::Error>() {
+      Some(zkerr::Error::QuotaExceeded(_)) => HttpResponse::PayloadTooLarge().body(e.to_string()),
+      Some(zkerr::Error::InvalidApiToken) => HttpResponse::Unauthorized().body(e.to_string()),
+      Some(zkerr::Error::ApiTokenScope) => HttpResponse::Forbidden().body(e.to_string()),
+      _ => HttpResponse::InternalServerError().body(format!("{:?}", e)),
+    },
+  }
+}
+
+// the user for file uploads; in tauri mode, the last logged in user.
+// api tokens need read-write or upload scope.
+fn upload_user(
+  conn: &Connection,
+  session: Session,
+  state: &web::Data<State>,
+  req: &HttpRequest,
+) -> Result<UserId, zkerr::Error> {
+  if let Some(bt) = bearer_token(req) {
+    match sqldata::api_token_user(&conn, bt.as_str())? {
+      (uid, zc::ApiTokenScope::ReadWrite) | (uid, zc::ApiTokenScope::UploadOnly) => Ok(uid),
+      (_, zc::ApiTokenScope::ReadOnly) => Err(zkerr::Error::ApiTokenScope),
+    }
+  } else if state.config.tauri_mode {
+    get_tauri_uid(&conn)?.ok_or(zkerr::Error::NotLoggedIn)
+  } else {
+    Ok(session_user(&conn, session, &state)?.id)
   }
 }
 
@@ -335,67 +446,209 @@ async fn make_file_notes(
   state: &web::Data<State>,
   lapin_info: &Option<LapinInfo>,
   payload: &mut Multipart,
+  req: &HttpRequest,
 ) -> Result<UploadReply, Box<dyn Error>> {
   let conn = sqldata::connection_open(state.config.orgauth_config.db.as_path())?;
-  let uid = if state.config.tauri_mode {
-    get_tauri_uid(&conn)?.ok_or(zkerr::Error::NotLoggedIn)?
-  } else {
-    session_user(&conn, session, &state)?.id
-  };
+  let uid = upload_user(&conn, session, &state, req)?;
 
   let server = local_server_id(&conn)?;
 
   // Save the files to our temp path.
   let tp = state.config.file_tmp_path.clone();
-  let saved_files = save_files(&tp, payload).await?;
+  let remaining = sqldata::remaining_file_bytes(&conn, &state.config.quotas, uid)?;
+  let (saved_files, fields) = save_files(&tp, payload, remaining).await?;
+
+  let target = match upload_target(&conn, &state.config.file_path, uid, &fields) {
+    Ok(t) => t,
+    Err(e) => {
+      for (_, fp) in saved_files {
+        std::fs::remove_file(fp)?;
+      }
+      return Err(e.into());
+    }
+  };
 
+  // file notes and their links are created together, or not at all.  files added to
+  // file_path are removed if the transaction rolls back, and notifications only go out
+  // once it commits.
   let mut zklns = Vec::new();
+  let mut links = Vec::new();
+  let mut made = Vec::new();
 
-  for (name, fp) in saved_files {
-    // compute hash.
-    let fpath = Path::new(&fp);
+  let tr = conn.unchecked_transaction()?;
 
-    let (nid64, _noteid, _fid) = sqldata::make_file_note(
-      &conn,
-      &server,
-      &lapin_info,
-      &state.config.file_path,
-      uid,
-      &name,
-      fpath,
-      false,
-    )
-    .await?;
+  let r = async {
+    for (name, fp) in saved_files.iter() {
+      let fpath = Path::new(fp);
+      let fh = sha256::try_digest(fpath)?;
+      let had_note = sqldata::user_file_note(&conn, uid, fh.as_str())?.is_some();
+      let hashpath = state.config.file_path.join(fh.as_str());
+      let had_file = hashpath.exists();
+
+      // copy, so the upload is still there if this rolls back.
+      let (nid64, noteid, _fid) = sqldata::make_file_note_with_hash(
+        &conn,
+        &server,
+        &None,
+        &state.config.file_path,
+        uid,
+        name,
+        fpath,
+        fh,
+        true,
+        &state.config.quotas,
+      )
+      .await?;
+      made.push((noteid, had_note, had_file, hashpath));
+
+      // return zknoteedit.
+      let listnote = sqldata::read_zklistnote(&conn, &state.config.file_path, Some(uid), nid64)?;
+
+      if let Some((tid, ref tnote, ref direction)) = target {
+        let ((fromid, from), (toid, to)) = match direction {
+          Direction::To => ((nid64, &listnote), (tid, tnote)),
+          Direction::From => ((tid, tnote), (nid64, &listnote)),
+        };
+        sqldata::save_zklink(&conn, fromid, toid, uid, None)?;
+        links.push(ZkLink {
+          from: from.id,
+          to: to.id,
+          user: uid,
+          linkzknote: None,
+          delete: None,
+          fromname: Some(from.title.clone()),
+          toname: Some(to.title.clone()),
+        });
+      }
+
+      zklns.push(listnote);
+    }
+    Ok::<(), zkerr::Error>(())
+  }
+  .await;
+
+  let r = match r {
+    Ok(()) => tr.commit().map_err(|e| e.into()),
+    Err(e) => Err(e),
+  };
+
+  for (_, fp) in saved_files.iter() {
+    match std::fs::remove_file(fp) {
+      Ok(()) => (),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+      Err(e) => error!("error removing upload temp file {}: {:?}", fp, e),
+    }
+  }
+
+  match r {
+    Ok(()) => {
+      for ((noteid, had_note, had_file, _), ln) in made.iter().zip(zklns.iter()) {
+        if !had_note {
+          sqldata::publish_saved_zknote(uid, lapin_info, *noteid).await?;
+        }
+        if !had_note || !had_file {
+          sqldata::publish_make_file_note(lapin_info, uid, *noteid, ln.title.as_str()).await?;
+        }
+      }
+    }
+    Err(e) => {
+      // the transaction rolled back; remove the files it added.
+      for (_, _, had_file, hashpath) in made.iter() {
+        if !had_file {
+          std::fs::remove_file(hashpath)?;
+        }
+      }
+      return Err(e.into());
+    }
+  }
+
+  Ok(match target {
+    Some(_) => UploadReply::UrFilesAttached(FilesAttached {
+      notes: zklns,
+      links,
+    }),
+    None => UploadReply::UrFilesUploaded(zklns),
+  })
+}
 
-    // return zknoteedit.
-    let listnote = sqldata::read_zklistnote(&conn, &state.config.file_path, Some(uid), nid64)?;
+// an upload can include 'noteid' and 'direction' form fields, to link the
+// new file notes with an existing note.  direction is from the file note's
+// point of view: 'To' (the default) links file -> note, 'From' links note -> file.
+fn upload_target(
+  conn: &Connection,
+  file_path: &Path,
+  uid: UserId,
+  fields: &HashMap<String, String>,
+) -> Result<Option<(i64, ZkListNote, Direction)>, zkerr::Error> {
+  let noteid = match fields.get("noteid") {
+    Some(s) => Uuid::parse_str(s.trim())?,
+    None => return Ok(None),
+  };
+  let tid = sqldata::note_id_for_uuid(&conn, &noteid)?;
 
-    zklns.push(listnote);
+  match sqldata::zknote_access_id(&conn, Some(uid), tid)? {
+    Access::Private => return Err(zkerr::Error::NoteIsPrivate),
+    Access::Read | Access::ReadWrite => (),
   }
-  Ok(UploadReply::UrFilesUploaded(zklns))
+
+  let direction = match fields.get("direction").map(|s| s.trim()) {
+    None | Some("To") => Direction::To,
+    Some("From") => Direction::From,
+    Some(d) => {
+      return Err(zkerr::Error::String(format!(
+        "invalid link direction: {}",
+        d
+      )))
+    }
+  };
+
+  let tnote = sqldata::read_zklistnote(&conn, file_path, Some(uid), tid)?;
+
+  Ok(Some((tid, tnote, direction)))
 }
 
 async fn save_files(
   to_dir: &Path,
   payload: &mut Multipart,
-) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+  remaining: Option<i64>,
+) -> Result<(Vec<(String, String)>, HashMap<String, String>), Box<dyn Error>> {
   // iterate over multipart stream
 
   let mut rv = Vec::new();
+  let mut fields = HashMap::new();
+  // total bytes received.  stop early if that's more than the quota allows,
+  // rather than filling the disk; make_file_note does the exact check.
+  let mut received: i64 = 0;
 
   while let Some(mut field) = payload.try_next().await? {
     // A multipart/form-data stream has to contain `content_disposition`
     let content_disposition = field.content_disposition().clone();
     // .ok_or(simple_error::SimpleError::new("bad"))?;
 
-    let filename = content_disposition
-      .get_filename()
-      .ok_or(zkerr::Error::String(
-        "filename not found in content_disposition".to_string(),
-      ))?;
-
-    // can't add this in actix_multipart_rfc7578
-    // let mbuuid = content_disposition.get_unknown("noteuuid");
+    let filename = match content_disposition.get_filename() {
+      Some(f) => f.to_string(),
+      None => {
+        // not a file; a form field like 'noteid'.
+        let name = content_disposition
+          .get_name()
+          .ok_or(zkerr::Error::String(
+            "name or filename not found in content_disposition".to_string(),
+          ))?
+          .to_string();
+        let mut value = Vec::new();
+        while let Some(chunk) = field.try_next().await? {
+          value.extend_from_slice(&chunk);
+          if value.len() > 1024 {
+            return Err(Box::new(zkerr::Error::String(format!(
+              "form field too long: {}",
+              name
+            ))));
+          }
+        }
+        fields.insert(name, String::from_utf8(value)?);
+        continue;
+      }
+    };
 
     let wkfilename = Uuid::new_v4().to_string();
 
@@ -409,6 +662,20 @@ async fn save_files(
 
     // Field in turn is stream of *Bytes* object
     while let Some(chunk) = field.try_next().await? {
+      received += chunk.len() as i64;
+      if let Some(r) = remaining {
+        if received > r {
+          drop(f);
+          std::fs::remove_file(&filepath)?;
+          for (_, p) in rv {
+            std::fs::remove_file(p)?;
+          }
+          return Err(Box::new(zkerr::Error::QuotaExceeded(format!(
+            "upload exceeds the remaining {} bytes",
+            r
+          ))));
+        }
+      }
       // filesystem operations are blocking, we have to use threadpool
       f = web::block(move || f.write_all(&chunk).map(|_| f)).await??;
     }
@@ -418,16 +685,26 @@ async fn save_files(
       .into_string()
       .map_err(|osstr| simple_error!("couldn't convert filename to string: {:?}", osstr));
 
-    rv.push((filename.to_string(), ps?));
+    rv.push((filename, ps?));
   }
 
-  Ok(rv)
+  Ok((rv, fields))
 }
 
 fn get_cookie_id(req: &HttpRequest) -> Option<String> {
   req.cookie("id").map(|c| c.value().to_string())
 }
 
+// api token from an 'Authorization: Bearer <token>' header.
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+  req
+    .headers()
+    .get(actix_web::http::header::AUTHORIZATION)
+    .and_then(|h| h.to_str().ok())
+    .and_then(|s| s.strip_prefix("Bearer "))
+    .map(|s| s.trim().to_string())
+}
+
 async fn private(
   session: Session,
   data: web::Data<State>,
@@ -438,7 +715,15 @@ async fn private(
 
   let token = get_cookie_id(&req);
 
-  match zk_interface_check(&session, &mut state, token, item.into_inner()).await {
+  match zk_interface_check(
+    &session,
+    &mut state,
+    token,
+    bearer_token(&req),
+    item.into_inner(),
+  )
+  .await
+  {
     Ok(sr) => HttpResponse::Ok().json(sr),
     Err(e) => {
       error!("'private' err: {:?}", e);
@@ -453,9 +738,16 @@ async fn private_streaming(
   session: Session,
   data: web::Data<State>,
   item: web::Json<PrivateStreamingMessage>,
-  _req: HttpRequest,
+  req: HttpRequest,
 ) -> HttpResponse {
-  match zk_interface_check_streaming(&session, &data.config, item.into_inner()).await {
+  match zk_interface_check_streaming(
+    &session,
+    &data.config,
+    bearer_token(&req),
+    item.into_inner(),
+  )
+  .await
+  {
     Ok(hr) => hr,
     Err(e) => {
       error!("'private_streaming err: {:?}", e);
@@ -469,8 +761,25 @@ async fn zk_interface_check(
   session: &Session,
   state: &State,
   token: Option<String>,
+  bearer: Option<String>,
   msg: PrivateRequest,
 ) -> Result<PrivateReply, zkerr::Error> {
+  if let Some(bt) = bearer {
+    let conn = sqldata::connection_open(state.config.orgauth_config.db.as_path())?;
+    return match sqldata::api_token_user(&conn, bt.as_str()) {
+      Err(e) => Ok(PrivateReply::PvyServerError(PrivateError::PveLoginError(
+        e.to_string(),
+      ))),
+      Ok((uid, scope)) => {
+        if interfaces::api_token_allows(&scope, &msg) {
+          interfaces::zk_interface_loggedin(state, &conn, token, uid, &msg).await
+        } else {
+          Err(zkerr::Error::ApiTokenScope)
+        }
+      }
+    };
+  }
+
   match session.get::<Uuid>("token")? {
     None => Ok(PrivateReply::PvyServerError(PrivateError::PveNotLoggedIn)),
     Some(token_uuid) => {
@@ -500,8 +809,21 @@ async fn zk_interface_check(
 async fn zk_interface_check_streaming(
   session: &Session,
   config: &Config,
+  bearer: Option<String>,
   msg: PrivateStreamingMessage,
 ) -> Result<HttpResponse, Box<dyn Error>> {
+  // streaming requests only read.
+  if let Some(bt) = bearer {
+    let conn = sqldata::connection_open(config.orgauth_config.db.as_path())?;
+    return match sqldata::api_token_user(&conn, bt.as_str()) {
+      Err(e) => Ok(HttpResponse::Ok().json(PrivateReply::PvyServerError(
+        PrivateError::PveLoginError(e.to_string()),
+      ))),
+      Ok((_, zc::ApiTokenScope::UploadOnly)) => Err(Box::new(zkerr::Error::ApiTokenScope)),
+      Ok((uid, _)) => interfaces::zk_interface_loggedin_streaming(&config, uid, &msg).await,
+    };
+  }
+
   match session.get::<Uuid>("token")? {
     None => Ok(HttpResponse::Ok().json(PrivateReply::PvyServerError(PrivateError::PveNotLoggedIn))),
 
@@ -537,7 +859,8 @@ async fn private_upstreaming(
   req: HttpRequest,
 ) -> HttpResponse {
   let li = connect_and_make_lapin_info(data.get_ref(), get_cookie_id(&req)).await;
-  match zk_interface_check_upstreaming(&session, &data.config, &li, body).await {
+  match zk_interface_check_upstreaming(&session, &data.config, &li, bearer_token(&req), body).await
+  {
     Ok(hr) => hr,
     Err(e) => {
       error!("'private' err: {:?}", e);
@@ -556,56 +879,75 @@ async fn zk_interface_check_upstreaming(
   session: &Session,
   config: &Config,
   lapin_info: &Option<LapinInfo>,
+  bearer: Option<String>,
   body: web::Payload,
 ) -> Result<HttpResponse, Box<dyn Error>> {
-  match session.get::<Uuid>("token")? {
-    None => Ok(HttpResponse::Ok().json(PrivateReply::PvyServerError(PrivateError::PveNotLoggedIn))),
-
-    Some(token) => {
-      let conn = sqldata::connection_open(config.orgauth_config.db.as_path())?;
+  let conn = sqldata::connection_open(config.orgauth_config.db.as_path())?;
 
-      let server = sqldata::local_server_id(&conn)?;
-
-      match orgauth::dbfun::read_user_by_token_api(
+  // syncing changes in takes a read-write token.
+  let ruser = match bearer {
+    Some(bt) => match sqldata::api_token_user(&conn, bt.as_str()) {
+      Ok((uid, zc::ApiTokenScope::ReadWrite)) => {
+        orgauth::dbfun::read_user_by_id(&conn, uid).map_err(|e| format!("{:?}", e))
+      }
+      Ok(_) => Err(zkerr::Error::ApiTokenScope.to_string()),
+      Err(e) => Err(e.to_string()),
+    },
+    None => match session.get::<Uuid>("token")? {
+      None => {
+        return Ok(
+          HttpResponse::Ok().json(PrivateReply::PvyServerError(PrivateError::PveNotLoggedIn)),
+        )
+      }
+      Some(token) => orgauth::dbfun::read_user_by_token_api(
         &conn,
         token,
         config.orgauth_config.login_token_expiration_ms,
         config.orgauth_config.regen_login_tokens,
-      ) {
-        Err(e) => {
-          info!("read_user_by_token_api error2: {:?}, {:?}", token, e);
+      )
+      .map_err(|e| {
+        info!("read_user_by_token_api error2: {:?}, {:?}", token, e);
+        format!("{:?}", e)
+      }),
+    },
+  };
 
-          Ok(
-            HttpResponse::Ok().json(PrivateReply::PvyServerError(PrivateError::PveLoginError(
-              format!("{:?}", e),
-            ))),
-          )
-        }
-        Ok(user) => {
-          // finally!  processing messages as logged in user.
-          let rstream =
-            body.map_err(convert_bodyerr as fn(actix_web::error::PayloadError) -> std::io::Error);
-
-          let mut br = StreamReader::new(rstream);
-
-          let sr = sync::sync_from_stream(
-            &conn,
-            &lapin_info,
-            &server,
-            &user,
-            &config.file_path,
-            None,
-            None,
-            None,
-            None,
-            &mut sqldata::zknotes_callbacks(),
-            &mut br,
-          )
-          .await?;
+  let server = sqldata::local_server_id(&conn)?;
 
-          Ok(HttpResponse::Ok().json(sr))
-        }
-      }
+  match ruser {
+    Err(e) => {
+      Ok(HttpResponse::Ok().json(PrivateReply::PvyServerError(PrivateError::PveLoginError(e))))
+    }
+    Ok(user) => {
+      // finally!  processing messages as logged in user.
+      let rstream =
+        body.map_err(convert_bodyerr as fn(actix_web::error::PayloadError) -> std::io::Error);
+
+      let mut br = StreamReader::new(rstream);
+
+      audit::audit(
+        &conn,
+        Some(user.id),
+        AuditAction::Sync,
+        "sync from client stream",
+      )?;
+
+      let sr = sync::sync_from_stream(
+        &conn,
+        &lapin_info,
+        &server,
+        &user,
+        &config.file_path,
+        None,
+        None,
+        None,
+        None,
+        &mut sqldata::zknotes_callbacks(),
+        &mut br,
+      )
+      .await?;
+
+      Ok(HttpResponse::Ok().json(sr))
     }
   }
 }
@@ -639,6 +981,9 @@ pub fn defcon() -> Config {
     error_index_note: None,
     tauri_mode: false,
     aqmp_uri: None,
+    quotas: config::Quotas::default(),
+    session: config::SessionConfig::default(),
+    ratelimits: config::RateLimits::default(),
     orgauth_config: oc,
   }
 }
@@ -728,6 +1073,13 @@ pub async fn err_main(
         .help("grant admin privileges to user")
         .takes_value(true),
     )
+    .arg(
+      Arg::with_name("rotate_session_key")
+        .short("r")
+        .long("rotate_session_key")
+        .help("replace the session cookie signing key; logs out all users")
+        .takes_value(false),
+    )
     .arg(
       Arg::with_name("create_admin_user")
         .short("a")
@@ -789,11 +1141,25 @@ pub async fn err_main(
     let mut user = orgauth::dbfun::read_user_by_name(&conn, uid)?;
     user.admin = true;
     orgauth::dbfun::update_user(&conn, &user)?;
+    audit::audit(
+      &conn,
+      None,
+      AuditAction::PromoteAdmin,
+      format!("promoted user {} to admin", uid).as_str(),
+    )?;
 
     info!("promoted user {} to admin", uid);
     return Ok(());
   }
 
+  // new session key?
+  if matches.is_present("rotate_session_key") {
+    sqldata::dbinit(config.orgauth_config.db.as_path(), None)?;
+    new_session_key(&config)?;
+    info!("session key replaced");
+    return Ok(());
+  }
+
   // creating an admin user?
   if let Some(username) = matches.value_of("create_admin_user") {
     // prompt for password.
@@ -825,6 +1191,13 @@ pub async fn err_main(
       &mut cb.on_new_user,
     )?;
 
+    audit::audit(
+      &conn,
+      None,
+      AuditAction::PromoteAdmin,
+      format!("created admin user {}", username).as_str(),
+    )?;
+
     println!("admin user created: {}", username);
     return Ok(());
   }
@@ -836,6 +1209,73 @@ pub async fn err_main(
   Ok(())
 }
 
+// the session cookie signing key, from the key file or the db.
+// if there isn't one yet, make one.
+fn session_key(config: &Config) -> Result<Key, Box<dyn Error>> {
+  let stored = match &config.session.key_file {
+    Some(kf) => {
+      if kf.exists() {
+        Some(std::fs::read(kf)?)
+      } else {
+        None
+      }
+    }
+    None => {
+      let conn = sqldata::connection_open(config.orgauth_config.db.as_path())?;
+      match get_single_value(&conn, "session_key")? {
+        Some(s) => {
+          if s.len() % 2 != 0 || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(
+              zkerr::Error::String("stored session_key is not valid hex".to_string()).into(),
+            );
+          }
+          Some(
+            (0..s.len())
+              .step_by(2)
+              .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+              .collect::<Result<Vec<u8>, _>>()?,
+          )
+        }
+        None => None,
+      }
+    }
+  };
+
+  match stored {
+    Some(bytes) => Ok(Key::try_from(&bytes[..])?),
+    None => new_session_key(config),
+  }
+}
+
+// generate and store a new session key.  existing session cookies become invalid.
+fn new_session_key(config: &Config) -> Result<Key, Box<dyn Error>> {
+  let key = Key::generate();
+  match &config.session.key_file {
+    Some(kf) => {
+      std::fs::write(kf, key.master())?;
+      #[cfg(unix)]
+      {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(kf, std::fs::Permissions::from_mode(0o600))?;
+      }
+    }
+    None => {
+      let conn = sqldata::connection_open(config.orgauth_config.db.as_path())?;
+      sqldata::set_single_value(
+        &conn,
+        "session_key",
+        key
+          .master()
+          .iter()
+          .map(|b| format!("{:02x}", b))
+          .collect::<String>()
+          .as_str(),
+      )?;
+    }
+  }
+  Ok(key)
+}
+
 pub async fn init_server(mut config: Config) -> Result<Server, Box<dyn Error>> {
   // ------------------------------------------------------
   // normal server ops
@@ -866,18 +1306,59 @@ pub async fn init_server(mut config: Config) -> Result<Server, Box<dyn Error>> {
     config.orgauth_config.login_token_expiration_ms,
   )?;
 
+  let session_key = session_key(&config)?;
+
   let timer = timer::Timer::new();
+  let mut guards = Vec::new();
 
   let ptconfig = config.clone();
 
-  let _guard =
+  guards.push(
     timer.schedule_repeating(
       chrono::Duration::days(1),
       move || match orgauth::dbfun::purge_tokens(&ptconfig.orgauth_config) {
         Err(e) => error!("purge_tokens error: {}", e),
         Ok(_) => (),
       },
-    );
+    ),
+  );
+
+  let upload_hashers: resumable::UploadHashers = Arc::new(std::sync::Mutex::new(HashMap::new()));
+  let puconfig = config.clone();
+  let puhashers = upload_hashers.clone();
+
+  guards.push(
+    timer.schedule_repeating(chrono::Duration::hours(1), move || {
+      match resumable::purge_stale_uploads(
+        puconfig.orgauth_config.db.as_path(),
+        puconfig.file_tmp_path.as_path(),
+        &puhashers,
+      ) {
+        Err(e) => error!("purge_stale_uploads error: {}", e),
+        Ok(_) => (),
+      }
+    }),
+  );
+
+  let ratelimiter = Arc::new(ratelimit::RateLimiter::new(config.ratelimits.clone()));
+  let rlpurge = ratelimiter.clone();
+
+  guards.push(timer.schedule_repeating(chrono::Duration::minutes(10), move || rlpurge.purge()));
+
+  let pdconfig = config.clone();
+
+  guards.push(
+    timer.schedule_repeating(
+      chrono::Duration::hours(1),
+      move || match search::purge_power_deletes(
+        pdconfig.orgauth_config.db.as_path(),
+        pdconfig.file_path.as_path(),
+      ) {
+        Err(e) => error!("purge_power_deletes error: {}", e),
+        Ok(_) => (),
+      },
+    ),
+  );
 
   let lapin_conn = match config.aqmp_uri {
     Some(ref uri) => {
@@ -908,11 +1389,19 @@ pub async fn init_server(mut config: Config) -> Result<Server, Box<dyn Error>> {
     jobcounter: { RwLock::new(0 as i64) },
     server,
     lapin_conn: lapin_conn.into(),
+    upload_hashers,
+    ratelimiter: ratelimiter.clone(),
+    purges: Some(state::Purges { timer, guards }),
   });
 
   let c = config.clone();
   let server = HttpServer::new(move || {
     let staticpath = c.static_path.clone().unwrap_or(PathBuf::from("static/"));
+    let same_site = match c.session.same_site {
+      config::SameSite::Strict => cookie::SameSite::Strict,
+      config::SameSite::Lax => cookie::SameSite::Lax,
+      config::SameSite::None => cookie::SameSite::None,
+    };
     let d = c.clone();
     let cors = Cors::default()
       .allowed_origin_fn(move |rv, rh| {
@@ -945,24 +1434,40 @@ pub async fn init_server(mut config: Config) -> Result<Server, Box<dyn Error>> {
 
     App::new()
       .app_data(state.clone()) // <- create app with shared state
+      .wrap(ratelimit::RateLimit::new(ratelimiter.clone()))
       .wrap(cors)
       .wrap(TracingLogger::default())
       .wrap(
-        SessionMiddleware::builder(CookieSessionStore::default(), Key::from(&[0; 64]))
-          .cookie_secure(false)
+        SessionMiddleware::builder(CookieSessionStore::default(), session_key.clone())
+          .cookie_secure(c.session.cookie_secure)
+          .cookie_same_site(same_site)
           // customize session and cookie expiration
           .session_lifecycle(
-            PersistentSession::default().session_ttl(cookie::time::Duration::weeks(52)),
+            PersistentSession::default()
+              .session_ttl(cookie::time::Duration::days(c.session.ttl_days)),
           )
           .build(),
       )
       .service(web::resource("/upload").route(web::post().to(receive_files)))
+      .service(web::resource("/upload/resumable").route(web::post().to(resumable::create_upload)))
+      .service(
+        web::resource(r"/upload/resumable/{id}")
+          .route(web::get().to(resumable::upload_status))
+          .route(web::head().to(resumable::upload_status_head))
+          .route(web::patch().to(resumable::patch_upload))
+          .route(web::delete().to(resumable::delete_upload)),
+      )
+      .service(
+        web::resource(r"/upload/resumable/{id}/finalize")
+          .route(web::post().to(resumable::finalize_upload)),
+      )
       .service(web::resource("/public").route(web::post().to(public)))
       .service(web::resource("/private").route(web::post().to(private)))
       .service(web::resource("/stream").route(web::post().to(private_streaming)))
       .service(web::resource("/upstream").route(web::post().to(private_upstreaming)))
       .service(web::resource("/user").route(web::post().to(user)))
       .service(web::resource("/admin").route(web::post().to(admin)))
+      .service(web::resource("/totp").route(web::post().to(totp_login)))
       .service(web::resource(r"/file/{id}").route(web::get().to(file)))
       .service(web::resource(r"/register/{uid}/{key}").route(web::get().to(register)))
       .service(web::resource(r"/newemail/{uid}/{token}").route(web::get().to(new_email)))
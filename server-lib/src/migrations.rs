@@ -3199,3 +3199,244 @@ pub fn udpate43(dbfile: &Path) -> Result<(), zkerr::Error> {
   tr.commit()?;
   Ok(())
 }
+
+pub fn udpate44(dbfile: &Path) -> Result<(), zkerr::Error> {
+  // db connection without foreign key checking.
+  let conn = Connection::open(dbfile)?;
+  let tr = conn.unchecked_transaction()?;
+
+  // partially received files, for resumable uploads.
+  conn.execute(
+    "CREATE TABLE \"upload\" (
+      \"id\" INTEGER PRIMARY KEY NOT NULL,
+      \"uuid\" TEXT NOT NULL UNIQUE,
+      \"user\" INTEGER NOT NULL REFERENCES orgauth_user(id),
+      \"name\" TEXT NOT NULL,
+      \"size\" INTEGER NOT NULL,
+      \"received\" INTEGER NOT NULL,
+      \"createdate\" INTEGER NOT NULL,
+      \"changeddate\" INTEGER NOT NULL)",
+    params![],
+  )?;
+
+  tr.commit()?;
+  Ok(())
+}
+
+pub fn udpate45(dbfile: &Path) -> Result<(), zkerr::Error> {
+  // db connection without foreign key checking.
+  let conn = Connection::open(dbfile)?;
+  let tr = conn.unchecked_transaction()?;
+
+  // personal api tokens.  only the sha256 of the token is kept.
+  conn.execute(
+    "CREATE TABLE \"apitoken\" (
+      \"id\" INTEGER PRIMARY KEY NOT NULL,
+      \"uuid\" TEXT NOT NULL UNIQUE,
+      \"user\" INTEGER NOT NULL REFERENCES orgauth_user(id),
+      \"name\" TEXT NOT NULL,
+      \"scope\" TEXT NOT NULL,
+      \"tokenhash\" TEXT NOT NULL UNIQUE,
+      \"createdate\" INTEGER NOT NULL,
+      \"lastused\" INTEGER)",
+    params![],
+  )?;
+
+  tr.commit()?;
+  Ok(())
+}
+
+pub fn udpate46(dbfile: &Path) -> Result<(), zkerr::Error> {
+  // db connection without foreign key checking.
+  let conn = Connection::open(dbfile)?;
+  let tr = conn.unchecked_transaction()?;
+
+  // per user permissions for share notes.  0 = read, 1 = comment, 2 = write.
+  // no record means write, which was the only option before.
+  conn.execute(
+    "CREATE TABLE \"sharepermission\" (
+      \"share\" INTEGER NOT NULL REFERENCES zknote(id),
+      \"user\" INTEGER NOT NULL REFERENCES orgauth_user(id),
+      \"permission\" INTEGER NOT NULL,
+      PRIMARY KEY (\"share\", \"user\"))",
+    params![],
+  )?;
+
+  tr.commit()?;
+  Ok(())
+}
+
+pub fn udpate47(dbfile: &Path) -> Result<(), zkerr::Error> {
+  // db connection without foreign key checking.
+  let conn = Connection::open(dbfile)?;
+  let tr = conn.unchecked_transaction()?;
+
+  // links that give read access to a note, or a share's notes, without an account.
+  conn.execute(
+    "CREATE TABLE \"sharelink\" (
+      \"id\" INTEGER NOT NULL PRIMARY KEY,
+      \"uuid\" TEXT NOT NULL UNIQUE,
+      \"token\" TEXT NOT NULL UNIQUE,
+      \"user\" INTEGER NOT NULL REFERENCES orgauth_user(id),
+      \"zknote\" INTEGER NOT NULL REFERENCES zknote(id),
+      \"createdate\" INTEGER NOT NULL,
+      \"expires\" INTEGER,
+      \"revoked\" BOOLEAN NOT NULL DEFAULT 0,
+      \"views\" INTEGER NOT NULL DEFAULT 0,
+      \"lastview\" INTEGER)",
+    params![],
+  )?;
+
+  tr.commit()?;
+  Ok(())
+}
+
+pub fn udpate48(dbfile: &Path) -> Result<(), zkerr::Error> {
+  // db connection without foreign key checking.
+  let conn = Connection::open(dbfile)?;
+  let tr = conn.unchecked_transaction()?;
+
+  // no foreign key on user; entries outlive deleted users.
+  conn.execute(
+    "CREATE TABLE \"audit\" (
+      \"id\" INTEGER NOT NULL PRIMARY KEY,
+      \"user\" INTEGER,
+      \"action\" TEXT NOT NULL,
+      \"detail\" TEXT NOT NULL,
+      \"createdate\" INTEGER NOT NULL)",
+    params![],
+  )?;
+
+  conn.execute(
+    "CREATE INDEX \"audit_createdate\" ON \"audit\" (\"createdate\")",
+    params![],
+  )?;
+
+  // append only.
+  conn.execute(
+    "CREATE TRIGGER \"audit_no_update\" BEFORE UPDATE ON \"audit\"
+      BEGIN SELECT RAISE(ABORT, 'audit log is append only'); END",
+    params![],
+  )?;
+  conn.execute(
+    "CREATE TRIGGER \"audit_no_delete\" BEFORE DELETE ON \"audit\"
+      BEGIN SELECT RAISE(ABORT, 'audit log is append only'); END",
+    params![],
+  )?;
+
+  tr.commit()?;
+  Ok(())
+}
+
+pub fn udpate49(dbfile: &Path) -> Result<(), zkerr::Error> {
+  // db connection without foreign key checking.
+  let conn = Connection::open(dbfile)?;
+  let tr = conn.unchecked_transaction()?;
+
+  // power deletes, for undo.
+  conn.execute(
+    "CREATE TABLE \"deletebatch\" (
+      \"id\" INTEGER NOT NULL PRIMARY KEY,
+      \"uuid\" TEXT NOT NULL UNIQUE,
+      \"user\" INTEGER NOT NULL REFERENCES orgauth_user(id),
+      \"count\" INTEGER NOT NULL,
+      \"createdate\" INTEGER NOT NULL,
+      \"undone\" INTEGER,
+      \"purged\" BOOLEAN NOT NULL DEFAULT 0)",
+    params![],
+  )?;
+
+  // archive is the note's state before deletion.  files aren't archived, so
+  // they're kept until the batch can no longer be undone.
+  conn.execute(
+    "CREATE TABLE \"deletebatchnote\" (
+      \"batch\" INTEGER NOT NULL REFERENCES deletebatch(id),
+      \"zknote\" INTEGER NOT NULL REFERENCES zknote(id),
+      \"archive\" INTEGER NOT NULL REFERENCES zkarch(id),
+      \"file\" INTEGER,
+      PRIMARY KEY (\"batch\", \"zknote\"))",
+    params![],
+  )?;
+
+  tr.commit()?;
+  Ok(())
+}
+
+pub fn udpate50(dbfile: &Path) -> Result<(), zkerr::Error> {
+  // db connection without foreign key checking.
+  let conn = Connection::open(dbfile)?;
+  let tr = conn.unchecked_transaction()?;
+
+  // two factor auth.  not enabled until the user confirms a code.
+  // lastcounter is the last totp time step used, so codes can't be replayed.
+  conn.execute(
+    "CREATE TABLE \"totp\" (
+      \"user\" INTEGER NOT NULL PRIMARY KEY REFERENCES orgauth_user(id),
+      \"secret\" TEXT NOT NULL,
+      \"enabled\" BOOLEAN NOT NULL DEFAULT 0,
+      \"lastcounter\" INTEGER,
+      \"createdate\" INTEGER NOT NULL)",
+    params![],
+  )?;
+
+  conn.execute(
+    "CREATE TABLE \"totprecovery\" (
+      \"user\" INTEGER NOT NULL REFERENCES orgauth_user(id),
+      \"codehash\" TEXT NOT NULL,
+      \"used\" INTEGER,
+      PRIMARY KEY (\"user\", \"codehash\"))",
+    params![],
+  )?;
+
+  tr.commit()?;
+  Ok(())
+}
+
+pub fn udpate51(dbfile: &Path) -> Result<(), zkerr::Error> {
+  // db connection without foreign key checking.
+  let conn = Connection::open(dbfile)?;
+  let tr = conn.unchecked_transaction()?;
+
+  // notes encrypted on the client.  content is opaque to the server.
+  conn.execute(
+    "ALTER TABLE \"zknote\" ADD COLUMN \"encrypted\" BOOLEAN NOT NULL DEFAULT 0",
+    params![],
+  )?;
+  conn.execute(
+    "ALTER TABLE \"zkarch\" ADD COLUMN \"encrypted\" BOOLEAN NOT NULL DEFAULT 0",
+    params![],
+  )?;
+
+  tr.commit()?;
+  Ok(())
+}
+
+pub fn udpate52(dbfile: &Path) -> Result<(), zkerr::Error> {
+  // db connection without foreign key checking.
+  let conn = Connection::open(dbfile)?;
+  let tr = conn.unchecked_transaction()?;
+
+  // the user who last saved a note; null for the owner, or unknown.
+  conn.execute(
+    "ALTER TABLE \"zknote\" ADD COLUMN \"editor\" INTEGER REFERENCES orgauth_user(id)",
+    params![],
+  )?;
+  conn.execute(
+    "ALTER TABLE \"zkarch\" ADD COLUMN \"editor\" INTEGER REFERENCES orgauth_user(id)",
+    params![],
+  )?;
+
+  // the changeddate of the note when each user last viewed it.
+  conn.execute(
+    "CREATE TABLE \"noteview\" (
+      \"user\" INTEGER NOT NULL REFERENCES orgauth_user(id),
+      \"zknote\" INTEGER NOT NULL REFERENCES zknote(id),
+      \"changeddate\" INTEGER NOT NULL,
+      \"viewdate\" INTEGER NOT NULL,
+      PRIMARY KEY (\"user\", \"zknote\"))",
+    params![],
+  )?;
+
+  tr.commit()?;
+  Ok(())
+}
@@ -0,0 +1,482 @@
+use crate::config::RateLimits;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header;
+use actix_web::{Error, HttpResponse};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// per-ip limits apply to these paths.
+const LIMITED_PATHS: [&str; 3] = ["/user", "/totp", "/public"];
+
+const WINDOW: Duration = Duration::from_secs(60);
+// failures short of a lockout are forgotten after this.
+const FAILURE_WINDOW: Duration = Duration::from_secs(15 * 60);
+// most keys kept in each map.
+const MAX_KEYS: usize = 100_000;
+
+struct Window {
+  start: Instant,
+  count: u32,
+}
+
+struct Failures {
+  first: Instant,
+  count: u32,
+  until: Option<Instant>,
+}
+
+// in-process rate limit state.  Err values are how long until a retry is allowed.
+pub struct RateLimiter {
+  limits: RateLimits,
+  max_keys: usize,
+  ips: Mutex<HashMap<String, Window>>,
+  accounts: Mutex<HashMap<String, Window>>,
+  failures: Mutex<HashMap<String, Failures>>,
+}
+
+// make room for a new key in a full map; expired entries go first, then the oldest.
+fn make_room<T>(
+  map: &mut HashMap<String, T>,
+  key: &str,
+  max: usize,
+  live: impl Fn(&T) -> bool,
+  started: impl Fn(&T) -> Instant,
+) {
+  if map.len() < max || map.contains_key(key) {
+    return;
+  }
+  map.retain(|_, v| live(v));
+  if map.len() >= max {
+    if let Some(oldest) = map
+      .iter()
+      .min_by_key(|(_, v)| started(v))
+      .map(|(k, _)| k.clone())
+    {
+      map.remove(&oldest);
+    }
+  }
+}
+
+// logins are limited by account, however the name was typed.
+fn account_key(account: &str) -> String {
+  account.trim().to_lowercase()
+}
+
+// count a request in a one minute window.
+fn count(
+  windows: &Mutex<HashMap<String, Window>>,
+  key: &str,
+  limit: u32,
+  max_keys: usize,
+  now: Instant,
+) -> Result<(), Duration> {
+  let mut windows = windows.lock().unwrap();
+  make_room(
+    &mut windows,
+    key,
+    max_keys,
+    |w| now.duration_since(w.start) < WINDOW,
+    |w| w.start,
+  );
+  let w = windows.entry(key.to_string()).or_insert(Window {
+    start: now,
+    count: 0,
+  });
+  if now.duration_since(w.start) >= WINDOW {
+    w.start = now;
+    w.count = 0;
+  }
+  if w.count >= limit {
+    Err(WINDOW - now.duration_since(w.start))
+  } else {
+    w.count += 1;
+    Ok(())
+  }
+}
+
+// the address a request is limited by.  X-Forwarded-For is followed back through
+// trusted proxies only, so clients can't pick their own address.
+fn client_ip(trusted: &[String], peer: Option<IpAddr>, forwarded_for: Option<&str>) -> String {
+  let mut ip = match peer {
+    Some(p) => p.to_string(),
+    None => return "".to_string(),
+  };
+  if let Some(ff) = forwarded_for {
+    for a in ff.rsplit(',').map(|a| a.trim()) {
+      if !trusted.contains(&ip) || a.is_empty() {
+        break;
+      }
+      ip = a.to_string();
+    }
+  }
+  ip
+}
+
+impl RateLimiter {
+  pub fn new(limits: RateLimits) -> Self {
+    RateLimiter {
+      limits,
+      max_keys: MAX_KEYS,
+      ips: Mutex::new(HashMap::new()),
+      accounts: Mutex::new(HashMap::new()),
+      failures: Mutex::new(HashMap::new()),
+    }
+  }
+
+  pub fn request_ip(&self, req: &ServiceRequest) -> String {
+    client_ip(
+      &self.limits.trusted_proxies,
+      req.peer_addr().map(|a| a.ip()),
+      req
+        .headers()
+        .get("x-forwarded-for")
+        .and_then(|h| h.to_str().ok()),
+    )
+  }
+
+  pub fn check_ip(&self, ip: &str) -> Result<(), Duration> {
+    match self.limits.ip_per_minute {
+      Some(limit) => count(&self.ips, ip, limit, self.max_keys, Instant::now()),
+      None => Ok(()),
+    }
+  }
+
+  // call before a login attempt.
+  pub fn check_login(&self, account: &str) -> Result<(), Duration> {
+    self.check_login_at(account, Instant::now())
+  }
+
+  fn check_login_at(&self, account: &str, now: Instant) -> Result<(), Duration> {
+    let key = account_key(account);
+    if let Some(until) = self
+      .failures
+      .lock()
+      .unwrap()
+      .get(&key)
+      .and_then(|f| f.until)
+    {
+      if until > now {
+        return Err(until - now);
+      }
+    }
+    match self.limits.account_logins_per_minute {
+      Some(limit) => count(&self.accounts, key.as_str(), limit, self.max_keys, now),
+      None => Ok(()),
+    }
+  }
+
+  pub fn login_failed(&self, account: &str) {
+    self.login_failed_at(account, Instant::now())
+  }
+
+  fn login_failed_at(&self, account: &str, now: Instant) {
+    let key = account_key(account);
+    let max = Duration::from_secs(self.limits.lockout_max_secs);
+    let mut failures = self.failures.lock().unwrap();
+    make_room(
+      &mut failures,
+      key.as_str(),
+      self.max_keys,
+      |f| failures_live(f, max, now),
+      |f| f.first,
+    );
+    let f = failures.entry(key).or_insert(Failures {
+      first: now,
+      count: 0,
+      until: None,
+    });
+    if f.until.is_none() && now.duration_since(f.first) >= FAILURE_WINDOW {
+      f.first = now;
+      f.count = 0;
+    }
+    f.count += 1;
+    if f.count >= self.limits.lockout_failures {
+      let doublings = (f.count - self.limits.lockout_failures).min(32);
+      let secs = self
+        .limits
+        .lockout_secs
+        .saturating_mul(1 << doublings)
+        .min(self.limits.lockout_max_secs);
+      f.until = Some(now + Duration::from_secs(secs));
+    }
+  }
+
+  pub fn login_succeeded(&self, account: &str) {
+    self.failures.lock().unwrap().remove(&account_key(account));
+  }
+
+  // forget expired windows, failures and lockouts.  after a lockout, failure counts
+  // are kept until the max lockout has passed, so repeat offenders stay at the longer
+  // lockouts.
+  pub fn purge(&self) {
+    self.purge_at(Instant::now())
+  }
+
+  fn purge_at(&self, now: Instant) {
+    self
+      .ips
+      .lock()
+      .unwrap()
+      .retain(|_, w| now.duration_since(w.start) < WINDOW);
+    self
+      .accounts
+      .lock()
+      .unwrap()
+      .retain(|_, w| now.duration_since(w.start) < WINDOW);
+    let max = Duration::from_secs(self.limits.lockout_max_secs);
+    self
+      .failures
+      .lock()
+      .unwrap()
+      .retain(|_, f| failures_live(f, max, now));
+  }
+}
+
+fn failures_live(f: &Failures, max: Duration, now: Instant) -> bool {
+  match f.until {
+    Some(u) => u + max > now,
+    None => now.duration_since(f.first) < FAILURE_WINDOW,
+  }
+}
+
+pub fn too_many_requests(retry: Duration) -> HttpResponse {
+  HttpResponse::TooManyRequests()
+    .insert_header((header::RETRY_AFTER, (retry.as_secs() + 1).to_string()))
+    .finish()
+}
+
+// middleware for the per-ip limits.  login limits are checked in the /user and /totp handlers,
+// which know the account and the login result.
+pub struct RateLimit {
+  limiter: Arc<RateLimiter>,
+}
+
+impl RateLimit {
+  pub fn new(limiter: Arc<RateLimiter>) -> Self {
+    RateLimit { limiter }
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = Error;
+  type Transform = RateLimitMiddleware<S>;
+  type InitError = ();
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(RateLimitMiddleware {
+      service: Rc::new(service),
+      limiter: self.limiter.clone(),
+    }))
+  }
+}
+
+pub struct RateLimitMiddleware<S> {
+  service: Rc<S>,
+  limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    if LIMITED_PATHS.contains(&req.path()) {
+      let ip = self.limiter.request_ip(&req);
+      if let Err(retry) = self.limiter.check_ip(ip.as_str()) {
+        let resp = too_many_requests(retry).map_into_right_body();
+        return Box::pin(async move { Ok(req.into_response(resp)) });
+      }
+    }
+
+    let service = self.service.clone();
+    Box::pin(async move { Ok(service.call(req).await?.map_into_left_body()) })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use actix_web::test::{call_service, init_service, TestRequest};
+  use actix_web::{http::StatusCode, web, App};
+
+  fn limits() -> RateLimits {
+    RateLimits {
+      ip_per_minute: Some(3),
+      account_logins_per_minute: Some(100),
+      lockout_failures: 2,
+      lockout_secs: 30,
+      lockout_max_secs: 100,
+      trusted_proxies: vec!["10.0.0.1".to_string()],
+    }
+  }
+
+  #[test]
+  fn test_window() {
+    let windows = Mutex::new(HashMap::new());
+    let start = Instant::now();
+    for _ in 0..3 {
+      assert!(count(&windows, "a", 3, MAX_KEYS, start).is_ok());
+    }
+    // over the limit, until the window is up.
+    let retry = count(&windows, "a", 3, MAX_KEYS, start + Duration::from_secs(20)).unwrap_err();
+    assert_eq!(retry, Duration::from_secs(40));
+    // other keys have their own windows.
+    assert!(count(&windows, "b", 3, MAX_KEYS, start).is_ok());
+    // a new window.
+    assert!(count(&windows, "a", 3, MAX_KEYS, start + WINDOW).is_ok());
+
+    // a full map drops expired windows, then the oldest.
+    let windows = Mutex::new(HashMap::new());
+    assert!(count(&windows, "a", 3, 2, start).is_ok());
+    assert!(count(&windows, "b", 3, 2, start + Duration::from_secs(10)).is_ok());
+    assert!(count(&windows, "c", 3, 2, start + Duration::from_secs(20)).is_ok());
+    assert_eq!(windows.lock().unwrap().len(), 2);
+    assert!(!windows.lock().unwrap().contains_key("a"));
+    assert!(count(
+      &windows,
+      "d",
+      3,
+      2,
+      start + WINDOW + Duration::from_secs(15)
+    )
+    .is_ok());
+    assert_eq!(windows.lock().unwrap().len(), 2);
+    assert!(windows.lock().unwrap().contains_key("c"));
+  }
+
+  #[test]
+  fn test_lockout() {
+    let rl = RateLimiter::new(limits());
+    assert!(rl.check_login("acct").is_ok());
+    rl.login_failed("acct");
+    assert!(rl.check_login("acct").is_ok());
+
+    // lockout_secs at lockout_failures, then doubling, up to lockout_max_secs.
+    rl.login_failed("acct");
+    let wait = rl.check_login("acct").unwrap_err();
+    assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30));
+    rl.login_failed("acct");
+    let wait = rl.check_login("acct").unwrap_err();
+    assert!(wait > Duration::from_secs(59) && wait <= Duration::from_secs(60));
+    rl.login_failed("acct");
+    let wait = rl.check_login("acct").unwrap_err();
+    assert!(wait > Duration::from_secs(99) && wait <= Duration::from_secs(100));
+
+    // other accounts aren't affected.
+    assert!(rl.check_login("other").is_ok());
+
+    rl.login_succeeded("acct");
+    assert!(rl.check_login("acct").is_ok());
+  }
+
+  #[test]
+  fn test_failure_window() {
+    let mut rl = RateLimiter::new(limits());
+    let start = Instant::now();
+
+    // the account name is normalized.
+    rl.login_failed_at("Acct", start);
+    rl.login_failed_at(" acct", start);
+    assert!(rl.check_login_at("ACCT", start).is_err());
+    rl.login_succeeded("acct ");
+    assert!(rl.check_login_at("acct", start).is_ok());
+
+    // failures short of a lockout are forgotten after the failure window.
+    rl.login_failed_at("acct", start);
+    rl.login_failed_at("acct", start + FAILURE_WINDOW);
+    assert!(rl.check_login_at("acct", start + FAILURE_WINDOW).is_ok());
+    rl.purge_at(start + FAILURE_WINDOW * 2);
+    assert!(rl.failures.lock().unwrap().is_empty());
+
+    // lockouts are kept for the max lockout after they end.
+    rl.login_failed_at("acct", start);
+    rl.login_failed_at("acct", start);
+    rl.purge_at(start + Duration::from_secs(120));
+    assert_eq!(rl.failures.lock().unwrap().len(), 1);
+    rl.purge_at(start + Duration::from_secs(130));
+    assert!(rl.failures.lock().unwrap().is_empty());
+
+    // the number of accounts tracked is capped.
+    rl.max_keys = 2;
+    for a in ["a", "b", "c"] {
+      rl.login_failed_at(a, start);
+    }
+    assert_eq!(rl.failures.lock().unwrap().len(), 2);
+  }
+
+  #[test]
+  fn test_client_ip() {
+    let trusted = limits().trusted_proxies;
+    let client: IpAddr = "192.168.1.5".parse().unwrap();
+    let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+
+    assert_eq!(client_ip(&trusted, Some(client), None), "192.168.1.5");
+    // untrusted peers can't choose their address.
+    assert_eq!(
+      client_ip(&trusted, Some(client), Some("1.2.3.4")),
+      "192.168.1.5"
+    );
+    // the last address the trusted proxy saw, not what the client added.
+    assert_eq!(
+      client_ip(&trusted, Some(proxy), Some("1.2.3.4, 192.168.1.5")),
+      "192.168.1.5"
+    );
+    assert_eq!(
+      client_ip(&trusted, Some(proxy), Some("192.168.1.5, 10.0.0.1")),
+      "192.168.1.5"
+    );
+    assert_eq!(client_ip(&trusted, Some(proxy), None), "10.0.0.1");
+    assert_eq!(client_ip(&trusted, None, Some("1.2.3.4")), "");
+  }
+
+  #[actix_web::test]
+  async fn test_too_many_requests() {
+    let rl = Arc::new(RateLimiter::new(limits()));
+    let app = init_service(
+      App::new()
+        .wrap(RateLimit::new(rl.clone()))
+        .route("/public", web::post().to(HttpResponse::Ok))
+        .route("/private", web::post().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    let req = |path: &str, xff: &str| {
+      TestRequest::post()
+        .uri(path)
+        .peer_addr("192.168.1.5:4000".parse().unwrap())
+        .insert_header(("x-forwarded-for", xff))
+        .to_request()
+    };
+
+    for i in 0..3 {
+      // a changing X-Forwarded-For doesn't get a fresh limit.
+      let resp = call_service(&app, req("/public", i.to_string().as_str())).await;
+      assert_eq!(resp.status(), StatusCode::OK);
+    }
+    let resp = call_service(&app, req("/public", "9.9.9.9")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp.headers().contains_key(header::RETRY_AFTER));
+
+    // only the listed paths are limited.
+    let resp = call_service(&app, req("/private", "")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+  }
+}
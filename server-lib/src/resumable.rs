@@ -0,0 +1,636 @@
+// resumable uploads, loosely following the tus protocol:
+//   POST   /upload/resumable                 create, with a CreateUpload json body.
+//   HEAD   /upload/resumable/{id}            Upload-Offset and Upload-Length headers.
+//   GET    /upload/resumable/{id}            UploadStatus json.
+//   PATCH  /upload/resumable/{id}            append bytes at the Upload-Offset header.
+//   POST   /upload/resumable/{id}/finalize   make the file note, returns UploadReply.
+//   DELETE /upload/resumable/{id}            abandon the upload.
+use crate::error as zkerr;
+use crate::interfaces::connect_and_make_lapin_info;
+use crate::sqldata::{self, local_server_id};
+use crate::state::State;
+use crate::{get_cookie_id, upload_user};
+use actix_session::Session;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures_util::StreamExt;
+use log::error;
+use orgauth::util::now;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+use zkprotocol::upload::{CreateUpload, UploadReply};
+
+// hash state for in-progress uploads.  Busy while a request is writing or finalizing
+// the upload, otherwise the number of bytes hashed so far and the hash.
+// if the server restarts, the hash is rebuilt from the partial file.
+pub enum UploadHash {
+  Busy,
+  Hashed(i64, Sha256),
+}
+
+pub type UploadHashers = Arc<Mutex<HashMap<Uuid, UploadHash>>>;
+
+// partial uploads with no new data for this long get deleted.
+pub const STALE_UPLOAD_MS: i64 = 24 * 60 * 60 * 1000;
+// uploads one user can have in progress at once.
+pub const MAX_OPEN_UPLOADS: i64 = 10;
+
+const UPLOAD_OFFSET: &str = "Upload-Offset";
+const UPLOAD_LENGTH: &str = "Upload-Length";
+
+fn part_path(tmp_path: &Path, id: &Uuid) -> PathBuf {
+  tmp_path.join(format!("upload-{}", id))
+}
+
+fn upload_id(req: &HttpRequest) -> Result<Uuid, zkerr::Error> {
+  match req.match_info().get("id") {
+    Some(id) => Ok(Uuid::parse_str(id)?),
+    None => Err(zkerr::Error::String("upload id required".to_string())),
+  }
+}
+
+fn err_response(e: zkerr::Error) -> HttpResponse {
+  match e {
+    zkerr::Error::NotLoggedIn | zkerr::Error::InvalidApiToken => {
+      HttpResponse::Unauthorized().body(e.to_string())
+    }
+    zkerr::Error::ApiTokenScope => HttpResponse::Forbidden().body(e.to_string()),
+    zkerr::Error::QuotaExceeded(_) => HttpResponse::PayloadTooLarge().body(e.to_string()),
+    zkerr::Error::UploadBusy => HttpResponse::Conflict().body(e.to_string()),
+    zkerr::Error::Uuid(_) => HttpResponse::BadRequest().body(e.to_string()),
+    zkerr::Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => {
+      HttpResponse::NotFound().body("upload not found")
+    }
+    _ => {
+      error!("resumable upload error: {:?}", e);
+      HttpResponse::InternalServerError().body(format!("{:?}", e))
+    }
+  }
+}
+
+// hash the first 'len' bytes of a partial upload.
+fn hash_prefix(path: &Path, len: i64) -> Result<Sha256, zkerr::Error> {
+  let mut hasher = Sha256::new();
+  let mut f = std::fs::File::open(path)?.take(len as u64);
+  let mut buf = vec![0; 64 * 1024];
+  loop {
+    let n = f.read(&mut buf)?;
+    if n == 0 {
+      break;
+    }
+    hasher.update(&buf[..n]);
+  }
+  Ok(hasher)
+}
+
+// claim an upload, so only one request at a time writes or finalizes it.
+// returns the saved hash state, if any.
+fn reserve(hashers: &UploadHashers, id: &Uuid) -> Result<Option<(i64, Sha256)>, zkerr::Error> {
+  let mut h = hashers
+    .lock()
+    .map_err(|e| zkerr::Error::String(e.to_string()))?;
+  match h.insert(*id, UploadHash::Busy) {
+    Some(UploadHash::Busy) => Err(zkerr::Error::UploadBusy),
+    Some(UploadHash::Hashed(n, hasher)) => Ok(Some((n, hasher))),
+    None => Ok(None),
+  }
+}
+
+// give up the claim, keeping the hash state if there is one.
+fn release(
+  hashers: &UploadHashers,
+  id: &Uuid,
+  hash: Option<(i64, Sha256)>,
+) -> Result<(), zkerr::Error> {
+  let mut h = hashers
+    .lock()
+    .map_err(|e| zkerr::Error::String(e.to_string()))?;
+  match hash {
+    // unless the upload was deleted meanwhile.
+    Some((n, hasher)) if h.contains_key(id) => h.insert(*id, UploadHash::Hashed(n, hasher)),
+    _ => h.remove(id),
+  };
+  Ok(())
+}
+
+// the hasher for this upload, rebuilt if it's missing or out of step.
+fn take_hasher(
+  saved: Option<(i64, Sha256)>,
+  path: &Path,
+  received: i64,
+) -> Result<Sha256, zkerr::Error> {
+  match saved {
+    Some((n, hasher)) if n == received => Ok(hasher),
+    _ => hash_prefix(path, received),
+  }
+}
+
+pub async fn create_upload(
+  session: Session,
+  state: web::Data<State>,
+  item: web::Json<CreateUpload>,
+  req: HttpRequest,
+) -> HttpResponse {
+  let r = async {
+    let cu = item.into_inner();
+    if cu.size < 0 {
+      return Ok(HttpResponse::BadRequest().body("negative upload size"));
+    }
+    let conn = sqldata::connection_open(state.config.orgauth_config.db.as_path())?;
+    let uid = upload_user(&conn, session, &state, &req)?;
+
+    let (open, open_bytes) = sqldata::open_uploads(&conn, uid)?;
+    if open >= MAX_OPEN_UPLOADS {
+      return Ok(
+        HttpResponse::TooManyRequests().body(format!("{} uploads already in progress", open)),
+      );
+    }
+    // uploads in progress count against the quota too.
+    if let Some(r) = sqldata::remaining_file_bytes(&conn, &state.config.quotas, uid)? {
+      if cu.size > r - open_bytes {
+        return Err(zkerr::Error::QuotaExceeded(format!(
+          "upload exceeds the remaining {} bytes, less {} bytes for uploads in progress",
+          r, open_bytes
+        )));
+      }
+    }
+
+    let us = sqldata::create_upload(&conn, uid, cu.name.as_str(), cu.size)?;
+    std::fs::File::create(part_path(&state.config.file_tmp_path, &us.id))?;
+
+    Ok::<HttpResponse, zkerr::Error>(
+      HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("/upload/resumable/{}", us.id)))
+        .insert_header((UPLOAD_OFFSET, "0"))
+        .json(us),
+    )
+  };
+  r.await.unwrap_or_else(err_response)
+}
+
+pub async fn upload_status(
+  session: Session,
+  state: web::Data<State>,
+  req: HttpRequest,
+) -> HttpResponse {
+  let r = || {
+    let conn = sqldata::connection_open(state.config.orgauth_config.db.as_path())?;
+    let uid = upload_user(&conn, session, &state, &req)?;
+    let us = sqldata::read_upload(&conn, uid, &upload_id(&req)?)?;
+    Ok::<HttpResponse, zkerr::Error>(
+      HttpResponse::Ok()
+        .insert_header((UPLOAD_OFFSET, us.received.to_string()))
+        .insert_header((UPLOAD_LENGTH, us.size.to_string()))
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(us),
+    )
+  };
+  r().unwrap_or_else(err_response)
+}
+
+pub async fn upload_status_head(
+  session: Session,
+  state: web::Data<State>,
+  req: HttpRequest,
+) -> HttpResponse {
+  let r = || {
+    let conn = sqldata::connection_open(state.config.orgauth_config.db.as_path())?;
+    let uid = upload_user(&conn, session, &state, &req)?;
+    let us = sqldata::read_upload(&conn, uid, &upload_id(&req)?)?;
+    Ok::<HttpResponse, zkerr::Error>(
+      HttpResponse::Ok()
+        .insert_header((UPLOAD_OFFSET, us.received.to_string()))
+        .insert_header((UPLOAD_LENGTH, us.size.to_string()))
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .finish(),
+    )
+  };
+  r().unwrap_or_else(err_response)
+}
+
+pub async fn patch_upload(
+  session: Session,
+  state: web::Data<State>,
+  req: HttpRequest,
+  mut body: web::Payload,
+) -> HttpResponse {
+  let r = async {
+    let conn = sqldata::connection_open(state.config.orgauth_config.db.as_path())?;
+    let uid = upload_user(&conn, session, &state, &req)?;
+    let id = upload_id(&req)?;
+    // check ownership before claiming the upload.
+    sqldata::read_upload(&conn, uid, &id)?;
+    let saved = reserve(&state.upload_hashers, &id)?;
+    let r = write_upload(&state, &conn, uid, &id, saved, &req, &mut body).await;
+    match r {
+      Ok((resp, hash)) => {
+        release(&state.upload_hashers, &id, hash)?;
+        Ok::<HttpResponse, zkerr::Error>(resp)
+      }
+      Err(e) => {
+        release(&state.upload_hashers, &id, None)?;
+        Err(e)
+      }
+    }
+  };
+  r.await.unwrap_or_else(err_response)
+}
+
+// append the request body to a claimed upload.  returns the hash state to keep.
+async fn write_upload(
+  state: &State,
+  conn: &rusqlite::Connection,
+  uid: orgauth::data::UserId,
+  id: &Uuid,
+  saved: Option<(i64, Sha256)>,
+  req: &HttpRequest,
+  body: &mut web::Payload,
+) -> Result<(HttpResponse, Option<(i64, Sha256)>), zkerr::Error> {
+  // read after claiming, so 'received' can't change underneath us.
+  let us = sqldata::read_upload(conn, uid, id)?;
+
+  let offset = match req
+    .headers()
+    .get(UPLOAD_OFFSET)
+    .and_then(|h| h.to_str().ok())
+    .and_then(|s| s.parse::<i64>().ok())
+  {
+    Some(o) => o,
+    None => {
+      return Ok((
+        HttpResponse::BadRequest().body("Upload-Offset header required"),
+        saved,
+      ))
+    }
+  };
+
+  // the client has to send what we asked for, not what it thinks we have.
+  if offset != us.received {
+    return Ok((
+      HttpResponse::Conflict()
+        .insert_header((UPLOAD_OFFSET, us.received.to_string()))
+        .finish(),
+      saved,
+    ));
+  }
+
+  let path = part_path(&state.config.file_tmp_path, id);
+  let mut hasher = take_hasher(saved, &path, us.received)?;
+
+  // drop anything past 'received' - from a write that didn't get recorded.
+  let mut f = OpenOptions::new().write(true).open(&path)?;
+  f.set_len(us.received as u64)?;
+  f.seek(SeekFrom::End(0))?;
+
+  // keep whatever arrives, even if the connection drops partway.
+  let mut received = us.received;
+  let mut res = Ok(());
+  while let Some(chunk) = body.next().await {
+    match chunk {
+      Ok(bytes) => {
+        if received + bytes.len() as i64 > us.size {
+          res = Err(zkerr::Error::String(
+            "upload exceeds its declared size".to_string(),
+          ));
+          break;
+        }
+        let wb = bytes.clone();
+        match web::block(move || f.write_all(&wb).map(|_| f)).await {
+          Ok(Ok(nf)) => f = nf,
+          Ok(Err(e)) => {
+            res = Err(e.into());
+            break;
+          }
+          Err(e) => {
+            res = Err(zkerr::Error::String(e.to_string()));
+            break;
+          }
+        }
+        hasher.update(&bytes);
+        received += bytes.len() as i64;
+      }
+      Err(e) => {
+        res = Err(zkerr::Error::String(e.to_string()));
+        break;
+      }
+    }
+  }
+
+  sqldata::set_upload_received(conn, id, received)?;
+
+  match res {
+    Ok(()) => Ok((
+      HttpResponse::NoContent()
+        .insert_header((UPLOAD_OFFSET, received.to_string()))
+        .finish(),
+      Some((received, hasher)),
+    )),
+    Err(e) => Err(e),
+  }
+}
+
+pub async fn finalize_upload(
+  session: Session,
+  state: web::Data<State>,
+  req: HttpRequest,
+) -> HttpResponse {
+  let r = async {
+    let conn = sqldata::connection_open(state.config.orgauth_config.db.as_path())?;
+    let uid = upload_user(&conn, session, &state, &req)?;
+    let id = upload_id(&req)?;
+    sqldata::read_upload(&conn, uid, &id)?;
+    let saved = reserve(&state.upload_hashers, &id)?;
+    let r = finish_upload(&state, &conn, uid, &id, saved, &req).await;
+    // if finalizing failed, the hash is rebuilt from the file next time.
+    release(&state.upload_hashers, &id, None)?;
+    r
+  };
+  r.await.unwrap_or_else(err_response)
+}
+
+// make the file note for a claimed upload.
+async fn finish_upload(
+  state: &State,
+  conn: &rusqlite::Connection,
+  uid: orgauth::data::UserId,
+  id: &Uuid,
+  saved: Option<(i64, Sha256)>,
+  req: &HttpRequest,
+) -> Result<HttpResponse, zkerr::Error> {
+  let us = sqldata::read_upload(conn, uid, id)?;
+
+  if us.received != us.size {
+    return Ok(
+      HttpResponse::Conflict()
+        .insert_header((UPLOAD_OFFSET, us.received.to_string()))
+        .body("upload incomplete"),
+    );
+  }
+
+  let path = part_path(&state.config.file_tmp_path, id);
+  let hasher = take_hasher(saved, &path, us.received)?;
+  let fh = format!("{:x}", hasher.finalize());
+
+  let server = local_server_id(conn)?;
+  let li = connect_and_make_lapin_info(state, get_cookie_id(req)).await;
+
+  let r = sqldata::make_file_note_with_hash(
+    conn,
+    &server,
+    &li,
+    &state.config.file_path,
+    uid,
+    &us.name,
+    path.as_path(),
+    fh,
+    false,
+    &state.config.quotas,
+  )
+  .await;
+
+  // make_file_note deletes the file if over quota, so the upload is done either way.
+  if let Ok(_) | Err(zkerr::Error::QuotaExceeded(_)) = r {
+    sqldata::delete_upload(conn, id)?;
+  }
+  let (nid64, _noteid, _fid) = r?;
+
+  let listnote = sqldata::read_zklistnote(conn, &state.config.file_path, Some(uid), nid64)?;
+
+  Ok(HttpResponse::Ok().json(UploadReply::UrFilesUploaded(vec![listnote])))
+}
+
+pub async fn delete_upload(
+  session: Session,
+  state: web::Data<State>,
+  req: HttpRequest,
+) -> HttpResponse {
+  let r = || {
+    let conn = sqldata::connection_open(state.config.orgauth_config.db.as_path())?;
+    let uid = upload_user(&conn, session, &state, &req)?;
+    let id = upload_id(&req)?;
+    // check ownership.
+    sqldata::read_upload(&conn, uid, &id)?;
+    remove_upload(
+      &conn,
+      &state.config.file_tmp_path,
+      &state.upload_hashers,
+      &id,
+    )?;
+    Ok::<HttpResponse, zkerr::Error>(HttpResponse::NoContent().finish())
+  };
+  r().unwrap_or_else(err_response)
+}
+
+fn remove_upload(
+  conn: &rusqlite::Connection,
+  tmp_path: &Path,
+  hashers: &UploadHashers,
+  id: &Uuid,
+) -> Result<(), zkerr::Error> {
+  match std::fs::remove_file(part_path(tmp_path, id)) {
+    Ok(()) => (),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+    Err(e) => Err(e)?,
+  }
+  sqldata::delete_upload(conn, id)?;
+  hashers
+    .lock()
+    .map_err(|e| zkerr::Error::String(e.to_string()))?
+    .remove(id);
+  Ok(())
+}
+
+// delete partial uploads that haven't been touched in a while.  uploads a request
+// has claimed are left alone; that request is still writing or finalizing them.
+pub fn purge_stale_uploads(
+  dbfile: &Path,
+  tmp_path: &Path,
+  hashers: &UploadHashers,
+) -> Result<(), zkerr::Error> {
+  let conn = sqldata::connection_open(dbfile)?;
+  for id in sqldata::stale_uploads(&conn, now()? - STALE_UPLOAD_MS)? {
+    match reserve(hashers, &id) {
+      Ok(_) => remove_upload(&conn, tmp_path, hashers, &id)?,
+      Err(zkerr::Error::UploadBusy) => (),
+      Err(e) => Err(e)?,
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::sqldata::{connection_open, create_api_token, dbinit, zknotes_callbacks};
+  use crate::state::offline_state;
+  use actix_web::http::StatusCode;
+  use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+  use actix_web::App;
+  use orgauth::data::RegistrationData;
+  use orgauth::dbfun::new_user;
+  use zkprotocol::content::ApiTokenScope;
+  use zkprotocol::upload::UploadStatus;
+
+  #[actix_web::test]
+  async fn test_resumable() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("resumable_test-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(dir.join("files"))?;
+    std::fs::create_dir_all(dir.join("temp"))?;
+
+    let mut config = crate::defcon();
+    config.orgauth_config.db = dir.join("resumable.db");
+    config.file_path = dir.join("files");
+    config.file_tmp_path = dir.join("temp");
+    config.quotas.user_bytes = Some(30);
+
+    let server = dbinit(config.orgauth_config.db.as_path(), None)?;
+    let conn = connection_open(config.orgauth_config.db.as_path())?;
+    let mut cb = zknotes_callbacks();
+    let uid = new_user(
+      &conn,
+      &RegistrationData {
+        uid: "uploader".to_string(),
+        pwd: "".to_string(),
+        email: "".to_string(),
+        remote_url: "".to_string(),
+      },
+      None,
+      None,
+      false,
+      None,
+      None,
+      None,
+      None,
+      None,
+      &mut cb.on_new_user,
+    )?;
+    let bearer = format!(
+      "Bearer {}",
+      create_api_token(&conn, uid, "test", &ApiTokenScope::ReadWrite)?.token
+    );
+
+    let state = web::Data::new(offline_state(config, server));
+    let app = init_service(
+      App::new()
+        .app_data(state.clone())
+        .route("/upload/resumable", web::post().to(create_upload))
+        .route("/upload/resumable/{id}", web::patch().to(patch_upload))
+        .route(
+          "/upload/resumable/{id}/finalize",
+          web::post().to(finalize_upload),
+        ),
+    )
+    .await;
+
+    let content = "0123456789abcdefghij";
+    let patch = |id: &Uuid, offset: i64, bytes: &'static str| {
+      TestRequest::patch()
+        .uri(format!("/upload/resumable/{}", id).as_str())
+        .insert_header((header::AUTHORIZATION, bearer.clone()))
+        .insert_header((UPLOAD_OFFSET, offset.to_string()))
+        .set_payload(bytes)
+        .to_request()
+    };
+    let offset_header = |resp: &actix_web::dev::ServiceResponse| {
+      resp
+        .headers()
+        .get(UPLOAD_OFFSET)
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.to_string())
+    };
+
+    let create = |size: i64| {
+      TestRequest::post()
+        .uri("/upload/resumable")
+        .insert_header((header::AUTHORIZATION, bearer.clone()))
+        .set_json(CreateUpload {
+          name: "resumable.txt".to_string(),
+          size,
+        })
+        .to_request()
+    };
+
+    // create.
+    let resp = call_service(&app, create(content.len() as i64)).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let us: UploadStatus = read_body_json(resp).await;
+    assert_eq!(us.received, 0);
+
+    // uploads in progress count against the quota, and there can only be so many.
+    let resp = call_service(&app, create(content.len() as i64)).await;
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    for _ in 1..MAX_OPEN_UPLOADS {
+      let resp = call_service(&app, create(1)).await;
+      assert_eq!(resp.status(), StatusCode::CREATED);
+    }
+    let resp = call_service(&app, create(1)).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // partial patch.
+    let resp = call_service(&app, patch(&us.id, 0, &content[..8])).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(offset_header(&resp), Some("8".to_string()));
+
+    // the wrong offset gets the right one back.
+    let resp = call_service(&app, patch(&us.id, 0, &content[..8])).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(offset_header(&resp), Some("8".to_string()));
+
+    // while another request has the upload, no one else can write it.
+    state
+      .upload_hashers
+      .lock()
+      .unwrap()
+      .insert(us.id, UploadHash::Busy);
+    let resp = call_service(&app, patch(&us.id, 8, &content[8..12])).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    // and it isn't purged, however stale it looks.
+    conn.execute(
+      "update upload set changeddate = 0 where uuid = ?1",
+      rusqlite::params![us.id.to_string()],
+    )?;
+    purge_stale_uploads(
+      state.config.orgauth_config.db.as_path(),
+      &state.config.file_tmp_path,
+      &state.upload_hashers,
+    )?;
+    assert!(sqldata::read_upload(&conn, uid, &us.id).is_ok());
+    assert!(part_path(&state.config.file_tmp_path, &us.id).exists());
+
+    // as after a restart, the hash is rebuilt from the partial file.
+    state.upload_hashers.lock().unwrap().clear();
+    let resp = call_service(&app, patch(&us.id, 8, &content[8..])).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(offset_header(&resp), Some(content.len().to_string()));
+
+    // finalize; the file is stored under the hash of the whole upload.
+    let resp = call_service(
+      &app,
+      TestRequest::post()
+        .uri(format!("/upload/resumable/{}/finalize", us.id).as_str())
+        .insert_header((header::AUTHORIZATION, bearer.clone()))
+        .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let reply: UploadReply = read_body_json(resp).await;
+    match reply {
+      UploadReply::UrFilesUploaded(notes) => assert_eq!(notes.len(), 1),
+      r => panic!("unexpected reply {:?}", r),
+    }
+    let hash = sha256::digest(content);
+    assert!(state.config.file_path.join(hash).exists());
+    assert!(!part_path(&state.config.file_tmp_path, &us.id).exists());
+    assert!(sqldata::read_upload(&conn, uid, &us.id).is_err());
+
+    std::fs::remove_dir_all(dir)?;
+
+    Ok(())
+  }
+}
@@ -1,36 +1,51 @@
+use crate::audit::audit;
 use crate::error as zkerr;
 use crate::sqldata;
 use crate::sqldata::local_server_id;
 use crate::sqldata::server_id;
-use crate::sqldata::{delete_zknote, get_sysids, note_id};
+use crate::sqldata::{
+  archive_zknote_i64, delete_zknote_batch, get_sysids, note_id, remove_unused_file,
+};
 use async_stream::try_stream;
 use futures::Stream;
+use hmac::{Hmac, Mac};
 use orgauth::data::UserId;
 use orgauth::dbfun::user_id;
-use rusqlite::Connection;
+use orgauth::util::now;
+use rusqlite::{params, Connection};
 use serde_derive::Serialize;
+use sha2::Sha256;
 use std::convert::TryInto;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 use uuid::Uuid;
 use zkprotocol::constants::SpecialUuids;
+use zkprotocol::content::AuditAction;
 use zkprotocol::content::FileStatus;
 use zkprotocol::content::ZkListNote;
 use zkprotocol::content::ZkNoteId;
 use zkprotocol::search::{
-  AndOr, ArchivesOrCurrent, OrderDirection, OrderField, ResultType, SearchMod, TagSearch,
+  AndOr, ArchivesOrCurrent, ChangedNote, GetChangedNotes, OrderDirection, OrderField, Ordering,
+  PowerDeleteBatch, PowerDeleteConfirm, PowerDeletePreview, ResultType, SearchMod, TagSearch,
   ZkIdSearchResult, ZkListNoteSearchResult, ZkNoteAndLinksSearchResult, ZkNoteSearch,
   ZkNoteSearchResult, ZkSearchResultHeader,
 };
 use zkprotocol::sync_data::{SyncMessage, ZkPhantomUser};
 
-pub fn power_delete_zknotes(
+// power delete confirmation tokens expire after 10 minutes.
+pub const POWER_DELETE_TOKEN_MS: i64 = 10 * 60 * 1000;
+// power deletes can be undone for a week.
+pub const POWER_DELETE_UNDO_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+// the notes a power delete would delete; the user's own notes matching the search.
+// also returns how many matching notes belong to others, and are skipped.
+fn power_delete_notes(
   conn: &Connection,
-  file_path: PathBuf,
+  file_path: &Path,
   user: UserId,
   search: &Vec<TagSearch>,
-) -> Result<i64, zkerr::Error> {
+) -> Result<(Vec<ZkListNote>, i64), zkerr::Error> {
   // get all, and delete all.  Maybe not a good idea for a big database, but ours is small
   // and soon to be replaced with indradb, perhaps.
 
@@ -44,57 +59,259 @@ pub fn power_delete_zknotes(
     deleted: false,
     ordering: None,
   };
-  let znsr = search_zknotes(conn, &file_path, user, &nolimsearch)?;
-  match znsr {
-    SearchResult::SrId(znsr) => {
-      let c = znsr
-        .notes
-        .len()
-        .try_into()
-        .map_err(|_| zkerr::Error::String("int conversion error".to_string()))?;
-
-      for n in znsr.notes {
-        delete_zknote(&conn, file_path.clone(), user, &n)?;
-      }
-      Ok(c)
-    }
+  match search_zknotes(conn, file_path, user, &nolimsearch)? {
     SearchResult::SrListNote(znsr) => {
-      let c = znsr
-        .notes
-        .len()
-        .try_into()
-        .map_err(|_| zkerr::Error::String("int conversion error".to_string()))?;
-
-      for n in znsr.notes {
-        delete_zknote(&conn, file_path.clone(), user, &n.id)?;
-      }
-      Ok(c)
+      let (own, others): (Vec<ZkListNote>, Vec<ZkListNote>) =
+        znsr.notes.into_iter().partition(|n| n.user == user);
+      Ok((own, others.len() as i64))
     }
-    SearchResult::SrNote(znsr) => {
-      let c = znsr
-        .notes
-        .len()
-        .try_into()
-        .map_err(|_| zkerr::Error::String("int conversion error".to_string()))?;
-
-      for n in znsr.notes {
-        delete_zknote(&conn, file_path.clone(), user, &n.id)?;
-      }
-      Ok(c)
+    _ => Err(zkerr::Error::String(
+      "unexpected search result type".to_string(),
+    )),
+  }
+}
+
+// key for signing power delete tokens, made on first use.
+fn power_delete_secret(conn: &Connection) -> Result<String, zkerr::Error> {
+  conn.execute(
+    "insert into singlevalue (name, value) values ('power_delete_secret', ?1)
+        on conflict (name) do nothing",
+    params![format!(
+      "{}{}",
+      Uuid::new_v4().simple(),
+      Uuid::new_v4().simple()
+    )],
+  )?;
+  sqldata::get_single_value(conn, "power_delete_secret")?.ok_or(zkerr::Error::String(
+    "power delete secret not found".to_string(),
+  ))
+}
+
+// the token signs the user, the time and the exact notes, so a changed search
+// result needs a new preview.
+fn power_delete_mac(
+  conn: &Connection,
+  user: UserId,
+  time: i64,
+  notes: &Vec<ZkListNote>,
+) -> Result<Hmac<Sha256>, zkerr::Error> {
+  let ids: Vec<String> = notes.iter().map(|n| n.id.to_string()).collect();
+  let mut mac = Hmac::<Sha256>::new_from_slice(power_delete_secret(conn)?.as_bytes())
+    .map_err(|e| zkerr::Error::String(format!("hmac error: {}", e)))?;
+  mac.update(format!("{}:{}:{}", user.to_i64(), time, ids.join(",")).as_bytes());
+  Ok(mac)
+}
+
+fn power_delete_token(
+  conn: &Connection,
+  user: UserId,
+  time: i64,
+  notes: &Vec<ZkListNote>,
+) -> Result<String, zkerr::Error> {
+  let sig: String = power_delete_mac(conn, user, time, notes)?
+    .finalize()
+    .into_bytes()
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect();
+  Ok(format!("{}-{}", time, sig))
+}
+
+pub fn power_delete_preview(
+  conn: &Connection,
+  file_path: &Path,
+  user: UserId,
+  search: &Vec<TagSearch>,
+) -> Result<PowerDeletePreview, zkerr::Error> {
+  let (notes, skipped) = power_delete_notes(conn, file_path, user, search)?;
+  let token = power_delete_token(conn, user, now()?, &notes)?;
+  Ok(PowerDeletePreview {
+    notes,
+    skipped,
+    token,
+  })
+}
+
+pub fn power_delete_zknotes(
+  conn: &Connection,
+  file_path: PathBuf,
+  user: UserId,
+  pd: &PowerDeleteConfirm,
+) -> Result<PowerDeleteBatch, zkerr::Error> {
+  let (notes, _) = power_delete_notes(conn, &file_path, user, &pd.search)?;
+
+  let now = now()?;
+  let invalid = || zkerr::Error::String("invalid power delete token".to_string());
+  let (time, sig): (i64, Vec<u8>) = match pd.token.split_once('-') {
+    Some((t, s)) if s.len() % 2 == 0 && s.chars().all(|c| c.is_ascii_hexdigit()) => (
+      t.parse().map_err(|_| invalid())?,
+      (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| invalid())?,
+    ),
+    _ => return Err(invalid()),
+  };
+  if now - time > POWER_DELETE_TOKEN_MS {
+    return Err(zkerr::Error::String(
+      "power delete token expired, preview again".to_string(),
+    ));
+  }
+  if power_delete_mac(conn, user, time, &notes)?
+    .verify_slice(&sig)
+    .is_err()
+  {
+    return Err(zkerr::Error::String(
+      "search results changed since the preview, preview again".to_string(),
+    ));
+  }
+
+  let id = Uuid::new_v4();
+  let count: i64 = notes
+    .len()
+    .try_into()
+    .map_err(|_| zkerr::Error::String("int conversion error".to_string()))?;
+
+  let tr = conn.unchecked_transaction()?;
+  conn.execute(
+    "insert into deletebatch (uuid, user, count, createdate) values (?1, ?2, ?3, ?4)",
+    params![id.to_string(), user.to_i64(), count, now],
+  )?;
+  let batch = conn.last_insert_rowid();
+
+  for n in notes {
+    delete_zknote_batch(&conn, file_path.clone(), user, &n.id, Some(batch))?;
+  }
+
+  audit(
+    conn,
+    Some(user),
+    AuditAction::PowerDelete,
+    format!(
+      "{} notes, batch {}, search: {}",
+      count,
+      id,
+      serde_json::to_string(&pd.search)?
+    )
+    .as_str(),
+  )?;
+  tr.commit()?;
+
+  Ok(PowerDeleteBatch {
+    id,
+    count,
+    undountil: now + POWER_DELETE_UNDO_MS,
+  })
+}
+
+// restore the notes of a power delete from their archives.
+pub fn undo_power_delete(conn: &Connection, user: UserId, id: &Uuid) -> Result<i64, zkerr::Error> {
+  let now = now()?;
+  let (batch, createdate, undone): (i64, i64, Option<i64>) = match conn.query_row(
+    "select id, createdate, undone from deletebatch where uuid = ?1 and user = ?2",
+    params![id.to_string(), user.to_i64()],
+    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+  ) {
+    Ok(r) => r,
+    Err(rusqlite::Error::QueryReturnedNoRows) => {
+      return Err(zkerr::Error::String(format!(
+        "power delete not found: {}",
+        id
+      )))
     }
-    SearchResult::SrNoteAndLink(znsr) => {
-      let c = znsr
-        .notes
-        .len()
-        .try_into()
-        .map_err(|_| zkerr::Error::String("int conversion error".to_string()))?;
-
-      for n in znsr.notes {
-        delete_zknote(&conn, file_path.clone(), user, &n.zknote.id)?;
-      }
-      Ok(c)
+    Err(e) => return Err(e.into()),
+  };
+  if undone.is_some() {
+    return Err(zkerr::Error::String(format!(
+      "power delete already undone: {}",
+      id
+    )));
+  }
+  if now - createdate > POWER_DELETE_UNDO_MS {
+    return Err(zkerr::Error::String(format!(
+      "power delete can no longer be undone: {}",
+      id
+    )));
+  }
+
+  let tr = conn.unchecked_transaction()?;
+
+  let mut pstmt =
+    conn.prepare("select zknote, archive, file from deletebatchnote where batch = ?1")?;
+  let notes = pstmt
+    .query_map(params![batch], |row| {
+      Ok((
+        row.get::<usize, i64>(0)?,
+        row.get::<usize, i64>(1)?,
+        row.get::<usize, Option<i64>>(2)?,
+      ))
+    })?
+    .collect::<Result<Vec<(i64, i64, Option<i64>)>, rusqlite::Error>>()?;
+
+  for (zknote, archive, file) in notes.iter() {
+    // archive the deleted state, like any other change.
+    archive_zknote_i64(&conn, *zknote)?;
+    conn.execute(
+      "update zknote set (title, content, editable, showtitle, deleted, encrypted, editor, file, changeddate) =
+        (select title, content, editable, showtitle, deleted, encrypted, editor, ?1, ?2 from zkarch where id = ?3)
+        where id = ?4",
+      params![file, now, archive, zknote],
+    )?;
+  }
+
+  conn.execute(
+    "update deletebatch set undone = ?1 where id = ?2",
+    params![now, batch],
+  )?;
+
+  let count: i64 = notes
+    .len()
+    .try_into()
+    .map_err(|_| zkerr::Error::String("int conversion error".to_string()))?;
+
+  audit(
+    conn,
+    Some(user),
+    AuditAction::PowerDelete,
+    format!("undo batch {}, {} notes restored", id, count).as_str(),
+  )?;
+  tr.commit()?;
+
+  Ok(count)
+}
+
+// once power deletes can't be undone, remove their files if nothing else uses them.
+pub fn purge_power_deletes(dbfile: &Path, file_path: &Path) -> Result<(), zkerr::Error> {
+  let conn = sqldata::connection_open(dbfile)?;
+  let mut pstmt = conn.prepare(
+    "select id from deletebatch
+      where not purged and undone is null and createdate < ?1",
+  )?;
+  let batches = pstmt
+    .query_map(params![now()? - POWER_DELETE_UNDO_MS], |row| {
+      row.get::<usize, i64>(0)
+    })?
+    .collect::<Result<Vec<i64>, rusqlite::Error>>()?;
+
+  for batch in batches {
+    // purged first, so the batch doesn't count as a use of its own files.
+    conn.execute(
+      "update deletebatch set purged = 1 where id = ?1",
+      params![batch],
+    )?;
+    let mut fstmt = conn.prepare(
+      "select F.id, F.hash from deletebatchnote B, file F
+        where B.batch = ?1 and B.file = F.id",
+    )?;
+    let files = fstmt
+      .query_map(params![batch], |row| Ok((row.get(0)?, row.get(1)?)))?
+      .collect::<Result<Vec<(i64, String)>, rusqlite::Error>>()?;
+    for (fileid, hash) in files {
+      remove_unused_file(&conn, file_path, fileid, hash.as_str())?;
     }
   }
+  Ok(())
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -105,6 +322,98 @@ pub enum SearchResult {
   SrNoteAndLink(ZkNoteAndLinksSearchResult),
 }
 
+// accessible notes that someone else changed since the user last viewed them,
+// or since gcn.since.  most recent first.
+pub fn changed_notes(
+  conn: &Connection,
+  user: UserId,
+  gcn: &GetChangedNotes,
+) -> Result<Vec<ChangedNote>, zkerr::Error> {
+  let search = ZkNoteSearch {
+    tagsearch: vec![TagSearch::SearchTerm {
+      mods: vec![SearchMod::Unviewed],
+      term: gcn.since.map(|s| s.to_string()).unwrap_or_default(),
+    }],
+    offset: gcn.offset,
+    limit: gcn.limit,
+    what: "".to_string(),
+    resulttype: ResultType::RtListNote,
+    archives: ArchivesOrCurrent::Current,
+    deleted: false,
+    ordering: Some(Ordering {
+      field: OrderField::Changed,
+      direction: OrderDirection::Descending,
+    }),
+  };
+  let (sql, mut args) = build_sql(conn, user, &search, None)?;
+  // the editor and the user's last view, joined onto the search results.
+  let nusql = format!(
+    "with SN ( id, uuid, zknote, title, file, user, createdate, changeddate) as ({})
+    select SN.id, SN.uuid, SN.zknote, SN.title, SN.file, SN.user, SN.createdate, SN.changeddate,
+      OU.id, OU.name, V.viewdate
+    from SN
+    join zknote N on N.id = SN.id
+    join orgauth_user OU on OU.id = coalesce(N.editor, N.user)
+    left join noteview V on V.zknote = N.id and V.user = cast(? as integer)
+    order by SN.changeddate desc",
+    sql
+  );
+  args.push(user.to_i64().to_string());
+
+  let mut pstmt = conn.prepare(nusql.as_str())?;
+  let sysid = user_id(&conn, "system")?;
+  let rec_iter = pstmt.query_and_then(rusqlite::params_from_iter(args.iter()), |row| {
+    Ok::<_, zkerr::Error>(ChangedNote {
+      note: zklistnote_row(conn, sysid, row)?,
+      editor: UserId::Uid(row.get(8)?),
+      editorname: row.get(9)?,
+      lastviewed: row.get(10)?,
+    })
+  })?;
+
+  let mut changed = Vec::new();
+  for rec in rec_iter {
+    changed.push(rec?);
+  }
+
+  Ok(changed)
+}
+
+// a ZkListNote from a row of build_sql results.
+fn zklistnote_row(
+  conn: &Connection,
+  sysid: UserId,
+  row: &rusqlite::Row<'_>,
+) -> Result<ZkListNote, zkerr::Error> {
+  let id = row.get(0)?;
+  let uuid = Uuid::parse_str(row.get::<usize, String>(1)?.as_str())?;
+  let pid = row
+    .get::<usize, String>(2)
+    .ok()
+    .and_then(|x| Uuid::parse_str(x.as_str()).ok());
+  let sysids = get_sysids(conn, sysid, id)?;
+  Ok(ZkListNote {
+    id: match pid {
+      Some(pid) => ZkNoteId::ArchiveZni(uuid, pid),
+      None => ZkNoteId::Zni(uuid),
+    },
+    title: row.get(3)?,
+    filestatus: {
+      let fs: String = row.get(4)?;
+      match fs.as_str() {
+        "n" => FileStatus::NotAFile,
+        "m" => FileStatus::FileMissing,
+        "e" => FileStatus::FilePresent,
+        _ => Err(zkerr::Error::String(format!("invalid filestatus: {}", fs)))?,
+      }
+    },
+    user: UserId::Uid(row.get(5)?),
+    createdate: row.get(6)?,
+    changeddate: row.get(7)?,
+    sysids,
+  })
+}
+
 pub fn search_zknotes(
   conn: &Connection,
   filedir: &Path,
@@ -116,33 +425,7 @@ pub fn search_zknotes(
   let mut pstmt = conn.prepare(sql.as_str())?;
   let sysid = user_id(&conn, "system")?;
   let rec_iter = pstmt.query_and_then(rusqlite::params_from_iter(args.iter()), |row| {
-    let id = row.get(0)?;
-    let uuid = Uuid::parse_str(row.get::<usize, String>(1)?.as_str())?;
-    let pid = row
-      .get::<usize, String>(2)
-      .ok()
-      .and_then(|x| Uuid::parse_str(x.as_str()).ok());
-    let sysids = get_sysids(conn, sysid, id)?;
-    Ok::<ZkListNote, zkerr::Error>(ZkListNote {
-      id: match pid {
-        Some(pid) => ZkNoteId::ArchiveZni(uuid, pid),
-        None => ZkNoteId::Zni(uuid),
-      },
-      title: row.get(3)?,
-      filestatus: {
-        let fs: String = row.get(4)?;
-        match fs.as_str() {
-          "n" => FileStatus::NotAFile,
-          "m" => FileStatus::FileMissing,
-          "e" => FileStatus::FilePresent,
-          _ => Err(zkerr::Error::String(format!("invalid filestatus: {}", fs)))?,
-        }
-      },
-      user: UserId::Uid(row.get(5)?),
-      createdate: row.get(6)?,
-      changeddate: row.get(7)?,
-      sysids,
-    })
+    zklistnote_row(conn, sysid, row)
   })?;
 
   match search.resulttype {
@@ -237,7 +520,7 @@ pub fn search_zknotes_stream(
   // {
   try_stream! {
     let (sql, args) = build_sql(&conn, user, &search, exclude_notes)?;
-
+    let sysid = user_id(&conn, "system").map_err(zkerr::Error::from)?;
 
     let mut stmt = conn.prepare(sql.as_str())?;
     let mut rows = stmt.query(rusqlite::params_from_iter(args.iter()))?;
@@ -250,9 +533,7 @@ pub fn search_zknotes_stream(
     while let Some(row) = rows.next()? {
       match search.resulttype {
         ResultType::RtId => yield SyncMessage::ZkNoteId(row.get::<usize, String>(1)?),
-        ResultType::RtListNote => {
-          yield SyncMessage::SyncError("unimplemented".to_string())
-        }
+        ResultType::RtListNote => yield SyncMessage::ZkListNote(zklistnote_row(&conn, sysid, row)?),
         ResultType::RtNote => {
           let uuid = Uuid::parse_str(row.get::<usize, String>(1)?.as_str())?;
           let parent : Option<Uuid>
@@ -749,6 +1030,7 @@ fn build_tagsearch_clause(
       let mut create = false;
       let mut modd = false;
       let mut server = false;
+      let mut unviewed = false;
 
       for m in mods {
         match m {
@@ -769,6 +1051,7 @@ fn build_tagsearch_clause(
             exact = true; // zknoteid implies exact.
           }
           SearchMod::Server => server = true,
+          SearchMod::Unviewed => unviewed = true,
         }
       }
       let field = if zknoteid {
@@ -780,8 +1063,46 @@ fn build_tagsearch_clause(
       } else {
         "title"
       };
-
-      if create || modd {
+      // encrypted content is opaque, so it never matches a content search.
+      let encrypted = field == "content";
+
+      if unviewed {
+        let notstr = if not { "not" } else { "" };
+        // a null editor is the owner.  coalesce() has no type affinity, so the
+        // bound text is cast to compare with the integer ids.
+        let changed = format!(
+          "{} (coalesce(N.editor, N.user) != cast(? as integer) and N.changeddate > ",
+          notstr
+        );
+        let uid = format!("{}", uid.to_i64());
+        if term.is_empty() {
+          let nid = match aoc {
+            TsArchivesOrCurrent::Current => "id",
+            TsArchivesOrCurrent::Archives => "zknote",
+          };
+          (
+            format!(
+              "{} coalesce((select V.changeddate from noteview V
+                   where V.user = ? and V.zknote = N.{}), 0))",
+              changed, nid
+            ),
+            vec![uid.clone(), uid],
+          )
+        } else {
+          // a timestamp.  anything else would be compared as text, which sqlite
+          // sorts after every number, and never match.
+          let since = term.trim().parse::<i64>().map_err(|_| {
+            zkerr::Error::String(format!(
+              "unviewed search term should be a timestamp: {}",
+              term
+            ))
+          })?;
+          (
+            format!("{} cast(? as integer))", changed),
+            vec![uid, since.to_string()],
+          )
+        }
+      } else if create || modd {
         let op = if before {
           " < "
         } else if after {
@@ -832,10 +1153,16 @@ fn build_tagsearch_clause(
             _ => "",
           };
 
+          let encclause = if encrypted {
+            "and zkn.encrypted = 0"
+          } else {
+            ""
+          };
+
           let clause = if exact {
-            format!("zkn.{} = ? {}", field, fileclause)
+            format!("zkn.{} = ? {} {}", field, encclause, fileclause)
           } else {
-            format!("zkn.{} like ? {}", field, fileclause)
+            format!("zkn.{} like ? {} {}", field, encclause, fileclause)
           };
 
           let notstr = if not { "not" } else { "" };
@@ -911,12 +1238,14 @@ fn build_tagsearch_clause(
             (false, true) => "",
           };
 
+          let encclause = if encrypted { "and N.encrypted = 0" } else { "" };
+
           (
             // clause
             if exact {
-              format!("N.{} {}= ? {}", field, notstr, fileclause)
+              format!("N.{} {}= ? {} {}", field, notstr, encclause, fileclause)
             } else {
-              format!("N.{} {} like ? {}", field, notstr, fileclause)
+              format!("N.{} {} like ? {} {}", field, notstr, encclause, fileclause)
             },
             // args
             if exact {
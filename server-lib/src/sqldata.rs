@@ -1,3 +1,5 @@
+use crate::audit::audit;
+use crate::config::Quotas;
 use crate::error as zkerr;
 use crate::error::to_orgauth_error;
 use crate::migrations as zkm;
@@ -22,12 +24,16 @@ use std::time::Duration;
 use uuid::Uuid;
 use zkprotocol::constants::SpecialUuids;
 use zkprotocol::content::{
-  ArchiveZkLink, Direction, EditLink, ExtraLoginData, FileInfo, FileStatus, GetZkNoteArchives,
-  GetZkNoteComments, GetZknIfChanged, ImportZkNote, LzLink, OnMakeFileNote, OnSavedZkNote,
-  SaveLzLink, SaveZkLink, SaveZkLink2, SaveZkNote, SavedZkNote, Server, Sysids, UuidZkLink, ZkLink,
-  ZkListNote, ZkNote, ZkNoteAndLinks, ZkNoteAndLinksWhat, ZkNoteId,
+  ApiToken, ApiTokenScope, ArchiveZkLink, AuditAction, CreateShareLink, Direction, EditLink,
+  ExtraLoginData, FileInfo, FileStats, FileStatus, GetZkNoteArchives, GetZkNoteComments,
+  GetZknIfChanged, ImportAction, ImportPolicy, ImportZkNote, ImportZkNoteResult, LzLink,
+  NewApiToken, OnMakeFileNote, OnSavedZkNote, SaveLzLink, SaveZkLink, SaveZkLink2, SaveZkNote,
+  SavedZkNote, Server, ShareLink, SharePermission, StorageStats, Sysids, UserSharePermission,
+  UserStorageStats, UuidZkLink, ZkLink, ZkListNote, ZkNote, ZkNoteAndLinks, ZkNoteAndLinksWhat,
+  ZkNoteId,
 };
 use zkprotocol::sync_data::SyncMessage;
+use zkprotocol::upload::UploadStatus;
 
 pub fn zknotes_callbacks() -> Callbacks {
   Callbacks {
@@ -137,6 +143,47 @@ pub fn on_delete_user(conn: &Connection, uid: UserId) -> Result<bool, orgauth::e
     params!(uid.to_i64()),
   )?;
   conn.execute("delete from zklink where user = ?1", params!(uid.to_i64()))?;
+  conn.execute("delete from upload where user = ?1", params!(uid.to_i64()))?;
+  conn.execute(
+    "delete from apitoken where user = ?1",
+    params!(uid.to_i64()),
+  )?;
+  conn.execute(
+    "delete from sharepermission where user = ?1
+      or share in (select id from zknote where user = ?1)",
+    params!(uid.to_i64()),
+  )?;
+  conn.execute(
+    "delete from sharelink where user = ?1
+      or zknote in (select id from zknote where user = ?1)",
+    params!(uid.to_i64()),
+  )?;
+  conn.execute(
+    "delete from deletebatchnote where batch in (select id from deletebatch where user = ?1)",
+    params!(uid.to_i64()),
+  )?;
+  conn.execute(
+    "delete from deletebatch where user = ?1",
+    params!(uid.to_i64()),
+  )?;
+  conn.execute("delete from totp where user = ?1", params!(uid.to_i64()))?;
+  conn.execute(
+    "delete from totprecovery where user = ?1",
+    params!(uid.to_i64()),
+  )?;
+  conn.execute(
+    "delete from noteview where user = ?1
+      or zknote in (select id from zknote where user = ?1)",
+    params!(uid.to_i64()),
+  )?;
+  conn.execute(
+    "update zknote set editor = null where editor = ?1",
+    params!(uid.to_i64()),
+  )?;
+  conn.execute(
+    "update zkarch set editor = null where editor = ?1",
+    params!(uid.to_i64()),
+  )?;
   conn.execute("delete from zknote where user = ?1", params!(uid.to_i64()))?;
   conn.execute("delete from user where id = ?1", params!(uid.to_i64()))?;
   Ok(true)
@@ -237,6 +284,20 @@ pub fn update_single_value(
   }
 }
 
+// the migration_level dbinit brings a db up to.
+pub const MIGRATION_LEVEL: i32 = 52;
+
+pub fn migration_level(conn: &Connection) -> Result<i32, zkerr::Error> {
+  match get_single_value(&conn, "migration_level") {
+    Err(_) => Ok(0),
+    Ok(None) => Ok(0),
+    Ok(Some(level)) => match level.parse::<i32>() {
+      Ok(l) => Ok(l),
+      Err(e) => Err(format!("{}", e).into()),
+    },
+  }
+}
+
 pub fn dbinit(dbfile: &Path, token_expiration_ms: Option<i64>) -> Result<Server, zkerr::Error> {
   let exists = dbfile.exists();
 
@@ -247,17 +308,7 @@ pub fn dbinit(dbfile: &Path, token_expiration_ms: Option<i64>) -> Result<Server,
     conn.execute_batch(zkm::initialdb().make::<Sqlite>().as_str())?;
   }
 
-  let nlevel = match get_single_value(&conn, "migration_level") {
-    Err(_) => 0,
-    Ok(None) => 0,
-    Ok(Some(level)) => {
-      let l = match level.parse::<i32>() {
-        Ok(l) => l,
-        Err(e) => return Err(format!("{}", e).into()),
-      };
-      l
-    }
-  };
+  let nlevel = migration_level(&conn)?;
 
   if nlevel < 1 {
     info!("udpate1");
@@ -474,6 +525,53 @@ pub fn dbinit(dbfile: &Path, token_expiration_ms: Option<i64>) -> Result<Server,
     zkm::udpate43(&dbfile)?;
     set_single_value(&conn, "migration_level", "43")?;
   }
+  if nlevel < 44 {
+    info!("udpate44");
+    zkm::udpate44(&dbfile)?;
+    set_single_value(&conn, "migration_level", "44")?;
+  }
+  if nlevel < 45 {
+    info!("udpate45");
+    zkm::udpate45(&dbfile)?;
+    set_single_value(&conn, "migration_level", "45")?;
+  }
+  if nlevel < 46 {
+    info!("udpate46");
+    zkm::udpate46(&dbfile)?;
+    set_single_value(&conn, "migration_level", "46")?;
+  }
+  if nlevel < 47 {
+    info!("udpate47");
+    zkm::udpate47(&dbfile)?;
+    set_single_value(&conn, "migration_level", "47")?;
+  }
+  if nlevel < 48 {
+    info!("udpate48");
+    zkm::udpate48(&dbfile)?;
+    set_single_value(&conn, "migration_level", "48")?;
+  }
+  if nlevel < 49 {
+    info!("udpate49");
+    zkm::udpate49(&dbfile)?;
+    set_single_value(&conn, "migration_level", "49")?;
+  }
+  if nlevel < 50 {
+    info!("udpate50");
+    zkm::udpate50(&dbfile)?;
+    set_single_value(&conn, "migration_level", "50")?;
+  }
+
+  if nlevel < 51 {
+    info!("udpate51");
+    zkm::udpate51(&dbfile)?;
+    set_single_value(&conn, "migration_level", "51")?;
+  }
+
+  if nlevel < 52 {
+    info!("udpate52");
+    zkm::udpate52(&dbfile)?;
+    set_single_value(&conn, "migration_level", "52")?;
+  }
 
   info!("db up to date.");
 
@@ -586,6 +684,17 @@ pub fn save_zklink(
     true
   };
 
+  // members with read permission on a share can't link to the share or its notes.
+  // comment or write permission can.
+  let readonly = |id: i64| -> Result<bool, zkerr::Error> {
+    Ok(
+      !is_zknote_mine(&conn, id, user)?
+        && (zknote_share_permission(&conn, id, user)? == Some(SharePermission::Read)
+          || share_member_permission(&conn, id, user)? == Some(SharePermission::Read)),
+    )
+  };
+  let authed = authed && !readonly(fromid)? && !readonly(toid)?;
+
   // yeesh.  doing this to exit with ? instead of having a big if-then to the end.
   (if authed {
     Ok(())
@@ -604,9 +713,13 @@ pub fn save_zklink(
     params![now, fromid, toid, user.to_i64(), linkzknote],
   )?;
 
-  // now create the new record or modify the existing.
+  // now create the new record, unless it's already there.  linkzknote is usually
+  // null, and the unique index doesn't count nulls as equal.
   conn.execute(
-    "insert into zklink (fromid, toid, user, linkzknote, createdate) values (?1, ?2, ?3, ?4, ?5)",
+    "insert into zklink (fromid, toid, user, linkzknote, createdate)
+      select ?1, ?2, ?3, ?4, ?5
+      where not exists (select 1 from zklink
+        where fromid = ?1 and toid = ?2 and user = ?3 and linkzknote is ?4)",
     params![fromid, toid, user.to_i64(), linkzknote, now],
   )?;
 
@@ -859,16 +972,17 @@ pub fn are_notes_linked(conn: &Connection, nid1: i64, nid2: i64) -> Result<bool,
   }
 }
 
-pub fn archive_zknote_i64(conn: &Connection, noteid: i64) -> Result<(), zkerr::Error> {
+// returns the zkarch id.
+pub fn archive_zknote_i64(conn: &Connection, noteid: i64) -> Result<i64, zkerr::Error> {
   let uuid = uuid::Uuid::new_v4();
   conn.execute(
-    "insert into zkarch (zknote, title, content, user, editable, showtitle, deleted, uuid, createdate, changeddate, server)
-     select id, title, content, user, editable, showtitle, deleted, ?1, createdate, changeddate, server from
+    "insert into zkarch (zknote, title, content, user, editable, showtitle, deleted, encrypted, editor, uuid, createdate, changeddate, server)
+     select id, title, content, user, editable, showtitle, deleted, encrypted, editor, ?1, createdate, changeddate, server from
          zknote where id = ?2",
     params![uuid.to_string(), noteid],
   )?;
 
-  Ok(())
+  Ok(conn.last_insert_rowid())
 }
 
 // write a zknote straight to archives.  should only happen during sync.
@@ -882,8 +996,8 @@ pub fn archive_zknote(
   // copy the note, with user 'system'.
   // exclude pubid, to avoid unique constraint problems.
   conn.execute(
-    "insert into zkarch (zknote, title, content, user, editable, showtitle, deleted, uuid, createdate, changeddate, server)
-     values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, (select id from server where uuid = ?11))",
+    "insert into zkarch (zknote, title, content, user, editable, showtitle, deleted, encrypted, uuid, createdate, changeddate, server)
+     values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, (select id from server where uuid = ?12))",
     params![
       noteid,
       note.title,
@@ -892,6 +1006,7 @@ pub fn archive_zknote(
       note.editable,
       note.showtitle,
       note.deleted,
+      note.encrypted,
       uuid.to_string(),
       note.createdate,
       note.changeddate,
@@ -967,6 +1082,36 @@ pub struct NoteDates {
   pub changeddate: i64,
 }
 
+pub async fn publish_saved_zknote(
+  uid: UserId,
+  lapin_info: &Option<LapinInfo>,
+  id: ZkNoteId,
+) -> Result<(), zkerr::Error> {
+  if let Some(li) = lapin_info {
+    let oszn = OnSavedZkNote {
+      id,
+      user: uid,
+      token: li.token.clone(),
+    };
+    match li
+      .channel
+      .basic_publish(
+        "",
+        "on_save_zknote",
+        lapin::options::BasicPublishOptions::default(),
+        &serde_json::to_vec(&oszn)?[..],
+        lapin::BasicProperties::default(),
+      )
+      .await
+    {
+      Ok(_) => info!("published to amqp on_save_zknote"),
+      Err(e) => error!("error publishing to AMQP: {:?}", e),
+    }
+  }
+
+  Ok(())
+}
+
 pub async fn save_zknote(
   conn: &Connection,
   lapin_info: &Option<LapinInfo>,
@@ -983,36 +1128,6 @@ pub async fn save_zknote(
     }
   };
 
-  async fn publish_szn(
-    uid: UserId,
-    lapin_info: &Option<LapinInfo>,
-    szn: &SavedZkNote,
-  ) -> Result<(), zkerr::Error> {
-    if let Some(li) = lapin_info {
-      let oszn = OnSavedZkNote {
-        id: szn.id,
-        user: uid,
-        token: li.token.clone(),
-      };
-      match li
-        .channel
-        .basic_publish(
-          "",
-          "on_save_zknote",
-          lapin::options::BasicPublishOptions::default(),
-          &serde_json::to_vec(&oszn)?[..],
-          lapin::BasicProperties::default(),
-        )
-        .await
-      {
-        Ok(_) => info!("published to amqp on_save_zknote"),
-        Err(e) => error!("error publishing to AMQP: {:?}", e),
-      }
-    }
-
-    Ok(())
-  }
-
   match note.id {
     Some(uuid) => {
       let id = note_id_for_zknoteid(conn, &uuid)?;
@@ -1034,8 +1149,10 @@ pub async fn save_zknote(
            editable = ?5,
            showtitle = ?6,
            deleted = ?7,
-           server = ?8
-         where id = ?9 and user = ?10",
+           encrypted = ?8,
+           server = ?9,
+           editor = null
+         where id = ?10 and user = ?11",
         params![
           note.title,
           note.content,
@@ -1044,6 +1161,7 @@ pub async fn save_zknote(
           note.editable,
           note.showtitle,
           note.deleted,
+          note.encrypted,
           server.id,
           id,
           uid.to_i64(),
@@ -1056,15 +1174,15 @@ pub async fn save_zknote(
             server: server.uuid.clone(),
             what: note.what.clone(),
           };
-          publish_szn(uid, lapin_info, &szn).await?;
+          publish_saved_zknote(uid, lapin_info, szn.id).await?;
           Ok((id, szn))
         }
         Ok(0) => {
           // editable flag must be true
           match conn.execute(
-            "update zknote set title = ?1, content = ?2, changeddate = ?3, pubid = ?4, showtitle = ?5, server = ?6
-             where id = ?7 and editable = 1",
-            params![note.title, note.content, changeddate, note.pubid, note.showtitle, server.id, id],
+            "update zknote set title = ?1, content = ?2, changeddate = ?3, pubid = ?4, showtitle = ?5, encrypted = ?6, server = ?7, editor = ?9
+             where id = ?8 and editable = 1",
+            params![note.title, note.content, changeddate, note.pubid, note.showtitle, note.encrypted, server.id, id, uid.to_i64()],
           )? {
             0 => Err( zkerr::Error::String(format!("can't update; note is not writable {} {}", note.title, id))),
             // params![note.title, note.content, now, note.pubid, note.showtitle, server.id, id],
@@ -1075,7 +1193,7 @@ pub async fn save_zknote(
                 server: server.uuid.clone(),
                 what: note.what.clone(),
               };
-              publish_szn( uid, lapin_info, &szn).await?;
+              publish_saved_zknote(uid, lapin_info, szn.id).await?;
               Ok((id, szn))},
             _ => bail!("unexpected update success!"),
           }
@@ -1089,8 +1207,8 @@ pub async fn save_zknote(
 
       let uuid = uuid::Uuid::new_v4();
       conn.execute(
-        "insert into zknote (title, content, user, pubid, editable, showtitle, deleted, uuid, createdate, changeddate, server)
-         values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        "insert into zknote (title, content, user, pubid, editable, showtitle, deleted, encrypted, uuid, createdate, changeddate, server)
+         values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
         params![
           note.title,
           note.content,
@@ -1099,6 +1217,7 @@ pub async fn save_zknote(
           note.editable,
           note.showtitle,
           note.deleted,
+          note.encrypted,
           uuid.to_string(),
           createdate,
           changeddate,
@@ -1112,7 +1231,7 @@ pub async fn save_zknote(
         server: server.uuid.clone(),
         what: note.what.clone(),
       };
-      publish_szn(uid, lapin_info, &szn).await?;
+      publish_saved_zknote(uid, lapin_info, szn.id).await?;
       Ok((id, szn))
     }
   }
@@ -1236,6 +1355,7 @@ pub fn read_zknote_unchecked(
         createdate: row.get(12)?,
         changeddate: row.get(13)?,
         server: row.get(14)?,
+        encrypted: row.get(15)?,
         sysids: Vec::new(),
       },
     ))
@@ -1243,7 +1363,8 @@ pub fn read_zknote_unchecked(
 
   conn.query_row_and_then(
         "select ZN.id, ZN.uuid, ZN.title, ZN.content, ZN.user, OU.name, ZKN.uuid,
-            ZN.pubid, ZN.editable, ZN.showtitle, ZN.deleted, ZN.file, ZN.createdate, ZN.changeddate, S.uuid
+            ZN.pubid, ZN.editable, ZN.showtitle, ZN.deleted, ZN.file, ZN.createdate, ZN.changeddate, S.uuid,
+            ZN.encrypted
           from zknote ZN, orgauth_user OU, user U, zknote ZKN, server S
           where ZN.uuid = ?1 and U.id = ZN.user and OU.id = ZN.user and ZKN.id = U.zknote and S.id = ZN.server",
           params![id.to_string()],
@@ -1277,6 +1398,7 @@ pub fn read_zkarch_unchecked(
         createdate: row.get(13)?,
         changeddate: row.get(14)?,
         server: row.get(15)?,
+        encrypted: row.get(16)?,
         sysids: Vec::new(),
       },
     ))
@@ -1284,7 +1406,8 @@ pub fn read_zkarch_unchecked(
 
   conn.query_row_and_then(
         "select ZN.id, ZN.uuid, ZKP.uuid, ZN.title, ZN.content, ZN.user, OU.name, ZKN.uuid,
-            ZN.pubid, ZN.editable, ZN.showtitle, ZN.deleted, ZN.file, ZN.createdate, ZN.changeddate, S.uuid
+            ZN.pubid, ZN.editable, ZN.showtitle, ZN.deleted, ZN.file, ZN.createdate, ZN.changeddate, S.uuid,
+            ZN.encrypted
           from zkarch ZN, orgauth_user OU, user U, zknote ZKN, zknote ZKP, server S
           where ZN.uuid = ?1 and U.id = ZN.user and OU.id = ZN.user and ZKP.id = ZN.zknote and ZKN.id = U.zknote and S.id = ZN.server",
           params![uuid.to_string()],
@@ -1317,6 +1440,17 @@ pub fn read_zknote(
   files_dir: &Path,
   uid: Option<UserId>,
   id: &ZkNoteId,
+) -> Result<(i64, ZkNote), zkerr::Error> {
+  read_zknote_sharelink(conn, files_dir, uid, None, id)
+}
+
+// read a note, with read access also granted by a share link's note, from sharelink_note().
+pub fn read_zknote_sharelink(
+  conn: &Connection,
+  files_dir: &Path,
+  uid: Option<UserId>,
+  sharelink: Option<i64>,
+  id: &ZkNoteId,
 ) -> Result<(i64, ZkNote), zkerr::Error> {
   match id {
     ZkNoteId::Zni(_nid) => {
@@ -1327,7 +1461,9 @@ pub fn read_zknote(
 
       note.sysids = sysids;
 
-      match zknote_access(conn, uid, id, &note) {
+      match zknote_access(conn, uid, id, &note)
+        .and_then(|a| sharelink_access(conn, a, sharelink, id))
+      {
         Ok(zna) => match zna {
           Access::ReadWrite => {
             note.editable = true;
@@ -1348,7 +1484,9 @@ pub fn read_zknote(
       let sysid = user_id(&conn, "system")?;
       let sysids = get_sysids(conn, sysid, pid)?;
 
-      match zknote_access(conn, uid, pid, &parentnote) {
+      match zknote_access(conn, uid, pid, &parentnote)
+        .and_then(|a| sharelink_access(conn, a, sharelink, pid))
+      {
         Ok(zna) => match zna {
           Access::ReadWrite => {
             let (id, mut note) = read_zkarch_unchecked(&conn, &files_dir, nid)?;
@@ -1448,6 +1586,168 @@ pub fn read_zklistarchnote(
   Ok(note)
 }
 
+fn share_permission_int(sp: &SharePermission) -> i64 {
+  match sp {
+    SharePermission::Read => 0,
+    SharePermission::Comment => 1,
+    SharePermission::Write => 2,
+  }
+}
+
+fn share_permission(i: i64) -> SharePermission {
+  match i {
+    0 => SharePermission::Read,
+    1 => SharePermission::Comment,
+    _ => SharePermission::Write,
+  }
+}
+
+// the user's best permission over the shares that include this note.
+// None if the note isn't in any of the user's shares.
+pub fn zknote_share_permission(
+  conn: &Connection,
+  zknoteid: i64,
+  uid: UserId,
+) -> Result<Option<SharePermission>, zkerr::Error> {
+  let shareid: i64 = note_id(conn, "system", "share")?;
+  let publicid: i64 = note_id(conn, "system", "public")?;
+  let usernoteid: i64 = user_note_id(&conn, uid)?;
+
+  // same share query as is_zknote_shared.  no permission record means write.
+  let p: Option<i64> = conn.query_row(
+    "select max(coalesce(P.permission, 2))
+      from zklink L,
+        (select U.toid id
+            from zklink U, zklink V
+            where
+              ((U.fromid = ?3 and U.toid = V.fromid and V.toid = ?2) or
+               (U.fromid = ?3 and U.toid = V.toid and V.fromid = ?2)) and
+              U.toid != ?4
+         union
+         select U.fromid id
+            from zklink U, zklink V
+            where
+              ((U.toid = ?3 and U.fromid = V.fromid and V.toid = ?2) or
+               (U.toid = ?3 and U.fromid = V.toid and V.fromid = ?2)) and
+              U.toid != ?4 ) shares
+        left join sharepermission P on P.share = shares.id and P.user = ?5
+      where
+        (L.fromid = shares.id and L.toid = ?1) or
+        (L.toid = shares.id and L.fromid = ?1)",
+    params![zknoteid, shareid, usernoteid, publicid, uid.to_i64()],
+    |row| row.get(0),
+  )?;
+
+  Ok(p.map(share_permission))
+}
+
+// permission record for a member of this share, if any.
+fn share_member_permission(
+  conn: &Connection,
+  share: i64,
+  uid: UserId,
+) -> Result<Option<SharePermission>, zkerr::Error> {
+  match conn.query_row(
+    "select permission from sharepermission where share = ?1 and user = ?2",
+    params![share, uid.to_i64()],
+    |row| row.get::<usize, i64>(0),
+  ) {
+    Ok(p) => Ok(Some(share_permission(p))),
+    Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+    Err(e) => Err(e.into()),
+  }
+}
+
+// access to a note through a share.  write still needs the note to be editable.
+fn share_access(sp: &SharePermission, editable: bool) -> Access {
+  match sp {
+    SharePermission::Write if editable => Access::ReadWrite,
+    _ => Access::Read,
+  }
+}
+
+// share owners set permissions for share members.
+pub fn set_share_permission(
+  conn: &Connection,
+  uid: UserId,
+  usp: &UserSharePermission,
+) -> Result<(), zkerr::Error> {
+  let share = note_id_for_zknoteid(conn, &usp.share)?;
+  if !is_zknote_mine(conn, share, uid)? {
+    return Err(zkerr::Error::String(
+      "only the share owner can set permissions".to_string(),
+    ));
+  }
+  if !are_notes_linked(conn, share, note_id(conn, "system", "share")?)? {
+    return Err(zkerr::Error::String(format!(
+      "not a share: {:?}",
+      usp.share
+    )));
+  }
+
+  conn.execute(
+    "insert into sharepermission (share, user, permission) values (?1, ?2, ?3)
+      on conflict (share, user) do update set permission = ?3",
+    params![
+      share,
+      usp.user.to_i64(),
+      share_permission_int(&usp.permission)
+    ],
+  )?;
+  Ok(())
+}
+
+pub fn read_share_permissions(
+  conn: &Connection,
+  uid: UserId,
+  shareid: &ZkNoteId,
+) -> Result<Vec<UserSharePermission>, zkerr::Error> {
+  let share = note_id_for_zknoteid(conn, shareid)?;
+  if !is_zknote_mine(conn, share, uid)? {
+    return Err(zkerr::Error::String(
+      "only the share owner can read permissions".to_string(),
+    ));
+  }
+
+  let mut pstmt = conn.prepare("select user, permission from sharepermission where share = ?1")?;
+  let r = pstmt
+    .query_map(params![share], |row| {
+      Ok(UserSharePermission {
+        share: *shareid,
+        user: UserId::Uid(row.get(0)?),
+        permission: share_permission(row.get(1)?),
+      })
+    })?
+    .collect::<Result<Vec<UserSharePermission>, rusqlite::Error>>()?;
+  Ok(r)
+}
+
+// read access through a share link, for notes that are otherwise private.
+// a share link grants its note, and if that note is a share, the notes on the share.
+fn sharelink_access(
+  conn: &Connection,
+  access: Access,
+  sharelink: Option<i64>,
+  id: i64,
+) -> Result<Access, zkerr::Error> {
+  match (access, sharelink) {
+    (Access::Private, Some(slnote)) => {
+      let shareid = note_id(conn, "system", "share")?;
+      let sysid = user_id(conn, "system")?;
+      if id == slnote
+        || (are_notes_linked(conn, slnote, shareid)?
+          && are_notes_linked(conn, slnote, id)?
+          && !is_zknote_mine(conn, id, sysid)?)
+      {
+        Ok(Access::Read)
+      } else {
+        Ok(Access::Private)
+      }
+    }
+    (a, _) => Ok(a),
+  }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Access {
   Private,
@@ -1472,13 +1772,8 @@ pub fn zknote_access(
         } else {
           Ok(Access::Read)
         }
-      } else if is_zknote_shared(conn, id, uid)? {
-        // editable and accessible.
-        if note.editable {
-          Ok(Access::ReadWrite)
-        } else {
-          Ok(Access::Read)
-        }
+      } else if let Some(sp) = zknote_share_permission(conn, id, uid)? {
+        Ok(share_access(&sp, note.editable))
       } else if is_zknote_public(conn, id)? {
         // accessible but not editable.
         Ok(Access::Read)
@@ -1548,14 +1843,8 @@ pub fn zknote_access_id(
           // read only.
           Ok(Access::Read)
         }
-      } else if is_zknote_shared(conn, noteid, uid)? {
-        if is_zknote_editable(conn, noteid)? {
-          // editable and accessible.
-          Ok(Access::ReadWrite)
-        } else {
-          // read only.
-          Ok(Access::Read)
-        }
+      } else if let Some(sp) = zknote_share_permission(conn, noteid, uid)? {
+        Ok(share_access(&sp, is_zknote_editable(conn, noteid)?))
       } else if is_zknote_public(conn, noteid)? {
         // accessible but not editable.
         Ok(Access::Read)
@@ -1615,7 +1904,8 @@ pub fn read_zknotepubid(
   let publicid = note_id(&conn, "system", "public")?;
   let (id, mut note) = match conn.query_row_and_then(
     "select A.id, A.uuid, A.title, A.content, A.user, OU.name, ZU.uuid,
-        A.pubid, A.editable, A.showtitle, A.deleted, A.file, A.createdate, A.changeddate, S.uuid
+        A.pubid, A.editable, A.showtitle, A.deleted, A.file, A.createdate, A.changeddate, S.uuid,
+        A.encrypted
       from zknote A, user U, orgauth_user OU, zklink L, server S
       left join zknote ZU on ZU.id = U.zknote
       where A.pubid = ?1
@@ -1645,6 +1935,7 @@ pub fn read_zknotepubid(
           createdate: row.get(12)?,
           changeddate: row.get(13)?,
           server: row.get(14)?,
+          encrypted: row.get(15)?,
           sysids: Vec::new(),
         },
       ))
@@ -1682,6 +1973,18 @@ pub fn delete_zknote(
   file_path: PathBuf,
   uid: UserId,
   noteid: &ZkNoteId,
+) -> Result<(), zkerr::Error> {
+  delete_zknote_batch(conn, file_path, uid, noteid, None)
+}
+
+// with a power delete batch, the note is recorded in the batch for undo,
+// and its file is kept until the batch is purged.
+pub fn delete_zknote_batch(
+  conn: &Connection,
+  file_path: PathBuf,
+  uid: UserId,
+  noteid: &ZkNoteId,
+  batch: Option<i64>,
 ) -> Result<(), zkerr::Error> {
   let nid = note_id_for_zknoteid(&conn, &noteid)?;
   match zknote_access_id(&conn, Some(uid), nid)? {
@@ -1689,7 +1992,23 @@ pub fn delete_zknote(
     _ => Err("can't delete zknote; write permission denied.".into()),
   }?;
 
-  archive_zknote_i64(&conn, nid)?;
+  // editors with write access can't delete; check before anything is archived or audited.
+  let owner: i64 = conn.query_row(
+    "select user from zknote where id = ?1",
+    params![nid],
+    |row| row.get(0),
+  )?;
+  if owner != *uid.to_i64() {
+    return Err("can't delete zknote; only the owner can delete it.".into());
+  }
+
+  let archiveid = archive_zknote_i64(&conn, nid)?;
+
+  let title: String = conn.query_row(
+    "select title from zknote where id = ?1",
+    params![nid],
+    |row| row.get(0),
+  )?;
 
   // get file info, if any.
   let filerec: Option<(i64, String)> = match conn.query_row(
@@ -1707,39 +2026,111 @@ pub fn delete_zknote(
   let now = now()?;
 
   // only delete when user is the owner.
-  conn.execute(
+  let deleted = conn.execute(
     "update zknote set deleted = 1, title = '<deleted>', content = '', file = null, changeddate = ?1
       where uuid = ?2
       and user = ?3",
     params![now, noteid.to_string(), uid.to_i64()],
   )?;
+  if deleted != 1 {
+    return Err("can't delete zknote; only the owner can delete it.".into());
+  }
+
+  audit(
+    conn,
+    Some(uid),
+    AuditAction::DeleteNote,
+    format!("{} - {}", noteid, title).as_str(),
+  )?;
 
-  // is this file referred to by any other notes?
-  match filerec {
-    Some((fileid, hash)) => {
-      let usecount: i32 = conn.query_row(
-        "select count(*) from zknote N
-        where N.file = ?1",
-        params![fileid],
-        |row| Ok(row.get(0)?),
+  match batch {
+    Some(batch) => {
+      conn.execute(
+        "insert into deletebatchnote (batch, zknote, archive, file) values (?1, ?2, ?3, ?4)",
+        params![
+          batch,
+          nid,
+          archiveid,
+          filerec.as_ref().map(|(fileid, _)| *fileid)
+        ],
       )?;
-      if usecount == 0 {
-        let mut rpath = file_path.clone();
-        rpath.push(Path::new(&hash));
+    }
+    None => match filerec {
+      Some((fileid, hash)) => remove_unused_file(conn, &file_path, fileid, &hash)?,
+      None => (),
+    },
+  }
 
-        std::fs::remove_file(rpath.as_path())?;
+  Ok(())
+}
 
-        conn.execute("delete from file where id = ?1", params![fileid])?;
+// remove a file if no notes refer to it.
+pub fn remove_unused_file(
+  conn: &Connection,
+  file_path: &Path,
+  fileid: i64,
+  hash: &str,
+) -> Result<(), zkerr::Error> {
+  // power deletes that can still be undone count as uses.
+  let usecount: i32 = conn.query_row(
+    "select (select count(*) from zknote N
+        where N.file = ?1)
+      + (select count(*) from deletebatchnote B, deletebatch D
+        where B.file = ?1 and B.batch = D.id
+          and not D.purged and D.undone is null)",
+    params![fileid],
+    |row| Ok(row.get(0)?),
+  )?;
+  if usecount == 0 {
+    let mut rpath = file_path.to_path_buf();
+    rpath.push(Path::new(&hash));
 
-        conn.execute("delete from files_dir where filename = ?1", params![hash])?;
-      }
-    }
-    None => (),
-  }
+    std::fs::remove_file(rpath.as_path())?;
+
+    conn.execute("delete from file where id = ?1", params![fileid])?;
 
+    conn.execute("delete from files_dir where filename = ?1", params![hash])?;
+  }
   Ok(())
 }
 
+// record links that make notes public, or change shares or their membership.
+fn audit_zklink(
+  conn: &Connection,
+  uid: UserId,
+  fromid: i64,
+  toid: i64,
+  delete: bool,
+) -> Result<(), zkerr::Error> {
+  let publicid = note_id(&conn, "system", "public")?;
+  let shareid = note_id(&conn, "system", "share")?;
+
+  let action = if fromid == publicid || toid == publicid {
+    AuditAction::PublicChange
+  } else if fromid == shareid
+    || toid == shareid
+    || are_notes_linked(&conn, fromid, shareid)?
+    || are_notes_linked(&conn, toid, shareid)?
+  {
+    AuditAction::ShareChange
+  } else {
+    return Ok(());
+  };
+
+  audit(
+    conn,
+    Some(uid),
+    action,
+    format!(
+      "{} link {} -> {}",
+      if delete { "removed" } else { "added" },
+      uuid_for_note_id(&conn, fromid)?,
+      uuid_for_note_id(&conn, toid)?
+    )
+    .as_str(),
+  )
+}
+
 pub fn save_zklinks(
   dbfile: &Path,
   uid: UserId,
@@ -1774,6 +2165,7 @@ pub fn save_zklinks(
 
       save_zklink(&conn, from, to, uid, lzn)?;
     }
+    audit_zklink(&conn, uid, from, to, zklink.delete == Some(true))?;
   }
 
   Ok(())
@@ -1814,6 +2206,7 @@ pub fn save_savezklinks(
           .and_then(|lzn| note_id_for_zknoteid(&conn, &lzn).ok());
         save_zklink(&conn, from, to, uid, linkzknote)?;
       }
+      audit_zklink(&conn, uid, from, to, link.delete == Some(true))?;
     }
   }
 
@@ -2070,14 +2463,40 @@ pub fn read_lzlinks(
 pub fn read_public_zklinks(
   conn: &Connection,
   noteid: &ZkNoteId,
+  sharelink: Option<i64>,
 ) -> Result<Vec<EditLink>, zkerr::Error> {
   let pubid = note_id(&conn, "system", "public")?;
+  let mut links = read_hub_zklinks(conn, noteid, pubid)?;
+
+  // with a share link to a share, links between notes on the share are visible too.
+  if let Some(slnote) = sharelink {
+    if are_notes_linked(conn, slnote, note_id(&conn, "system", "share")?)? {
+      for l in read_hub_zklinks(conn, noteid, slnote)? {
+        if !links
+          .iter()
+          .any(|el| el.otherid == l.otherid && el.direction == l.direction)
+        {
+          links.push(l);
+        }
+      }
+    }
+  }
+
+  Ok(links)
+}
+
+// links to or from notes that link to the hub note, 'public' or a share.
+fn read_hub_zklinks(
+  conn: &Connection,
+  noteid: &ZkNoteId,
+  hubid: i64,
+) -> Result<Vec<EditLink>, zkerr::Error> {
   let sysid = user_id(&conn, "system")?;
   let zknid = note_id_for_zknoteid(&conn, noteid)?;
 
   // TODO: integrate sysid lookup in the query?
   let mut pstmt = conn.prepare(
-    // return zklinks that link to or from notes that link to 'public'.
+    // return zklinks that link to or from notes that link to the hub.
     "select A.fromid, A.toid, A.user, A.linkzknote, L.uuid, L.title, R.uuid, R.title
        from zklink A, zklink B
        inner join zknote as L ON A.fromid = L.id
@@ -2090,7 +2509,7 @@ pub fn read_public_zklinks(
   )?;
 
   let r = Result::from_iter(pstmt.query_and_then(
-    params![zknid, pubid, sysid.to_i64()],
+    params![zknid, hubid, sysid.to_i64()],
     |row| {
       let fromid: i64 = row.get(0)?;
       let toid: i64 = row.get(1)?;
@@ -2674,6 +3093,18 @@ pub fn accessible_notes(
   Ok((sqlbase, baseargs))
 }
 
+// remember the version of the note the user has seen, for changed_notes.
+pub fn record_note_view(conn: &Connection, uid: UserId, noteid: i64) -> Result<(), zkerr::Error> {
+  conn.execute(
+    "insert into noteview (user, zknote, changeddate, viewdate)
+      select ?1, id, changeddate, ?3 from zknote where id = ?2
+      on conflict (user, zknote) do update
+        set changeddate = excluded.changeddate, viewdate = excluded.viewdate",
+    params![uid.to_i64(), noteid, now()?],
+  )?;
+  Ok(())
+}
+
 pub fn read_zknoteandlinks(
   conn: &Connection,
   files_dir: &Path,
@@ -2683,9 +3114,13 @@ pub fn read_zknoteandlinks(
   // should do an ownership check for us
   let (id, zknote) = read_zknote(conn, files_dir, uid, zknoteid)?;
 
+  if let (Some(uid), ZkNoteId::Zni(_)) = (uid, zknoteid) {
+    record_note_view(conn, uid, id)?;
+  }
+
   let links = match uid {
     Some(uid) => read_zklinks(conn, uid, id)?,
-    None => read_public_zklinks(conn, &zknote.id)?,
+    None => read_public_zklinks(conn, &zknote.id, None)?,
   };
 
   let lzlinks = match uid {
@@ -2728,117 +3163,238 @@ pub fn read_zneifchanged(
   }
 }
 
+// find or create a note by title, for import links.
+async fn import_link_note(
+  conn: &Connection,
+  lapin_info: &Option<LapinInfo>,
+  server: &Server,
+  uid: UserId,
+  title: &String,
+) -> Result<i64, zkerr::Error> {
+  match note_id2(&conn, uid, title)? {
+    Some(n) => Ok(n),
+    None => Ok(
+      save_zknote(
+        &conn,
+        lapin_info,
+        server,
+        uid,
+        &SaveZkNote {
+          id: None,
+          title: title.clone(),
+          pubid: None,
+          content: "".to_string(),
+          editable: false,
+          showtitle: true,
+          deleted: false,
+          encrypted: false,
+          what: None,
+        },
+        None,
+      )
+      .await?
+      .0,
+    ),
+  }
+}
+
+// imports go through save_zknote, so existing notes are archived and changes are published
+// and synced.  policy decides what happens to notes with the same title as an existing note.
 pub async fn save_importzknotes(
   conn: &Connection,
   lapin_info: &Option<LapinInfo>,
   server: &Server,
   uid: UserId,
   izns: &Vec<ImportZkNote>,
-) -> Result<(), zkerr::Error> {
+  policy: &ImportPolicy,
+) -> Result<Vec<ImportZkNoteResult>, zkerr::Error> {
+  let mut results = Vec::new();
   for izn in izns.iter() {
-    // create the note if it doesn't exist.
-    let nid = match note_id2(&conn, uid, izn.title.as_str())? {
+    let newnote = SaveZkNote {
+      id: None,
+      title: izn.title.clone(),
+      pubid: None,
+      content: izn.content.clone(),
+      editable: false,
+      showtitle: true,
+      deleted: false,
+      encrypted: false,
+      what: None,
+    };
+    let existing = match policy {
+      ImportPolicy::Duplicate => None,
+      _ => note_id2(&conn, uid, izn.title.as_str())?,
+    };
+    let (nid, action) = match existing {
       Some(i) => {
-        // update the content.
-        conn.execute(
-          "update zknote set content = ?1 where
-            user = ?2 and id = ?3",
-          params![izn.content, uid.to_i64(), i],
+        let (uuid, content, pubid, editable, showtitle, deleted, encrypted): (
+          String,
+          String,
+          Option<String>,
+          bool,
+          bool,
+          bool,
+          bool,
+        ) = conn.query_row(
+          "select uuid, content, pubid, editable, showtitle, deleted, encrypted
+            from zknote where id = ?1",
+          params![i],
+          |row| {
+            Ok((
+              row.get(0)?,
+              row.get(1)?,
+              row.get(2)?,
+              row.get(3)?,
+              row.get(4)?,
+              row.get(5)?,
+              row.get(6)?,
+            ))
+          },
         )?;
-
-        i
+        // imported content is plaintext, so it can't replace or be appended to
+        // encrypted content.
+        let newcontent = match policy {
+          ImportPolicy::Skip | ImportPolicy::Duplicate => None,
+          ImportPolicy::Replace => {
+            if encrypted {
+              return Err(zkerr::Error::String(format!(
+                "can't replace encrypted note: {}",
+                izn.title
+              )));
+            } else if content == izn.content && !deleted {
+              None
+            } else {
+              Some((izn.content.clone(), ImportAction::Replaced))
+            }
+          }
+          ImportPolicy::Append => {
+            if encrypted {
+              return Err(zkerr::Error::String(format!(
+                "can't append to encrypted note: {}",
+                izn.title
+              )));
+            } else if izn.content.is_empty() || content.ends_with(izn.content.as_str()) {
+              // already appended; re-running an import doesn't repeat it.
+              None
+            } else if content.is_empty() {
+              Some((izn.content.clone(), ImportAction::Appended))
+            } else {
+              Some((
+                format!("{}\n\n{}", content, izn.content),
+                ImportAction::Appended,
+              ))
+            }
+          }
+        };
+        match (newcontent, policy) {
+          (None, ImportPolicy::Skip) => (i, ImportAction::Skipped),
+          (None, _) => (i, ImportAction::Unchanged),
+          (Some((content, action)), _) => {
+            save_zknote(
+              &conn,
+              lapin_info,
+              server,
+              uid,
+              &SaveZkNote {
+                id: Some(ZkNoteId::Zni(Uuid::parse_str(uuid.as_str())?)),
+                content,
+                pubid,
+                editable,
+                showtitle,
+                ..newnote
+              },
+              None,
+            )
+            .await?;
+            (i, action)
+          }
+        }
       }
       None => {
-        // new note.
-        save_zknote(
-          &conn,
-          lapin_info,
-          server,
-          uid,
-          &SaveZkNote {
-            id: None,
-            title: izn.title.clone(),
-            pubid: None,
-            content: izn.content.clone(),
-            editable: false,
-            showtitle: true,
-            deleted: false,
-            what: None,
-          },
-          None,
-        )
-        .await?
-        .0
-      }
-    };
-    // now add the 'from' links.
-    for title in izn.fromLinks.iter() {
-      // if the 'from' note doesn't exist, create it.
-      let fromid = match note_id2(&conn, uid, title)? {
-        Some(n) => n,
-        None => {
-          // new note.
-          save_zknote(
-            &conn,
-            lapin_info,
-            server,
-            uid,
-            &SaveZkNote {
-              id: None,
-              title: title.clone(),
-              pubid: None,
-              content: "".to_string(),
-              editable: false,
-              showtitle: true,
-              deleted: false,
-              what: None,
-            },
-            None,
-          )
+        let nid = save_zknote(&conn, lapin_info, server, uid, &newnote, None)
           .await?
-          .0
+          .0;
+        match policy {
+          ImportPolicy::Duplicate => (nid, ImportAction::Duplicated),
+          _ => (nid, ImportAction::Added),
         }
-      };
+      }
+    };
 
-      // save link.
-      save_zklink(&conn, fromid, nid, uid, None)?;
+    // a skipped note keeps its links as they are.
+    if action != ImportAction::Skipped {
+      // now add the 'from' links, creating the 'from' notes if needed.
+      for title in izn.fromLinks.iter() {
+        let fromid = import_link_note(conn, lapin_info, server, uid, title).await?;
+        save_zklink(&conn, fromid, nid, uid, None)?;
+      }
+      // add the 'to' links (and their notes)
+      for title in izn.toLinks.iter() {
+        let toid = import_link_note(conn, lapin_info, server, uid, title).await?;
+        save_zklink(&conn, nid, toid, uid, None)?;
+      }
     }
-    // add the 'to' links (and their notes)
-    for title in izn.toLinks.iter() {
-      let toid = match note_id2(&conn, uid, title)? {
-        Some(n) => n,
-        None => {
-          // new note.
-          save_zknote(
-            &conn,
-            lapin_info,
-            server,
-            uid,
-            &SaveZkNote {
-              id: None,
-              title: title.clone(),
-              pubid: None,
-              content: "".to_string(),
-              editable: false,
-              showtitle: true,
-              deleted: false,
-              what: None,
-            },
-            None,
-          )
-          .await?
-          .0
-        }
-      };
 
-      // save link.
-      save_zklink(&conn, nid, toid, uid, None)?;
-    }
+    results.push(ImportZkNoteResult {
+      title: izn.title.clone(),
+      id: ZkNoteId::Zni(uuid_for_note_id(&conn, nid)?),
+      action,
+    });
   }
 
+  Ok(results)
+}
+
+pub async fn publish_make_file_note(
+  lapin_info: &Option<LapinInfo>,
+  uid: UserId,
+  zni: ZkNoteId,
+  title: &str,
+) -> Result<(), zkerr::Error> {
+  if let Some(li) = lapin_info {
+    let oszn = OnMakeFileNote {
+      id: zni,
+      user: uid,
+      token: li.token.clone(),
+      title: title.to_string(),
+    };
+    // send the message.
+    match li
+      .channel
+      .basic_publish(
+        "",
+        "on_make_file_note",
+        lapin::options::BasicPublishOptions::default(),
+        &serde_json::to_vec(&oszn)?[..],
+        lapin::BasicProperties::default(),
+      )
+      .await
+    {
+      Ok(_) => info!("OnMakeFileNote published to amqp"),
+      Err(e) => error!("error publishing to AMQP: {:?}", e),
+    }
+  }
   Ok(())
 }
 
+// the user's note for a file, if there is one.
+pub fn user_file_note(
+  conn: &Connection,
+  uid: UserId,
+  hash: &str,
+) -> Result<Option<i64>, zkerr::Error> {
+  match conn.query_row(
+    "select zknote.id from zknote, file
+      where file.hash = ?1 and zknote.file = file.id and zknote.user = ?2",
+    params![hash, uid.to_i64()],
+    |row| row.get(0),
+  ) {
+    Ok(id) => Ok(Some(id)),
+    Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+    Err(e) => Err(e.into()),
+  }
+}
+
 pub async fn make_file_note(
   conn: &Connection,
   server: &Server,
@@ -2848,12 +3404,40 @@ pub async fn make_file_note(
   name: &String,
   fpath: &Path,
   copy: bool,
+  quotas: &Quotas,
 ) -> Result<(i64, ZkNoteId, i64), zkerr::Error> {
   // compute hash.
   let fh = sha256::try_digest(fpath)?;
+  make_file_note_with_hash(
+    conn, server, lapin_info, files_dir, uid, name, fpath, fh, copy, quotas,
+  )
+  .await
+}
+
+// for when the sha256 hash of fpath is already known.
+pub async fn make_file_note_with_hash(
+  conn: &Connection,
+  server: &Server,
+  lapin_info: &Option<LapinInfo>,
+  files_dir: &Path,
+  uid: UserId,
+  name: &String,
+  fpath: &Path,
+  fh: String,
+  copy: bool,
+  quotas: &Quotas,
+) -> Result<(i64, ZkNoteId, i64), zkerr::Error> {
   let size = std::fs::metadata(fpath)?.len();
   let hashpath = files_dir.join(Path::new(fh.as_str()));
 
+  // over quota?  then don't keep the file.
+  if let Err(e) = check_file_quota(conn, quotas, uid, fh.as_str(), size as i64) {
+    if !copy {
+      std::fs::remove_file(fpath)?;
+    }
+    return Err(e);
+  }
+
   let mut existed = false;
 
   // file exists?
@@ -2881,33 +3465,6 @@ pub async fn make_file_note(
       Err(x) => Err(x),
     }?;
 
-  let send_on_make_file_node = async |zni: ZkNoteId, title: &str| {
-    if let Some(li) = lapin_info {
-      let oszn = OnMakeFileNote {
-        id: zni,
-        user: uid,
-        token: li.token.clone(),
-        title: title.to_string(),
-      };
-      // send the message.
-      match li
-        .channel
-        .basic_publish(
-          "",
-          "on_make_file_note",
-          lapin::options::BasicPublishOptions::default(),
-          &serde_json::to_vec(&oszn)?[..],
-          lapin::BasicProperties::default(),
-        )
-        .await
-      {
-        Ok(_) => info!("OnMakeFileNote published to amqp"),
-        Err(e) => error!("error publishing to AMQP: {:?}", e),
-      }
-    }
-    Ok::<(), zkerr::Error>(())
-  };
-
   // use existing file.id, or create new
   let fid = match oid {
     Some(fid) => {
@@ -2926,7 +3483,7 @@ pub async fn make_file_note(
         Ok((id, uuid, title)) => {
           let zni = ZkNoteId::Zni(Uuid::parse_str(uuid.as_str())?);
           if !existed {
-            send_on_make_file_node(zni, title.as_str()).await?;
+            publish_make_file_note(lapin_info, uid, zni, title.as_str()).await?;
           }
           return Ok((id, zni, fid));
         }
@@ -2969,6 +3526,7 @@ pub async fn make_file_note(
       editable: false,
       showtitle: false,
       deleted: false,
+      encrypted: false,
       what: None,
     },
     None,
@@ -2978,11 +3536,464 @@ pub async fn make_file_note(
   // set the file id in that note.
   set_zknote_file(&conn, id, fid)?;
 
-  send_on_make_file_node(sn.id, name).await?;
+  publish_make_file_note(lapin_info, uid, sn.id, name).await?;
 
   Ok((id, sn.id, fid))
 }
 
+fn api_token_scope_str(scope: &ApiTokenScope) -> &'static str {
+  match scope {
+    ApiTokenScope::ReadOnly => "ReadOnly",
+    ApiTokenScope::ReadWrite => "ReadWrite",
+    ApiTokenScope::UploadOnly => "UploadOnly",
+  }
+}
+
+fn api_token_scope(s: &str) -> Result<ApiTokenScope, zkerr::Error> {
+  match s {
+    "ReadOnly" => Ok(ApiTokenScope::ReadOnly),
+    "ReadWrite" => Ok(ApiTokenScope::ReadWrite),
+    "UploadOnly" => Ok(ApiTokenScope::UploadOnly),
+    _ => Err(zkerr::Error::String(format!(
+      "unknown api token scope: {}",
+      s
+    ))),
+  }
+}
+
+pub fn create_api_token(
+  conn: &Connection,
+  uid: UserId,
+  name: &str,
+  scope: &ApiTokenScope,
+) -> Result<NewApiToken, zkerr::Error> {
+  let id = Uuid::new_v4();
+  let token = format!("zkt_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+  let now = now()?;
+  conn.execute(
+    "insert into apitoken (uuid, user, name, scope, tokenhash, createdate)
+      values (?1, ?2, ?3, ?4, ?5, ?6)",
+    params![
+      id.to_string(),
+      uid.to_i64(),
+      name,
+      api_token_scope_str(scope),
+      sha256::digest(token.as_str()),
+      now
+    ],
+  )?;
+  Ok(NewApiToken {
+    info: ApiToken {
+      id,
+      name: name.to_string(),
+      scope: scope.clone(),
+      createdate: now,
+      lastused: None,
+    },
+    token,
+  })
+}
+
+pub fn read_api_tokens(conn: &Connection, uid: UserId) -> Result<Vec<ApiToken>, zkerr::Error> {
+  let mut pstmt = conn.prepare(
+    "select uuid, name, scope, createdate, lastused from apitoken
+      where user = ?1 order by createdate",
+  )?;
+  let r = pstmt
+    .query_and_then(params![uid.to_i64()], |row| {
+      Ok::<ApiToken, zkerr::Error>(ApiToken {
+        id: Uuid::parse_str(row.get::<usize, String>(0)?.as_str())?,
+        name: row.get(1)?,
+        scope: api_token_scope(row.get::<usize, String>(2)?.as_str())?,
+        createdate: row.get(3)?,
+        lastused: row.get(4)?,
+      })
+    })?
+    .collect::<Result<Vec<ApiToken>, zkerr::Error>>()?;
+  Ok(r)
+}
+
+pub fn revoke_api_token(conn: &Connection, uid: UserId, id: &Uuid) -> Result<(), zkerr::Error> {
+  match conn.execute(
+    "delete from apitoken where uuid = ?1 and user = ?2",
+    params![id.to_string(), uid.to_i64()],
+  )? {
+    0 => Err(zkerr::Error::String(format!("api token not found: {}", id))),
+    _ => Ok(()),
+  }
+}
+
+// find the user and scope for a token, and note that it was used.
+pub fn api_token_user(
+  conn: &Connection,
+  token: &str,
+) -> Result<(UserId, ApiTokenScope), zkerr::Error> {
+  let th = sha256::digest(token);
+  let (id, user, scope): (i64, i64, String) = match conn.query_row(
+    "select id, user, scope from apitoken where tokenhash = ?1",
+    params![th],
+    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+  ) {
+    Ok(r) => r,
+    Err(rusqlite::Error::QueryReturnedNoRows) => return Err(zkerr::Error::InvalidApiToken),
+    Err(e) => return Err(e.into()),
+  };
+  conn.execute(
+    "update apitoken set lastused = ?1 where id = ?2",
+    params![now()?, id],
+  )?;
+  Ok((UserId::Uid(user), api_token_scope(scope.as_str())?))
+}
+
+// only the note owner can make share links for it.
+pub fn create_share_link(
+  conn: &Connection,
+  uid: UserId,
+  csl: &CreateShareLink,
+) -> Result<ShareLink, zkerr::Error> {
+  let zknid = note_id_for_zknoteid(conn, &csl.zknote)?;
+  if !is_zknote_mine(conn, zknid, uid)? {
+    return Err(zkerr::Error::String(
+      "only the note owner can make share links".to_string(),
+    ));
+  }
+  let id = Uuid::new_v4();
+  let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+  let now = now()?;
+  conn.execute(
+    "insert into sharelink (uuid, token, user, zknote, createdate, expires)
+      values (?1, ?2, ?3, ?4, ?5, ?6)",
+    params![id.to_string(), token, uid.to_i64(), zknid, now, csl.expires],
+  )?;
+  Ok(ShareLink {
+    id,
+    token,
+    zknote: csl.zknote,
+    createdate: now,
+    expires: csl.expires,
+    revoked: false,
+    views: 0,
+    lastview: None,
+  })
+}
+
+pub fn read_share_links(
+  conn: &Connection,
+  uid: UserId,
+  zknote: &ZkNoteId,
+) -> Result<Vec<ShareLink>, zkerr::Error> {
+  let zknid = note_id_for_zknoteid(conn, zknote)?;
+  let mut pstmt = conn.prepare(
+    "select uuid, token, createdate, expires, revoked, views, lastview from sharelink
+      where user = ?1 and zknote = ?2 order by createdate",
+  )?;
+  let r = pstmt
+    .query_and_then(params![uid.to_i64(), zknid], |row| {
+      Ok::<ShareLink, zkerr::Error>(ShareLink {
+        id: Uuid::parse_str(row.get::<usize, String>(0)?.as_str())?,
+        token: row.get(1)?,
+        zknote: *zknote,
+        createdate: row.get(2)?,
+        expires: row.get(3)?,
+        revoked: row.get(4)?,
+        views: row.get(5)?,
+        lastview: row.get(6)?,
+      })
+    })?
+    .collect::<Result<Vec<ShareLink>, zkerr::Error>>()?;
+  Ok(r)
+}
+
+// revoked links are kept, so their view counts are still available.
+pub fn revoke_share_link(conn: &Connection, uid: UserId, id: &Uuid) -> Result<(), zkerr::Error> {
+  match conn.execute(
+    "update sharelink set revoked = 1 where uuid = ?1 and user = ?2",
+    params![id.to_string(), uid.to_i64()],
+  )? {
+    0 => Err(zkerr::Error::String(format!(
+      "share link not found: {}",
+      id
+    ))),
+    _ => Ok(()),
+  }
+}
+
+// the note for a current share link, counting the view.
+pub fn sharelink_note(conn: &Connection, token: &str) -> Result<i64, zkerr::Error> {
+  let now = now()?;
+  let (id, zknote): (i64, i64) = match conn.query_row(
+    "select id, zknote from sharelink
+      where token = ?1 and not revoked and (expires is null or expires > ?2)",
+    params![token, now],
+    |row| Ok((row.get(0)?, row.get(1)?)),
+  ) {
+    Ok(r) => r,
+    Err(rusqlite::Error::QueryReturnedNoRows) => return Err(zkerr::Error::NoteIsPrivate),
+    Err(e) => return Err(e.into()),
+  };
+  conn.execute(
+    "update sharelink set views = views + 1, lastview = ?1 where id = ?2",
+    params![now, id],
+  )?;
+  Ok(zknote)
+}
+
+pub fn create_upload(
+  conn: &Connection,
+  uid: UserId,
+  name: &str,
+  size: i64,
+) -> Result<UploadStatus, zkerr::Error> {
+  let id = Uuid::new_v4();
+  let now = now()?;
+  conn.execute(
+    "insert into upload (uuid, user, name, size, received, createdate, changeddate)
+      values (?1, ?2, ?3, ?4, 0, ?5, ?5)",
+    params![id.to_string(), uid.to_i64(), name, size, now],
+  )?;
+  Ok(UploadStatus {
+    id,
+    name: name.to_string(),
+    size,
+    received: 0,
+  })
+}
+
+// only the user who started an upload can see it.
+pub fn read_upload(
+  conn: &Connection,
+  uid: UserId,
+  id: &Uuid,
+) -> Result<UploadStatus, zkerr::Error> {
+  Ok(conn.query_row(
+    "select name, size, received from upload where uuid = ?1 and user = ?2",
+    params![id.to_string(), uid.to_i64()],
+    |row| {
+      Ok(UploadStatus {
+        id: *id,
+        name: row.get(0)?,
+        size: row.get(1)?,
+        received: row.get(2)?,
+      })
+    },
+  )?)
+}
+
+pub fn set_upload_received(
+  conn: &Connection,
+  id: &Uuid,
+  received: i64,
+) -> Result<(), zkerr::Error> {
+  conn.execute(
+    "update upload set received = ?1, changeddate = ?2 where uuid = ?3",
+    params![received, now()?, id.to_string()],
+  )?;
+  Ok(())
+}
+
+pub fn delete_upload(conn: &Connection, id: &Uuid) -> Result<(), zkerr::Error> {
+  conn.execute(
+    "delete from upload where uuid = ?1",
+    params![id.to_string()],
+  )?;
+  Ok(())
+}
+
+// uploads that haven't received data since the cutoff time.
+// the number of uploads a user has in progress, and their declared bytes.
+pub fn open_uploads(conn: &Connection, uid: UserId) -> Result<(i64, i64), zkerr::Error> {
+  Ok(conn.query_row(
+    "select count(*), coalesce(sum(size), 0) from upload where user = ?1",
+    params![uid.to_i64()],
+    |row| Ok((row.get(0)?, row.get(1)?)),
+  )?)
+}
+
+pub fn stale_uploads(conn: &Connection, cutoff: i64) -> Result<Vec<Uuid>, zkerr::Error> {
+  let mut pstmt = conn.prepare("select uuid from upload where changeddate < ?1")?;
+  let r = pstmt
+    .query_and_then(params![cutoff], |row| {
+      Ok::<Uuid, zkerr::Error>(Uuid::parse_str(row.get::<usize, String>(0)?.as_str())?)
+    })?
+    .collect::<Result<Vec<Uuid>, zkerr::Error>>()?;
+  Ok(r)
+}
+
+// bytes of stored files referenced by this user's notes, each file counted once.
+pub fn user_file_bytes(conn: &Connection, uid: UserId) -> Result<i64, zkerr::Error> {
+  Ok(conn.query_row(
+    "select coalesce(sum(F.size), 0) from file F, files_dir FD
+      where FD.filename = F.hash
+      and F.id in (select N.file from zknote N where N.user = ?1)",
+    params![uid.to_i64()],
+    |row| row.get(0),
+  )?)
+}
+
+// bytes of all stored files.
+pub fn total_file_bytes(conn: &Connection) -> Result<i64, zkerr::Error> {
+  Ok(conn.query_row(
+    "select coalesce(sum(F.size), 0) from file F, files_dir FD
+      where FD.filename = F.hash",
+    params![],
+    |row| row.get(0),
+  )?)
+}
+
+// bytes the user can still store before hitting a quota, if there is one.
+pub fn remaining_file_bytes(
+  conn: &Connection,
+  quotas: &Quotas,
+  uid: UserId,
+) -> Result<Option<i64>, zkerr::Error> {
+  let ur = match quotas.user_bytes {
+    Some(q) => Some(q - user_file_bytes(conn, uid)?),
+    None => None,
+  };
+  let gr = match quotas.global_bytes {
+    Some(q) => Some(q - total_file_bytes(conn)?),
+    None => None,
+  };
+  Ok(match (ur, gr) {
+    (Some(u), Some(g)) => Some(std::cmp::min(u, g)),
+    (u, g) => u.or(g),
+  })
+}
+
+// would storing this file put the user or the server over quota?
+// files are stored by hash, so a file that's already on disk takes no new space.
+pub fn check_file_quota(
+  conn: &Connection,
+  quotas: &Quotas,
+  uid: UserId,
+  hash: &str,
+  size: i64,
+) -> Result<(), zkerr::Error> {
+  if quotas.user_bytes.is_none() && quotas.global_bytes.is_none() {
+    return Ok(());
+  }
+
+  let stored: bool = conn.query_row(
+    "select count(*) from files_dir where filename = ?1",
+    params![hash],
+    |row| Ok(row.get::<usize, i64>(0)? > 0),
+  )?;
+
+  if let Some(q) = quotas.global_bytes {
+    if !stored && total_file_bytes(conn)? + size > q {
+      return Err(zkerr::Error::QuotaExceeded(format!(
+        "global file storage limit of {} bytes",
+        q
+      )));
+    }
+  }
+
+  if let Some(q) = quotas.user_bytes {
+    let users: bool = conn.query_row(
+      "select count(*) from zknote N, file F
+        where N.user = ?1 and N.file = F.id and F.hash = ?2",
+      params![uid.to_i64(), hash],
+      |row| Ok(row.get::<usize, i64>(0)? > 0),
+    )?;
+    if !(stored && users) && user_file_bytes(conn, uid)? + size > q {
+      return Err(zkerr::Error::QuotaExceeded(format!(
+        "user file storage limit of {} bytes",
+        q
+      )));
+    }
+  }
+
+  Ok(())
+}
+
+// storage used per user, and overall.  file sizes come from the file table;
+// since files are stored by hash, notes with the same file share one copy.
+// deleted notes aren't counted, archives are.
+pub fn read_storage_stats(conn: &Connection, largest: i64) -> Result<StorageStats, zkerr::Error> {
+  let mut pstmt = conn.prepare(
+    "with NS (user, notes, files, file_bytes) as (
+        select N.user, count(*), count(N.file), coalesce(sum(F.size), 0)
+        from zknote N left join file F on F.id = N.file
+        where N.deleted = 0
+        group by N.user),
+      UF (user, unique_file_bytes) as (
+        select UF.user, sum(UF.size)
+        from (select distinct N.user, F.id, F.size
+          from zknote N join file F on F.id = N.file
+          where N.deleted = 0) UF
+        group by UF.user),
+      AN (user, archives) as (
+        select A.user, count(*) from zkarch A group by A.user)
+    select OU.id, OU.name, coalesce(NS.notes, 0), coalesce(AN.archives, 0),
+      coalesce(NS.files, 0), coalesce(NS.file_bytes, 0), coalesce(UF.unique_file_bytes, 0)
+      from orgauth_user OU
+      left join NS on NS.user = OU.id
+      left join UF on UF.user = OU.id
+      left join AN on AN.user = OU.id
+      order by OU.name",
+  )?;
+
+  let mut users = pstmt
+    .query_map(params![], |row| {
+      Ok(UserStorageStats {
+        user: UserId::Uid(row.get(0)?),
+        name: row.get(1)?,
+        notes: row.get(2)?,
+        archives: row.get(3)?,
+        files: row.get(4)?,
+        file_bytes: row.get(5)?,
+        unique_file_bytes: row.get(6)?,
+        largest_files: Vec::new(),
+      })
+    })?
+    .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+
+  // each user's largest files, biggest first.
+  let mut lstmt = conn.prepare(
+    "select L.user, L.uuid, L.title, L.hash, L.size
+      from (select N.user, N.uuid, N.title, F.hash, F.size,
+          row_number() over (partition by N.user order by F.size desc) as rank
+        from zknote N join file F on F.id = N.file
+        where N.deleted = 0) L
+      where L.rank <= ?1
+      order by L.user, L.rank",
+  )?;
+  let largest_files = lstmt
+    .query_and_then(params![largest], |row| {
+      Ok::<_, zkerr::Error>((
+        UserId::Uid(row.get(0)?),
+        FileStats {
+          id: ZkNoteId::Zni(Uuid::parse_str(row.get::<usize, String>(1)?.as_str())?),
+          title: row.get(2)?,
+          hash: row.get(3)?,
+          size: row.get(4)?,
+        },
+      ))
+    })?
+    .collect::<Result<Vec<_>, zkerr::Error>>()?;
+  for (uid, fs) in largest_files {
+    if let Some(us) = users.iter_mut().find(|us| us.user == uid) {
+      us.largest_files.push(fs);
+    }
+  }
+
+  let (file_bytes, unique_file_bytes) = conn.query_row(
+    "select
+      (select coalesce(sum(F.size), 0) from zknote N, file F
+        where N.file = F.id and N.deleted = 0),
+      (select coalesce(sum(F.size), 0) from file F
+        where F.id in (select N.file from zknote N where N.deleted = 0))",
+    params![],
+    |row| Ok((row.get(0)?, row.get(1)?)),
+  )?;
+
+  Ok(StorageStats {
+    users,
+    file_bytes,
+    unique_file_bytes,
+  })
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ZkDatabase {
   notes: Vec<ZkNote>,
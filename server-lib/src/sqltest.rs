@@ -1,5 +1,8 @@
 #[cfg(test)]
 mod tests {
+  use crate::config::Quotas;
+  use crate::error as zkerr;
+  use crate::interfaces::{api_token_allows, zk_interface_loggedin};
   use crate::search::*;
   use crate::sqldata::*;
   use orgauth::data::RegistrationData;
@@ -7,7 +10,11 @@ mod tests {
   use std::error::Error;
   use std::fs;
   use std::path::Path;
-  use zkprotocol::content::SaveZkNote;
+  use zkprotocol::content::{
+    ApiTokenScope, CreateShareLink, GetStorageStats, GetZkNoteArchives, ImportAction, ImportPolicy,
+    ImportZkNote, SaveZkNote, SharePermission, UserSharePermission, ZkNoteId,
+  };
+  use zkprotocol::private::{PrivateReply, PrivateRequest};
   use zkprotocol::search::*;
 
   // Note this useful idiom: importing names from outer (for mod tests) scope.
@@ -110,6 +117,7 @@ mod tests {
         content: "note1 content".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -130,6 +138,7 @@ mod tests {
         content: "note1-2 content".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -154,6 +163,7 @@ mod tests {
         content: "note1-3 content".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -192,6 +202,7 @@ mod tests {
         content: "note1-4 content".to_string(),
         editable: true,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -215,6 +226,7 @@ mod tests {
         content: "note1-5 content".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -223,7 +235,7 @@ mod tests {
     save_zklink(&conn, szn1_5_id, szn1_3_share_id, uid1, None)?;
 
     // user 1 note 6 - shared w user link
-    let (szn1_6_id, _szn1_6) = save_zknote(
+    let (szn1_6_id, szn1_6) = save_zknote(
       &conn,
       &None,
       &server,
@@ -236,6 +248,7 @@ mod tests {
         content: "note1-6 content".to_string(),
         editable: true,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -259,6 +272,7 @@ mod tests {
         content: "note1-7 content".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -282,6 +296,7 @@ mod tests {
         content: "note1-4 content FROM USER 2".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -311,6 +326,7 @@ mod tests {
         content: "note1-5 content FROM USER 2".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -336,6 +352,7 @@ mod tests {
         content: "note2 content".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -375,6 +392,7 @@ mod tests {
         content: "note1 content".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -404,6 +422,7 @@ mod tests {
         content: "note1 content".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -430,6 +449,7 @@ mod tests {
         content: "note1-2 content changed".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -454,6 +474,7 @@ mod tests {
         content: "note1-6 content changed by user2".to_string(),
         editable: true,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -719,6 +740,91 @@ mod tests {
       _ => panic!("test failed"),
     }
 
+    // share permissions for u2 on share 2.  no record means write.
+    assert_eq!(
+      zknote_access_id(&conn, Some(uid2), szn1_4_id)?,
+      Access::ReadWrite
+    );
+
+    let setperm = |permission| {
+      set_share_permission(
+        &conn,
+        uid1,
+        &UserSharePermission {
+          share: szn1_2_share.id,
+          user: uid2,
+          permission,
+        },
+      )
+    };
+
+    // u2 can't set permissions on u1's share.
+    match set_share_permission(
+      &conn,
+      uid2,
+      &UserSharePermission {
+        share: szn1_2_share.id,
+        user: uid2,
+        permission: SharePermission::Write,
+      },
+    ) {
+      Ok(_) => panic!("wat"),
+      Err(_e) => (),
+    };
+
+    // read: can't edit, can't link.
+    setperm(SharePermission::Read)?;
+    assert_eq!(
+      zknote_access_id(&conn, Some(uid2), szn1_4_id)?,
+      Access::Read
+    );
+    match save_zklink(&conn, szn2_1_id, szn1_4_id, uid2, None) {
+      Ok(_) => panic!("wat"),
+      Err(_e) => (),
+    };
+
+    // comment: can't edit, can link.
+    setperm(SharePermission::Comment)?;
+    assert_eq!(
+      zknote_access_id(&conn, Some(uid2), szn1_4_id)?,
+      Access::Read
+    );
+    save_zklink(&conn, szn2_1_id, szn1_4_id, uid2, None)?;
+
+    // write: can edit.
+    setperm(SharePermission::Write)?;
+    assert_eq!(
+      zknote_access_id(&conn, Some(uid2), szn1_4_id)?,
+      Access::ReadWrite
+    );
+    println!("24.1");
+
+    // a share link to share 2 can read notes on share 2, but not share 3.
+    let sl = create_share_link(
+      &conn,
+      uid1,
+      &CreateShareLink {
+        zknote: szn1_2_share.id,
+        expires: None,
+      },
+    )?;
+    let slnote = sharelink_note(&conn, sl.token.as_str())?;
+    let n4uuid = ZkNoteId::Zni(uuid_for_note_id(&conn, szn1_4_id)?);
+    let n5uuid = ZkNoteId::Zni(uuid_for_note_id(&conn, szn1_5_id)?);
+    read_zknote_sharelink(&conn, filesdir, None, Some(slnote), &n4uuid)?;
+    match read_zknote_sharelink(&conn, filesdir, None, Some(slnote), &n5uuid) {
+      Ok(_) => panic!("wat"),
+      Err(_e) => (),
+    };
+
+    // revoked links don't work.
+    revoke_share_link(&conn, uid1, &sl.id)?;
+    match sharelink_note(&conn, sl.token.as_str()) {
+      Ok(_) => panic!("wat"),
+      Err(_e) => (),
+    };
+    println!("24.2");
+
     // can save changes to a share note, without error.
 
     // TODO test search modifiers.
@@ -871,6 +977,649 @@ mod tests {
       Ok(x) => panic!("archive note found in zknote table?! {:?}", x),
     };
 
+    // --------------------------------
+    // power delete with preview, then undo.
+    let (_pdn_id, pdn) = save_zknote(
+      &conn,
+      &None,
+      &server,
+      uid1,
+      &SaveZkNote {
+        id: None,
+        title: "u1 power delete me".to_string(),
+        showtitle: true,
+        pubid: None,
+        content: "pd content".to_string(),
+        editable: false,
+        deleted: false,
+        encrypted: false,
+        what: None,
+      },
+      None,
+    )
+    .await?;
+
+    let pdsearch = vec![TagSearch::SearchTerm {
+      mods: vec![SearchMod::ExactMatch],
+      term: "u1 power delete me".to_string(),
+    }];
+    let preview = power_delete_preview(&conn, filesdir, uid1, &pdsearch)?;
+    assert_eq!(preview.notes.len(), 1);
+
+    // wrong token fails.
+    match power_delete_zknotes(
+      &conn,
+      filesdir.to_path_buf(),
+      uid1,
+      &PowerDeleteConfirm {
+        search: pdsearch.clone(),
+        token: "0-nope".to_string(),
+      },
+    ) {
+      Ok(_) => panic!("wat"),
+      Err(_e) => (),
+    };
+
+    // so does an unsigned one.
+    let time = preview.token.split('-').next().unwrap_or("");
+    match power_delete_zknotes(
+      &conn,
+      filesdir.to_path_buf(),
+      uid1,
+      &PowerDeleteConfirm {
+        search: pdsearch.clone(),
+        token: format!(
+          "{}-{}",
+          time,
+          sha256::digest(format!("{}:{}:{}", uid1.to_i64(), time, pdn.id))
+        ),
+      },
+    ) {
+      Ok(_) => panic!("wat"),
+      Err(_e) => (),
+    };
+
+    // other users' notes are skipped, and counted in the preview.
+    let (u2pd_id, _) = save_zknote(
+      &conn,
+      &None,
+      &server,
+      uid2,
+      &SaveZkNote {
+        id: None,
+        title: "u2 power delete me".to_string(),
+        showtitle: true,
+        pubid: None,
+        content: "pd content".to_string(),
+        editable: false,
+        deleted: false,
+        encrypted: false,
+        what: None,
+      },
+      None,
+    )
+    .await?;
+    save_zklink(&conn, u2pd_id, publicid, uid2, None)?;
+    let wide = power_delete_preview(
+      &conn,
+      filesdir,
+      uid1,
+      &vec![TagSearch::SearchTerm {
+        mods: vec![],
+        term: "power delete me".to_string(),
+      }],
+    )?;
+    assert_eq!(wide.notes.len(), 1);
+    assert_eq!(wide.skipped, 1);
+
+    let batch = power_delete_zknotes(
+      &conn,
+      filesdir.to_path_buf(),
+      uid1,
+      &PowerDeleteConfirm {
+        search: pdsearch.clone(),
+        token: preview.token,
+      },
+    )?;
+    assert_eq!(batch.count, 1);
+    assert!(read_zknote(&conn, filesdir, Some(uid1), &pdn.id)?.1.deleted);
+
+    assert_eq!(undo_power_delete(&conn, uid1, &batch.id)?, 1);
+    let (_, restored) = read_zknote(&conn, filesdir, Some(uid1), &pdn.id)?;
+    assert!(!restored.deleted);
+    assert_eq!(restored.title, "u1 power delete me");
+    assert_eq!(restored.content, "pd content");
+
+    // can't undo twice.
+    match undo_power_delete(&conn, uid1, &batch.id) {
+      Ok(_) => panic!("wat"),
+      Err(_e) => (),
+    };
+
+    println!("30");
+
+    // --------------------------------
+    // encrypted notes don't match content searches.
+    for (title, encrypted) in [("u1 encrypted", true), ("u1 not encrypted", false)] {
+      save_zknote(
+        &conn,
+        &None,
+        &server,
+        uid1,
+        &SaveZkNote {
+          id: None,
+          title: title.to_string(),
+          showtitle: true,
+          pubid: None,
+          content: "opaquecontent".to_string(),
+          editable: false,
+          deleted: false,
+          encrypted,
+          what: None,
+        },
+        None,
+      )
+      .await?;
+    }
+
+    let enc_search = ZkNoteSearch {
+      tagsearch: vec![TagSearch::SearchTerm {
+        mods: vec![SearchMod::Note],
+        term: "opaquecontent".to_string(),
+      }],
+      offset: 0,
+      limit: None,
+      what: "test".to_string(),
+      resulttype: ResultType::RtNote,
+      ordering: None,
+      archives: ArchivesOrCurrent::Current,
+      deleted: false,
+    };
+
+    match search_zknotes(&conn, filesdir, uid1, &enc_search)? {
+      SearchResult::SrNote(zkr) => {
+        assert_eq!(zkr.notes.len(), 1);
+        assert_eq!(zkr.notes[0].title, "u1 not encrypted");
+        assert!(!zkr.notes[0].encrypted);
+      }
+      _ => panic!("test failed"),
+    };
+
+    println!("31");
+
+    // --------------------------------
+    // notes changed by someone else since they were last viewed.
+    let gcn = GetChangedNotes {
+      since: None,
+      offset: 0,
+      limit: None,
+    };
+    read_zknoteandlinks(&conn, filesdir, Some(uid1), &szn1_6.id)?;
+    assert!(!changed_notes(&conn, uid1, &gcn)?
+      .iter()
+      .any(|c| c.note.id == szn1_6.id));
+
+    save_zknote(
+      &conn,
+      &None,
+      &server,
+      uid2,
+      &SaveZkNote {
+        id: Some(szn1_6.id),
+        title: "u1 note6 - direct share".to_string(),
+        showtitle: true,
+        pubid: None,
+        content: "note1-6 content edited by user2".to_string(),
+        editable: true,
+        deleted: false,
+        encrypted: false,
+        what: None,
+      },
+      None,
+    )
+    .await?;
+
+    let changed = changed_notes(&conn, uid1, &gcn)?;
+    match changed.iter().find(|c| c.note.id == szn1_6.id) {
+      Some(c) => assert_eq!(c.editor, uid2),
+      None => panic!("test failed"),
+    };
+    // user2 made the change, so it isn't new to them.
+    assert!(!changed_notes(&conn, uid2, &gcn)?
+      .iter()
+      .any(|c| c.note.id == szn1_6.id));
+
+    read_zknoteandlinks(&conn, filesdir, Some(uid1), &szn1_6.id)?;
+    assert!(!changed_notes(&conn, uid1, &gcn)?
+      .iter()
+      .any(|c| c.note.id == szn1_6.id));
+
+    // editors can't delete, and nothing is archived or audited when they try.
+    let rows = |table: &str| -> Result<i64, rusqlite::Error> {
+      conn.query_row(
+        format!("select count(*) from {}", table).as_str(),
+        rusqlite::params![],
+        |row| row.get(0),
+      )
+    };
+    let (archives, audits) = (rows("zkarch")?, rows("audit")?);
+    assert!(delete_zknote(&conn, filesdir.to_path_buf(), uid2, &szn1_6.id).is_err());
+    assert!(
+      !read_zknote(&conn, filesdir, Some(uid1), &szn1_6.id)?
+        .1
+        .deleted
+    );
+    assert_eq!(rows("zkarch")?, archives);
+    assert_eq!(rows("audit")?, audits);
+
+    // the owner's saves leave the editor null.
+    save_zknote(
+      &conn,
+      &None,
+      &server,
+      uid1,
+      &SaveZkNote {
+        id: Some(szn1_6.id),
+        title: "u1 note6 - direct share".to_string(),
+        showtitle: true,
+        pubid: None,
+        content: "note1-6 content edited by user1".to_string(),
+        editable: true,
+        deleted: false,
+        encrypted: false,
+        what: None,
+      },
+      None,
+    )
+    .await?;
+    let editor: Option<i64> = conn.query_row(
+      "select editor from zknote where uuid = ?1",
+      rusqlite::params![szn1_6.id.to_string()],
+      |row| row.get(0),
+    )?;
+    assert_eq!(editor, None);
+
+    println!("32");
+
+    // --------------------------------
+    // import merge policies.
+    let izn = |content: &str| {
+      vec![ImportZkNote {
+        title: "u1 imported".to_string(),
+        content: content.to_string(),
+        fromLinks: Vec::new(),
+        toLinks: Vec::new(),
+      }]
+    };
+    let r = save_importzknotes(
+      &conn,
+      &None,
+      &server,
+      uid1,
+      &izn("one"),
+      &ImportPolicy::Replace,
+    )
+    .await?;
+    assert_eq!(r[0].action, ImportAction::Added);
+    let impid = r[0].id;
+    let r = save_importzknotes(
+      &conn,
+      &None,
+      &server,
+      uid1,
+      &izn("one"),
+      &ImportPolicy::Replace,
+    )
+    .await?;
+    assert_eq!(r[0].action, ImportAction::Unchanged);
+    let r = save_importzknotes(
+      &conn,
+      &None,
+      &server,
+      uid1,
+      &izn("two"),
+      &ImportPolicy::Append,
+    )
+    .await?;
+    assert_eq!(r[0].action, ImportAction::Appended);
+    assert_eq!(
+      read_zknote_unchecked(&conn, filesdir, &impid)?.1.content,
+      "one\n\ntwo"
+    );
+    let r = save_importzknotes(
+      &conn,
+      &None,
+      &server,
+      uid1,
+      &izn("three"),
+      &ImportPolicy::Skip,
+    )
+    .await?;
+    assert_eq!(r[0].action, ImportAction::Skipped);
+    let r = save_importzknotes(
+      &conn,
+      &None,
+      &server,
+      uid1,
+      &izn("three"),
+      &ImportPolicy::Duplicate,
+    )
+    .await?;
+    assert_eq!(r[0].action, ImportAction::Duplicated);
+    assert!(r[0].id != impid);
+    // the replaced content was archived.
+    let archives = read_zknotearchives(
+      &conn,
+      filesdir,
+      uid1,
+      &GetZkNoteArchives {
+        zknote: impid,
+        offset: 0,
+        limit: None,
+      },
+    )?;
+    assert_eq!(archives.len(), 1);
+
+    // plaintext doesn't go into encrypted notes.
+    for policy in [ImportPolicy::Replace, ImportPolicy::Append] {
+      assert!(save_importzknotes(
+        &conn,
+        &None,
+        &server,
+        uid1,
+        &vec![ImportZkNote {
+          title: "u1 encrypted".to_string(),
+          content: "plaintext".to_string(),
+          fromLinks: Vec::new(),
+          toLinks: Vec::new(),
+        }],
+        &policy,
+      )
+      .await
+      .is_err());
+    }
+    let encid = note_id2(&conn, uid1, "u1 encrypted")?.ok_or("test failed")?;
+    let enc = read_zknote_i64(&conn, filesdir, Some(uid1), encid)?;
+    assert!(enc.encrypted);
+    assert_eq!(enc.content, "opaquecontent");
+
+    println!("33");
+
+    // --------------------------------
+    // file quotas.  files are stored by hash, so a file the user already has is free.
+    let qdir = Path::new("quotatest");
+    let _ = fs::remove_dir_all(qdir);
+    fs::create_dir_all(qdir)?;
+    let f1 = qdir.join("f1");
+    fs::write(&f1, "0123456789")?;
+    let f1hash = sha256::try_digest(f1.as_path())?;
+    let f2 = qdir.join("f2");
+    fs::write(&f2, "abcdefghij")?;
+    let f2hash = sha256::try_digest(f2.as_path())?;
+
+    let quotas = Quotas {
+      user_bytes: Some(15),
+      global_bytes: None,
+    };
+    assert_eq!(remaining_file_bytes(&conn, &quotas, uid1)?, Some(15));
+    make_file_note(
+      &conn,
+      &server,
+      &None,
+      qdir,
+      uid1,
+      &"f1".to_string(),
+      &f1,
+      true,
+      &quotas,
+    )
+    .await?;
+    assert_eq!(remaining_file_bytes(&conn, &quotas, uid1)?, Some(5));
+
+    // the same file again.
+    check_file_quota(&conn, &quotas, uid1, f1hash.as_str(), 10)?;
+    make_file_note(
+      &conn,
+      &server,
+      &None,
+      qdir,
+      uid1,
+      &"f1 again".to_string(),
+      &f1,
+      true,
+      &quotas,
+    )
+    .await?;
+    assert_eq!(remaining_file_bytes(&conn, &quotas, uid1)?, Some(5));
+
+    // a new file over quota isn't kept.
+    match make_file_note(
+      &conn,
+      &server,
+      &None,
+      qdir,
+      uid1,
+      &"f2".to_string(),
+      &f2,
+      true,
+      &quotas,
+    )
+    .await
+    {
+      Err(zkerr::Error::QuotaExceeded(_)) => (),
+      _ => panic!("test failed"),
+    };
+    assert!(!qdir.join(f2hash.as_str()).exists());
+
+    // another user's copy counts for them.
+    let quotas = Quotas {
+      user_bytes: Some(5),
+      global_bytes: None,
+    };
+    assert!(check_file_quota(&conn, &quotas, uid2, f1hash.as_str(), 10).is_err());
+
+    // the global quota only counts new files.
+    let quotas = Quotas {
+      user_bytes: None,
+      global_bytes: Some(15),
+    };
+    assert_eq!(remaining_file_bytes(&conn, &quotas, uid2)?, Some(5));
+    check_file_quota(&conn, &quotas, uid2, f1hash.as_str(), 10)?;
+    assert!(check_file_quota(&conn, &quotas, uid2, f2hash.as_str(), 10).is_err());
+
+    println!("34");
+
+    // --------------------------------
+    // storage stats.  user2 gets a note for the file user1 already stored.
+    let (_, u2f1, _) = make_file_note(
+      &conn,
+      &server,
+      &None,
+      qdir,
+      uid2,
+      &"u2 f1".to_string(),
+      &f1,
+      true,
+      &Quotas::default(),
+    )
+    .await?;
+
+    let ss = read_storage_stats(&conn, 5)?;
+    assert_eq!(ss.file_bytes, 20);
+    assert_eq!(ss.unique_file_bytes, 10);
+    for (name, title) in [("user1", "f1"), ("user2", "u2 f1")] {
+      match ss.users.iter().find(|u| u.name == name) {
+        Some(us) => {
+          assert_eq!(us.files, 1);
+          assert_eq!(us.file_bytes, 10);
+          assert_eq!(us.unique_file_bytes, 10);
+          assert_eq!(us.largest_files.len(), 1);
+          assert_eq!(us.largest_files[0].title, title);
+          assert_eq!(us.largest_files[0].hash, f1hash);
+        }
+        None => panic!("test failed"),
+      }
+    }
+    assert_eq!(
+      read_storage_stats(&conn, 0)?.users[0].largest_files.len(),
+      0
+    );
+
+    // deleted notes don't count.
+    delete_zknote(&conn, qdir.to_path_buf(), uid2, &u2f1)?;
+    let ss = read_storage_stats(&conn, 5)?;
+    assert_eq!(ss.file_bytes, 10);
+    assert_eq!(ss.unique_file_bytes, 10);
+    match ss.users.iter().find(|u| u.name == "user2") {
+      Some(us) => {
+        assert_eq!(us.files, 0);
+        assert_eq!(us.file_bytes, 0);
+        assert_eq!(us.largest_files.len(), 0);
+      }
+      None => panic!("test failed"),
+    }
+
+    // only admins get them.
+    let state = crate::state::offline_state(crate::defcon(), server.clone());
+    let gss = PrivateRequest::PvqGetStorageStats(GetStorageStats { largest: 5 });
+    match zk_interface_loggedin(&state, &conn, None, uid1, &gss).await {
+      Err(zkerr::Error::NotAdmin) => (),
+      _ => panic!("test failed"),
+    };
+    let mut user = orgauth::dbfun::read_user_by_id(&conn, uid1)?;
+    user.admin = true;
+    orgauth::dbfun::update_user(&conn, &user)?;
+    match zk_interface_loggedin(&state, &conn, None, uid1, &gss).await? {
+      PrivateReply::PvyStorageStats(ss) => assert_eq!(ss.unique_file_bytes, 10),
+      _ => panic!("test failed"),
+    };
+    // nor through api tokens.
+    for scope in [ApiTokenScope::ReadOnly, ApiTokenScope::ReadWrite] {
+      assert!(!api_token_allows(&scope, &gss));
+    }
+
+    println!("35");
+
+    // --------------------------------
+    // a non admin can't apply a bundle that changes someone else's note.
+    let (u1nid, _) = save_zknote(
+      &conn,
+      &None,
+      &server,
+      uid1,
+      &SaveZkNote {
+        id: None,
+        title: "u1 bundle target".to_string(),
+        showtitle: true,
+        pubid: None,
+        content: "original".to_string(),
+        editable: false,
+        deleted: false,
+        encrypted: false,
+        what: None,
+      },
+      None,
+    )
+    .await?;
+    // forge a newer version of the note, bundle it, then put the note back.
+    conn.execute(
+      "update zknote set content = 'forged', changeddate = changeddate + 1000 where id = ?1",
+      rusqlite::params![u1nid],
+    )?;
+    let bundlezip = qdir.join("forged.zip");
+    crate::bundle::write_bundle(
+      std::sync::Arc::new(connection_open(dbp)?),
+      qdir,
+      qdir,
+      uid1,
+      None,
+      &bundlezip,
+      &mut cb,
+      &crate::jobs::LogMonitor {},
+    )
+    .await?;
+    conn.execute(
+      "update zknote set content = 'original', changeddate = changeddate - 1000 where id = ?1",
+      rusqlite::params![u1nid],
+    )?;
+
+    let (_, bundlenote, _) = make_file_note(
+      &conn,
+      &server,
+      &None,
+      qdir,
+      uid2,
+      &"forged.zip".to_string(),
+      &bundlezip,
+      true,
+      &Quotas::default(),
+    )
+    .await?;
+    match zk_interface_loggedin(
+      &state,
+      &conn,
+      None,
+      uid2,
+      &PrivateRequest::PvqApplySyncBundle(bundlenote),
+    )
+    .await
+    {
+      Err(zkerr::Error::NotAdmin) => (),
+      _ => panic!("test failed"),
+    };
+    let content: String = conn.query_row(
+      "select content from zknote where id = ?1",
+      rusqlite::params![u1nid],
+      |row| row.get(0),
+    )?;
+    assert_eq!(content, "original");
+
+    println!("36");
+
+    // --------------------------------
+    // a file stays while a power delete of a note using it can be undone.
+    let pdf = qdir.join("pdf");
+    fs::write(&pdf, "power delete file")?;
+    let pdfhash = sha256::try_digest(pdf.as_path())?;
+    let mut pdfnotes = Vec::new();
+    for title in ["pd file", "pd file again"] {
+      pdfnotes.push(
+        make_file_note(
+          &conn,
+          &server,
+          &None,
+          qdir,
+          uid1,
+          &title.to_string(),
+          &pdf,
+          true,
+          &Quotas::default(),
+        )
+        .await?,
+      );
+    }
+    let pdfsearch = vec![TagSearch::SearchTerm {
+      mods: vec![SearchMod::ExactMatch],
+      term: "pd file again".to_string(),
+    }];
+    let batch = power_delete_zknotes(
+      &conn,
+      qdir.to_path_buf(),
+      uid1,
+      &PowerDeleteConfirm {
+        search: pdfsearch.clone(),
+        token: power_delete_preview(&conn, qdir, uid1, &pdfsearch)?.token,
+      },
+    )?;
+    assert_eq!(batch.count, 1);
+    delete_zknote(&conn, qdir.to_path_buf(), uid1, &pdfnotes[0].1)?;
+    assert!(qdir.join(pdfhash.as_str()).exists());
+    undo_power_delete(&conn, uid1, &batch.id)?;
+    assert_eq!(read_file_info(&conn, pdfnotes[1].0)?.hash, pdfhash);
+    assert!(qdir.join(pdfhash.as_str()).exists());
+
+    println!("37");
+
     Ok(())
   }
 }
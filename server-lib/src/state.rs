@@ -1,9 +1,12 @@
 use crate::config::Config;
 use crate::jobs::JobId;
+use crate::ratelimit::RateLimiter;
+use crate::resumable::UploadHashers;
 use girlboss::Girlboss;
 use girlboss::Monitor;
 use lapin::Connection;
 use orgauth::data::UserId;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::RwLock;
 use zkprotocol::content::Server;
@@ -14,6 +17,16 @@ pub struct State {
   pub jobcounter: RwLock<i64>,
   pub server: Server,
   pub lapin_conn: RwLock<Option<Connection>>,
+  pub upload_hashers: UploadHashers,
+  pub ratelimiter: Arc<RateLimiter>,
+  pub purges: Option<Purges>,
+}
+
+// the timer for periodic cleanup, and the guards for its jobs.  dropping either
+// cancels the jobs, so they're kept for the life of the State.
+pub struct Purges {
+  pub timer: timer::Timer,
+  pub guards: Vec<timer::Guard>,
 }
 
 pub fn new_jobid(state: &State, uid: UserId) -> JobId {
@@ -26,3 +39,17 @@ pub fn new_jobid(state: &State, uid: UserId) -> JobId {
     jobno: *j,
   }
 }
+
+// a State without the web server or an amqp connection, for using a db directly.
+pub fn offline_state(config: Config, server: Server) -> State {
+  State {
+    ratelimiter: Arc::new(RateLimiter::new(config.ratelimits.clone())),
+    config,
+    girlboss: Arc::new(RwLock::new(Girlboss::new())),
+    jobcounter: RwLock::new(0),
+    server,
+    lapin_conn: RwLock::new(None),
+    upload_hashers: Arc::new(std::sync::Mutex::new(HashMap::new())),
+    purges: None,
+  }
+}
@@ -1,3 +1,4 @@
+use crate::config::Quotas;
 use crate::error as zkerr;
 use crate::jobs::JobMonitor;
 use crate::search::build_sql;
@@ -32,7 +33,7 @@ use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::AsyncBufReadExt;
 use tokio::io::AsyncWriteExt;
-use tokio_util::io::StreamReader;
+use tokio_util::io::{ReaderStream, StreamReader};
 use uuid::Uuid;
 use zkprotocol::constants::{PrivateStreamingRequests, SpecialUuids};
 use zkprotocol::content::{FileStatus, OnMakeFileNote, SaveZkNote, Server, SyncSince, ZkNoteId};
@@ -51,6 +52,10 @@ fn convert_payloaderr(err: PayloadError) -> std::io::Error {
   todo!()
 }
 
+fn convert_syncerr(err: Box<dyn std::error::Error>) -> std::io::Error {
+  std::io::Error::new(std::io::ErrorKind::Other, err.to_string())
+}
+
 pub async fn prev_sync(
   conn: &Connection,
   file_path: &Path,
@@ -137,6 +142,7 @@ pub async fn save_sync(
       editable: false,
       showtitle: false,
       deleted: false,
+      encrypted: false,
       what: None,
     },
     None,
@@ -300,12 +306,249 @@ pub async fn sync(
   }
 }
 
+// sync a user between two db files, without the network.  the same steps as sync(),
+// with the other db standing in for the remote.  the user is matched by uuid.
+pub async fn sync_local(
+  dbpath: &Path,
+  file_path: &Path,
+  other_dbpath: &Path,
+  other_file_path: &Path,
+  lapin_info: &Option<LapinInfo>,
+  uid: UserId,
+  callbacks: &mut Callbacks,
+  monitor: &dyn JobMonitor,
+) -> Result<PrivateReply, Box<dyn std::error::Error>> {
+  let conn = Arc::new(sqldata::connection_open(dbpath)?);
+  let oconn = Arc::new(sqldata::connection_open(other_dbpath)?);
+  let user = orgauth::dbfun::read_user_by_id(&conn, uid)?;
+  let ouser = orgauth::dbfun::read_user_by_uuid(&oconn, &user.uuid).map_err(|e| {
+    zkerr::annotate_string(
+      format!("user {} not found in {}", user.uuid, other_dbpath.display()),
+      e.into(),
+    )
+  })?;
+  let extra_login_data = sqldata::read_extra_login_data(&conn, user.id)?;
+
+  let after = prev_sync(&conn, &file_path, &extra_login_data.zknote)
+    .await?
+    .map(|cs| cs.now);
+
+  let server = local_server_id(&conn)?;
+  let oserver = local_server_id(&oconn)?;
+
+  let tr = conn.unchecked_transaction()?;
+  let otr = oconn.unchecked_transaction()?;
+
+  let ttn = temp_tables(&conn)?;
+
+  write!(monitor, "syncing from {}", other_dbpath.display());
+  let ostream = sync_stream(
+    oconn.clone(),
+    PathBuf::from(other_file_path),
+    ouser.id,
+    None,
+    None,
+    None,
+    None,
+    SyncStart {
+      after,
+      before: now()?,
+      server: Uuid::from_str(oserver.uuid.as_str())?,
+    },
+    callbacks,
+    monitor,
+  );
+  let mut obr = StreamReader::new(Box::pin(ostream.map_err(convert_syncerr)));
+  sync_from_stream(
+    &conn,
+    lapin_info,
+    &server,
+    &user,
+    file_path,
+    Some(&ttn.notetemp),
+    Some(&ttn.archivenotetemp),
+    Some(&ttn.linktemp),
+    Some(&ttn.archivelinktemp),
+    callbacks,
+    &mut obr,
+  )
+  .await?;
+
+  write!(monitor, "syncing to {}", other_dbpath.display());
+  let lstream = sync_stream(
+    conn.clone(),
+    PathBuf::from(file_path),
+    user.id,
+    Some(ttn.notetemp.clone()),
+    Some(ttn.archivenotetemp.clone()),
+    Some(ttn.linktemp.clone()),
+    Some(ttn.archivelinktemp.clone()),
+    SyncStart {
+      after,
+      before: now()?,
+      server: Uuid::from_str(server.uuid.as_str())?,
+    },
+    callbacks,
+    monitor,
+  );
+  let mut lbr = StreamReader::new(Box::pin(lstream.map_err(convert_syncerr)));
+  sync_from_stream(
+    &oconn,
+    &None,
+    &oserver,
+    &ouser,
+    other_file_path,
+    None,
+    None,
+    None,
+    None,
+    callbacks,
+    &mut lbr,
+  )
+  .await?;
+
+  let down = copy_files(&conn, file_path, other_file_path, user.id)?;
+  let up = copy_files(&oconn, other_file_path, file_path, ouser.id)?;
+  write!(monitor, "copied {} files, and {} files back", down, up);
+
+  otr.commit()?;
+  tr.commit()?;
+
+  write!(monitor, "sync completed");
+
+  Ok(PrivateReply::PvySyncComplete)
+}
+
+// copy the user's missing files from another files dir, if it has them.
+pub fn copy_files(
+  conn: &Connection,
+  file_path: &Path,
+  from_file_path: &Path,
+  uid: UserId,
+) -> Result<usize, zkerr::Error> {
+  let idsearch = ZkNoteSearch {
+    tagsearch: vec![TagSearch::SearchTerm {
+      mods: vec![SearchMod::FileMinus],
+      term: "".to_string(),
+    }],
+    offset: 0,
+    limit: None,
+    what: "".to_string(),
+    resulttype: ResultType::RtId,
+    archives: ArchivesOrCurrent::Current,
+    deleted: false,
+    ordering: None,
+  };
+
+  let (sql, args) = build_sql(&conn, uid, &idsearch, None)?;
+  let mut pstmt = conn.prepare(sql.as_str())?;
+  let ids = pstmt
+    .query_and_then(rusqlite::params_from_iter(args.iter()), |row| {
+      Ok::<i64, rusqlite::Error>(row.get(0)?)
+    })?
+    .collect::<Result<Vec<i64>, rusqlite::Error>>()?;
+
+  std::fs::create_dir_all(file_path)?;
+
+  let mut count = 0;
+  for id in ids {
+    let hash = match sqldata::read_zknote_filehash(&conn, Some(uid), id)? {
+      Some(h) => h,
+      None => continue,
+    };
+    let from = from_file_path.join(hash.as_str());
+    let to = file_path.join(hash.as_str());
+    if to.exists() || !from.exists() {
+      continue;
+    }
+    std::fs::copy(from, to)?;
+
+    // add to files_dir table.
+    match conn.execute(
+      "insert into files_dir (filename) values (?1)",
+      params![hash],
+    ) {
+      Ok(_) => (),
+      Err(e) => {
+        // if insert error, log it but proceed.
+        error!("insert into files_dir error {:?}", e);
+      }
+    }
+    count = count + 1;
+  }
+
+  Ok(count)
+}
+
+// write a user's sync stream to a file, to apply elsewhere with read_sync_file.
+pub async fn write_sync_file(
+  conn: Arc<Connection>,
+  file_path: &Path,
+  uid: UserId,
+  after: Option<i64>,
+  out: &Path,
+  callbacks: &mut Callbacks,
+  monitor: &dyn JobMonitor,
+) -> Result<SyncStart, Box<dyn std::error::Error>> {
+  let ls = local_server_id(&conn)?;
+  let syncstart = SyncStart {
+    after,
+    before: now()?,
+    server: Uuid::from_str(ls.uuid.as_str())?,
+  };
+
+  let ss = sync_stream(
+    conn,
+    PathBuf::from(file_path),
+    uid,
+    None,
+    None,
+    None,
+    None,
+    syncstart.clone(),
+    callbacks,
+    monitor,
+  );
+
+  let mut file = tokio::fs::File::create(out).await?;
+  let mut ss = Box::pin(ss);
+  while let Some(bytes) = ss.next().await {
+    file.write_all(&bytes?).await?;
+  }
+  file.flush().await?;
+
+  Ok(syncstart)
+}
+
+// apply a sync stream from write_sync_file.
+pub async fn read_sync_file(
+  conn: &Connection,
+  lapin_info: &Option<LapinInfo>,
+  server: &Server,
+  user: &User,
+  file_path: &Path,
+  callbacks: &mut Callbacks,
+  path: &Path,
+) -> Result<PrivateReply, Box<dyn std::error::Error>> {
+  let mut br = StreamReader::new(ReaderStream::new(tokio::fs::File::open(path).await?));
+
+  let tr = conn.unchecked_transaction()?;
+  let reply = apply_sync_stream(
+    conn, lapin_info, server, user, file_path, None, None, None, None, callbacks, true, &mut br,
+  )
+  .await?;
+  tr.commit()?;
+
+  Ok(reply)
+}
+
 #[derive(Deserialize, Serialize, Debug, PartialEq)]
 pub enum DownloadResult {
   Downloaded,
   AlreadyDownloaded,
   NoSource,
   DownloadFailed,
+  OverQuota,
 }
 
 pub async fn download_file(
@@ -315,17 +558,31 @@ pub async fn download_file(
   tmp_files_dir: &Path,
   files_dir: &Path,
   note_id: i64,
+  quotas: &Quotas,
 ) -> Result<DownloadResult, zkerr::Error> {
   // get the note hash, and verify there's a source with this user's id.
-  let (uuid, title, ohash, fs_user_id): (String, String, Option<String>, Option<i64>) = conn
-    .query_row(
-      "select N.uuid, N.title, F.hash, FS.user_id from zknote N
+  let (uuid, title, ohash, osize, fs_user_id): (
+    String,
+    String,
+    Option<String>,
+    Option<i64>,
+    Option<i64>,
+  ) = conn.query_row(
+    "select N.uuid, N.title, F.hash, F.size, FS.user_id from zknote N
       left join file F on N.file = F.id 
       left join file_source FS on F.id == FS.file_id and FS.user_id = ?2
       where N.id = ?1",
-      params![note_id, user.id.to_i64()],
-      |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
-    )?;
+    params![note_id, user.id.to_i64()],
+    |row| {
+      Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+      ))
+    },
+  )?;
 
   let hash = match ohash {
     Some(h) => h,
@@ -345,6 +602,15 @@ pub async fn download_file(
     return Ok(DownloadResult::AlreadyDownloaded);
   }
 
+  match sqldata::check_file_quota(conn, quotas, user.id, hash.as_str(), osize.unwrap_or(0)) {
+    Ok(()) => (),
+    Err(zkerr::Error::QuotaExceeded(s)) => {
+      info!("not downloading {}, quota exceeded: {}", title, s);
+      return Ok(DownloadResult::OverQuota);
+    }
+    Err(e) => return Err(e),
+  }
+
   let (c, url) = match (user.cookie.clone(), user.remote_url.clone()) {
     (Some(c), Some(url)) => (c, url),
     _ => return Err("can't remote sync - not a remote user".into()),
@@ -408,6 +674,18 @@ pub async fn download_file(
     std::fs::rename(temphashpath, finalhashpath)?;
   }
 
+  // add to files_dir table.
+  match conn.execute(
+    "insert into files_dir (filename) values (?1)",
+    params![hash],
+  ) {
+    Ok(_) => (),
+    Err(e) => {
+      // if insert error, log it but proceed.
+      error!("insert into files_dir error {:?}", e);
+    }
+  }
+
   if let Some(li) = lapin_info {
     let oszn = OnMakeFileNote {
       id: ZkNoteId::Zni(Uuid::parse_str(uuid.as_str())?),
@@ -544,6 +822,7 @@ pub async fn upload_file(
 
   match prm {
     UploadReply::UrFilesUploaded(_) => Ok(UploadResult::Uploaded),
+    UploadReply::UrFilesAttached(_) => Ok(UploadResult::Uploaded),
   }
 }
 
@@ -554,6 +833,7 @@ pub async fn sync_files_down(
   file_path: &Path,
   uid: UserId,
   search: &ZkNoteSearch,
+  quotas: &Quotas,
 ) -> Result<Vec<DownloadResult>, zkerr::Error> {
   // TODO pass this in from calling ftn?
   let user = orgauth::dbfun::read_user_by_id(&conn, uid)?;
@@ -578,11 +858,22 @@ pub async fn sync_files_down(
   for rec in rec_iter {
     match rec {
       Ok(id) => {
-        match download_file(&conn, &lapin_info, &user, temp_file_path, file_path, id).await? {
+        match download_file(
+          &conn,
+          &lapin_info,
+          &user,
+          temp_file_path,
+          file_path,
+          id,
+          quotas,
+        )
+        .await?
+        {
           DownloadResult::Downloaded => resvec.push(DownloadResult::Downloaded),
           DownloadResult::AlreadyDownloaded => (), // resvec.push(DownloadResult::AlreadyDownloaded),
           DownloadResult::NoSource => resvec.push(DownloadResult::NoSource),
           DownloadResult::DownloadFailed => resvec.push(DownloadResult::DownloadFailed),
+          DownloadResult::OverQuota => resvec.push(DownloadResult::OverQuota),
         };
       }
       Err(_) => (),
@@ -774,6 +1065,42 @@ pub async fn sync_from_stream<S>(
   callbacks: &mut Callbacks,
   br: &mut StreamReader<S, bytes::Bytes>,
 ) -> Result<PrivateReply, Box<dyn std::error::Error>>
+where
+  S: Stream<Item = Result<Bytes, std::io::Error>> + Unpin,
+{
+  apply_sync_stream(
+    conn,
+    lapin_info,
+    server,
+    user,
+    file_path,
+    notetemp,
+    archivenotetemp,
+    linktemp,
+    archivelinktemp,
+    callbacks,
+    false,
+    br,
+  )
+  .await
+}
+
+// bundle is for streams that were saved to a file.  those skip the clock check, and
+// the sync is recorded as of when the stream was made, rather than now.
+pub async fn apply_sync_stream<S>(
+  conn: &Connection,
+  lapin_info: &Option<LapinInfo>,
+  server: &Server,
+  user: &User,
+  file_path: &Path,
+  notetemp: Option<&str>,
+  archivenotetemp: Option<&str>,
+  linktemp: Option<&str>,
+  archivelinktemp: Option<&str>,
+  callbacks: &mut Callbacks,
+  bundle: bool,
+  br: &mut StreamReader<S, bytes::Bytes>,
+) -> Result<PrivateReply, Box<dyn std::error::Error>>
 where
   S: Stream<Item = Result<Bytes, std::io::Error>> + Unpin,
 {
@@ -794,7 +1121,7 @@ where
   let remotenow = ss.before;
 
   // milliseconds
-  if (now - remotenow).abs() > 10000 {
+  if !bundle && (now - remotenow).abs() > 10000 {
     return Err(
       format!(
         "remote time too far off! local: {}, remote: {}",
@@ -941,8 +1268,8 @@ where
     }?;
 
     let ex = conn.execute(
-        "insert into zknote (title, content, user, pubid, editable, showtitle, deleted, uuid, file, server, createdate, changeddate)
-         values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        "insert into zknote (title, content, user, pubid, editable, showtitle, deleted, encrypted, uuid, file, server, createdate, changeddate)
+         values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
       params![
         note.title,
         note.content,
@@ -951,6 +1278,7 @@ where
         note.editable,
         note.showtitle,
         note.deleted,
+        note.encrypted,
         note.id.to_string(),
         file_id,
         server_id,
@@ -983,6 +1311,7 @@ where
                   editable: note.editable,
                   showtitle: note.showtitle,
                   deleted: note.deleted,
+                  encrypted: note.encrypted,
                   what: None,
                 },
                 Some(NoteDates {
@@ -1079,8 +1408,8 @@ where
       Err(Err(e)) => Err(e),
     }?;
     let mbid = match conn.execute(
-      "insert into zkarch (zknote, title, content, user, pubid, editable, showtitle, deleted, uuid, server, createdate, changeddate)
-       values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+      "insert into zkarch (zknote, title, content, user, pubid, editable, showtitle, deleted, encrypted, uuid, server, createdate, changeddate)
+       values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
         params![
           pid,
           note.title,
@@ -1090,6 +1419,7 @@ where
           note.editable,
           note.showtitle,
           note.deleted,
+          note.encrypted,
           nid.to_string(),
           server_id,
           note.createdate,
@@ -1337,7 +1667,7 @@ where
       unote,
       CompletedSync {
         after: ss.after,
-        now,
+        now: if bundle { ss.before } else { now },
         local: Some(Uuid::parse_str(server.uuid.as_str())?),
         remote: Some(ss.server),
       },
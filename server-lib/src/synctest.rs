@@ -99,6 +99,7 @@ mod tests {
         content: "initial content".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -303,6 +304,7 @@ mod tests {
         content: "public-note initial content".to_string(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -354,6 +356,7 @@ mod tests {
       &fname,
       fpath,
       false,
+      &crate::config::Quotas::default(),
     )
     .await
     .map_err(|e| {
@@ -636,6 +639,7 @@ mod tests {
         content: cpub2.content.clone(),
         editable: false,
         deleted: false,
+        encrypted: false,
         what: None,
       },
       None,
@@ -1272,6 +1276,7 @@ mod tests {
       client_ts.filepath.as_path(),
       client_ts.syncuser,
       &fssearch,
+      &crate::config::Quotas::default(),
     )
     .await?;
 
@@ -1345,4 +1350,158 @@ mod tests {
 
     Ok(())
   }
+
+  #[actix_web::test]
+  async fn test_local_sync() -> Result<(), Box<dyn Error>> {
+    let dbp_a = Path::new("local_a.db");
+    let dbp_b = Path::new("local_b.db");
+    let mut cb = zknotes_callbacks();
+
+    for dbp in [dbp_a, dbp_b] {
+      match fs::remove_file(dbp) {
+        Ok(_) => (),
+        Err(e) => println!("error removing db: '{}'; {}", dbp.display(), e),
+      }
+      dbinit(dbp, None)?;
+    }
+
+    let a_conn = connection_open(dbp_a)?;
+    let a_ts = setup_db(&a_conn, &mut cb, "".to_string(), None, None, "local_a").await?;
+    let asu = read_user_by_id(&a_conn, a_ts.syncuser)?;
+    let ald = sqldata::read_extra_login_data(&a_conn, a_ts.syncuser)?;
+
+    let b_conn = connection_open(dbp_b)?;
+    let b_ts = setup_db(
+      &b_conn,
+      &mut cb,
+      "".to_string(),
+      None,
+      Some((asu.uuid, ald)),
+      "local_b",
+    )
+    .await?;
+    // not testing duplicate public ids here.
+    b_conn.execute(
+      "update zknote set pubid = 'local-b-public-note' where pubid = 'public-note'",
+      params![],
+    )?;
+
+    let lm = LogMonitor {};
+    sync_local(
+      dbp_a,
+      &a_ts.filepath,
+      dbp_b,
+      &b_ts.filepath,
+      &None,
+      a_ts.syncuser,
+      &mut cb,
+      &lm,
+    )
+    .await?;
+
+    for (_, uuid) in a_ts.synced_notes.iter() {
+      assert!(note_id_for_uuid(&b_conn, uuid).is_ok());
+    }
+    for (_, uuid) in b_ts.synced_notes.iter() {
+      assert!(note_id_for_uuid(&a_conn, uuid).is_ok());
+    }
+    for (_, uuid) in a_ts.unvisible_notes.iter() {
+      assert!(note_id_for_uuid(&b_conn, uuid).is_err());
+    }
+    for (_, uuid) in b_ts.unvisible_notes.iter() {
+      assert!(note_id_for_uuid(&a_conn, uuid).is_err());
+    }
+
+    // files are copied both ways.
+    let afi = read_file_info(&a_conn, a_ts.filenote)?;
+    assert!(b_ts.filepath.join(afi.hash).exists());
+    let bfi = read_file_info(&b_conn, b_ts.filenote)?;
+    assert!(a_ts.filepath.join(bfi.hash).exists());
+
+    // a note made after the sync goes over in a sync file.
+    let since = now()? - 1000;
+    let (_, bundled) = makenote(
+      &a_conn,
+      a_ts.syncuser,
+      "local_a bundled note".to_string(),
+      &local_server_id(&a_conn)?,
+    )
+    .await?;
+    let bundled: Uuid = bundled.id.into();
+    assert!(note_id_for_uuid(&b_conn, &bundled).is_err());
+
+    let syncfile = Path::new("local_a_sync.txt");
+    write_sync_file(
+      Arc::new(connection_open(dbp_a)?),
+      &a_ts.filepath,
+      a_ts.syncuser,
+      Some(since),
+      syncfile,
+      &mut cb,
+      &lm,
+    )
+    .await?;
+
+    read_sync_file(
+      &b_conn,
+      &None,
+      &local_server_id(&b_conn)?,
+      &read_user_by_id(&b_conn, b_ts.syncuser)?,
+      &b_ts.filepath,
+      &mut cb,
+      syncfile,
+    )
+    .await?;
+    assert!(note_id_for_uuid(&b_conn, &bundled).is_ok());
+
+    // a sync bundle brings its files along.
+    let since = now()? - 1000;
+    let fname = "local_a_bundle_file.txt";
+    orgauth::util::write_string(fname, "local_a bundle file")
+      .map_err(|e| zkerr::annotate_string("write_string error".to_string(), e.into()))?;
+    let (bfnote, _, _) = sqldata::make_file_note(
+      &a_conn,
+      &local_server_id(&a_conn)?,
+      &None,
+      &a_ts.filepath,
+      a_ts.syncuser,
+      &fname.to_string(),
+      Path::new(fname),
+      false,
+      &crate::config::Quotas::default(),
+    )
+    .await?;
+    let bffi = read_file_info(&a_conn, bfnote)?;
+
+    let bundlefile = Path::new("local_a_bundle.zip");
+    crate::bundle::write_bundle(
+      Arc::new(connection_open(dbp_a)?),
+      &a_ts.filepath,
+      &a_ts.tempfilepath,
+      a_ts.syncuser,
+      Some(since),
+      bundlefile,
+      &mut cb,
+      &lm,
+    )
+    .await?;
+
+    let files = crate::bundle::read_bundle(
+      &b_conn,
+      &None,
+      &local_server_id(&b_conn)?,
+      &read_user_by_id(&b_conn, b_ts.syncuser)?,
+      &b_ts.filepath,
+      &b_ts.tempfilepath,
+      &crate::config::Quotas::default(),
+      bundlefile,
+      &mut cb,
+      &lm,
+    )
+    .await?;
+    assert!(files == 1);
+    assert!(b_ts.filepath.join(bffi.hash).exists());
+
+    Ok(())
+  }
 }
@@ -0,0 +1,486 @@
+use crate::error as zkerr;
+use hmac::{Hmac, Mac};
+use orgauth::data::UserId;
+use orgauth::util::now;
+use rusqlite::{params, Connection};
+use sha1::Sha1;
+use uuid::Uuid;
+use zkprotocol::content::TotpEnrollment;
+
+// rfc 6238 defaults, which is what authenticator apps expect.
+const STEP_SECS: i64 = 30;
+const DIGITS: u32 = 6;
+// accept codes from one step either side, for clock drift.
+const SKEW: i64 = 1;
+const RECOVERY_CODES: usize = 10;
+
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+fn base32_encode(data: &[u8]) -> String {
+  let mut out = String::new();
+  let mut buffer: u32 = 0;
+  let mut bits = 0;
+  for b in data {
+    buffer = (buffer << 8) | *b as u32;
+    bits += 8;
+    while bits >= 5 {
+      out.push(BASE32[((buffer >> (bits - 5)) & 31) as usize] as char);
+      bits -= 5;
+    }
+  }
+  if bits > 0 {
+    out.push(BASE32[((buffer << (5 - bits)) & 31) as usize] as char);
+  }
+  out
+}
+
+fn base32_decode(s: &str) -> Result<Vec<u8>, zkerr::Error> {
+  let mut out = Vec::new();
+  let mut buffer: u32 = 0;
+  let mut bits = 0;
+  for c in s.bytes() {
+    let v = BASE32
+      .iter()
+      .position(|b| *b == c)
+      .ok_or(zkerr::Error::String("invalid totp secret".to_string()))?;
+    buffer = (buffer << 5) | v as u32;
+    bits += 5;
+    if bits >= 8 {
+      out.push((buffer >> (bits - 8)) as u8);
+      bits -= 8;
+    }
+  }
+  Ok(out)
+}
+
+// rfc 4226 hotp.
+fn hotp(secret: &[u8], counter: u64) -> Result<u32, zkerr::Error> {
+  let mut mac = Hmac::<Sha1>::new_from_slice(secret)
+    .map_err(|e| zkerr::Error::String(format!("hmac error: {}", e)))?;
+  mac.update(&counter.to_be_bytes());
+  let hash = mac.finalize().into_bytes();
+  let offset = (hash[hash.len() - 1] & 0xf) as usize;
+  let code = u32::from_be_bytes([
+    hash[offset] & 0x7f,
+    hash[offset + 1],
+    hash[offset + 2],
+    hash[offset + 3],
+  ]);
+  Ok(code % 10u32.pow(DIGITS))
+}
+
+// the time step that code matches, if any.
+fn match_step(secret: &[u8], code: &str, nowsecs: i64) -> Result<Option<i64>, zkerr::Error> {
+  let code = code.trim();
+  if code.len() != DIGITS as usize {
+    return Ok(None);
+  }
+  let code: u32 = match code.parse() {
+    Ok(c) => c,
+    Err(_) => return Ok(None),
+  };
+  let step = nowsecs / STEP_SECS;
+  for s in (step - SKEW)..=(step + SKEW) {
+    if hotp(secret, s as u64)? == code {
+      return Ok(Some(s));
+    }
+  }
+  Ok(None)
+}
+
+fn uri_encode(s: &str) -> String {
+  s.bytes()
+    .map(|b| match b {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+        (b as char).to_string()
+      }
+      _ => format!("%{:02X}", b),
+    })
+    .collect()
+}
+
+pub fn totp_enabled(conn: &Connection, uid: UserId) -> Result<bool, zkerr::Error> {
+  match conn.query_row(
+    "select enabled from totp where user = ?1",
+    params![uid.to_i64()],
+    |row| row.get(0),
+  ) {
+    Ok(e) => Ok(e),
+    Err(rusqlite::Error::QueryReturnedNoRows) => Ok(false),
+    Err(e) => Err(e.into()),
+  }
+}
+
+pub fn totp_enabled_for_name(conn: &Connection, name: &str) -> Result<bool, zkerr::Error> {
+  match conn.query_row(
+    "select T.enabled from totp T, orgauth_user U where T.user = U.id and U.name = ?1",
+    params![name],
+    |row| row.get(0),
+  ) {
+    Ok(e) => Ok(e),
+    Err(rusqlite::Error::QueryReturnedNoRows) => Ok(false),
+    Err(e) => Err(e.into()),
+  }
+}
+
+// start enrollment with a new secret.  2fa isn't on until confirm_totp.
+pub fn begin_totp(
+  conn: &Connection,
+  uid: UserId,
+  issuer: &str,
+) -> Result<TotpEnrollment, zkerr::Error> {
+  if totp_enabled(conn, uid)? {
+    return Err(zkerr::Error::String(
+      "two factor auth is already enabled".to_string(),
+    ));
+  }
+
+  let mut secret = Vec::new();
+  secret.extend_from_slice(Uuid::new_v4().as_bytes());
+  secret.extend_from_slice(Uuid::new_v4().as_bytes());
+  secret.truncate(20);
+  let secret = base32_encode(&secret);
+
+  conn.execute(
+    "insert into totp (user, secret, enabled, createdate) values (?1, ?2, 0, ?3)
+      on conflict (user) do update set secret = ?2, enabled = 0, lastcounter = null, createdate = ?3",
+    params![uid.to_i64(), secret, now()?],
+  )?;
+
+  let name: String = conn.query_row(
+    "select name from orgauth_user where id = ?1",
+    params![uid.to_i64()],
+    |row| row.get(0),
+  )?;
+
+  Ok(TotpEnrollment {
+    uri: format!(
+      "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+      uri_encode(issuer),
+      uri_encode(name.as_str()),
+      secret,
+      uri_encode(issuer),
+      DIGITS,
+      STEP_SECS
+    ),
+    secret,
+  })
+}
+
+// check a totp code, and mark its time step used.
+fn check_code(conn: &Connection, uid: UserId, code: &str) -> Result<bool, zkerr::Error> {
+  let (secret, lastcounter): (String, Option<i64>) = match conn.query_row(
+    "select secret, lastcounter from totp where user = ?1",
+    params![uid.to_i64()],
+    |row| Ok((row.get(0)?, row.get(1)?)),
+  ) {
+    Ok(r) => r,
+    Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(false),
+    Err(e) => return Err(e.into()),
+  };
+
+  match match_step(&base32_decode(secret.as_str())?, code, now()? / 1000)? {
+    Some(step) if lastcounter.map_or(true, |lc| step > lc) => {
+      conn.execute(
+        "update totp set lastcounter = ?1 where user = ?2",
+        params![step, uid.to_i64()],
+      )?;
+      Ok(true)
+    }
+    _ => Ok(false),
+  }
+}
+
+// check a recovery code, and use it up.
+fn check_recovery_code(conn: &Connection, uid: UserId, code: &str) -> Result<bool, zkerr::Error> {
+  let n = conn.execute(
+    "update totprecovery set used = ?1 where user = ?2 and codehash = ?3 and used is null",
+    params![now()?, uid.to_i64(), sha256::digest(code.trim())],
+  )?;
+  Ok(n > 0)
+}
+
+// the first code turns 2fa on.  returns recovery codes, which aren't available later.
+pub fn confirm_totp(
+  conn: &Connection,
+  uid: UserId,
+  code: &str,
+) -> Result<Vec<String>, zkerr::Error> {
+  if totp_enabled(conn, uid)? {
+    return Err(zkerr::Error::String(
+      "two factor auth is already enabled".to_string(),
+    ));
+  }
+  if !check_code(conn, uid, code)? {
+    return Err(zkerr::Error::InvalidTotp);
+  }
+
+  conn.execute(
+    "update totp set enabled = 1 where user = ?1",
+    params![uid.to_i64()],
+  )?;
+  conn.execute(
+    "delete from totprecovery where user = ?1",
+    params![uid.to_i64()],
+  )?;
+
+  let mut codes = Vec::new();
+  for _ in 0..RECOVERY_CODES {
+    let s = Uuid::new_v4().simple().to_string();
+    let code = format!("{}-{}", &s[0..5], &s[5..10]);
+    conn.execute(
+      "insert into totprecovery (user, codehash) values (?1, ?2)",
+      params![uid.to_i64(), sha256::digest(code.as_str())],
+    )?;
+    codes.push(code);
+  }
+
+  Ok(codes)
+}
+
+// second factor for login; a totp code or an unused recovery code.
+pub fn check_totp_login(conn: &Connection, uid: UserId, code: &str) -> Result<bool, zkerr::Error> {
+  Ok(check_code(conn, uid, code)? || check_recovery_code(conn, uid, code)?)
+}
+
+pub fn disable_totp(conn: &Connection, uid: UserId, code: &str) -> Result<(), zkerr::Error> {
+  if !check_totp_login(conn, uid, code)? {
+    return Err(zkerr::Error::InvalidTotp);
+  }
+  reset_totp(conn, uid)
+}
+
+// a login token made by a password check, when the second factor is missing or wrong.
+pub fn drop_login_token(conn: &Connection, token: &Uuid) -> Result<(), zkerr::Error> {
+  conn.execute(
+    "delete from orgauth_token where token = ?1",
+    params![token.to_string()],
+  )?;
+  Ok(())
+}
+
+// for admins, when a user has lost their authenticator and recovery codes.
+pub fn reset_totp(conn: &Connection, uid: UserId) -> Result<(), zkerr::Error> {
+  conn.execute("delete from totp where user = ?1", params![uid.to_i64()])?;
+  conn.execute(
+    "delete from totprecovery where user = ?1",
+    params![uid.to_i64()],
+  )?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::sqldata::{connection_open, dbinit, zknotes_callbacks};
+  use orgauth::data::RegistrationData;
+  use orgauth::dbfun::new_user;
+  use std::path::Path;
+
+  // the rfc 4226 and 6238 test secret.
+  const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+  #[test]
+  fn test_base32() {
+    // rfc 4648 vectors, without padding.
+    for (data, enc) in [
+      ("", ""),
+      ("f", "MY"),
+      ("fo", "MZXQ"),
+      ("foo", "MZXW6"),
+      ("foob", "MZXW6YQ"),
+      ("fooba", "MZXW6YTB"),
+      ("foobar", "MZXW6YTBOI"),
+      ("12345678901234567890", "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"),
+    ] {
+      assert_eq!(base32_encode(data.as_bytes()), enc);
+      assert_eq!(base32_decode(enc).unwrap(), data.as_bytes());
+    }
+    assert!(base32_decode("MZXW1").is_err());
+  }
+
+  #[test]
+  fn test_hotp() {
+    // rfc 4226 appendix d.
+    let codes = [
+      755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489,
+    ];
+    for (counter, code) in codes.iter().enumerate() {
+      assert_eq!(hotp(RFC_SECRET, counter as u64).unwrap(), *code);
+    }
+  }
+
+  #[test]
+  fn test_totp() {
+    // rfc 6238 appendix b, sha1, with the last 6 of the 8 digits.
+    for (time, code) in [
+      (59, "287082"),
+      (1111111109, "081804"),
+      (1111111111, "050471"),
+      (1234567890, "005924"),
+      (2000000000, "279037"),
+      (20000000000, "353130"),
+    ] {
+      assert_eq!(
+        match_step(RFC_SECRET, code, time).unwrap(),
+        Some(time / STEP_SECS)
+      );
+    }
+    // one step either side is accepted, but not two.
+    assert_eq!(match_step(RFC_SECRET, "287082", 89).unwrap(), Some(1));
+    assert_eq!(match_step(RFC_SECRET, "287082", 90).unwrap(), None);
+    assert_eq!(match_step(RFC_SECRET, " 287082\n", 59).unwrap(), Some(1));
+    assert_eq!(match_step(RFC_SECRET, "28708", 59).unwrap(), None);
+    assert_eq!(match_step(RFC_SECRET, "28708x", 59).unwrap(), None);
+  }
+
+  #[test]
+  fn test_replay_and_recovery() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = Path::new("totp_test");
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir)?;
+    let dbfile = dir.join("totp.db");
+    dbinit(dbfile.as_path(), None)?;
+    let conn = connection_open(dbfile.as_path())?;
+    let mut cb = zknotes_callbacks();
+    let uid = new_user(
+      &conn,
+      &RegistrationData {
+        uid: "twofactor".to_string(),
+        pwd: "".to_string(),
+        email: "".to_string(),
+        remote_url: "".to_string(),
+      },
+      None,
+      None,
+      false,
+      None,
+      None,
+      None,
+      None,
+      None,
+      &mut cb.on_new_user,
+    )?;
+
+    let enrollment = begin_totp(&conn, uid, "zknotes")?;
+    let secret = base32_decode(enrollment.secret.as_str())?;
+    let code = || -> Result<String, zkerr::Error> {
+      Ok(format!(
+        "{:06}",
+        hotp(&secret, (now()? / 1000 / STEP_SECS) as u64)?
+      ))
+    };
+    assert!(!totp_enabled(&conn, uid)?);
+
+    // the code that confirms enrollment can't be used again.
+    let c = code()?;
+    let recovery = confirm_totp(&conn, uid, c.as_str())?;
+    assert!(totp_enabled(&conn, uid)?);
+    assert_eq!(recovery.len(), RECOVERY_CODES);
+    assert!(!check_totp_login(&conn, uid, c.as_str())?);
+
+    // neither can an earlier step's code.
+    let earlier = format!(
+      "{:06}",
+      hotp(&secret, (now()? / 1000 / STEP_SECS - 1) as u64)?
+    );
+    assert!(!check_totp_login(&conn, uid, earlier.as_str())?);
+
+    // each recovery code works once.
+    assert!(check_totp_login(&conn, uid, recovery[0].as_str())?);
+    assert!(!check_totp_login(&conn, uid, recovery[0].as_str())?);
+    assert!(check_totp_login(&conn, uid, recovery[1].as_str())?);
+    assert!(!check_totp_login(&conn, uid, "00000-00000")?);
+
+    // disabling takes a recovery code too, and clears everything.
+    assert!(disable_totp(&conn, uid, recovery[1].as_str()).is_err());
+    disable_totp(&conn, uid, recovery[2].as_str())?;
+    assert!(!totp_enabled(&conn, uid)?);
+    assert!(!check_totp_login(&conn, uid, recovery[3].as_str())?);
+
+    Ok(())
+  }
+
+  #[actix_web::test]
+  async fn test_login_token() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = Path::new("totp_login_test");
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir)?;
+    let dbfile = dir.join("totp.db");
+    dbinit(dbfile.as_path(), None)?;
+    let conn = connection_open(dbfile.as_path())?;
+    let config = crate::defcon();
+    let uid = new_user(
+      &conn,
+      &RegistrationData {
+        uid: "twofactor".to_string(),
+        pwd: "pwd".to_string(),
+        email: "".to_string(),
+        remote_url: "".to_string(),
+      },
+      None,
+      None,
+      false,
+      None,
+      None,
+      None,
+      None,
+      None,
+      &mut zknotes_callbacks().on_new_user,
+    )?;
+    let enrollment = begin_totp(&conn, uid, "zknotes")?;
+    let secret = base32_decode(enrollment.secret.as_str())?;
+    let recovery = confirm_totp(
+      &conn,
+      uid,
+      format!("{:06}", hotp(&secret, (now()? / 1000 / STEP_SECS) as u64)?).as_str(),
+    )?;
+    let tokens = || -> Result<i64, rusqlite::Error> {
+      conn.query_row(
+        "select count(*) from orgauth_token where user = ?1",
+        params![uid.to_i64()],
+        |row| row.get(0),
+      )
+    };
+
+    // the password alone doesn't leave a usable login token behind.
+    let mut ut = orgauth::endpoints::UuidTokener { uuid: None };
+    match crate::interfaces::user_interface(
+      &conn,
+      &mut ut,
+      &config,
+      orgauth::data::UserRequest::UrqLogin(orgauth::data::Login {
+        uid: "twofactor".to_string(),
+        pwd: "pwd".to_string(),
+      }),
+    )
+    .await
+    {
+      Err(zkerr::Error::TotpRequired) => (),
+      _ => panic!("test failed"),
+    };
+    assert_eq!(tokens()?, 0);
+
+    // neither does a wrong code.
+    let mut tl = zkprotocol::content::TotpLogin {
+      uid: "twofactor".to_string(),
+      pwd: "pwd".to_string(),
+      code: "00000-00000".to_string(),
+    };
+    match crate::interfaces::totp_login(&conn, &config, &tl).await {
+      Err(zkerr::Error::InvalidTotp) => (),
+      _ => panic!("test failed"),
+    };
+    assert_eq!(tokens()?, 0);
+
+    // the right one does.
+    tl.code = recovery[0].clone();
+    match crate::interfaces::totp_login(&conn, &config, &tl).await? {
+      (orgauth::data::UserResponse::UrpLoggedIn(_), Some(_)) => (),
+      _ => panic!("test failed"),
+    };
+    assert_eq!(tokens()?, 1);
+
+    Ok(())
+  }
+}
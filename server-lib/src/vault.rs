@@ -0,0 +1,685 @@
+use crate::config::Quotas;
+use crate::error as zkerr;
+use crate::jobs::JobMonitor;
+use crate::sqldata::{self, save_importzknotes, save_zklink, LapinInfo};
+use orgauth::data::UserId;
+use regex::{Captures, Regex};
+use rusqlite::Connection;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use uuid::Uuid;
+use zkprotocol::content::{
+  ImportAction, ImportPolicy, ImportZkNote, ImportZkNoteResult, Server, ZkNoteId,
+};
+
+// import of markdown folders, like obsidian or logseq vaults.
+
+enum VaultFile {
+  Entry(usize),
+  File(PathBuf),
+}
+
+pub struct Vault {
+  archive: Option<zip::ZipArchive<File>>,
+  // relative paths with '/' separators.
+  files: BTreeMap<String, VaultFile>,
+}
+
+struct VaultNote {
+  path: String,
+  title: String,
+  tags: Vec<String>,
+  content: String,
+}
+
+fn path_string(p: &Path) -> String {
+  p.components()
+    .filter_map(|c| match c {
+      Component::Normal(s) => Some(s.to_string_lossy().to_string()),
+      _ => None,
+    })
+    .collect::<Vec<String>>()
+    .join("/")
+}
+
+// hidden files and folders, like .obsidian, .trash and .git, aren't imported.
+fn hidden(path: &str) -> bool {
+  path.split('/').any(|p| p.starts_with('.'))
+}
+
+fn walk(
+  root: &Path,
+  dir: &Path,
+  files: &mut BTreeMap<String, VaultFile>,
+) -> Result<(), zkerr::Error> {
+  for entry in std::fs::read_dir(dir)? {
+    let entry = entry?;
+    let path = entry.path();
+    // symlinks are skipped, so the import stays in the vault.
+    let ft = entry.file_type()?;
+    if ft.is_dir() {
+      walk(root, &path, files)?;
+    } else if ft.is_file() {
+      if let Ok(rel) = path.strip_prefix(root) {
+        files.insert(path_string(rel), VaultFile::File(path.clone()));
+      }
+    }
+  }
+  Ok(())
+}
+
+impl Vault {
+  pub fn from_zip(path: &Path) -> Result<Vault, zkerr::Error> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut files = BTreeMap::new();
+    for i in 0..archive.len() {
+      let zf = archive.by_index(i)?;
+      // enclosed_name is None for absolute paths and paths with '..'.
+      if let (false, Some(p)) = (zf.is_dir(), zf.enclosed_name()) {
+        files.insert(path_string(&p), VaultFile::Entry(i));
+      }
+    }
+    files.retain(|p, _| !hidden(p));
+    Ok(Vault {
+      archive: Some(archive),
+      files,
+    })
+  }
+
+  pub fn from_dir(dir: &Path) -> Result<Vault, zkerr::Error> {
+    if !dir.is_dir() {
+      return Err(zkerr::Error::String(format!(
+        "not a directory: {}",
+        dir.display()
+      )));
+    }
+    let mut files = BTreeMap::new();
+    walk(dir, dir, &mut files)?;
+    files.retain(|p, _| !hidden(p));
+    Ok(Vault {
+      archive: None,
+      files,
+    })
+  }
+}
+
+fn read(
+  archive: &mut Option<zip::ZipArchive<File>>,
+  files: &BTreeMap<String, VaultFile>,
+  path: &str,
+) -> Result<Vec<u8>, zkerr::Error> {
+  let mut buf = Vec::new();
+  match (files.get(path), archive) {
+    (Some(VaultFile::Entry(i)), Some(archive)) => {
+      archive.by_index(*i)?.read_to_end(&mut buf)?;
+    }
+    (Some(VaultFile::File(p)), _) => {
+      File::open(p)?.read_to_end(&mut buf)?;
+    }
+    _ => return Err(zkerr::Error::String(format!("file not in vault: {}", path))),
+  }
+  Ok(buf)
+}
+
+fn is_markdown(path: &str) -> bool {
+  path.to_lowercase().ends_with(".md")
+}
+
+fn basename(path: &str) -> &str {
+  path.rsplit('/').next().unwrap_or(path)
+}
+
+fn stem(path: &str) -> &str {
+  let name = basename(path);
+  if is_markdown(name) {
+    &name[..name.len() - 3]
+  } else {
+    name
+  }
+}
+
+fn unquote(s: &str) -> String {
+  s.trim().trim_matches(|c| c == '"' || c == '\'').to_string()
+}
+
+fn tag_list(value: &str) -> Vec<String> {
+  value
+    .trim()
+    .trim_start_matches('[')
+    .trim_end_matches(']')
+    .split(',')
+    .flat_map(|t| t.split_whitespace())
+    .map(|t| {
+      unquote(t)
+        .trim_matches(|c| c == '[' || c == ']')
+        .trim_start_matches('#')
+        .to_string()
+    })
+    .collect()
+}
+
+// title and tags from yaml front matter, or logseq 'key:: value' properties.
+// returns the content without them.
+fn front_matter(text: &str) -> (Option<String>, Vec<String>, String) {
+  let lines: Vec<&str> = text.lines().collect();
+  let mut title = None;
+  let mut tags = Vec::new();
+
+  let (props, body): (Vec<(&str, &str)>, &[&str]) =
+    if lines.first().map(|l| l.trim_end()) == Some("---") {
+      match lines[1..].iter().position(|l| l.trim_end() == "---") {
+        Some(end) => {
+          let fm = &lines[1..end + 1];
+          let mut props = Vec::new();
+          let mut listkey = None;
+          for l in fm {
+            match (listkey, l.trim().strip_prefix('-')) {
+              (Some(key), Some(item)) if l.starts_with(' ') || l.starts_with('-') => {
+                props.push((key, item));
+                continue;
+              }
+              _ => listkey = None,
+            }
+            if let Some((key, value)) = l.split_once(':') {
+              if value.trim().is_empty() {
+                listkey = Some(key.trim());
+              } else {
+                props.push((key.trim(), value));
+              }
+            }
+          }
+          (props, &lines[end + 2..])
+        }
+        None => (Vec::new(), &lines[..]),
+      }
+    } else {
+      let n = lines
+        .iter()
+        .take_while(|l| {
+          l.split_once(":: ")
+            .map_or(false, |(k, _)| !k.is_empty() && !k.contains(' '))
+        })
+        .count();
+      (
+        lines[..n]
+          .iter()
+          .filter_map(|l| l.split_once("::"))
+          .collect(),
+        &lines[n..],
+      )
+    };
+
+  for (key, value) in props {
+    match key.to_lowercase().as_str() {
+      "title" => title = Some(unquote(value)),
+      "tags" | "tag" => tags.extend(tag_list(value)),
+      _ => (),
+    }
+  }
+  tags.retain(|t| !t.is_empty());
+
+  (title, tags, body.join("\n"))
+}
+
+fn percent_decode(s: &str) -> String {
+  let bytes = s.as_bytes();
+  let mut out = Vec::new();
+  let mut i = 0;
+  while i < bytes.len() {
+    if bytes[i] == b'%' && i + 2 < bytes.len() {
+      if let Some(b) = std::str::from_utf8(&bytes[i + 1..i + 3])
+        .ok()
+        .and_then(|h| u8::from_str_radix(h, 16).ok())
+      {
+        out.push(b);
+        i += 3;
+        continue;
+      }
+    }
+    out.push(bytes[i]);
+    i += 1;
+  }
+  String::from_utf8_lossy(&out).to_string()
+}
+
+// join a relative link to the folder of the note, resolving '.' and '..'.
+fn relative_path(dir: &str, target: &str) -> String {
+  let mut parts: Vec<&str> = dir.split('/').filter(|p| !p.is_empty()).collect();
+  for p in target.split('/') {
+    match p {
+      "" | "." => (),
+      ".." => {
+        parts.pop();
+      }
+      p => parts.push(p),
+    }
+  }
+  parts.join("/")
+}
+
+struct Resolver<'a> {
+  files: &'a BTreeMap<String, VaultFile>,
+  // lowercase file name -> path.  first path wins, for repeatable imports.
+  names: BTreeMap<String, &'a str>,
+}
+
+impl<'a> Resolver<'a> {
+  fn new(files: &'a BTreeMap<String, VaultFile>) -> Resolver<'a> {
+    let mut names = BTreeMap::new();
+    for p in files.keys() {
+      names
+        .entry(basename(p).to_lowercase())
+        .or_insert(p.as_str());
+    }
+    Resolver { files, names }
+  }
+
+  // like obsidian: relative to the note, then from the vault root, then
+  // any file with that name.  wikilinks to notes leave off the '.md'.
+  fn resolve(&self, notepath: &str, target: &str) -> Option<&'a str> {
+    let target = percent_decode(target.trim());
+    let dir = notepath.rsplit_once('/').map_or("", |(d, _)| d);
+    let mut candidates = vec![target.clone()];
+    if !is_markdown(target.as_str()) {
+      candidates.push(format!("{}.md", target));
+    }
+    for c in candidates.iter() {
+      for p in [
+        relative_path(dir, c.as_str()),
+        relative_path("", c.as_str()),
+      ] {
+        if let Some((k, _)) = self.files.get_key_value(p.as_str()) {
+          return Some(k.as_str());
+        }
+      }
+    }
+    for c in candidates.iter() {
+      if let Some(p) = self.names.get(&basename(c.as_str()).to_lowercase()) {
+        return Some(p);
+      }
+    }
+    None
+  }
+}
+
+fn wikilink_re() -> Regex {
+  // ![[embed]], [[target]], [[target#heading]], [[target|alias]]
+  Regex::new(r"(!?)\[\[([^\[\]|#]+)(?:#[^\[\]|]*)?(?:\|[^\[\]]*)?\]\]").unwrap()
+}
+
+fn mdembed_re() -> Regex {
+  // ![alt](path) and ![alt](path "title")
+  Regex::new(r#"!\[[^\]]*\]\(([^)\s]+)(?:\s+"[^"]*")?\)"#).unwrap()
+}
+
+// links to the web, or to zknotes files, aren't vault files.
+fn local_target(target: &str) -> bool {
+  !(target.contains("://") || target.starts_with('/') || target.starts_with('#'))
+}
+
+// import a markdown vault.  notes are matched by title, and file notes by hash,
+// so importing the same vault again doesn't make duplicates.
+pub async fn import_vault(
+  conn: &Connection,
+  server: &Server,
+  lapin_info: &Option<LapinInfo>,
+  files_dir: &Path,
+  file_tmp_path: &Path,
+  quotas: &Quotas,
+  uid: UserId,
+  vault: &mut Vault,
+  policy: &ImportPolicy,
+  monitor: &dyn JobMonitor,
+) -> Result<(Vec<ImportZkNoteResult>, usize), zkerr::Error> {
+  let Vault { archive, files } = vault;
+  let files = &*files;
+  let mdpaths: Vec<&String> = files.keys().filter(|p| is_markdown(p)).collect();
+
+  write!(monitor, "reading {} notes", mdpaths.len());
+
+  let mut notes = Vec::new();
+  for path in mdpaths {
+    let text = String::from_utf8_lossy(&read(archive, files, path.as_str())?).to_string();
+    let (title, tags, content) = front_matter(text.as_str());
+    notes.push(VaultNote {
+      path: path.clone(),
+      title: title.unwrap_or_else(|| stem(path.as_str()).to_string()),
+      tags,
+      content,
+    });
+  }
+
+  let titles: BTreeMap<String, String> = notes
+    .iter()
+    .map(|n| (n.path.clone(), n.title.clone()))
+    .collect();
+
+  let wikilink = wikilink_re();
+  let mdembed = mdembed_re();
+  let resolver = Resolver::new(files);
+
+  // vault path -> file note.
+  let mut filenotes: BTreeMap<String, (i64, ZkNoteId)> = BTreeMap::new();
+  let count = notes.len();
+  let mut results = Vec::new();
+
+  for (i, note) in notes.iter().enumerate() {
+    write!(
+      monitor,
+      "importing note {} of {}: {}",
+      i + 1,
+      count,
+      note.path
+    );
+
+    // resolve links and embeds.  links to notes become zklinks, and
+    // attachments become file notes.
+    let mut tolinks: BTreeSet<String> = note.tags.iter().cloned().collect();
+    let mut attachments: BTreeSet<&str> = BTreeSet::new();
+    for cap in wikilink.captures_iter(note.content.as_str()) {
+      match (resolver.resolve(note.path.as_str(), &cap[2]), &cap[1]) {
+        (Some(p), _) if is_markdown(p) => {
+          if let Some(t) = titles.get(p) {
+            tolinks.insert(t.clone());
+          }
+        }
+        (Some(p), _) => {
+          attachments.insert(p);
+        }
+        // a link to a note that doesn't exist yet.
+        (None, "") => {
+          tolinks.insert(stem(cap[2].trim()).to_string());
+        }
+        // an embed of a file that isn't in the vault.
+        (None, _) => (),
+      }
+    }
+    for cap in mdembed.captures_iter(note.content.as_str()) {
+      if local_target(&cap[1]) {
+        if let Some(p) = resolver.resolve(note.path.as_str(), &cap[1]) {
+          if !is_markdown(p) {
+            attachments.insert(p);
+          }
+        }
+      }
+    }
+    tolinks.remove(&note.title);
+
+    for path in attachments.iter() {
+      if filenotes.contains_key(*path) {
+        continue;
+      }
+      // make_file_note moves the file, so stage a copy.
+      let tmp = file_tmp_path.join(Uuid::new_v4().to_string());
+      std::fs::write(&tmp, read(archive, files, path)?)?;
+      let (id, zni, _fid) = sqldata::make_file_note(
+        conn,
+        server,
+        lapin_info,
+        files_dir,
+        uid,
+        &basename(path).to_string(),
+        &tmp,
+        false,
+        quotas,
+      )
+      .await?;
+      filenotes.insert(path.to_string(), (id, zni));
+    }
+
+    // embeds show the file note.
+    let content = {
+      let embed = |target: &str, original: &str| -> String {
+        match resolver
+          .resolve(note.path.as_str(), target)
+          .and_then(|p| filenotes.get(p))
+        {
+          Some((_, zni)) => format!("<note id=\"{}\"/>", zni),
+          None => original.to_string(),
+        }
+      };
+      let content = wikilink.replace_all(note.content.as_str(), |cap: &Captures| {
+        if &cap[1] == "!" {
+          embed(&cap[2], &cap[0])
+        } else {
+          cap[0].to_string()
+        }
+      });
+      mdembed
+        .replace_all(&content, |cap: &Captures| {
+          if local_target(&cap[1]) {
+            embed(&cap[1], &cap[0])
+          } else {
+            cap[0].to_string()
+          }
+        })
+        .to_string()
+    };
+
+    for r in save_importzknotes(
+      conn,
+      lapin_info,
+      server,
+      uid,
+      &vec![ImportZkNote {
+        title: note.title.clone(),
+        content,
+        fromLinks: Vec::new(),
+        toLinks: tolinks.into_iter().collect(),
+      }],
+      policy,
+    )
+    .await?
+    {
+      if r.action != ImportAction::Skipped {
+        let nid = sqldata::note_id_for_zknoteid(conn, &r.id)?;
+        for path in attachments.iter() {
+          if let Some((fid, _)) = filenotes.get(*path) {
+            save_zklink(conn, nid, *fid, uid, None)?;
+          }
+        }
+      }
+      results.push(r);
+    }
+  }
+
+  Ok((results, filenotes.len()))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::jobs::LogMonitor;
+  use crate::sqldata::{connection_open, dbinit, read_zklinks, zknotes_callbacks};
+  use orgauth::data::RegistrationData;
+  use orgauth::dbfun::new_user;
+  use std::io::Write;
+
+  #[test]
+  fn test_front_matter() {
+    let (title, tags, body) = front_matter(
+      "---\ntitle: \"My Note\"\ntags: [one, \"#two\"]\naliases:\n  - other\n---\nbody\nmore",
+    );
+    assert_eq!(title, Some("My Note".to_string()));
+    assert_eq!(tags, vec!["one", "two"]);
+    assert_eq!(body, "body\nmore");
+
+    // tags as a list.
+    let (title, tags, body) =
+      front_matter("---\ntags:\n  - one\n  - two\ncreated: today\n---\nbody");
+    assert_eq!(title, None);
+    assert_eq!(tags, vec!["one", "two"]);
+    assert_eq!(body, "body");
+
+    // logseq properties.
+    let (title, tags, body) = front_matter("title:: Logseq Page\ntags:: [[one]], two\n\n- block");
+    assert_eq!(title, Some("Logseq Page".to_string()));
+    assert_eq!(tags, vec!["one", "two"]);
+    assert_eq!(body, "\n- block");
+
+    // no front matter, or no end to it.
+    for text in ["just: text\nhere", "---\ntitle: x\nno end"] {
+      let (title, tags, body) = front_matter(text);
+      assert_eq!(title, None);
+      assert!(tags.is_empty());
+      assert_eq!(body, text);
+    }
+  }
+
+  #[test]
+  fn test_resolve() {
+    let files: BTreeMap<String, VaultFile> = [
+      "a.md",
+      "notes/a.md",
+      "notes/sub/b.md",
+      "notes/img.png",
+      "attachments/img.png",
+      "attachments/My Pic.jpg",
+    ]
+    .iter()
+    .map(|p| (p.to_string(), VaultFile::File(PathBuf::new())))
+    .collect();
+    let r = Resolver::new(&files);
+
+    // relative to the note first, then from the root.
+    assert_eq!(r.resolve("notes/sub/b.md", "../a"), Some("notes/a.md"));
+    assert_eq!(r.resolve("notes/sub/b.md", "a"), Some("a.md"));
+    assert_eq!(r.resolve("notes/a.md", "img.png"), Some("notes/img.png"));
+    assert_eq!(r.resolve("a.md", "notes/sub/b.md"), Some("notes/sub/b.md"));
+    // then by name anywhere, ignoring case; the first path wins.
+    assert_eq!(r.resolve("a.md", "B"), Some("notes/sub/b.md"));
+    assert_eq!(r.resolve("a.md", "img.png"), Some("attachments/img.png"));
+    assert_eq!(
+      r.resolve("a.md", "My%20Pic.jpg"),
+      Some("attachments/My Pic.jpg")
+    );
+    assert_eq!(r.resolve("a.md", "missing"), None);
+  }
+
+  #[test]
+  fn test_link_res() {
+    let caps: Vec<(String, String)> = wikilink_re()
+      .captures_iter("[[a]] ![[img.png]] [[b#heading]] [[c|alias]] [[d#h|alias]] [not a link]")
+      .map(|c| (c[1].to_string(), c[2].to_string()))
+      .collect();
+    assert_eq!(
+      caps,
+      vec![
+        ("".to_string(), "a".to_string()),
+        ("!".to_string(), "img.png".to_string()),
+        ("".to_string(), "b".to_string()),
+        ("".to_string(), "c".to_string()),
+        ("".to_string(), "d".to_string()),
+      ]
+    );
+
+    let caps: Vec<String> = mdembed_re()
+      .captures_iter(
+        "![](img.png) ![alt](pics/a.jpg \"title\") [link](b.md) ![x](https://e.com/c.png)",
+      )
+      .map(|c| c[1].to_string())
+      .collect();
+    assert_eq!(caps, vec!["img.png", "pics/a.jpg", "https://e.com/c.png"]);
+    assert!(local_target("pics/a.jpg"));
+    assert!(!local_target("https://e.com/c.png"));
+    assert!(!local_target("/file/abc"));
+  }
+
+  #[actix_web::test]
+  async fn test_import_vault() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = Path::new("vault_test");
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir.join("files"))?;
+    std::fs::create_dir_all(dir.join("temp"))?;
+
+    let dbfile = dir.join("vault.db");
+    let server = dbinit(dbfile.as_path(), None)?;
+    let conn = connection_open(dbfile.as_path())?;
+    let mut cb = zknotes_callbacks();
+    let uid = new_user(
+      &conn,
+      &RegistrationData {
+        uid: "importer".to_string(),
+        pwd: "".to_string(),
+        email: "".to_string(),
+        remote_url: "".to_string(),
+      },
+      None,
+      None,
+      false,
+      None,
+      None,
+      None,
+      None,
+      None,
+      &mut cb.on_new_user,
+    )?;
+
+    let zippath = dir.join("vault.zip");
+    let mut zw = zip::ZipWriter::new(File::create(&zippath)?);
+    for (path, content) in [
+      (
+        "notes/a.md",
+        "---\ntitle: Alpha\ntags: [topic]\n---\nfirst note",
+      ),
+      ("notes/b.md", "see [[a]] and ![[img.png]]"),
+      ("attachments/img.png", "not really a png"),
+      (".obsidian/app.json", "{}"),
+    ] {
+      zw.start_file(path, zip::write::SimpleFileOptions::default())?;
+      zw.write_all(content.as_bytes())?;
+    }
+    zw.finish()?;
+
+    let import = || async {
+      import_vault(
+        &conn,
+        &server,
+        &None,
+        &dir.join("files"),
+        &dir.join("temp"),
+        &Quotas::default(),
+        uid,
+        &mut Vault::from_zip(&zippath)?,
+        &ImportPolicy::Replace,
+        &LogMonitor {},
+      )
+      .await
+    };
+
+    let (results, files) = import().await?;
+    assert_eq!(files, 1);
+    assert_eq!(
+      results
+        .iter()
+        .map(|r| (r.title.as_str(), r.action.clone()))
+        .collect::<Vec<_>>(),
+      vec![("Alpha", ImportAction::Added), ("b", ImportAction::Added)]
+    );
+    let bid = sqldata::note_id_for_zknoteid(&conn, &results[1].id)?;
+    let links = read_zklinks(&conn, uid, bid)?.len();
+    // the embed points at the file note.
+    let b = sqldata::read_zknote_unchecked(&conn, &dir.join("files"), &results[1].id)?.1;
+    assert!(b.content.contains("<note id=\""));
+    assert!(!b.content.contains("![[img.png]]"));
+
+    // the same zip again changes nothing.
+    let (again, files) = import().await?;
+    assert_eq!(files, 1);
+    for (r, a) in results.iter().zip(again.iter()) {
+      assert_eq!(r.id, a.id);
+      assert_eq!(a.action, ImportAction::Unchanged);
+    }
+    assert_eq!(read_zklinks(&conn, uid, bid)?.len(), links);
+    assert_eq!(
+      sqldata::read_zknote_unchecked(&conn, &dir.join("files"), &results[1].id)?
+        .1
+        .content,
+      b.content
+    );
+
+    Ok(())
+  }
+}
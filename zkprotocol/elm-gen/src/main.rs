@@ -39,6 +39,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     zc::EditLink,
                     zc::ZkLinks,
                     zc::ImportZkNote,
+                    zc::ImportPolicy,
+                    zc::ImportZkNotes,
+                    zc::ImportAction,
+                    zc::ImportZkNoteResult,
+                    zc::VaultSource,
+                    zc::ImportVault,
+                    zc::ExportSubtree,
+                    zc::ExportSelection,
+                    zc::ExportFormat,
+                    zc::ExportDestination,
+                    zc::ExportSite,
+                    zc::MakeSyncBundle,
                     zc::GetZkLinks,
                     zc::GetZkNoteAndLinks,
                     zc::GetZknIfChanged,
@@ -54,6 +66,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     zc::ZkNoteAndLinksWhat,
                     zc::JobState,
                     zc::JobStatus,
+                    zc::GetStorageStats,
+                    zc::FileStats,
+                    zc::UserStorageStats,
+                    zc::StorageStats,
+                    zc::SharePermission,
+                    zc::UserSharePermission,
+                    zc::ApiTokenScope,
+                    zc::CreateApiToken,
+                    zc::ApiToken,
+                    zc::NewApiToken,
+                    zc::CreateShareLink,
+                    zc::ShareLink,
+                    zc::GetShareLinkNote,
+                    zc::AuditAction,
+                    zc::AuditEntry,
+                    zc::AuditQuery,
+                    zc::TotpEnrollment,
+                    zc::TotpLogin,
                     zpub::PublicRequest,
                     zpub::PublicReply,
                     zpub::PublicError,
@@ -64,6 +94,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     zprv::PrivateClosureReply,
                     zprv::ZkNoteRq,
                     upload::UploadReply,
+                    upload::FilesAttached,
+                    upload::CreateUpload,
+                    upload::UploadStatus,
                     zs::ZkNoteSearch,
                     zs::Ordering,
                     zs::OrderDirection,
@@ -74,6 +107,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     zs::AndOr,
                     zs::ZkIdSearchResult,
                     zs::ZkListNoteSearchResult,
+                    zs::PowerDeletePreview,
+                    zs::PowerDeleteConfirm,
+                    zs::PowerDeleteBatch,
+                    zs::GetChangedNotes,
+                    zs::ChangedNote,
                     zs::ZkNoteSearchResult,
                     zs::ZkSearchResultHeader,
                     zs::ZkNoteAndLinksSearchResult,
@@ -101,6 +139,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     zc::EditLink,
                     zc::ZkLinks,
                     zc::ImportZkNote,
+                    zc::ImportPolicy,
+                    zc::ImportZkNotes,
+                    zc::ImportAction,
+                    zc::ImportZkNoteResult,
+                    zc::VaultSource,
+                    zc::ImportVault,
+                    zc::ExportSubtree,
+                    zc::ExportSelection,
+                    zc::ExportFormat,
+                    zc::ExportDestination,
+                    zc::ExportSite,
+                    zc::MakeSyncBundle,
                     zc::GetZkLinks,
                     zc::GetZkNoteAndLinks,
                     zc::GetZknIfChanged,
@@ -116,6 +166,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     zc::ZkNoteAndLinksWhat,
                     zc::JobState,
                     zc::JobStatus,
+                    zc::GetStorageStats,
+                    zc::FileStats,
+                    zc::UserStorageStats,
+                    zc::StorageStats,
+                    zc::SharePermission,
+                    zc::UserSharePermission,
+                    zc::ApiTokenScope,
+                    zc::CreateApiToken,
+                    zc::ApiToken,
+                    zc::NewApiToken,
+                    zc::CreateShareLink,
+                    zc::ShareLink,
+                    zc::GetShareLinkNote,
+                    zc::AuditAction,
+                    zc::AuditEntry,
+                    zc::AuditQuery,
+                    zc::TotpEnrollment,
+                    zc::TotpLogin,
                     zpub::PublicRequest,
                     zpub::PublicReply,
                     zpub::PublicError,
@@ -126,6 +194,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     zprv::PrivateClosureReply,
                     zprv::ZkNoteRq,
                     upload::UploadReply,
+                    upload::FilesAttached,
+                    upload::CreateUpload,
+                    upload::UploadStatus,
                     zs::ZkNoteSearch,
                     zs::Ordering,
                     zs::OrderDirection,
@@ -136,6 +207,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     zs::AndOr,
                     zs::ZkIdSearchResult,
                     zs::ZkListNoteSearchResult,
+                    zs::PowerDeletePreview,
+                    zs::PowerDeleteConfirm,
+                    zs::PowerDeleteBatch,
+                    zs::GetChangedNotes,
+                    zs::ChangedNote,
                     zs::ZkNoteSearchResult,
                     zs::ZkSearchResultHeader,
                     zs::ZkNoteAndLinksSearchResult,
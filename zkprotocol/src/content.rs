@@ -3,6 +3,7 @@
 use std::fmt::Display;
 
 use crate::search::ZkListNoteSearchResult;
+use crate::search::ZkNoteSearch;
 use elm_rs::{Elm, ElmDecode, ElmEncode};
 use orgauth::data::UserId;
 use uuid::Uuid;
@@ -64,6 +65,9 @@ pub struct ZkNote {
   pub createdate: i64,
   pub changeddate: i64,
   pub deleted: bool,
+  // content is ciphertext from the client; the server can't read it.
+  #[serde(default)]
+  pub encrypted: bool,
   pub filestatus: FileStatus,
   pub server: String,
   pub sysids: Vec<ZkNoteId>,
@@ -119,10 +123,12 @@ pub struct SaveZkNote {
   pub editable: bool,
   pub showtitle: bool,
   pub deleted: bool,
+  #[serde(default)]
+  pub encrypted: bool,
   pub what: Option<String>,
 }
 
-#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Direction {
   From,
   To,
@@ -209,6 +215,96 @@ pub struct ImportZkNote {
   pub toLinks: Vec<String>,
 }
 
+// what to do when an imported note has the same title as an existing note.
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ImportPolicy {
+  Replace,
+  Append,
+  Skip,
+  Duplicate,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub struct ImportZkNotes {
+  pub notes: Vec<ImportZkNote>,
+  pub policy: ImportPolicy,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ImportAction {
+  Added,
+  Replaced,
+  Appended,
+  Unchanged,
+  Skipped,
+  Duplicated,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub struct ImportZkNoteResult {
+  pub title: String,
+  pub id: ZkNoteId,
+  pub action: ImportAction,
+}
+
+// a folder of markdown files, as from obsidian or logseq.
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub enum VaultSource {
+  // an uploaded zip file note.
+  Zip(ZkNoteId),
+  // a directory on the server; admin only.
+  Directory(String),
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub struct ImportVault {
+  pub source: VaultSource,
+  pub policy: ImportPolicy,
+}
+
+// a note and the notes it links to, to depth links away.
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub struct ExportSubtree {
+  pub root: ZkNoteId,
+  pub depth: i64,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub enum ExportSelection {
+  Search(ZkNoteSearch),
+  Subtree(ExportSubtree),
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub enum ExportFormat {
+  Markdown,
+  Html,
+  // an obsidian vault, with front matter and wikilinks.
+  Obsidian,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub enum ExportDestination {
+  // a zip file note for the user.
+  ZipNote,
+  // a directory on the server; admin only.
+  ServerDirectory(String),
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub struct ExportSite {
+  pub selection: ExportSelection,
+  pub format: ExportFormat,
+  pub destination: ExportDestination,
+}
+
+// a zip file note with the user's sync stream and files, for syncing without a network.
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub struct MakeSyncBundle {
+  // only changes after this time; everything if None.
+  pub after: Option<i64>,
+}
+
 #[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug)]
 pub struct GetZkLinks {
   pub zknote: ZkNoteId,
@@ -315,6 +411,155 @@ pub struct JobStatus {
   pub message: String,
 }
 
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct GetStorageStats {
+  pub largest: i64, // how many of each user's largest files to list.
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct FileStats {
+  pub id: ZkNoteId,
+  pub title: String,
+  pub hash: String,
+  pub size: i64,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct UserStorageStats {
+  pub user: UserId,
+  pub name: String,
+  pub notes: i64,
+  pub archives: i64,
+  pub files: i64,
+  pub file_bytes: i64, // sum of file sizes over all this user's file notes.
+  pub unique_file_bytes: i64, // same, but each distinct file counted once.
+  pub largest_files: Vec<FileStats>,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct StorageStats {
+  pub users: Vec<UserStorageStats>,
+  pub file_bytes: i64,        // total if every file note had its own copy.
+  pub unique_file_bytes: i64, // what's actually stored, after dedup by hash.
+}
+
+// what members of a share can do with the notes in it.
+// comment allows linking (and so commenting), but not editing.
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum SharePermission {
+  Read,
+  Comment,
+  Write,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct UserSharePermission {
+  pub share: ZkNoteId,
+  pub user: UserId,
+  pub permission: SharePermission,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum ApiTokenScope {
+  ReadOnly,
+  ReadWrite,
+  UploadOnly,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct CreateApiToken {
+  pub name: String,
+  pub scope: ApiTokenScope,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct ApiToken {
+  pub id: Uuid,
+  pub name: String,
+  pub scope: ApiTokenScope,
+  pub createdate: i64,
+  pub lastused: Option<i64>,
+}
+
+// the token itself is only available when it's created.
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct NewApiToken {
+  pub info: ApiToken,
+  pub token: String,
+}
+
+// read access to a note, or to all the notes of a share, without an account.
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct CreateShareLink {
+  pub zknote: ZkNoteId,
+  pub expires: Option<i64>,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct ShareLink {
+  pub id: Uuid,
+  pub token: String,
+  pub zknote: ZkNoteId,
+  pub createdate: i64,
+  pub expires: Option<i64>,
+  pub revoked: bool,
+  pub views: i64,
+  pub lastview: Option<i64>,
+}
+
+// zknote is the share link's note if None, otherwise a note on that share.
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct GetShareLinkNote {
+  pub token: String,
+  pub zknote: Option<ZkNoteId>,
+}
+
+// uri is an otpauth:// provisioning uri, for authenticator apps or a qr code.
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct TotpEnrollment {
+  pub uri: String,
+  pub secret: String,
+}
+
+// login for accounts with two factor auth.  code is a totp code or a recovery code.
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct TotpLogin {
+  pub uid: String,
+  pub pwd: String,
+  pub code: String,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum AuditAction {
+  DeleteNote,
+  PowerDelete,
+  ShareChange,
+  PublicChange,
+  Admin,
+  PromoteAdmin,
+  Sync,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct AuditEntry {
+  pub id: i64,
+  pub user: Option<UserId>,
+  pub action: AuditAction,
+  pub detail: String,
+  pub createdate: i64,
+}
+
+// all filters optional.  after and before are createdate bounds.
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct AuditQuery {
+  pub user: Option<UserId>,
+  pub action: Option<AuditAction>,
+  pub after: Option<i64>,
+  pub before: Option<i64>,
+  pub offset: i64,
+  pub limit: Option<i64>,
+}
+
 #[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
 pub struct Server {
   pub id: i64,
@@ -1,18 +1,22 @@
 use crate::{
   content::{
-    ArchiveZkLink, GetArchiveZkLinks, GetZkLinksSince, GetZkNoteAndLinks, GetZkNoteArchives,
-    GetZkNoteComments, GetZknIfChanged, ImportZkNote, JobStatus, SaveZkLinks, SaveZkNote,
-    SaveZkNoteAndLinks, SavedZkNote, UuidZkLink, ZkListNote, ZkNote, ZkNoteAndLinksWhat,
-    ZkNoteArchives, ZkNoteId,
+    ApiToken, ArchiveZkLink, AuditEntry, AuditQuery, CreateApiToken, CreateShareLink, ExportSite,
+    GetArchiveZkLinks, GetStorageStats, GetZkLinksSince, GetZkNoteAndLinks, GetZkNoteArchives,
+    GetZkNoteComments, GetZknIfChanged, ImportVault, ImportZkNote, ImportZkNoteResult,
+    ImportZkNotes, JobStatus, MakeSyncBundle, NewApiToken, SaveZkLinks, SaveZkNote,
+    SaveZkNoteAndLinks, SavedZkNote, ShareLink, StorageStats, TotpEnrollment, UserSharePermission,
+    UuidZkLink, ZkListNote, ZkNote, ZkNoteAndLinksWhat, ZkNoteArchives, ZkNoteId,
   },
   search::{
+    ChangedNote, GetChangedNotes, PowerDeleteBatch, PowerDeleteConfirm, PowerDeletePreview,
     TagSearch, ZkIdSearchResult, ZkListNoteSearchResult, ZkNoteAndLinksSearchResult, ZkNoteSearch,
     ZkNoteSearchResult,
   },
 };
 use elm_rs::{Elm, ElmDecode, ElmEncode};
+use orgauth::data::UserId;
 use serde_derive::{Deserialize, Serialize};
-// use uuid::Uuid;
+use uuid::Uuid;
 
 #[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug)]
 pub struct PrivateClosureRequest {
@@ -35,16 +39,39 @@ pub enum PrivateRequest {
   PvqGetArchiveZklinks(GetArchiveZkLinks),
   PvqGetZkLinksSince(GetZkLinksSince),
   PvqSearchZkNotes(ZkNoteSearch),
-  PvqPowerDelete(Vec<TagSearch>),
+  PvqGetChangedNotes(GetChangedNotes),
+  PvqPowerDeletePreview(Vec<TagSearch>),
+  PvqPowerDelete(PowerDeleteConfirm),
+  PvqUndoPowerDelete(Uuid),
   PvqDeleteZkNote(ZkNoteId),
   PvqSaveZkNote(SaveZkNote),
   PvqSaveZkLinks(SaveZkLinks),
   PvqSaveZkNoteAndLinks(SaveZkNoteAndLinks),
   PvqSaveImportZkNotes(Vec<ImportZkNote>),
+  PvqImportZkNotes(ImportZkNotes),
+  PvqImportVault(ImportVault),
+  PvqExportSite(ExportSite),
   PvqSetHomeNote(ZkNoteId),
   PvqSyncRemote,
   PvqSyncFiles(ZkNoteSearch),
+  PvqMakeSyncBundle(MakeSyncBundle),
+  PvqApplySyncBundle(ZkNoteId),
   PvqGetJobStatus(i64),
+  PvqGetStorageStats(GetStorageStats),
+  PvqCreateApiToken(CreateApiToken),
+  PvqGetApiTokens,
+  PvqRevokeApiToken(Uuid),
+  PvqSetSharePermission(UserSharePermission),
+  PvqGetSharePermissions(ZkNoteId),
+  PvqCreateShareLink(CreateShareLink),
+  PvqGetShareLinks(ZkNoteId),
+  PvqRevokeShareLink(Uuid),
+  PvqGetAuditLog(AuditQuery),
+  PvqTotpStatus,
+  PvqTotpBegin,
+  PvqTotpConfirm(String),
+  PvqTotpDisable(String),
+  PvqAdminResetTotp(UserId),
 }
 
 #[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug)]
@@ -62,17 +89,35 @@ pub enum PrivateReply {
   PvyZkNoteSearchResult(ZkNoteSearchResult),
   PvyZkNoteIdSearchResult(ZkIdSearchResult),
   PvyZkNoteAndLinksSearchResult(ZkNoteAndLinksSearchResult),
-  PvyPowerDeleteComplete(i64),
+  PvyChangedNotes(Vec<ChangedNote>),
+  PvyPowerDeletePreview(PowerDeletePreview),
+  PvyPowerDeleteComplete(PowerDeleteBatch),
+  PvyPowerDeleteUndone(i64),
   PvyDeletedZkNote(ZkNoteId),
   PvySavedZkNote(SavedZkNote),
   PvySavedZkLinks,
   PvySavedZkNoteAndLinks(SavedZkNote),
   PvySavedImportZkNotes,
+  PvyImportedZkNotes(Vec<ImportZkNoteResult>),
   PvyHomeNoteSet(ZkNoteId),
   PvyJobStatus(JobStatus),
   PvyJobNotFound(i64),
   PvyFileSyncComplete,
   PvySyncComplete,
+  PvyStorageStats(StorageStats),
+  PvyApiToken(NewApiToken),
+  PvyApiTokens(Vec<ApiToken>),
+  PvyApiTokenRevoked(Uuid),
+  PvySharePermissionSet,
+  PvySharePermissions(Vec<UserSharePermission>),
+  PvyShareLink(ShareLink),
+  PvyShareLinks(Vec<ShareLink>),
+  PvyShareLinkRevoked(Uuid),
+  PvyAuditLog(Vec<AuditEntry>),
+  PvyTotpStatus(bool),
+  PvyTotpEnrollment(TotpEnrollment),
+  PvyTotpRecoveryCodes(Vec<String>),
+  PvyTotpDisabled,
 }
 
 #[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug)]
@@ -1,4 +1,6 @@
-use crate::content::{GetZkNoteAndLinks, GetZknIfChanged, ZkNoteAndLinks, ZkNoteAndLinksWhat};
+use crate::content::{
+  GetShareLinkNote, GetZkNoteAndLinks, GetZknIfChanged, ZkNoteAndLinks, ZkNoteAndLinksWhat,
+};
 use elm_rs::{Elm, ElmDecode, ElmEncode};
 use serde_derive::{Deserialize, Serialize};
 
@@ -7,6 +9,7 @@ pub enum PublicRequest {
   PbrGetZkNoteAndLinks(GetZkNoteAndLinks),
   PbrGetZknIfChanged(GetZknIfChanged),
   PbrGetZkNotePubId(String),
+  PbrGetShareLinkNote(GetShareLinkNote),
 }
 
 #[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug)]
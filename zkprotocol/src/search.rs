@@ -1,5 +1,7 @@
 use crate::content::{ZkListNote, ZkNote, ZkNoteAndLinks, ZkNoteId};
 use elm_rs::{Elm, ElmDecode, ElmEncode};
+use orgauth::data::UserId;
+use uuid::Uuid;
 
 #[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
 pub struct ZkNoteSearch {
@@ -79,6 +81,9 @@ pub enum SearchMod {
   Create,
   Mod,
   Server,
+  // changed by someone else since I viewed it.  a non-empty term is a
+  // timestamp to use instead of the last view.
+  Unviewed,
 }
 
 #[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
@@ -121,3 +126,41 @@ pub struct ZkNoteAndLinksSearchResult {
   pub offset: i64,
   pub what: String,
 }
+
+// the notes a power delete would delete, and a token to confirm with.  skipped
+// is the number of matching notes that belong to other users, which aren't deleted.
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub struct PowerDeletePreview {
+  pub notes: Vec<ZkListNote>,
+  pub skipped: i64,
+  pub token: String,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub struct PowerDeleteConfirm {
+  pub search: Vec<TagSearch>,
+  pub token: String,
+}
+
+// a completed power delete, which can be undone until undountil.
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub struct PowerDeleteBatch {
+  pub id: Uuid,
+  pub count: i64,
+  pub undountil: i64,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub struct GetChangedNotes {
+  pub since: Option<i64>, // None for changed since each note was last viewed.
+  pub offset: i64,
+  pub limit: Option<i64>,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Serialize, Deserialize, Debug, Clone)]
+pub struct ChangedNote {
+  pub note: ZkListNote,
+  pub editor: UserId,
+  pub editorname: String,
+  pub lastviewed: Option<i64>,
+}
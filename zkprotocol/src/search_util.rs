@@ -25,6 +25,7 @@ fn search_mod(input: &str) -> IResult<&str, SearchMod> {
     value(SearchMod::Create, tag("c")),
     value(SearchMod::Mod, tag("m")),
     value(SearchMod::Server, tag("s")),
+    value(SearchMod::Unviewed, tag("v")),
   ))
   .parse(input)
 }
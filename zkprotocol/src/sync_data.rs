@@ -2,7 +2,7 @@ use orgauth::data::UserId;
 use uuid::Uuid;
 
 use crate::{
-  content::{ArchiveZkLink, FileInfo, UuidZkLink, ZkNote},
+  content::{ArchiveZkLink, FileInfo, UuidZkLink, ZkListNote, ZkNote},
   search::ZkSearchResultHeader,
 };
 
@@ -32,6 +32,7 @@ pub enum SyncMessage {
   ZkSearchResultHeader(ZkSearchResultHeader),
   ZkNoteId(String),
   ZkNote(ZkNote, Option<FileInfo>),
+  ZkListNote(ZkListNote),
   ArchiveZkLinkHeader,
   ArchiveZkLink(ArchiveZkLink),
   UuidZkLinkHeader,
@@ -1,9 +1,33 @@
 use elm_rs::{Elm, ElmDecode, ElmEncode};
 use serde_derive::{Deserialize, Serialize};
 
-use crate::content::ZkListNote;
+use crate::content::{ZkLink, ZkListNote};
+use uuid::Uuid;
 
 #[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug)]
 pub enum UploadReply {
   UrFilesUploaded(Vec<ZkListNote>),
+  UrFilesAttached(FilesAttached),
+}
+
+// reply for uploads with a target note; the new file notes and their links to it.
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug)]
+pub struct FilesAttached {
+  pub notes: Vec<ZkListNote>,
+  pub links: Vec<ZkLink>,
+}
+
+// start a resumable upload of a file of this many bytes.
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct CreateUpload {
+  pub name: String,
+  pub size: i64,
+}
+
+#[derive(Elm, ElmDecode, ElmEncode, Deserialize, Serialize, Debug, Clone)]
+pub struct UploadStatus {
+  pub id: Uuid,
+  pub name: String,
+  pub size: i64,
+  pub received: i64,
 }